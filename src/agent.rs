@@ -0,0 +1,556 @@
+//! Answering requests: a command responder for SNMPv1 and SNMPv2c (RFC 3416 4.2).
+
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rasn::types::OctetString;
+use rasn_snmp::{v1, v2, v2c};
+
+use crate::pdu::{decode, encode};
+use crate::transport::UDP_MAX_MESSAGE_SIZE;
+use crate::trap;
+use crate::{ber, is_timeout, trace};
+use crate::{ErrorStatus, IntoOid, Oid, SnmpError, SnmpResult, Value};
+
+/// How often the serving thread checks whether its [`AgentHandle`] was dropped.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+/// Serves the instances of one subtree registered with [`Agent::register`]. The agent
+/// only passes OIDs inside that subtree to [`Handler::get`] and [`Handler::set`], and
+/// ignores what [`Handler::get_next`] returns outside it.
+pub trait Handler: Send + Sync {
+    /// The value of the instance `oid`, or `NoSuchObject` or `NoSuchInstance`.
+    fn get(&self, oid: &Oid) -> Value;
+
+    /// The first instance after `oid` in lexicographic order, if any.
+    fn get_next(&self, oid: &Oid) -> Option<(Oid, Value)>;
+
+    /// Sets the instance `oid`; read-only handlers keep the default, which refuses.
+    fn set(&self, _oid: &Oid, _value: &Value) -> Result<(), ErrorStatus> {
+        Err(ErrorStatus::NotWritable)
+    }
+}
+
+/// A fixed set of read-only instances.
+impl Handler for BTreeMap<Oid, Value> {
+    fn get(&self, oid: &Oid) -> Value {
+        self.get(oid).cloned().unwrap_or(Value::NoSuchObject)
+    }
+
+    fn get_next(&self, oid: &Oid) -> Option<(Oid, Value)> {
+        self.range((Bound::Excluded(oid), Bound::Unbounded))
+            .next()
+            .map(|(name, value)| (name.clone(), value.clone()))
+    }
+}
+
+/// Instances that can be overwritten with a value of the same type, but not created.
+impl Handler for RwLock<BTreeMap<Oid, Value>> {
+    fn get(&self, oid: &Oid) -> Value {
+        Handler::get(&*self.read().unwrap_or_else(PoisonError::into_inner), oid)
+    }
+
+    fn get_next(&self, oid: &Oid) -> Option<(Oid, Value)> {
+        self.read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_next(oid)
+    }
+
+    fn set(&self, oid: &Oid, value: &Value) -> Result<(), ErrorStatus> {
+        let mut instances = self.write().unwrap_or_else(PoisonError::into_inner);
+
+        match instances.get_mut(oid) {
+            Some(current) if mem::discriminant(current) == mem::discriminant(value) => {
+                *current = value.clone();
+                Ok(())
+            }
+            Some(_) => Err(ErrorStatus::WrongType),
+            None => Err(ErrorStatus::NoCreation),
+        }
+    }
+}
+
+/// The SNMPv1 error-status for an SNMPv2 one (RFC 3584 4.4).
+fn v1_status(status: ErrorStatus) -> ErrorStatus {
+    match status {
+        ErrorStatus::TooBig
+        | ErrorStatus::NoSuchName
+        | ErrorStatus::BadValue
+        | ErrorStatus::ReadOnly
+        | ErrorStatus::GenErr => status,
+        ErrorStatus::WrongValue
+        | ErrorStatus::WrongEncoding
+        | ErrorStatus::WrongType
+        | ErrorStatus::WrongLength
+        | ErrorStatus::InconsistentValue => ErrorStatus::BadValue,
+        ErrorStatus::NoAccess
+        | ErrorStatus::NotWritable
+        | ErrorStatus::NoCreation
+        | ErrorStatus::InconsistentName
+        | ErrorStatus::AuthorizationError => ErrorStatus::NoSuchName,
+        _ => ErrorStatus::GenErr,
+    }
+}
+
+/// Answers Get, GetNext, GetBulk and Set requests from [`Handler`]s registered on
+/// subtrees of the OID tree, e.g.
+/// `agent.register("1.3.6.1.2.1.1", BTreeMap::from([(sys_descr, value)]))?`.
+///
+/// Requests with an unknown community are dropped. Sets need the write community, and
+/// are applied binding by binding: when one fails, the ones before it stay applied.
+pub struct Agent {
+    community: Vec<u8>,
+    write_community: Option<Vec<u8>>,
+    max_message_size: usize,
+    /// Sorted by prefix, and never nested in each other.
+    handlers: Vec<(Oid, Box<dyn Handler>)>,
+}
+
+impl Default for Agent {
+    fn default() -> Self {
+        Agent::new()
+    }
+}
+
+impl Agent {
+    /// An agent with no handlers, answering the community `public`, read-only.
+    pub fn new() -> Self {
+        Agent {
+            community: b"public".to_vec(),
+            write_community: None,
+            max_message_size: UDP_MAX_MESSAGE_SIZE,
+            handlers: Vec::new(),
+        }
+    }
+
+    /// The community that grants read access.
+    pub fn community(mut self, community: impl AsRef<[u8]>) -> Self {
+        self.community = community.as_ref().to_vec();
+        self
+    }
+
+    /// The community that grants read and write access.
+    pub fn write_community(mut self, community: impl AsRef<[u8]>) -> Self {
+        self.write_community = Some(community.as_ref().to_vec());
+        self
+    }
+
+    /// Largest response sent; GetBulk responses are cut short to fit, and other requests
+    /// whose response does not fit fail with tooBig.
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Serves the subtree at `prefix` with `handler`. Subtrees must not overlap.
+    pub fn register(
+        &mut self,
+        prefix: impl IntoOid,
+        handler: impl Handler + 'static,
+    ) -> SnmpResult<()> {
+        let prefix = prefix.into_oid()?;
+
+        if self
+            .handlers
+            .iter()
+            .any(|(other, _)| prefix.starts_with(other) || other.starts_with(&prefix))
+        {
+            return Err(SnmpError::Unsupported("overlapping handler subtrees"));
+        }
+
+        let at = self.handlers.partition_point(|(other, _)| *other < prefix);
+        self.handlers.insert(at, (prefix, Box::new(handler)));
+
+        Ok(())
+    }
+
+    /// The prefixes of the registered subtrees, in order.
+    pub(crate) fn subtrees(&self) -> impl Iterator<Item = &Oid> {
+        self.handlers.iter().map(|(prefix, _)| prefix)
+    }
+
+    pub(crate) fn handler(&self, oid: &Oid) -> Option<&dyn Handler> {
+        self.handlers
+            .iter()
+            .find(|(prefix, _)| oid.starts_with(prefix))
+            .map(|(_, handler)| handler.as_ref())
+    }
+
+    pub(crate) fn get(&self, oid: &Oid) -> Value {
+        match self.handler(oid) {
+            Some(handler) => handler.get(oid),
+            None => Value::NoSuchObject,
+        }
+    }
+
+    /// The first instance after `oid` across all subtrees; SNMPv1 skips Counter64s, which
+    /// it cannot express (RFC 3584 4.2.2.1).
+    pub(crate) fn get_next(&self, oid: &Oid, v1: bool) -> (Oid, Value) {
+        let mut current = oid.clone();
+
+        'search: loop {
+            for (prefix, handler) in &self.handlers {
+                let next = if current.starts_with(prefix) {
+                    handler.get_next(&current)
+                } else if current < *prefix {
+                    handler.get_next(prefix)
+                } else {
+                    continue;
+                };
+
+                let Some((name, value)) = next.filter(|(name, value)| {
+                    name.starts_with(prefix) && *name > current && !value.is_exception()
+                }) else {
+                    continue;
+                };
+
+                if v1 && matches!(value, Value::Counter64(_)) {
+                    current = name;
+                    continue 'search;
+                }
+                return (name, value);
+            }
+
+            return (oid.clone(), Value::EndOfMibView);
+        }
+    }
+
+    /// GetBulk (RFC 3416 4.2.3): the successors of the first `non_repeaters` names, then
+    /// up to `max_repetitions` rows for the rest, stopping early once every repeater is
+    /// past the end of the MIB or the rows no longer fit in a message.
+    fn get_bulk(
+        &self,
+        names: &[Oid],
+        non_repeaters: usize,
+        max_repetitions: usize,
+    ) -> Vec<v2::VarBind> {
+        let non_repeaters = non_repeaters.min(names.len());
+        let mut vars: Vec<v2::VarBind> = names[..non_repeaters]
+            .iter()
+            .map(|name| var_bind(self.get_next(name, false)))
+            .collect();
+
+        let mut last = names[non_repeaters..].to_vec();
+        let mut size = 0;
+        for _ in 0..max_repetitions {
+            let mut ended = true;
+
+            for name in &mut last {
+                let (next, value) = self.get_next(name, false);
+                ended &= value == Value::EndOfMibView;
+                *name = next.clone();
+
+                let var = var_bind((next, value));
+                size += encode(&var).map_or(0, |bytes| bytes.len());
+                vars.push(var);
+            }
+
+            if ended || size > self.max_message_size {
+                break;
+            }
+        }
+
+        vars
+    }
+
+    /// Applies the bindings of a SetRequest in order, failing at the first one refused.
+    fn set(&self, bindings: &[(Oid, Value)]) -> Result<(), (ErrorStatus, u32)> {
+        for (i, (name, value)) in bindings.iter().enumerate() {
+            let handler = self
+                .handler(name)
+                .ok_or((ErrorStatus::NotWritable, i as u32 + 1))?;
+            handler
+                .set(name, value)
+                .map_err(|status| (status, i as u32 + 1))?;
+        }
+
+        Ok(())
+    }
+
+    /// The Response to a request PDU, before SNMPv1 translation; `None` for PDUs that
+    /// are not requests.
+    fn answer(&self, data: v2::Pdus, write: bool, v1: bool) -> Option<v2::Pdu> {
+        let (request, vars) = match data {
+            v2::Pdus::GetRequest(v2::GetRequest(pdu)) => {
+                let vars = pdu
+                    .variable_bindings
+                    .iter()
+                    .map(|var| {
+                        let name = Oid::from_asn(&var.name);
+                        let value = self.get(&name);
+                        var_bind((name, value))
+                    })
+                    .collect();
+                (pdu, vars)
+            }
+            v2::Pdus::GetNextRequest(v2::GetNextRequest(pdu)) => {
+                let vars = pdu
+                    .variable_bindings
+                    .iter()
+                    .map(|var| var_bind(self.get_next(&Oid::from_asn(&var.name), v1)))
+                    .collect();
+                (pdu, vars)
+            }
+            v2::Pdus::GetBulkRequest(v2::GetBulkRequest(bulk)) => {
+                let names: Vec<Oid> = bulk
+                    .variable_bindings
+                    .iter()
+                    .map(|var| Oid::from_asn(&var.name))
+                    .collect();
+                let vars = self.get_bulk(
+                    &names,
+                    bulk.non_repeaters as usize,
+                    bulk.max_repetitions as usize,
+                );
+                let request = v2::Pdu {
+                    request_id: bulk.request_id,
+                    error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+                    error_index: 0,
+                    variable_bindings: bulk.variable_bindings,
+                };
+                (request, vars)
+            }
+            v2::Pdus::SetRequest(v2::SetRequest(pdu)) => {
+                let bindings: Vec<(Oid, Value)> = pdu
+                    .variable_bindings
+                    .iter()
+                    .map(|var| (Oid::from_asn(&var.name), Value::from(var.value.clone())))
+                    .collect();
+
+                let applied = if write {
+                    self.set(&bindings)
+                } else {
+                    Err((ErrorStatus::NoAccess, 1))
+                };
+                if let Err((status, index)) = applied {
+                    return Some(error(pdu, status, index));
+                }
+
+                let vars = pdu.variable_bindings.clone();
+                (pdu, vars)
+            }
+            _ => return None,
+        };
+
+        // SNMPv1 has neither exceptions nor Counter64 (RFC 3584 4.2.2.1).
+        if v1 {
+            if let Some(i) = vars.iter().position(|var| {
+                let value = Value::from(var.value.clone());
+                value.is_exception() || matches!(value, Value::Counter64(_))
+            }) {
+                return Some(error(request, ErrorStatus::NoSuchName, i as u32 + 1));
+            }
+        }
+
+        Some(v2::Pdu {
+            request_id: request.request_id,
+            error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+            error_index: 0,
+            variable_bindings: vars,
+        })
+    }
+
+    /// Encodes the Response, cutting GetBulk bindings short or failing with tooBig when
+    /// it does not fit in [`Agent::max_message_size`].
+    fn encode_response(
+        &self,
+        mut response: v2::Pdu,
+        bulk: bool,
+        encode_message: impl Fn(v2::Pdu) -> SnmpResult<Vec<u8>>,
+    ) -> SnmpResult<Vec<u8>> {
+        let message = encode_message(response.clone())?;
+        if message.len() <= self.max_message_size {
+            return Ok(message);
+        }
+
+        let vars = mem::take(&mut response.variable_bindings);
+        if !bulk {
+            response.error_status = ErrorStatus::TooBig.into();
+            response.error_index = 0;
+            return encode_message(response);
+        }
+
+        // Each enclosing SEQUENCE may need up to three more length octets once filled.
+        let mut budget = self
+            .max_message_size
+            .saturating_sub(encode_message(response.clone())?.len() + 9);
+        for var in vars {
+            let len = encode(&var)?.len();
+            if len > budget {
+                break;
+            }
+            budget -= len;
+            response.variable_bindings.push(var);
+        }
+
+        encode_message(response)
+    }
+
+    /// The encoded response to one request message, or `None` when it gets none: for an
+    /// unknown community, anything undecodable and anything but a request.
+    pub fn respond(&self, request: &[u8]) -> Option<Vec<u8>> {
+        let response = match ber::message_version(request)? {
+            0 => {
+                let message: v1::Message<v1::Pdus> = decode(request).ok()?;
+                let write = self.access(&message.community)?;
+                let data = crate::v1::from_pdus(message.data).ok()?;
+
+                let mut response = self.answer(data, write, true)?;
+                if response.error_status != v2::Pdu::ERROR_STATUS_NO_ERROR {
+                    response.error_status = v1_status(response.error_status.into()).into();
+                }
+
+                self.encode_response(response, false, |response| {
+                    let data = crate::v1::to_pdus(v2::Pdus::Response(v2::Response(response)))?;
+                    encode(&v1::Message {
+                        version: message.version.clone(),
+                        community: message.community.clone(),
+                        data,
+                    })
+                })
+            }
+            1 => {
+                let message: v2c::Message<v2::Pdus> = decode(request).ok()?;
+                let write = self.access(&message.community)?;
+                let bulk = matches!(message.data, v2::Pdus::GetBulkRequest(_));
+
+                let response = self.answer(message.data, write, false)?;
+
+                self.encode_response(response, bulk, |response| {
+                    encode(&v2c::Message {
+                        version: message.version.clone(),
+                        community: message.community.clone(),
+                        data: v2::Pdus::Response(v2::Response(response)),
+                    })
+                })
+            }
+            _ => return None,
+        };
+
+        response
+            .inspect_err(|_err| {
+                trace::event!(debug, error = %_err, "failed to encode response");
+            })
+            .ok()
+    }
+
+    /// Whether `community` grants write access, or `None` when it grants none.
+    fn access(&self, community: &OctetString) -> Option<bool> {
+        if self.write_community.as_deref() == Some(&community[..]) {
+            Some(true)
+        } else if self.community[..] == community[..] {
+            Some(false)
+        } else {
+            trace::event!(debug, "dropping request with unknown community");
+            None
+        }
+    }
+
+    /// Answers requests arriving on `socket` until receiving fails. A read timeout on
+    /// the socket only makes receiving start over.
+    pub fn run(&self, socket: &UdpSocket) -> io::Result<()> {
+        self.serve(socket, &AtomicBool::new(false))
+    }
+
+    fn serve(&self, socket: &UdpSocket, stopped: &AtomicBool) -> io::Result<()> {
+        let mut buf = vec![0; UDP_MAX_MESSAGE_SIZE];
+
+        while !stopped.load(Ordering::Relaxed) {
+            let (len, source) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(err) if is_timeout(&err) => continue,
+                Err(err) if trap::is_icmp_error(&err) => continue,
+                Err(err) => return Err(err),
+            };
+
+            if let Some(response) = self.respond(&buf[..len]) {
+                if let Err(_err) = socket.send_to(&response, source) {
+                    trace::event!(debug, %source, error = %_err, "failed to send response");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Binds `addr`, e.g. `127.0.0.1:0` or `0.0.0.0:161`, and answers requests on it from
+    /// a background thread until the returned handle is dropped.
+    pub fn spawn<A: ToSocketAddrs>(self, addr: A) -> io::Result<AgentHandle> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(SHUTDOWN_POLL))?;
+
+        let shared = Arc::new(Shared {
+            agent: self,
+            socket,
+            stopped: AtomicBool::new(false),
+        });
+
+        let server = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                if let Err(_err) = shared.agent.serve(&shared.socket, &shared.stopped) {
+                    trace::event!(debug, error = %_err, "agent socket failed");
+                }
+            })
+        };
+
+        Ok(AgentHandle {
+            shared,
+            server: Some(server),
+        })
+    }
+}
+
+fn var_bind((name, value): (Oid, Value)) -> v2::VarBind {
+    v2::VarBind {
+        name: name.to_asn(),
+        value: value.into(),
+    }
+}
+
+/// A Response failing with `status` at the 1-based `index`, echoing the request bindings
+/// (RFC 3416 4.2.1).
+fn error(request: v2::Pdu, status: ErrorStatus, index: u32) -> v2::Pdu {
+    v2::Pdu {
+        error_status: status.into(),
+        error_index: index,
+        ..request
+    }
+}
+
+struct Shared {
+    agent: Agent,
+    socket: UdpSocket,
+    stopped: AtomicBool,
+}
+
+/// An [`Agent`] serving from a background thread, as started by [`Agent::spawn`]; it
+/// stops when this is dropped.
+pub struct AgentHandle {
+    shared: Arc<Shared>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl AgentHandle {
+    /// The address the agent is bound to, with the port picked for port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.shared.socket.local_addr()
+    }
+}
+
+impl Drop for AgentHandle {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
@@ -0,0 +1,742 @@
+//! AgentX subagents (RFC 2741): serving the subtrees of an [`Agent`] through the host's
+//! master agent, e.g. net-snmp's snmpd with `master agentx` in snmpd.conf, instead of
+//! binding a port of its own.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{Ipv4Addr, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::trace;
+use crate::{Agent, ErrorStatus, Oid, Value};
+
+/// The port masters listen on for AgentX over TCP.
+pub const TCP_PORT: u16 = 705;
+/// Where net-snmp's master listens by default.
+#[cfg(unix)]
+pub const UNIX_SOCKET: &str = "/var/agentx/master";
+
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 20;
+
+pub(crate) const NON_DEFAULT_CONTEXT: u8 = 0x08;
+pub(crate) const NETWORK_BYTE_ORDER: u8 = 0x10;
+
+pub(crate) const OPEN: u8 = 1;
+pub(crate) const CLOSE: u8 = 2;
+pub(crate) const REGISTER: u8 = 3;
+pub(crate) const GET: u8 = 5;
+pub(crate) const GET_NEXT: u8 = 6;
+pub(crate) const GET_BULK: u8 = 7;
+pub(crate) const TEST_SET: u8 = 8;
+pub(crate) const COMMIT_SET: u8 = 9;
+pub(crate) const UNDO_SET: u8 = 10;
+pub(crate) const CLEANUP_SET: u8 = 11;
+pub(crate) const PING: u8 = 13;
+pub(crate) const RESPONSE: u8 = 18;
+
+/// Response errors beyond the SNMP error-status values (RFC 2741 6.2.16).
+const PARSE_ERROR: u16 = 266;
+const PROCESSING_ERROR: u16 = 268;
+
+/// Close reason: the subagent is going away.
+const REASON_SHUTDOWN: u8 = 5;
+
+/// A Response's bindings, or its error and 1-based error index.
+type Answer = Result<Vec<(Oid, Value)>, (u16, u16)>;
+
+fn status(status: ErrorStatus) -> u16 {
+    u32::from(status) as u16
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// The fixed 20-octet header of every AgentX PDU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) kind: u8,
+    pub(crate) flags: u8,
+    pub(crate) session_id: u32,
+    pub(crate) transaction_id: u32,
+    pub(crate) packet_id: u32,
+}
+
+impl Header {
+    /// Encodes a PDU in network byte order, which it says in its flags.
+    pub(crate) fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        let mut pdu = Writer::default();
+        pdu.u8(VERSION);
+        pdu.u8(self.kind);
+        pdu.u8(self.flags | NETWORK_BYTE_ORDER);
+        pdu.u8(0);
+        pdu.u32(self.session_id);
+        pdu.u32(self.transaction_id);
+        pdu.u32(self.packet_id);
+        pdu.u32(payload.len() as u32);
+
+        let mut pdu = pdu.0;
+        pdu.extend_from_slice(payload);
+        pdu
+    }
+
+    /// Splits the first complete PDU off `bytes`: its header, its payload and its length.
+    pub(crate) fn decode(bytes: &[u8]) -> io::Result<Option<(Header, &[u8], usize)>> {
+        if bytes.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        if bytes[0] != VERSION {
+            return Err(invalid("unsupported AgentX version"));
+        }
+
+        let big_endian = bytes[2] & NETWORK_BYTE_ORDER != 0;
+        let mut fields = Reader::new(&bytes[4..HEADER_LEN], big_endian);
+        let header = Header {
+            kind: bytes[1],
+            flags: bytes[2],
+            session_id: fields.u32()?,
+            transaction_id: fields.u32()?,
+            packet_id: fields.u32()?,
+        };
+        let len = usize::try_from(fields.u32()?)
+            .ok()
+            .and_then(|len| len.checked_add(HEADER_LEN))
+            .ok_or_else(|| invalid("AgentX PDU too large"))?;
+
+        Ok(bytes
+            .get(HEADER_LEN..len)
+            .map(|payload| (header, payload, len)))
+    }
+
+    /// A reader for the PDU's payload, in the byte order the header announces.
+    pub(crate) fn reader<'a>(&self, payload: &'a [u8]) -> Reader<'a> {
+        Reader::new(payload, self.flags & NETWORK_BYTE_ORDER != 0)
+    }
+
+    fn response(&self) -> Header {
+        Header {
+            kind: RESPONSE,
+            flags: 0,
+            ..*self
+        }
+    }
+}
+
+/// Builds PDU payloads, always in network byte order.
+#[derive(Debug, Default)]
+pub(crate) struct Writer(pub(crate) Vec<u8>);
+
+impl Writer {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// An Object Identifier, shortened by the `1.3.6.1.x` prefix where it has one.
+    pub(crate) fn oid(&mut self, oid: &[u32], include: bool) {
+        let (prefix, arcs) = match oid {
+            [1, 3, 6, 1, prefix @ 1..=255, arcs @ ..] => (*prefix as u8, arcs),
+            _ => (0, oid),
+        };
+
+        self.u8(arcs.len() as u8);
+        self.u8(prefix);
+        self.u8(include.into());
+        self.u8(0);
+        for arc in arcs {
+            self.u32(*arc);
+        }
+    }
+
+    /// An Octet String, padded to a multiple of four octets.
+    pub(crate) fn octets(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.0.extend_from_slice(bytes);
+        self.0.resize(self.0.len() + (4 - bytes.len() % 4) % 4, 0);
+    }
+
+    /// Fails, writing nothing, for an INTEGER that does not fit its 32 bits.
+    pub(crate) fn var_bind(&mut self, name: &Oid, value: &Value) -> io::Result<()> {
+        let integer = match value {
+            Value::Integer(int) => {
+                i32::try_from(*int).map_err(|_| invalid("INTEGER out of 32-bit range"))?
+            }
+            _ => 0,
+        };
+        let kind = match value {
+            Value::Integer(_) => 2,
+            Value::OctetString(_) => 4,
+            Value::Null => 5,
+            Value::Oid(_) => 6,
+            Value::IpAddress(_) => 64,
+            Value::Counter32(_) => 65,
+            Value::Gauge32(_) => 66,
+            Value::TimeTicks(_) => 67,
+            Value::Opaque(_)
+            | Value::Float(_)
+            | Value::Double(_)
+            | Value::Integer64(_)
+            | Value::Unsigned64(_) => 68,
+            Value::Counter64(_) => 70,
+            Value::NoSuchObject => 128,
+            Value::NoSuchInstance => 129,
+            Value::EndOfMibView => 130,
+        };
+
+        self.u16(kind);
+        self.u16(0);
+        self.oid(name, false);
+        match value {
+            Value::Integer(_) => self.u32(integer as u32),
+            Value::OctetString(bytes) => self.octets(bytes),
+            Value::Opaque(_)
+            | Value::Float(_)
+            | Value::Double(_)
+            | Value::Integer64(_)
+            | Value::Unsigned64(_) => self.octets(&value.opaque_contents().unwrap_or_default()),
+            Value::Oid(oid) => self.oid(oid, false),
+            Value::IpAddress(addr) => self.octets(&addr.octets()),
+            Value::Counter32(n) | Value::Gauge32(n) | Value::TimeTicks(n) => self.u32(*n),
+            Value::Counter64(n) => self.u64(*n),
+            Value::Null | Value::NoSuchObject | Value::NoSuchInstance | Value::EndOfMibView => {}
+        }
+        Ok(())
+    }
+}
+
+/// Reads PDU payloads in the byte order their header announces.
+#[derive(Debug)]
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], big_endian: bool) -> Self {
+        Reader { bytes, big_endian }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let (taken, rest) = self
+            .bytes
+            .split_first_chunk()
+            .ok_or_else(|| invalid("truncated AgentX PDU"))?;
+        self.bytes = rest;
+
+        Ok(*taken)
+    }
+
+    pub(crate) fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take()?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take()?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let bytes = self.take()?;
+        Ok(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+
+    /// An Object Identifier and its include flag.
+    pub(crate) fn oid(&mut self) -> io::Result<(Oid, bool)> {
+        let [len, prefix, include, _] = self.take()?;
+
+        let mut arcs = match prefix {
+            0 => Vec::with_capacity(len.into()),
+            prefix => vec![1, 3, 6, 1, prefix.into()],
+        };
+        for _ in 0..len {
+            arcs.push(self.u32()?);
+        }
+
+        Ok((arcs.into(), include != 0))
+    }
+
+    pub(crate) fn octets(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        let padded = len.div_ceil(4).saturating_mul(4);
+        if padded > self.bytes.len() {
+            return Err(invalid("truncated AgentX PDU"));
+        }
+
+        let (bytes, rest) = self.bytes.split_at(padded);
+        self.bytes = rest;
+
+        Ok(bytes[..len].to_vec())
+    }
+
+    pub(crate) fn var_bind(&mut self) -> io::Result<(Oid, Value)> {
+        let kind = self.u16()?;
+        self.u16()?;
+        let (name, _) = self.oid()?;
+
+        let value = match kind {
+            2 => Value::Integer(self.u32()? as i32 as i64),
+            4 => Value::OctetString(self.octets()?),
+            5 => Value::Null,
+            6 => Value::Oid(self.oid()?.0.into()),
+            64 => {
+                let octets: [u8; 4] = self
+                    .octets()?
+                    .try_into()
+                    .map_err(|_| invalid("IpAddress is not four octets"))?;
+                Value::IpAddress(Ipv4Addr::from(octets))
+            }
+            65 => Value::Counter32(self.u32()?),
+            66 => Value::Gauge32(self.u32()?),
+            67 => Value::TimeTicks(self.u32()?),
+            68 => Value::from_opaque(self.octets()?),
+            70 => Value::Counter64(self.u64()?),
+            128 => Value::NoSuchObject,
+            129 => Value::NoSuchInstance,
+            130 => Value::EndOfMibView,
+            _ => return Err(invalid("unknown AgentX value type")),
+        };
+
+        Ok((name, value))
+    }
+
+    /// The SearchRanges making up the rest of a Get, GetNext or GetBulk PDU.
+    fn search_ranges(&mut self) -> io::Result<Vec<SearchRange>> {
+        let mut ranges = Vec::new();
+        while !self.is_empty() {
+            let (start, include) = self.oid()?;
+            let (end, _) = self.oid()?;
+            ranges.push(SearchRange {
+                start,
+                include,
+                end,
+            });
+        }
+
+        Ok(ranges)
+    }
+}
+
+/// Where a GetNext may look: from `start`, itself included when `include`, up to `end`
+/// exclusive, or to the end of the MIB when `end` is empty.
+#[derive(Debug, Clone)]
+struct SearchRange {
+    start: Oid,
+    include: bool,
+    end: Oid,
+}
+
+trait Stream: Read + Write + Send {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl Stream for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl Stream for UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+/// An AgentX session with a master agent, serving an [`Agent`]'s subtrees, e.g.
+/// `Subagent::connect_tcp("127.0.0.1:705", agent, "my service")?.run()`. The community
+/// settings of the agent do not apply: the master decides who may ask.
+///
+/// Connecting opens the session and registers every subtree of the agent; [`Subagent::run`]
+/// then answers the master's requests. Sets are checked for a handler at TestSet and applied
+/// in order at CommitSet, so one failing there can leave the ones before it applied.
+pub struct Subagent {
+    stream: Box<dyn Stream>,
+    agent: Agent,
+    session_id: u32,
+    packet_id: u32,
+    opened: Instant,
+    ping_interval: Duration,
+    buffer: Vec<u8>,
+    /// The bindings of the TestSet awaiting its CommitSet.
+    pending: Vec<(Oid, Value)>,
+}
+
+impl Subagent {
+    /// Opens a session with a master listening on TCP, usually on port [`TCP_PORT`].
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A, agent: Agent, descr: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+
+        Subagent::open(Box::new(stream), agent, descr)
+    }
+
+    /// Opens a session with a master listening on a Unix socket, usually [`UNIX_SOCKET`].
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>, agent: Agent, descr: &str) -> io::Result<Self> {
+        Subagent::open(Box::new(UnixStream::connect(path)?), agent, descr)
+    }
+
+    fn open(stream: Box<dyn Stream>, agent: Agent, descr: &str) -> io::Result<Self> {
+        let mut subagent = Subagent {
+            stream,
+            agent,
+            session_id: 0,
+            packet_id: 0,
+            opened: Instant::now(),
+            ping_interval: Duration::from_secs(15),
+            buffer: Vec::new(),
+            pending: Vec::new(),
+        };
+
+        // The master's default timeout, no subagent identifier (RFC 2741 6.2.1).
+        let mut open = Writer::default();
+        open.u32(0);
+        open.oid(&[], false);
+        open.octets(descr.as_bytes());
+        let (header, _) = subagent.call(OPEN, &open.0)?;
+        subagent.session_id = header.session_id;
+
+        let subtrees: Vec<Oid> = subagent.agent.subtrees().cloned().collect();
+        for subtree in subtrees {
+            // The master's default timeout and priority, no range.
+            let mut register = Writer::default();
+            register.u8(0);
+            register.u8(127);
+            register.u8(0);
+            register.u8(0);
+            register.oid(&subtree, false);
+            subagent.call(REGISTER, &register.0).map_err(|err| {
+                io::Error::new(err.kind(), format!("registering {}: {}", subtree, err))
+            })?;
+        }
+
+        Ok(subagent)
+    }
+
+    /// How long the session may be idle before the subagent pings the master, 15 seconds
+    /// by default. A master that leaves a ping unanswered ends [`Subagent::run`].
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    fn header(&mut self, kind: u8) -> Header {
+        self.packet_id = self.packet_id.wrapping_add(1);
+
+        Header {
+            kind,
+            flags: 0,
+            session_id: self.session_id,
+            transaction_id: 0,
+            packet_id: self.packet_id,
+        }
+    }
+
+    /// Waits for the next PDU, or `None` when none arrives within the read timeout.
+    fn recv(&mut self) -> io::Result<Option<(Header, Vec<u8>)>> {
+        loop {
+            if let Some((header, payload, len)) = Header::decode(&self.buffer)? {
+                let pdu = (header, payload.to_vec());
+                self.buffer.drain(..len);
+                return Ok(Some(pdu));
+            }
+
+            let mut chunk = [0; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "master closed the connection",
+                    ))
+                }
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(err) if crate::is_timeout(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Sends a PDU to the master and waits for its Response, failing on a Response error.
+    fn call(&mut self, kind: u8, payload: &[u8]) -> io::Result<(Header, Vec<u8>)> {
+        let header = self.header(kind);
+        self.stream.write_all(&header.encode(payload))?;
+
+        loop {
+            let Some((response, payload)) = self.recv()? else {
+                continue;
+            };
+            if response.kind != RESPONSE || response.packet_id != header.packet_id {
+                continue;
+            }
+
+            let mut fields = response.reader(&payload);
+            fields.u32()?;
+            match fields.u16()? {
+                0 => return Ok((response, payload)),
+                error => {
+                    return Err(io::Error::other(format!(
+                        "master answered with AgentX error {}",
+                        error
+                    )))
+                }
+            }
+        }
+    }
+
+    /// Answers the master's requests until it closes the session or stops answering pings.
+    pub fn run(&mut self) -> io::Result<()> {
+        self.stream.set_read_timeout(Some(self.ping_interval))?;
+        let mut ping: Option<u32> = None;
+
+        loop {
+            let Some((header, payload)) = self.recv()? else {
+                if ping.is_some() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "master did not answer a ping",
+                    ));
+                }
+
+                let header = self.header(PING);
+                self.stream.write_all(&header.encode(&[]))?;
+                ping = Some(header.packet_id);
+                continue;
+            };
+
+            match header.kind {
+                RESPONSE => {
+                    if ping == Some(header.packet_id) {
+                        ping = None;
+                    }
+                }
+                CLOSE => return Ok(()),
+                _ => {
+                    let response = self.answer(&header, header.reader(&payload));
+                    if let Some(response) = response {
+                        self.stream
+                            .write_all(&header.response().encode(&response))?;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Closes the session, telling the master the subagent is shutting down.
+    pub fn close(mut self) -> io::Result<()> {
+        let mut close = Writer::default();
+        close.u8(REASON_SHUTDOWN);
+        close.0.extend_from_slice(&[0; 3]);
+        let header = self.header(CLOSE);
+
+        self.stream.write_all(&header.encode(&close.0))
+    }
+
+    /// The bindings of a request's Response, or its error and error index; `None` for
+    /// CleanupSet, which gets no Response.
+    fn dispatch(
+        &mut self,
+        header: &Header,
+        payload: &mut Reader<'_>,
+    ) -> io::Result<Option<Answer>> {
+        if header.flags & NON_DEFAULT_CONTEXT != 0 {
+            // Only the default context is registered, so there is nothing to tell apart.
+            payload.octets()?;
+        }
+
+        let answer = match header.kind {
+            GET => Ok(payload
+                .search_ranges()?
+                .into_iter()
+                .map(|range| {
+                    let value = self.agent.get(&range.start);
+                    (range.start, value)
+                })
+                .collect()),
+            GET_NEXT => Ok(payload
+                .search_ranges()?
+                .iter()
+                .map(|range| self.next(range))
+                .collect()),
+            GET_BULK => {
+                let non_repeaters = payload.u16()? as usize;
+                let max_repetitions = payload.u16()? as usize;
+                let ranges = payload.search_ranges()?;
+                Ok(self.bulk(ranges, non_repeaters, max_repetitions))
+            }
+            TEST_SET => {
+                let mut bindings = Vec::new();
+                while !payload.is_empty() {
+                    bindings.push(payload.var_bind()?);
+                }
+                self.test_set(bindings)
+            }
+            COMMIT_SET => self.commit_set(),
+            UNDO_SET => {
+                self.pending.clear();
+                Err((status(ErrorStatus::UndoFailed), 0))
+            }
+            CLEANUP_SET => {
+                self.pending.clear();
+                return Ok(None);
+            }
+            _ => Err((PROCESSING_ERROR, 0)),
+        };
+
+        Ok(Some(answer))
+    }
+
+    /// The payload of the Response to a request, if it gets one.
+    fn answer(&mut self, header: &Header, mut payload: Reader<'_>) -> Option<Vec<u8>> {
+        let (vars, error, index) = match self.dispatch(header, &mut payload) {
+            Ok(None) => return None,
+            Ok(Some(Ok(vars))) => (vars, 0, 0),
+            Ok(Some(Err((error, index)))) => (Vec::new(), error, index),
+            Err(_err) => {
+                trace::event!(debug, error = %_err, "malformed AgentX request");
+                (Vec::new(), PARSE_ERROR, 0)
+            }
+        };
+
+        let mut response = self.response(error, index);
+        for (i, (name, value)) in vars.iter().enumerate() {
+            if let Err(_err) = response.var_bind(name, value) {
+                trace::event!(debug, error = %_err, "unencodable AgentX binding");
+                let response = self.response(status(ErrorStatus::GenErr), i as u16 + 1);
+                return Some(response.0);
+            }
+        }
+
+        Some(response.0)
+    }
+
+    /// The start of a Response payload, up to the bindings.
+    fn response(&self, error: u16, index: u16) -> Writer {
+        let mut response = Writer::default();
+        response.u32((self.opened.elapsed().as_millis() / 10) as u32);
+        response.u16(error);
+        response.u16(index);
+        response
+    }
+
+    fn next(&self, range: &SearchRange) -> (Oid, Value) {
+        if range.include {
+            let value = self.agent.get(&range.start);
+            if !value.is_exception() {
+                return (range.start.clone(), value);
+            }
+        }
+
+        match self.agent.get_next(&range.start, false) {
+            (name, _) if !range.end.is_empty() && name >= range.end => {
+                (range.start.clone(), Value::EndOfMibView)
+            }
+            next => next,
+        }
+    }
+
+    fn bulk(
+        &self,
+        ranges: Vec<SearchRange>,
+        non_repeaters: usize,
+        max_repetitions: usize,
+    ) -> Vec<(Oid, Value)> {
+        let non_repeaters = non_repeaters.min(ranges.len());
+        let mut vars: Vec<(Oid, Value)> = ranges[..non_repeaters]
+            .iter()
+            .map(|range| self.next(range))
+            .collect();
+
+        let mut repeaters = ranges[non_repeaters..].to_vec();
+        for _ in 0..max_repetitions {
+            let mut ended = true;
+
+            for range in &mut repeaters {
+                let (name, value) = self.next(range);
+                ended &= value == Value::EndOfMibView;
+                range.start = name.clone();
+                range.include = false;
+                vars.push((name, value));
+            }
+
+            if ended {
+                break;
+            }
+        }
+
+        vars
+    }
+
+    /// Checks that every binding has a handler and, for existing instances, the type they
+    /// already have.
+    fn test_set(&mut self, bindings: Vec<(Oid, Value)>) -> Answer {
+        for (i, (name, value)) in bindings.iter().enumerate() {
+            let index = i as u16 + 1;
+            let Some(handler) = self.agent.handler(name) else {
+                return Err((status(ErrorStatus::NotWritable), index));
+            };
+
+            let current = handler.get(name);
+            if !current.is_exception() && mem::discriminant(&current) != mem::discriminant(value) {
+                return Err((status(ErrorStatus::WrongType), index));
+            }
+        }
+
+        self.pending = bindings;
+        Ok(Vec::new())
+    }
+
+    fn commit_set(&mut self) -> Answer {
+        for (i, (name, value)) in self.pending.drain(..).enumerate() {
+            let applied = self
+                .agent
+                .handler(&name)
+                .map_or(Err(ErrorStatus::NotWritable), |handler| {
+                    handler.set(&name, &value)
+                });
+
+            if let Err(_status) = applied {
+                trace::event!(debug, %name, status = %_status, "commit failed");
+                return Err((status(ErrorStatus::CommitFailed), i as u16 + 1));
+            }
+        }
+
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,250 @@
+//! Raising and clearing alarms on thresholds over collected samples.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Instant, SystemTime};
+
+use crate::{CounterTracker, IntoOid, Oid, Sample, SnmpResult, Value};
+
+/// What a [`Rule`] measures of every instance it applies to, as a number.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Measure {
+    /// The value of instances under the OID, e.g. hrProcessorLoad.
+    Value(Oid),
+    /// How fast the counters under the OID increase, per second, between successive
+    /// samples of a job, e.g. ifInErrors.
+    Rate(Oid),
+    /// The values under the first OID divided by those with the same index under the
+    /// second, e.g. hrStorageUsed by hrStorageSize.
+    Ratio(Oid, Oid),
+}
+
+impl Measure {
+    pub fn value(oid: impl IntoOid) -> SnmpResult<Self> {
+        Ok(Measure::Value(oid.into_oid()?))
+    }
+
+    pub fn rate(oid: impl IntoOid) -> SnmpResult<Self> {
+        Ok(Measure::Rate(oid.into_oid()?))
+    }
+
+    pub fn ratio(numerator: impl IntoOid, denominator: impl IntoOid) -> SnmpResult<Self> {
+        Ok(Measure::Ratio(
+            numerator.into_oid()?,
+            denominator.into_oid()?,
+        ))
+    }
+
+    fn oids(&self) -> Vec<&Oid> {
+        match self {
+            Measure::Value(oid) | Measure::Rate(oid) => vec![oid],
+            Measure::Ratio(numerator, denominator) => vec![numerator, denominator],
+        }
+    }
+}
+
+/// A threshold on a [`Measure`], with hysteresis: an alarm raised once the measure crosses
+/// the threshold is only cleared once it is back past the clearing level, which defaults
+/// to the threshold itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    name: String,
+    measure: Measure,
+    raise: f64,
+    clear: f64,
+    above: bool,
+}
+
+impl Rule {
+    /// Raises an alarm when the measure goes above `threshold`, e.g.
+    /// `Rule::above("errors", Measure::rate(IF_IN_ERRORS)?, 100.0 / 60.0)` for more than
+    /// 100 errors a minute.
+    pub fn above(name: impl Into<String>, measure: Measure, threshold: f64) -> Self {
+        Rule {
+            name: name.into(),
+            measure,
+            raise: threshold,
+            clear: threshold,
+            above: true,
+        }
+    }
+
+    /// Raises an alarm when the measure goes below `threshold`.
+    pub fn below(name: impl Into<String>, measure: Measure, threshold: f64) -> Self {
+        Rule {
+            above: false,
+            ..Rule::above(name, measure, threshold)
+        }
+    }
+
+    /// Clears a raised alarm only once the measure is back at or past `level`, below the
+    /// threshold of an [`above`](Rule::above) rule or above that of a
+    /// [`below`](Rule::below) one.
+    pub fn clear_at(mut self, level: f64) -> Self {
+        self.clear = level;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn raises(&self, measured: f64) -> bool {
+        if self.above {
+            measured > self.raise
+        } else {
+            measured < self.raise
+        }
+    }
+
+    fn clears(&self, measured: f64) -> bool {
+        if self.above {
+            measured <= self.clear
+        } else {
+            measured >= self.clear
+        }
+    }
+}
+
+/// A change of an alarm, for one rule, job and instance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    /// Index of the rule in the order they were added.
+    pub rule: usize,
+    /// The job of the sample, as in [`Sample::job`].
+    pub job: usize,
+    /// The index of the instance under the measured OIDs, `[0]` for a scalar.
+    pub index: Vec<u32>,
+    /// What was measured.
+    pub measured: f64,
+    /// Whether the alarm was raised, or cleared.
+    pub raised: bool,
+    pub time: SystemTime,
+}
+
+/// Evaluates [`Rule`]s over the [`Sample`]s of a [`Scheduler`](crate::Scheduler), keeping
+/// track of the counters rates are computed from and of which alarms are raised, e.g.
+/// `for sample in scheduler.samples() { for alarm in evaluator.evaluate(&sample) { .. } }`.
+#[derive(Debug, Default)]
+pub struct Evaluator {
+    rules: Vec<Rule>,
+    /// Counters by rule and job, so that rules on the same counter each see every sample.
+    rates: HashMap<(usize, usize), CounterTracker>,
+    /// Raised alarms by rule, job and index.
+    raised: HashSet<(usize, usize, Vec<u32>)>,
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Evaluator::default()
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Measures every instance of `sample` the rules apply to, and returns the alarms that
+    /// were raised or cleared by it. Failed samples change nothing.
+    pub fn evaluate(&mut self, sample: &Sample) -> Vec<Alarm> {
+        let Ok(collected) = &sample.result else {
+            return Vec::new();
+        };
+        let bindings = collected.bindings();
+
+        let mut alarms = Vec::new();
+        for rule in 0..self.rules.len() {
+            for (index, measured) in self.measure(rule, sample.job, &bindings, sample.started) {
+                let key = (rule, sample.job, index);
+                let raised = self.raised.contains(&key);
+
+                let changed = if raised {
+                    self.rules[rule].clears(measured) && self.raised.remove(&key)
+                } else {
+                    self.rules[rule].raises(measured) && self.raised.insert(key.clone())
+                };
+                if changed {
+                    alarms.push(Alarm {
+                        rule,
+                        job: sample.job,
+                        index: key.2,
+                        measured,
+                        raised: !raised,
+                        time: sample.time,
+                    });
+                }
+            }
+        }
+
+        alarms
+    }
+
+    /// The alarms raised now, by rule, job and index.
+    pub fn raised(&self) -> impl Iterator<Item = (usize, usize, &[u32])> {
+        self.raised
+            .iter()
+            .map(|(rule, job, index)| (*rule, *job, index.as_slice()))
+    }
+
+    /// The OIDs the rules measure, for asking for all of them in a [`Job`](crate::Job).
+    pub fn oids(&self) -> Vec<Oid> {
+        let mut oids: Vec<Oid> = self
+            .rules
+            .iter()
+            .flat_map(|rule| rule.measure.oids())
+            .cloned()
+            .collect();
+        oids.sort();
+        oids.dedup();
+        oids
+    }
+
+    /// The measure of `rule` for every instance in `bindings`, by index.
+    fn measure(
+        &mut self,
+        rule: usize,
+        job: usize,
+        bindings: &[(Oid, Value)],
+        at: Instant,
+    ) -> BTreeMap<Vec<u32>, f64> {
+        let measure = &self.rules[rule].measure;
+        let under = |oid: &Oid| -> BTreeMap<Vec<u32>, &Value> {
+            bindings
+                .iter()
+                .filter_map(|(name, value)| Some((name.suffix(oid)?.to_vec(), value)))
+                .collect()
+        };
+        let number = |value: &Value| f64::try_from(value.clone()).ok();
+
+        match measure {
+            Measure::Value(oid) => under(oid)
+                .into_iter()
+                .filter_map(|(index, value)| Some((index, number(value)?)))
+                .collect(),
+            Measure::Rate(oid) => {
+                let rates = self.rates.entry((rule, job)).or_default();
+                under(oid)
+                    .into_iter()
+                    .filter_map(|(index, value)| {
+                        let instance: Oid = [oid.as_slice(), &index].concat().into();
+                        Some((index, rates.record_at(instance, value, at)?))
+                    })
+                    .collect()
+            }
+            Measure::Ratio(numerator, denominator) => {
+                let denominators = under(denominator);
+                under(numerator)
+                    .into_iter()
+                    .filter_map(|(index, value)| {
+                        let denominator = number(denominators.get(&index)?)?;
+                        let ratio = number(value)? / denominator;
+                        ratio.is_finite().then_some((index, ratio))
+                    })
+                    .collect()
+            }
+        }
+    }
+}
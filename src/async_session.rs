@@ -0,0 +1,1217 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::ControlFlow;
+use std::slice;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use rasn_snmp::v2;
+use tokio::net::ToSocketAddrs;
+
+use crate::bridge::{
+    self, BASE_PORT_COLUMNS, BASE_PORT_TABLE, FDB_COLUMNS, Q_TP_FDB_TABLE, TP_FDB_TABLE,
+};
+use crate::buffers::BufferPool;
+use crate::builder::Config;
+use crate::hooks::{Answer, Hooks};
+use crate::interfaces::{self, IF_COLUMNS, IF_TABLE, IF_X_COLUMNS, IF_X_TABLE};
+use crate::limit::Permit;
+use crate::neighbors::{
+    self, NET_TO_MEDIA_COLUMNS, NET_TO_MEDIA_TABLE, NET_TO_PHYSICAL_COLUMNS, NET_TO_PHYSICAL_TABLE,
+};
+use crate::options::Context;
+use crate::row::{self, Row};
+use crate::security::Security;
+use crate::stats::Stats;
+use crate::system::SYSTEM_OIDS;
+use crate::table::TableWalk;
+use crate::trace;
+use crate::visit::VisitWalk;
+#[cfg(feature = "pcap")]
+use crate::PcapWriter;
+use crate::{
+    oid, pdu, v1, AsyncTransport, AsyncUdpTransport, Decoding, EngineId, FdbEntry, Interface,
+    IntoOid, Neighbor, Notification, Oid, PartialWalk, RateLimiter, RequestOptions, RetryPolicy,
+    RowStatus, SessionBuilder, SessionStats, SnmpError, SnmpResult, SystemInfo, Table, UsmUser,
+    Value, ValueRef, Version, WalkCursor,
+};
+
+/// The non-blocking counterpart of [`SyncSession`](crate::SyncSession), built on tokio.
+pub struct AsyncSession<T = AsyncUdpTransport> {
+    security: Security,
+    transport: T,
+    timeout: Duration,
+    retry: RetryPolicy,
+    max_message_size: usize,
+    recv_buffer_size: usize,
+    exceptions_as_errors: bool,
+    decoding: Decoding,
+    version_fallback: bool,
+    limiter: Option<RateLimiter>,
+    resolve_every: Option<Duration>,
+    resolve_on_failure: bool,
+    /// The SNMPv3 context of requests that do not give their own.
+    context: Context,
+    /// When the agent was last looked up, in milliseconds since `started`.
+    resolved: AtomicU64,
+    request_id: AtomicI32,
+    started: Instant,
+    stats: Stats,
+    buffers: BufferPool,
+    hooks: Hooks,
+    #[cfg(feature = "pcap")]
+    capture: Option<PcapWriter>,
+}
+
+impl AsyncSession {
+    /// Starts configuring a session to `host`; finish with
+    /// [`SessionBuilder::build_async`].
+    pub fn builder(host: impl Into<String>) -> SessionBuilder {
+        SessionBuilder::new(host)
+    }
+
+    /// Creates a community-based session; `version` is the wire value, 0 for SNMPv1 and 1
+    /// for SNMPv2c.
+    pub async fn new<A>(
+        version: u8,
+        dest_addr: A,
+        community: &[u8],
+        timeout: u64,
+    ) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let config = Config::with_timeout(Duration::from_millis(timeout));
+
+        Self::open(Security::community(version, community), dest_addr, config).await
+    }
+
+    /// Creates an SNMPv3 session; the agent's engine is discovered on the first request.
+    pub async fn new_v3<A>(dest_addr: A, user: UsmUser, timeout: u64) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let config = Config::with_timeout(Duration::from_millis(timeout));
+        let security = Security::usm(user, None, config.max_message_size)?;
+
+        Self::open(security, dest_addr, config).await
+    }
+
+    pub(crate) async fn open<A>(
+        security: Security,
+        dest_addr: A,
+        config: Config,
+    ) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let transport = AsyncUdpTransport::open(dest_addr, &config.socket).await?;
+
+        Ok(Self::with_transport(security, transport, config))
+    }
+}
+
+impl<T: AsyncTransport> AsyncSession<T> {
+    pub(crate) fn with_transport(security: Security, transport: T, config: Config) -> Self {
+        AsyncSession {
+            security,
+            max_message_size: config.max_message_size.min(transport.max_msg_size()),
+            recv_buffer_size: config
+                .recv_buffer_size
+                .unwrap_or_else(|| transport.max_msg_size()),
+            exceptions_as_errors: config.exceptions_as_errors,
+            decoding: config.decoding,
+            version_fallback: config.version_fallback,
+            limiter: config.rate_limit,
+            resolve_every: config.resolve_every,
+            resolve_on_failure: config.resolve_on_failure,
+            context: config.context,
+            resolved: AtomicU64::new(0),
+            transport,
+            timeout: config.timeout,
+            retry: config.retry,
+            request_id: AtomicI32::new(pdu::initial_request_id()),
+            started: Instant::now(),
+            stats: Stats::default(),
+            buffers: BufferPool::default(),
+            hooks: config.hooks,
+            #[cfg(feature = "pcap")]
+            capture: config.capture,
+        }
+    }
+
+    /// Sends `message`, the encoding of `request`, as the send hook leaves it, once the rate
+    /// limit allows, and captures what was sent; the permit counts the request as
+    /// outstanding until dropped.
+    async fn transmit(
+        &self,
+        message: &[u8],
+        request: Option<&v2::Pdus>,
+    ) -> SnmpResult<Option<Permit>> {
+        let permit = match &self.limiter {
+            Some(limiter) => Some(limiter.acquire_async().await),
+            None => None,
+        };
+        let message = self.hooks.sending(message, request);
+        self.transport.send(&message).await?;
+        #[cfg(feature = "pcap")]
+        if let Some(capture) = &self.capture {
+            let (local, peer) = (self.transport.local_addr(), self.transport.peer_addr());
+            capture.record(local, peer, &message, true);
+        }
+        trace::event!(trace, len = message.len(), "sent message");
+        Ok(permit)
+    }
+
+    /// Sends `send` and feeds received datagrams to `accept` until it returns a value,
+    /// retransmitting according to the retry policy whenever the timeout runs out first.
+    /// Malformed or unauthentic datagrams are dropped; the request fails with why only if
+    /// nothing else answers it.
+    async fn send_and_recv<R>(
+        &self,
+        send: &[u8],
+        request: Option<&v2::Pdus>,
+        opts: &RequestOptions,
+        mut accept: impl FnMut(&[u8]) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R>
+    where
+        R: Answer,
+    {
+        let mut recv = self.buffers.get().zeroed(self.recv_buffer_size);
+        let (timeout, retry) = (opts.timeout_or(self.timeout), opts.retry_or(&self.retry));
+        // Why the last datagram was dropped, should no other answer the request.
+        let mut rejected = None;
+
+        for attempt in 0..=retry.retries() {
+            if attempt > 0 {
+                let pause = retry.pause(attempt);
+                trace::event!(debug, attempt, ?pause, "retransmitting request");
+                tokio::time::sleep(pause).await;
+            }
+
+            let _permit = self.transmit(send, request).await?;
+            self.stats.sent(attempt);
+            let sent = Instant::now();
+
+            let deadline = tokio::time::Instant::now() + timeout;
+
+            loop {
+                match tokio::time::timeout_at(deadline, self.transport.recv(&mut recv)).await {
+                    Ok(Ok(len)) => {
+                        trace::event!(trace, len, "received message");
+                        #[cfg(feature = "pcap")]
+                        if let Some(capture) = &self.capture {
+                            let (local, peer) =
+                                (self.transport.local_addr(), self.transport.peer_addr());
+                            capture.record(local, peer, &recv[..len], false);
+                        }
+                        let accepted = pdu::check_truncated(&recv[..len], recv.len())
+                            .and_then(|()| accept(&recv[..len]));
+                        let answer = accepted.as_ref().ok().and_then(Option::as_ref);
+                        self.hooks.received(&recv[..len], answer);
+                        match accepted {
+                            Ok(Some(value)) => {
+                                self.stats.answered(attempt, sent.elapsed());
+                                return Ok(value);
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                self.stats.rejected(&err);
+                                if !err.is_bad_datagram() {
+                                    return Err(err);
+                                }
+                                trace::event!(debug, error = %err, "dropping rejected message");
+                                rejected = Some(err);
+                            }
+                        }
+                    }
+                    Ok(Err(err)) => return Err(err.into()),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        trace::event!(debug, attempts = retry.retries() + 1, "request timed out");
+        self.stats.timed_out();
+        Err(rejected.unwrap_or(SnmpError::Timeout))
+    }
+
+    /// The agent address requests currently go to; see [`SyncSession::peer_addr`](crate::SyncSession::peer_addr).
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+
+    /// Counters of the requests sent so far; see [`SyncSession::stats`](crate::SyncSession::stats).
+    pub fn stats(&self) -> SessionStats {
+        self.stats.snapshot()
+    }
+
+    /// The version requests are sent in; see [`SyncSession::version`](crate::SyncSession::version).
+    pub fn version(&self) -> Version {
+        self.security.version()
+    }
+
+    /// The community requests are sent with; see
+    /// [`SyncSession::community`](crate::SyncSession::community).
+    pub fn community(&self) -> Option<&[u8]> {
+        self.security.current_community()
+    }
+
+    /// The engine ID the agent reported in SNMPv3 discovery; see
+    /// [`SyncSession::engine_id`](crate::SyncSession::engine_id).
+    pub fn engine_id(&self) -> Option<EngineId> {
+        self.security.engine_id()
+    }
+
+    /// Replaces the retry policy used for every request from now on.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    fn next_request_id(&self) -> i32 {
+        self.request_id.fetch_add(1, Ordering::Relaxed) & i32::MAX
+    }
+
+    async fn request(&self, data: v2::Pdus, opts: &RequestOptions) -> SnmpResult<v2::Pdus> {
+        self.request_with(data, opts, |response, message, request_id| {
+            self.security
+                .decode(response, message, request_id, self.decoding)
+        })
+        .await
+    }
+
+    /// Sends `data` like [`Self::request`], with the responses taken by `accept`, given
+    /// the message and request-id they should answer.
+    async fn request_with<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        mut accept: impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        self.resolve_if_due().await;
+
+        let result = match self.exchange(data.clone(), opts, &mut accept).await {
+            // The agent rebooted or was replaced; resend once against the relearned engine.
+            Err(SnmpError::NotInTimeWindow | SnmpError::UnknownEngineId)
+                if self.security.resync() =>
+            {
+                trace::event!(debug, "resending request after engine resync");
+                self.exchange(data, opts, &mut accept).await
+            }
+            Err(SnmpError::Timeout) => self.fail_over(data, opts, &mut accept).await,
+            result => result,
+        };
+
+        if let Err(_err) = &result {
+            trace::event!(debug, error = %_err, "request failed");
+        }
+        result
+    }
+
+    /// Resends a request that timed out to each of the agent's other addresses, staying
+    /// with the first that answers, then with other credentials.
+    async fn fail_over<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        for _ in 0..self.transport.alternatives() {
+            self.transport.fail_over()?;
+            trace::event!(debug, peer = ?self.transport.peer_addr().ok(), "failing over");
+
+            match self.exchange(data.clone(), opts, accept).await {
+                Err(SnmpError::Timeout) => continue,
+                result => return result,
+            }
+        }
+
+        if self.resolve_on_failure && self.resolve().await {
+            match self.exchange(data.clone(), opts, accept).await {
+                Err(SnmpError::Timeout) => {}
+                result => return result,
+            }
+        }
+
+        self.fall_back(data, opts, accept).await
+    }
+
+    /// Looks the agent up again once [`SessionBuilder::resolve_every`](crate::SessionBuilder::resolve_every)
+    /// has passed since it last was.
+    async fn resolve_if_due(&self) {
+        let Some(every) = self.resolve_every else {
+            return;
+        };
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.resolved.load(Ordering::Relaxed);
+
+        // Of requests sent at once, only one looks the agent up.
+        if now.saturating_sub(last) >= every.as_millis() as u64
+            && self
+                .resolved
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.resolve().await;
+        }
+    }
+
+    /// Looks the agent up again and returns whether its addresses changed.
+    async fn resolve(&self) -> bool {
+        match self.transport.resolve().await {
+            Ok(changed) => {
+                if changed {
+                    trace::event!(debug, peer = ?self.transport.peer_addr().ok(), "agent moved");
+                }
+                changed
+            }
+            Err(_err) => {
+                trace::event!(debug, error = %_err, "looking the agent up again failed");
+                false
+            }
+        }
+    }
+
+    /// Resends a request that timed out with each of the other communities, and versions
+    /// with version fallback, keeping the first that is answered.
+    async fn fall_back<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        let Some(original) = self.security.credentials() else {
+            return Err(SnmpError::Timeout);
+        };
+
+        for credentials in self.security.fallbacks(self.version_fallback) {
+            self.security.set_credentials(credentials);
+            trace::event!(debug, ?credentials, "retrying with other credentials");
+
+            if let Ok(answer) = self.exchange(data.clone(), opts, accept).await {
+                return Ok(answer);
+            }
+        }
+
+        self.security.set_credentials(original);
+        Err(SnmpError::Timeout)
+    }
+
+    async fn exchange<R: Answer>(
+        &self,
+        mut data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        while let Some(message) = self.security.handshake()? {
+            self.send_and_recv(&message, None, opts, |response| {
+                self.security.complete_handshake(response).map(Some)
+            })
+            .await?;
+        }
+
+        let request_id = self.next_request_id();
+        pdu::set_request_id(&mut data, request_id);
+        let request = self.hooks.request(&data);
+
+        let mut message = self.buffers.get();
+        let context = opts.context_or(&self.context);
+        self.security
+            .encode(data, &context, self.max_message_size, &mut message)?;
+
+        self.send_and_recv(&message, request.as_ref(), opts, |response| {
+            accept(response, &message, request_id)
+        })
+        .await
+    }
+
+    pub async fn get(&self, oid: impl IntoOid) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_with(oid, &RequestOptions::default()).await
+    }
+
+    /// [`AsyncSession::get`] with the session's settings overridden by `opts`; see
+    /// [`SyncSession::get_with`](crate::SyncSession::get_with).
+    pub async fn get_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_many_with(&[oid.into_oid()?], opts).await
+    }
+
+    pub async fn getnext(&self, oid: impl IntoOid) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_with(oid, &RequestOptions::default()).await
+    }
+
+    pub async fn getnext_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_many_with(&[oid.into_oid()?], opts).await
+    }
+
+    /// Fetches several OIDs in one GET; see
+    /// [`SyncSession::get_many`](crate::SyncSession::get_many).
+    pub async fn get_many<O: IntoOid + Clone>(&self, oids: &[O]) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_many_with(oids, &RequestOptions::default()).await
+    }
+
+    pub async fn get_many_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let vars = self.fetch(&oid::into_oids(oids)?, opts).await?;
+
+        if self.exceptions_as_errors {
+            pdu::check_exceptions(&vars)?;
+        }
+        Ok(vars)
+    }
+
+    /// GETs `oid` as text; see [`SyncSession::get_string`](crate::SyncSession::get_string).
+    pub async fn get_string(&self, oid: impl IntoOid) -> SnmpResult<String> {
+        self.get_value(oid).await?.try_into()
+    }
+
+    /// GETs `oid` as a 32-bit unsigned integer; see [`SyncSession::get_u32`](crate::SyncSession::get_u32).
+    pub async fn get_u32(&self, oid: impl IntoOid) -> SnmpResult<u32> {
+        self.get_value(oid).await?.try_into()
+    }
+
+    /// GETs `oid` as an unsigned integer; see [`SyncSession::get_u64`](crate::SyncSession::get_u64).
+    pub async fn get_u64(&self, oid: impl IntoOid) -> SnmpResult<u64> {
+        self.get_value(oid).await?.try_into()
+    }
+
+    /// GETs `oid` as an address; see [`SyncSession::get_ip`](crate::SyncSession::get_ip).
+    pub async fn get_ip(&self, oid: impl IntoOid) -> SnmpResult<IpAddr> {
+        self.get_value(oid).await?.try_into()
+    }
+
+    /// GETs `oid` as an OBJECT IDENTIFIER; see [`SyncSession::get_oid`](crate::SyncSession::get_oid).
+    pub async fn get_oid(&self, oid: impl IntoOid) -> SnmpResult<Oid> {
+        self.get_value(oid).await?.try_into()
+    }
+
+    /// GETs `oid` alone, failing on exceptions and on a response for another OID.
+    async fn get_value(&self, oid: impl IntoOid) -> SnmpResult<Value> {
+        let oid = oid.into_oid()?;
+        let vars = self
+            .fetch(slice::from_ref(&oid), &RequestOptions::default())
+            .await?;
+        pdu::check_exceptions(&vars)?;
+
+        match vars.into_iter().next() {
+            Some((name, value)) if name == oid => Ok(value),
+            _ => Err(SnmpError::InvalidMessage(
+                "response OID does not match the request",
+            )),
+        }
+    }
+
+    /// GETs `oids`, returning exceptions as values regardless of the session's setting.
+    async fn fetch(&self, oids: &[Oid], opts: &RequestOptions) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut batches = pdu::Batches::new(oids);
+        let mut vars = Vec::with_capacity(oids.len());
+
+        while let Some(batch) = batches.next() {
+            match pdu::parse_aligned_response(self.request(pdu::get(batch), opts).await?, batch) {
+                Ok(batch) => {
+                    vars.extend(batch);
+                    batches.advance();
+                }
+                Err(err) => batches.shrink(err)?,
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// Fetches the successors of several OIDs in one GETNEXT; see
+    /// [`SyncSession::getnext_many`](crate::SyncSession::getnext_many).
+    pub async fn getnext_many<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_many_with(oids, &RequestOptions::default())
+            .await
+    }
+
+    pub async fn getnext_many_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let oids = oid::into_oids(oids)?;
+
+        let mut batches = pdu::Batches::new(&oids);
+        let mut vars = Vec::with_capacity(oids.len());
+
+        while let Some(batch) = batches.next() {
+            match self.getnext_batch(batch.to_vec(), opts).await {
+                Ok(batch) => {
+                    vars.extend(batch);
+                    batches.advance();
+                }
+                Err(err) => batches.shrink(err)?,
+            }
+        }
+
+        Ok(vars)
+    }
+
+    async fn getnext_batch(
+        &self,
+        oids: Vec<Oid>,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut next = pdu::NextRequest::new(oids);
+
+        while let Some(data) = next.request() {
+            if let Some(vars) = next.response(self.request(data, opts).await?)? {
+                return Ok(vars);
+            }
+        }
+
+        Ok(next.finish())
+    }
+
+    /// Issues a GETBULK; see [`SyncSession::getbulk`](crate::SyncSession::getbulk).
+    pub async fn getbulk<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        non_repeaters: u32,
+        max_repetitions: u32,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let opts = RequestOptions::new().max_repetitions(max_repetitions);
+
+        self.getbulk_with(oids, non_repeaters, &opts).await
+    }
+
+    /// [`AsyncSession::getbulk`] with max-repetitions taken from `opts`.
+    pub async fn getbulk_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        non_repeaters: u32,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut max_repetitions = opts.max_repetitions_or_default();
+
+        self.bulk(
+            &oid::into_oids(oids)?,
+            non_repeaters,
+            &mut max_repetitions,
+            opts,
+        )
+        .await
+    }
+
+    /// A GETBULK that leaves `max_repetitions` at the value the agent last accepted.
+    async fn bulk(
+        &self,
+        oids: &[Oid],
+        non_repeaters: u32,
+        max_repetitions: &mut u32,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        loop {
+            let data = pdu::getbulk(oids, non_repeaters, *max_repetitions);
+
+            match self.request(data, opts).await.and_then(|data| {
+                pdu::parse_bulk_response(data, oids, non_repeaters, *max_repetitions)
+            }) {
+                Err(err) if err.is_too_big() && *max_repetitions > 1 => {
+                    *max_repetitions /= 2;
+                    trace::event!(
+                        debug,
+                        max_repetitions,
+                        "reducing max-repetitions after tooBig"
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub async fn set<O: IntoOid + Clone>(
+        &self,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.set_with(bindings, &RequestOptions::default()).await
+    }
+
+    pub async fn set_with<O: IntoOid + Clone>(
+        &self,
+        bindings: &[(O, Value)],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let bindings = oid::into_bindings(bindings)?;
+        let data = self.request(pdu::set(&bindings), opts).await?;
+        let response = pdu::parse_set_response(data, &bindings)?;
+
+        if opts.verifies() {
+            let oids: Vec<Oid> = bindings.iter().map(|(oid, _)| oid.clone()).collect();
+            pdu::verify_set(&bindings, self.fetch(&oids, opts).await?)?;
+        }
+
+        Ok(response)
+    }
+
+    /// Creates a row of a table; see [`SyncSession::create_row`](crate::SyncSession::create_row).
+    pub async fn create_row(
+        &self,
+        entry: impl IntoOid,
+        status_column: u32,
+        index: &[u32],
+        columns: &[(u32, Value)],
+    ) -> SnmpResult<()> {
+        let row = Row::new(entry.into_oid()?, status_column, index);
+
+        match self
+            .set(&row.with_status(columns, RowStatus::CreateAndGo))
+            .await
+        {
+            Err(err) if row::create_in_steps(&err) => {}
+            result => return result.map(drop),
+        }
+
+        self.set(&[row.status(RowStatus::CreateAndWait)]).await?;
+        let mut result = Ok(Vec::new());
+        if !columns.is_empty() {
+            result = self.set(&row.columns(columns)).await;
+        }
+        if result.is_ok() {
+            result = self.set(&[row.status(RowStatus::Active)]).await;
+        }
+        if result.is_err() {
+            // The error that matters is the one that left the row unfinished.
+            let _ = self.set(&[row.status(RowStatus::Destroy)]).await;
+        }
+
+        result.map(drop)
+    }
+
+    /// Deletes a row of a table; see [`SyncSession::destroy_row`](crate::SyncSession::destroy_row).
+    pub async fn destroy_row(
+        &self,
+        entry: impl IntoOid,
+        status_column: u32,
+        index: &[u32],
+    ) -> SnmpResult<()> {
+        let row = Row::new(entry.into_oid()?, status_column, index);
+
+        self.set(&[row.status(RowStatus::Destroy)]).await.map(drop)
+    }
+
+    /// Hundredths of a second since the session was created, reported as sysUpTime.0.
+    fn uptime(&self) -> u32 {
+        (self.started.elapsed().as_millis() / 10) as u32
+    }
+
+    /// Sends an SNMPv2-Trap; see [`SyncSession::send_trap`](crate::SyncSession::send_trap).
+    pub async fn send_trap<O: IntoOid + Clone>(
+        &self,
+        trap_oid: impl IntoOid,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let bindings = oid::into_bindings(bindings)?;
+
+        let mut data = pdu::trap(self.uptime(), &trap_oid.into_oid()?, &bindings);
+        pdu::set_request_id(&mut data, self.next_request_id());
+        let request = self.hooks.request(&data);
+
+        let mut message = self.buffers.get();
+        self.security
+            .encode(data, &self.context, self.max_message_size, &mut message)?;
+
+        self.transmit(&message, request.as_ref()).await?;
+
+        Ok(())
+    }
+
+    /// Sends an SNMPv1 Trap-PDU; only available on SNMPv1 sessions. The agent address is the
+    /// session's local IPv4 address.
+    pub async fn send_v1_trap<O: IntoOid + Clone>(
+        &self,
+        enterprise: impl IntoOid,
+        generic_trap: u32,
+        specific_trap: u32,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let agent_addr = match self.transport.local_addr() {
+            Ok(SocketAddr::V4(addr)) => *addr.ip(),
+            _ => Ipv4Addr::UNSPECIFIED,
+        };
+
+        let trap = v1::trap(
+            &enterprise.into_oid()?,
+            agent_addr,
+            generic_trap,
+            specific_trap,
+            self.uptime(),
+            &oid::into_bindings(bindings)?,
+        )?;
+        let message = self.security.encode_v1_trap(trap)?;
+
+        self.transmit(&message, None).await?;
+
+        Ok(())
+    }
+
+    /// See [`SyncSession::send_notification`](crate::SyncSession::send_notification).
+    pub async fn send_notification(&self, notification: &Notification) -> SnmpResult<()> {
+        match notification {
+            Notification::V1 {
+                enterprise,
+                agent_addr,
+                generic_trap,
+                specific_trap,
+                timestamp,
+                bindings,
+            } => {
+                let trap = v1::trap(
+                    enterprise,
+                    *agent_addr,
+                    *generic_trap,
+                    *specific_trap,
+                    *timestamp,
+                    bindings,
+                )?;
+                let message = self.security.encode_v1_trap(trap)?;
+
+                self.transmit(&message, None).await?;
+            }
+            Notification::V2 {
+                uptime,
+                trap_oid,
+                bindings,
+            } => {
+                let mut data = pdu::trap(*uptime, trap_oid, bindings);
+                pdu::set_request_id(&mut data, self.next_request_id());
+                let request = self.hooks.request(&data);
+
+                let mut message = self.buffers.get();
+                self.security
+                    .encode(data, &self.context, self.max_message_size, &mut message)?;
+
+                self.transmit(&message, request.as_ref()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends an InformRequest, retransmitting until the receiver acknowledges it.
+    pub async fn send_inform<O: IntoOid + Clone>(
+        &self,
+        trap_oid: impl IntoOid,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let bindings = oid::into_bindings(bindings)?;
+        let data = pdu::inform(self.uptime(), &trap_oid.into_oid()?, &bindings);
+
+        pdu::parse_response(self.request(data, &RequestOptions::default()).await?).map(drop)
+    }
+
+    /// See [`SyncSession::send_pdu`](crate::SyncSession::send_pdu).
+    pub async fn send_pdu(&self, data: v2::Pdus) -> SnmpResult<v2::Pdus> {
+        self.send_pdu_with(data, &RequestOptions::default()).await
+    }
+
+    pub async fn send_pdu_with(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+    ) -> SnmpResult<v2::Pdus> {
+        self.request(data, opts).await
+    }
+
+    /// See [`SyncSession::send_raw`](crate::SyncSession::send_raw).
+    pub async fn send_raw(&self, message: &[u8]) -> SnmpResult<Vec<u8>> {
+        self.send_and_recv(message, None, &RequestOptions::default(), |response| {
+            Ok(Some(response.to_vec()))
+        })
+        .await
+    }
+
+    pub async fn walk(&self, oid: impl IntoOid) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.walk_with(oid, &RequestOptions::default()).await
+    }
+
+    /// [`AsyncSession::walk`] with every GETNEXT of the walk sent with `opts`.
+    pub async fn walk_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.walk_partial(oid, opts)
+            .await
+            .map_err(|partial| partial.error)
+    }
+
+    /// Walks a subtree with GETNEXT, keeping what was collected when it fails; see
+    /// [`SyncSession::walk_partial`](crate::SyncSession::walk_partial).
+    pub async fn walk_partial(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        let start = oid.into_oid()?;
+
+        let mut current = start.clone();
+        let mut result = BTreeMap::new();
+
+        loop {
+            if opts.cancelled() {
+                return Ok(result);
+            }
+            let step = self
+                .getnext_with(&current, opts)
+                .await
+                .and_then(|vars| pdu::collect_subtree(&start, &current, vars, &mut result));
+
+            match step {
+                Ok(Some(next)) => {
+                    opts.report(result.len(), &next);
+                    current = next;
+                }
+                Ok(None) => return Ok(result),
+                Err(error) => return Err(pdu::partial_walk(&start, current, result, error)),
+            }
+        }
+    }
+
+    /// Continues the walk of `cursor` with at most `max_requests` requests; see
+    /// [`SyncSession::walk_resume`](crate::SyncSession::walk_resume).
+    pub async fn walk_resume(
+        &self,
+        cursor: &mut WalkCursor,
+        max_requests: usize,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        let mut max_repetitions = opts.max_repetitions_or_default();
+        let mut result = BTreeMap::new();
+
+        for _ in 0..max_requests {
+            let Some(current) = cursor.current() else {
+                break;
+            };
+            if opts.cancelled() {
+                break;
+            }
+
+            let vars = if self.security.is_v1() {
+                self.getnext_with(&current, opts).await
+            } else {
+                self.bulk(slice::from_ref(&current), 0, &mut max_repetitions, opts)
+                    .await
+            };
+            let step = vars
+                .and_then(|vars| pdu::collect_subtree(cursor.start(), &current, vars, &mut result));
+
+            match step {
+                Ok(next) => {
+                    if let Some(next) = &next {
+                        opts.report(result.len(), next);
+                    }
+                    cursor.advance(next);
+                }
+                Err(error) => {
+                    return Err(PartialWalk {
+                        collected: result,
+                        last: cursor.after().cloned(),
+                        error,
+                    })
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Fetches the system group in one GET; see
+    /// [`SyncSession::system_info`](crate::SyncSession::system_info).
+    pub async fn system_info(&self) -> SnmpResult<SystemInfo> {
+        let oids = SYSTEM_OIDS.map(Oid::from);
+        let vars = self.fetch(&oids, &RequestOptions::default()).await?;
+
+        Ok(SystemInfo::from_bindings(vars))
+    }
+
+    /// Walks ifTable and ifXTable; see
+    /// [`SyncSession::interfaces`](crate::SyncSession::interfaces).
+    pub async fn interfaces(&self) -> SnmpResult<Vec<Interface>> {
+        let if_table = self.get_table(IF_TABLE, &IF_COLUMNS).await?;
+        let if_x_table = self.get_table(IF_X_TABLE, &IF_X_COLUMNS).await?;
+
+        Ok(interfaces::join(if_table, if_x_table))
+    }
+
+    /// Reads the MAC forwarding table of a switch; see
+    /// [`SyncSession::fdb_table`](crate::SyncSession::fdb_table).
+    pub async fn fdb_table(&self) -> SnmpResult<Vec<FdbEntry>> {
+        let base_ports = self.get_table(BASE_PORT_TABLE, &BASE_PORT_COLUMNS).await?;
+
+        let fdb = self.get_table(Q_TP_FDB_TABLE, &FDB_COLUMNS).await?;
+        if !fdb.is_empty() {
+            return Ok(bridge::entries(fdb, true, &base_ports));
+        }
+
+        let fdb = self.get_table(TP_FDB_TABLE, &FDB_COLUMNS).await?;
+        Ok(bridge::entries(fdb, false, &base_ports))
+    }
+
+    /// Reads the ARP and IPv6 neighbor caches; see
+    /// [`SyncSession::neighbor_table`](crate::SyncSession::neighbor_table).
+    pub async fn neighbor_table(&self) -> SnmpResult<Vec<Neighbor>> {
+        let table = self
+            .get_table(NET_TO_PHYSICAL_TABLE, &NET_TO_PHYSICAL_COLUMNS)
+            .await?;
+        if !table.is_empty() {
+            return Ok(neighbors::entries(table, false));
+        }
+
+        let table = self
+            .get_table(NET_TO_MEDIA_TABLE, &NET_TO_MEDIA_COLUMNS)
+            .await?;
+        Ok(neighbors::entries(table, true))
+    }
+
+    /// Fetches the given `columns` of a table such as ifTable, row by row; an empty slice
+    /// fetches every column.
+    pub async fn get_table(&self, table: impl IntoOid, columns: &[u32]) -> SnmpResult<Table> {
+        self.get_table_with(table, columns, &RequestOptions::default())
+            .await
+    }
+
+    /// Fetches a table with every GETNEXT sent with `opts`; see
+    /// [`SyncSession::get_table_with`](crate::SyncSession::get_table_with).
+    pub async fn get_table_with(
+        &self,
+        table: impl IntoOid,
+        columns: &[u32],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Table> {
+        let mut walk = TableWalk::new(table.into_oid()?, columns);
+
+        while let Some(oids) = walk.request() {
+            if opts.cancelled() {
+                break;
+            }
+            walk.response(self.getnext_many_with(&oids, opts).await?)?;
+            if let Some((rows, last)) = walk.progress() {
+                opts.report(rows, last);
+            }
+        }
+
+        Ok(walk.finish())
+    }
+
+    /// Fetches a table with GETBULK; see
+    /// [`SyncSession::get_table_bulk`](crate::SyncSession::get_table_bulk).
+    pub async fn get_table_bulk(
+        &self,
+        table: impl IntoOid,
+        columns: &[u32],
+        mut max_repetitions: u32,
+    ) -> SnmpResult<Table> {
+        if self.security.is_v1() {
+            return self.get_table(table, columns).await;
+        }
+
+        let mut walk = TableWalk::new(table.into_oid()?, columns);
+        let opts = RequestOptions::default();
+
+        while let Some(oids) = walk.request() {
+            let vars = self.bulk(&oids, 0, &mut max_repetitions, &opts).await?;
+            walk.bulk_response(vars)?;
+        }
+
+        Ok(walk.finish())
+    }
+
+    /// Walks a subtree with GETBULK; see [`SyncSession::bulk_walk`](crate::SyncSession::bulk_walk).
+    pub async fn bulk_walk(
+        &self,
+        oid: impl IntoOid,
+        max_repetitions: u32,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        let opts = RequestOptions::new().max_repetitions(max_repetitions);
+
+        self.bulk_walk_with(oid, &opts).await
+    }
+
+    /// [`AsyncSession::bulk_walk`] with max-repetitions taken from `opts`.
+    pub async fn bulk_walk_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.bulk_walk_partial(oid, opts)
+            .await
+            .map_err(|partial| partial.error)
+    }
+
+    /// Walks a subtree with GETBULK, keeping what was collected when it fails; see
+    /// [`SyncSession::bulk_walk_partial`](crate::SyncSession::bulk_walk_partial).
+    pub async fn bulk_walk_partial(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        if self.security.is_v1() {
+            return self.walk_partial(oid, opts).await;
+        }
+
+        let start = oid.into_oid()?;
+        let mut max_repetitions = opts.max_repetitions_or_default();
+
+        let mut current = start.clone();
+        let mut result = BTreeMap::new();
+
+        loop {
+            if opts.cancelled() {
+                return Ok(result);
+            }
+            let step = self
+                .bulk(slice::from_ref(&current), 0, &mut max_repetitions, opts)
+                .await
+                .and_then(|vars| pdu::collect_subtree(&start, &current, vars, &mut result));
+
+            match step {
+                Ok(Some(next)) => {
+                    opts.report(result.len(), &next);
+                    current = next;
+                }
+                Ok(None) => return Ok(result),
+                Err(error) => return Err(pdu::partial_walk(&start, current, result, error)),
+            }
+        }
+    }
+
+    /// Walks a subtree keeping only some instances; see
+    /// [`SyncSession::walk_filtered`](crate::SyncSession::walk_filtered).
+    pub async fn walk_filtered<F>(
+        &self,
+        oid: impl IntoOid,
+        mut keep: F,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>>
+    where
+        F: FnMut(&[u32], &Value) -> bool,
+    {
+        self.walk_filter_map(oid, |suffix, value| keep(suffix, &value).then_some(value))
+            .await
+    }
+
+    /// Walks a subtree mapping and skipping instances; see
+    /// [`SyncSession::walk_filter_map`](crate::SyncSession::walk_filter_map).
+    pub async fn walk_filter_map<U, F>(
+        &self,
+        oid: impl IntoOid,
+        mut f: F,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, U>>
+    where
+        F: FnMut(&[u32], Value) -> Option<U>,
+    {
+        let start = oid.into_oid()?;
+        let mut result = BTreeMap::new();
+
+        self.bulk_walk_visit(&start, |name, value| {
+            let suffix = &name[start.len()..];
+            if let Some(mapped) = f(suffix, value.into()) {
+                result.insert(suffix.to_vec(), mapped);
+            }
+            ControlFlow::Continue(())
+        })
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Walks a subtree without collecting it; see
+    /// [`SyncSession::bulk_walk_visit`](crate::SyncSession::bulk_walk_visit).
+    pub async fn bulk_walk_visit<F>(&self, oid: impl IntoOid, visit: F) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        self.bulk_walk_visit_with(oid, &RequestOptions::default(), visit)
+            .await
+    }
+
+    pub async fn bulk_walk_visit_with<F>(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+        mut visit: F,
+    ) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        let mut walk = VisitWalk::new(oid.into_oid()?, opts.max_repetitions_or_default());
+
+        while let Some(current) = walk.current() {
+            if self.security.is_v1() {
+                let vars = self.getnext_with(&current, opts).await?;
+                walk.accept_owned(&current, vars, &mut visit)?;
+            } else if let Some(community) = self.security.v2c_community() {
+                self.visit_bulk(&mut walk, community, &current, opts, &mut visit)
+                    .await?;
+            } else {
+                let oids = slice::from_ref(&current);
+                let vars = self.bulk(oids, 0, &mut walk.max_repetitions, opts).await?;
+                walk.accept_owned(&current, vars, &mut visit)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// One GETBULK of an [`AsyncSession::bulk_walk_visit`] over SNMPv2c, sent like any other
+    /// request, fail-over and community fallback included.
+    async fn visit_bulk<F>(
+        &self,
+        walk: &mut VisitWalk,
+        community: &[u8],
+        current: &Oid,
+        opts: &RequestOptions,
+        visit: &mut F,
+    ) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        loop {
+            let data = walk.request(current);
+            let response = self
+                .request_with(data, opts, |response, message, request_id| {
+                    match walk.accept(response, community, request_id, visit) {
+                        Some(visited) => visited.map(|()| Some(None)),
+                        None => self
+                            .security
+                            .decode(response, message, request_id, self.decoding)
+                            .map(|data| data.map(Some)),
+                    }
+                })
+                .await?;
+
+            // Anything the in-place decoding does not cover, tooBig included.
+            let Some(data) = response else {
+                return Ok(());
+            };
+            let oids = slice::from_ref(current);
+            match pdu::parse_bulk_response(data, oids, 0, walk.max_repetitions) {
+                Err(err) if err.is_too_big() && walk.max_repetitions > 1 => {
+                    walk.max_repetitions /= 2;
+                    trace::event!(
+                        debug,
+                        max_repetitions = walk.max_repetitions,
+                        "reducing max-repetitions after tooBig"
+                    );
+                }
+                result => return walk.accept_owned(current, result?, visit),
+            }
+        }
+    }
+}
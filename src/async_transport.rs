@@ -0,0 +1,242 @@
+//! Transports for [`AsyncSession`](crate::AsyncSession), the tokio counterparts of
+//! [`Transport`](crate::Transport).
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{lookup_host, TcpStream, ToSocketAddrs, UdpSocket};
+use tokio::sync::Mutex;
+
+use crate::transport::{Hosts, Peers, SocketOptions, TCP_MAX_MESSAGE_SIZE, UDP_MAX_MESSAGE_SIZE};
+
+/// Moves whole SNMP messages to and from one agent. The session enforces timeouts by
+/// dropping the `recv` future.
+pub trait AsyncTransport {
+    fn send(&self, data: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Receives one message into `buf` and returns its length.
+    fn recv(&self, buf: &mut [u8]) -> impl Future<Output = io::Result<usize>> + Send;
+
+    /// The largest message the transport can carry in either direction.
+    fn max_msg_size(&self) -> usize;
+
+    /// The local address, which SNMPv1 traps report as their agent address.
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// The address of the agent requests currently go to.
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// How many other addresses of the agent [`AsyncTransport::fail_over`] can switch to.
+    fn alternatives(&self) -> usize {
+        0
+    }
+
+    /// Switches to the next address of the agent, after the current one left a request
+    /// unanswered.
+    fn fail_over(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Looks the agent's host names up again; see
+    /// [`Transport::resolve`](crate::Transport::resolve).
+    fn resolve(&self) -> impl Future<Output = io::Result<bool>> + Send {
+        async { Ok(false) }
+    }
+}
+
+/// Plain SNMP over UDP (RFC 3417), one message per datagram, failing over between the
+/// agent's addresses like [`UdpTransport`](crate::UdpTransport).
+#[derive(Debug)]
+pub struct AsyncUdpTransport {
+    socket: RwLock<Arc<UdpSocket>>,
+    peers: Option<Peers>,
+}
+
+/// Hands a socket set up with the standard library to tokio.
+fn to_tokio(socket: std::net::UdpSocket) -> io::Result<Arc<UdpSocket>> {
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket).map(Arc::new)
+}
+
+impl AsyncUdpTransport {
+    pub async fn connect<A: ToSocketAddrs>(dest_addr: A) -> io::Result<Self> {
+        Self::open(dest_addr, &SocketOptions::default()).await
+    }
+
+    pub(crate) async fn open<A: ToSocketAddrs>(
+        dest_addr: A,
+        options: &SocketOptions,
+    ) -> io::Result<Self> {
+        let addrs = lookup_host(dest_addr).await?.collect();
+        let (peers, socket) = Peers::connect(addrs, options.clone(), None, to_tokio)?;
+
+        Ok(AsyncUdpTransport {
+            socket: RwLock::new(socket),
+            peers: Some(peers),
+        })
+    }
+
+    pub(crate) async fn open_hosts(hosts: Hosts, options: &SocketOptions) -> io::Result<Self> {
+        let addrs = hosts.resolve_async().await?;
+        let (peers, socket) = Peers::connect(addrs, options.clone(), Some(hosts), to_tokio)?;
+
+        Ok(AsyncUdpTransport {
+            socket: RwLock::new(socket),
+            peers: Some(peers),
+        })
+    }
+
+    /// The current socket, which stays usable across an await even if the transport fails
+    /// over meanwhile.
+    fn socket(&self) -> Arc<UdpSocket> {
+        self.socket
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Wraps a socket that is already connected to the agent.
+impl From<UdpSocket> for AsyncUdpTransport {
+    fn from(socket: UdpSocket) -> Self {
+        AsyncUdpTransport {
+            socket: RwLock::new(Arc::new(socket)),
+            peers: None,
+        }
+    }
+}
+
+impl AsyncTransport for AsyncUdpTransport {
+    async fn send(&self, data: &[u8]) -> io::Result<()> {
+        self.socket().send(data).await.map(drop)
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.socket().recv(buf).await
+    }
+
+    fn max_msg_size(&self) -> usize {
+        UDP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().peer_addr()
+    }
+
+    fn alternatives(&self) -> usize {
+        self.peers.as_ref().map_or(0, Peers::alternatives)
+    }
+
+    fn fail_over(&self) -> io::Result<()> {
+        if let Some(peers) = &self.peers {
+            let socket = peers.next(to_tokio)?;
+            *self.socket.write().unwrap_or_else(PoisonError::into_inner) = socket;
+        }
+
+        Ok(())
+    }
+
+    async fn resolve(&self) -> io::Result<bool> {
+        let Some((peers, hosts)) = self
+            .peers
+            .as_ref()
+            .and_then(|peers| Some((peers, peers.hosts()?)))
+        else {
+            return Ok(false);
+        };
+        let Some(socket) = peers.replace(hosts.resolve_async().await?, to_tokio)? else {
+            return Ok(false);
+        };
+
+        *self.socket.write().unwrap_or_else(PoisonError::into_inner) = socket;
+        Ok(true)
+    }
+}
+
+/// Reads one BER-framed message; see [`crate::transport::read_message`].
+async fn read_message(mut stream: impl AsyncRead + Unpin, buf: &mut [u8]) -> io::Result<usize> {
+    let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    if buf.len() < 6 {
+        return Err(invalid("message larger than the receive buffer"));
+    }
+
+    stream.read_exact(&mut buf[..2]).await?;
+    let extra = match buf[1] {
+        len if len & 0x80 != 0 => (len & 0x7f) as usize,
+        _ => 0,
+    };
+    if extra > 4 {
+        return Err(invalid("invalid BER length"));
+    }
+    stream.read_exact(&mut buf[2..2 + extra]).await?;
+
+    let (head, len) =
+        crate::ber::header(&buf[..2 + extra]).ok_or_else(|| invalid("invalid BER length"))?;
+    let total = head
+        .checked_add(len)
+        .filter(|total| *total <= buf.len())
+        .ok_or_else(|| invalid("message larger than the receive buffer"))?;
+
+    stream.read_exact(&mut buf[head..total]).await?;
+
+    Ok(total)
+}
+
+/// SNMP over TCP (RFC 3430); messages are delimited by their BER framing. A timeout in the
+/// middle of a message leaves the stream out of step, so prefer generous timeouts.
+#[derive(Debug)]
+pub struct AsyncTcpTransport {
+    reader: Mutex<OwnedReadHalf>,
+    writer: Mutex<OwnedWriteHalf>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl AsyncTcpTransport {
+    pub async fn connect<A: ToSocketAddrs>(dest_addr: A) -> io::Result<Self> {
+        Ok(TcpStream::connect(dest_addr).await?.into())
+    }
+}
+
+impl From<TcpStream> for AsyncTcpTransport {
+    fn from(stream: TcpStream) -> Self {
+        let local_addr = stream.local_addr().ok();
+        let (reader, writer) = stream.into_split();
+
+        AsyncTcpTransport {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            local_addr,
+        }
+    }
+}
+
+impl AsyncTransport for AsyncTcpTransport {
+    async fn send(&self, data: &[u8]) -> io::Result<()> {
+        self.writer.lock().await.write_all(data).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        read_message(&mut *self.reader.lock().await, buf).await
+    }
+
+    fn max_msg_size(&self) -> usize {
+        TCP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.local_addr.ok_or(io::ErrorKind::Unsupported.into())
+    }
+}
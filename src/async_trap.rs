@@ -0,0 +1,159 @@
+//! The tokio counterpart of [`TrapListener`](crate::TrapListener).
+
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::io::ReadBuf;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+use crate::trace;
+use crate::transport::UDP_MAX_MESSAGE_SIZE;
+use crate::trap::{is_icmp_error, Receiver};
+use crate::{EngineId, Mib, TrapEvent, TrapSink, TrapStats, UsmUserTable};
+
+/// Receives notifications as a [`Stream`]. Datagrams are only read while the stream is
+/// polled, so a slow consumer leaves them queued in the socket buffer rather than in memory.
+/// Informs are acknowledged as they are read; if the socket cannot take the acknowledgement
+/// right away it is skipped and the sender retransmits.
+pub struct AsyncTrapListener {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    receiver: Receiver,
+}
+
+impl AsyncTrapListener {
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(AsyncTrapListener {
+            socket: UdpSocket::bind(addr).await?,
+            buffer: vec![0; UDP_MAX_MESSAGE_SIZE],
+            receiver: Receiver::default(),
+        })
+    }
+
+    /// See [`TrapListener::communities`](crate::TrapListener::communities).
+    pub fn communities<C: AsRef<[u8]>>(mut self, communities: impl IntoIterator<Item = C>) -> Self {
+        self.receiver.communities(communities);
+        self
+    }
+
+    /// See [`TrapListener::source_communities`](crate::TrapListener::source_communities).
+    pub fn source_communities<C: AsRef<[u8]>>(
+        mut self,
+        source: IpAddr,
+        communities: impl IntoIterator<Item = C>,
+    ) -> Self {
+        self.receiver.source_communities(source, communities);
+        self
+    }
+
+    /// See [`TrapListener::users`](crate::TrapListener::users).
+    pub fn users(mut self, users: &UsmUserTable) -> Self {
+        self.receiver.users(users);
+        self
+    }
+
+    /// See [`TrapListener::local_engine`](crate::TrapListener::local_engine).
+    pub fn local_engine(mut self, engine_id: EngineId, boots: u32) -> Self {
+        self.receiver.local_engine(engine_id, boots);
+        self
+    }
+
+    /// See [`TrapListener::mib`](crate::TrapListener::mib).
+    pub fn mib(mut self, mib: impl Into<Arc<Mib>>) -> Self {
+        self.receiver.mib(mib.into());
+        self
+    }
+
+    /// See [`TrapListener::hook`](crate::TrapListener::hook).
+    pub fn hook(mut self, hook: impl FnMut(&mut TrapEvent) + Send + 'static) -> Self {
+        self.receiver.hook(Box::new(hook));
+        self
+    }
+
+    /// See [`TrapListener::dedup`](crate::TrapListener::dedup).
+    pub fn dedup(mut self, window: Duration) -> Self {
+        self.receiver.dedup(window);
+        self
+    }
+
+    /// See [`TrapListener::source_rate_limit`](crate::TrapListener::source_rate_limit).
+    pub fn source_rate_limit(mut self, per_second: u32, burst: u32) -> Self {
+        self.receiver.source_rate_limit(per_second, burst);
+        self
+    }
+
+    /// See [`TrapListener::sink`](crate::TrapListener::sink).
+    pub fn sink(mut self, sink: impl TrapSink + 'static) -> Self {
+        self.receiver.sink(Box::new(sink));
+        self
+    }
+
+    pub fn stats(&self) -> TrapStats {
+        self.receiver.stats()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// See [`TrapListener::recv`](crate::TrapListener::recv).
+    pub async fn recv(&mut self) -> io::Result<TrapEvent> {
+        loop {
+            let (len, source) = match self.socket.recv_from(&mut self.buffer).await {
+                Ok(received) => received,
+                Err(err) if is_icmp_error(&err) => continue,
+                Err(err) => return Err(err),
+            };
+
+            let (event, reply) = self.receiver.receive(source, &self.buffer[..len]);
+            if let Some(reply) = reply {
+                if let Err(_err) = self.socket.send_to(&reply, source).await {
+                    trace::event!(debug, %source, error = %_err, "failed to send reply");
+                }
+            }
+            if let Some(event) = event {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// See [`TrapListener::run`](crate::TrapListener::run).
+    pub async fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.recv().await?;
+        }
+    }
+}
+
+impl Stream for AsyncTrapListener {
+    type Item = io::Result<TrapEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let mut buf = ReadBuf::new(&mut this.buffer);
+            let source = match ready!(this.socket.poll_recv_from(cx, &mut buf)) {
+                Ok(source) => source,
+                Err(err) if is_icmp_error(&err) => continue,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+            let len = buf.filled().len();
+
+            let (event, reply) = this.receiver.receive(source, &this.buffer[..len]);
+            if let Some(reply) = reply {
+                if let Err(_err) = this.socket.try_send_to(&reply, source) {
+                    trace::event!(debug, %source, error = %_err, "failed to send reply");
+                }
+            }
+            if let Some(event) = event {
+                return Poll::Ready(Some(Ok(event)));
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Minimal BER framing helpers for the places where rasn's typed decoding is not enough.
+
+use std::iter;
+
+/// Parses a tag and length, returning the header size and the content length.
+pub(crate) fn header(bytes: &[u8]) -> Option<(usize, usize)> {
+    let first = *bytes.get(1)?;
+    if first & 0x80 == 0 {
+        return Some((2, first as usize));
+    }
+
+    let count = (first & 0x7f) as usize;
+    if count == 0 || count > 4 {
+        return None;
+    }
+
+    let len = bytes
+        .get(2..2 + count)?
+        .iter()
+        .fold(0, |len, byte| (len << 8) | *byte as usize);
+
+    Some((2 + count, len))
+}
+
+/// The total length of the message `bytes` starts with, which exceeds `bytes.len()` when
+/// it was cut short.
+pub(crate) fn message_len(bytes: &[u8]) -> Option<usize> {
+    header(bytes).and_then(|(head, len)| head.checked_add(len))
+}
+
+/// The version field of an SNMP message, read without decoding the rest: the first element
+/// of every message is a one-octet INTEGER (0 for v1, 1 for v2c, 3 for v3).
+pub(crate) fn message_version(bytes: &[u8]) -> Option<u8> {
+    let (head, _) = header(bytes)?;
+    let version = bytes.get(head..)?;
+
+    match header(version)? {
+        (2, 1) if version[0] == 0x02 => version.get(2).copied(),
+        _ => None,
+    }
+}
+
+/// Splits the first element off `bytes`: its tag, contents and what follows it.
+pub(crate) fn element(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (head, len) = header(bytes)?;
+    let end = head.checked_add(len)?;
+    let contents = bytes.get(head..end)?;
+
+    Some((bytes[0], contents, &bytes[end..]))
+}
+
+/// The tags and contents of the primitive elements inside the element `bytes` starts
+/// with, depth first; the walk ends at the first malformed one.
+pub(crate) fn primitives(bytes: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut pending: Vec<&[u8]> = message_len(bytes)
+        .and_then(|len| bytes.get(..len))
+        .into_iter()
+        .collect();
+
+    iter::from_fn(move || loop {
+        let (tag, contents, rest) = element(pending.pop()?)?;
+        if !rest.is_empty() {
+            pending.push(rest);
+        }
+
+        if tag & 0x20 == 0 {
+            return Some((tag, contents));
+        }
+        if !contents.is_empty() {
+            pending.push(contents);
+        }
+    })
+}
+
+/// The identifier that pairs a response with its request, read without decoding the
+/// message: the msgID of SNMPv3 messages, the request-id of the PDU otherwise.
+pub(crate) fn message_id(bytes: &[u8]) -> Option<i64> {
+    let (_, message, _) = element(bytes)?;
+    let (_, version, rest) = element(message)?;
+
+    let id = if version == [3] {
+        let (_, global_data, _) = element(rest)?;
+        element(global_data)?
+    } else {
+        let (_, _community, rest) = element(rest)?;
+        let (_, pdu, _) = element(rest)?;
+        element(pdu)?
+    };
+
+    match id {
+        (0x02, id @ [_, ..], _) if id.len() <= 8 => {
+            let sign = if id[0] & 0x80 != 0 { -1 } else { 0 };
+            Some(
+                id.iter()
+                    .fold(sign, |value, byte| (value << 8) | *byte as i64),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Walks the TLV structure of `bytes` and returns the offset of the first element that is
+/// malformed or runs past its parent, or `None` when the framing is intact.
+pub(crate) fn invalid_offset(bytes: &[u8]) -> Option<usize> {
+    let mut ends = vec![bytes.len()];
+    let mut pos = 0;
+    let mut truncated = None;
+
+    while let Some(&end) = ends.last() {
+        if pos >= end {
+            ends.pop();
+            continue;
+        }
+
+        let Some((head, len)) = header(&bytes[pos..end]) else {
+            return Some(pos);
+        };
+
+        // Lengths of four octets can overflow the address space of 32-bit targets.
+        let Some(content_end) = (pos + head).checked_add(len) else {
+            return Some(pos);
+        };
+        let constructed = bytes[pos] & 0x20 != 0;
+
+        if content_end > end {
+            if !constructed {
+                return Some(pos);
+            }
+
+            // Look inside for a more precise offset before blaming the whole element.
+            truncated.get_or_insert(pos);
+            ends.push(end);
+            pos += head;
+        } else if constructed {
+            ends.push(content_end);
+            pos += head;
+        } else {
+            pos = content_end;
+        }
+    }
+
+    truncated
+}
@@ -0,0 +1,521 @@
+//! A net-snmp-like command line client: `yar-snmp get -v 2c -c public 10.0.0.1 sysDescr.0`.
+
+use std::env;
+use std::net::Ipv4Addr;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use yar_snmp::{
+    AuthProtocol, Formatter, Hex, Mib, Oid, PrivProtocol, SyncSession, Ticks, UsmUser, Value,
+    Version,
+};
+
+const USAGE: &str = "\
+usage: yar-snmp COMMAND [OPTIONS] AGENT ARGS...
+
+commands:
+  get OID...                       GET the given instances
+  getnext OID...                   GETNEXT from the given OIDs
+  walk [OID]                       walk a subtree with GETNEXT, all of mib-2 by default
+  bulkwalk [OID]                   walk a subtree with GETBULK
+  set OID TYPE VALUE...            SET instances; TYPE is one of i u c C t a o s x n
+  trap TRAP-OID [OID TYPE VALUE]...
+                                   send an SNMPv2-Trap; with -v 1, the arguments are
+                                   ENTERPRISE GENERIC SPECIFIC [OID TYPE VALUE]...
+
+options:
+  -v 1|2c|3      SNMP version, 2c by default
+  -c COMMUNITY   community, public by default
+  -t SECONDS     timeout of each attempt, 1 by default
+  -r RETRIES     retransmissions, 1 by default
+  -u USER        SNMPv3 user
+  -l LEVEL       noAuthNoPriv, authNoPriv or authPriv
+  -a PROTOCOL    MD5, SHA, SHA-224, SHA-256, SHA-384 or SHA-512
+  -A PASSPHRASE  authentication passphrase
+  -x PROTOCOL    DES, AES, AES-192 or AES-256
+  -X PASSPHRASE  privacy passphrase
+  -M DIR         load the MIB modules in DIR, for names in OIDs and output
+  -O FLAGS       n: numeric OIDs, q: no types, v: values only
+  -C r<N>        max-repetitions of bulkwalk, 10 by default
+
+AGENT is HOST or HOST:PORT; the port is 161 by default, 162 for trap.
+";
+
+/// The `mib-2` subtree walks default to, as net-snmp's do.
+const MIB_2: &str = "1.3.6.1.2.1";
+
+#[derive(Debug, Default)]
+struct Options {
+    version: Option<String>,
+    community: Option<String>,
+    timeout: Option<Duration>,
+    retries: Option<u32>,
+    user: Option<String>,
+    level: Option<String>,
+    auth_protocol: Option<String>,
+    auth_passphrase: Option<String>,
+    priv_protocol: Option<String>,
+    priv_passphrase: Option<String>,
+    mib_dirs: Vec<String>,
+    numeric: bool,
+    quick: bool,
+    values_only: bool,
+    max_repetitions: u32,
+}
+
+/// Splits the flags off `args`, leaving the agent and the arguments of the command.
+/// Values may follow their flag either attached, `-v2c`, or as the next argument.
+fn parse_options(args: Vec<String>) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options {
+        max_repetitions: 10,
+        ..Options::default()
+    };
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix('-').filter(|flag| !flag.is_empty()) else {
+            rest.push(arg);
+            continue;
+        };
+        if flag == "h" || flag == "-help" {
+            return Err(String::new());
+        }
+
+        let (name, attached) = flag.split_at(1);
+        let mut value = || match attached {
+            "" => args.next().ok_or(format!("-{} needs a value", name)),
+            attached => Ok(attached.to_string()),
+        };
+
+        match name {
+            "v" => options.version = Some(value()?),
+            "c" => options.community = Some(value()?),
+            "t" => {
+                let secs = value()?.parse().map_err(|_| "invalid timeout")?;
+                let timeout = Duration::try_from_secs_f64(secs).map_err(|_| "invalid timeout")?;
+                options.timeout = Some(timeout);
+            }
+            "r" => options.retries = Some(value()?.parse().map_err(|_| "invalid retries")?),
+            "u" => options.user = Some(value()?),
+            "l" => options.level = Some(value()?),
+            "a" => options.auth_protocol = Some(value()?),
+            "A" => options.auth_passphrase = Some(value()?),
+            "x" => options.priv_protocol = Some(value()?),
+            "X" => options.priv_passphrase = Some(value()?),
+            "M" => options.mib_dirs.push(value()?),
+            "O" => {
+                for flag in value()?.chars() {
+                    match flag {
+                        'n' => options.numeric = true,
+                        'q' => options.quick = true,
+                        'v' => options.values_only = true,
+                        _ => return Err(format!("unknown output flag {}", flag)),
+                    }
+                }
+            }
+            "C" => {
+                let value = value()?;
+                let reps = value.strip_prefix('r').ok_or("unknown -C flag")?;
+                options.max_repetitions = reps.parse().map_err(|_| "invalid max-repetitions")?;
+            }
+            // A negative number, e.g. an INTEGER to set.
+            _ if name.starts_with(|c: char| c.is_ascii_digit()) => rest.push(arg),
+            _ => return Err(format!("unknown option -{}", name)),
+        }
+    }
+
+    Ok((options, rest))
+}
+
+fn auth_protocol(name: &str) -> Result<AuthProtocol, String> {
+    Ok(match name.to_ascii_uppercase().as_str() {
+        "MD5" => AuthProtocol::Md5,
+        "SHA" | "SHA1" | "SHA-1" => AuthProtocol::Sha1,
+        "SHA-224" | "SHA224" => AuthProtocol::Sha224,
+        "SHA-256" | "SHA256" => AuthProtocol::Sha256,
+        "SHA-384" | "SHA384" => AuthProtocol::Sha384,
+        "SHA-512" | "SHA512" => AuthProtocol::Sha512,
+        _ => return Err(format!("unknown authentication protocol {}", name)),
+    })
+}
+
+fn priv_protocol(name: &str) -> Result<PrivProtocol, String> {
+    Ok(match name.to_ascii_uppercase().as_str() {
+        "DES" => PrivProtocol::Des,
+        "AES" | "AES128" | "AES-128" => PrivProtocol::Aes128,
+        "AES192" | "AES-192" => PrivProtocol::Aes192,
+        "AES256" | "AES-256" => PrivProtocol::Aes256,
+        _ => return Err(format!("unknown privacy protocol {}", name)),
+    })
+}
+
+fn user(options: &Options) -> Result<UsmUser, String> {
+    let name = options.user.as_deref().ok_or("SNMPv3 needs a user (-u)")?;
+    let mut user = UsmUser::new(name.as_bytes());
+    let level = options.level.as_deref().unwrap_or("noAuthNoPriv");
+
+    if level == "authNoPriv" || level == "authPriv" {
+        let protocol = auth_protocol(options.auth_protocol.as_deref().unwrap_or("MD5"))?;
+        let passphrase = options
+            .auth_passphrase
+            .as_deref()
+            .ok_or("authentication needs a passphrase (-A)")?;
+        user = user.auth(protocol, passphrase.as_bytes());
+    }
+    if level == "authPriv" {
+        let protocol = priv_protocol(options.priv_protocol.as_deref().unwrap_or("DES"))?;
+        let passphrase = options
+            .priv_passphrase
+            .as_deref()
+            .ok_or("privacy needs a passphrase (-X)")?;
+        user = user.privacy(protocol, passphrase.as_bytes());
+    } else if level != "noAuthNoPriv" && level != "authNoPriv" {
+        return Err(format!("unknown security level {}", level));
+    }
+
+    Ok(user)
+}
+
+/// A session to `agent`, on `default_port` unless it names one.
+fn session(options: &Options, agent: &str, default_port: u16) -> Result<SyncSession, String> {
+    // net-snmp's `udp:host:port` transport prefix.
+    let agent = agent.strip_prefix("udp:").unwrap_or(agent);
+    let (host, port) = match agent.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => {
+            (host, port.parse().map_err(|_| "invalid port")?)
+        }
+        _ => (agent, default_port),
+    };
+
+    let mut builder = SyncSession::builder(host).port(port);
+    builder = match options.version.as_deref().unwrap_or("2c") {
+        "1" => builder.version(Version::V1),
+        "2c" => builder.version(Version::V2c),
+        "3" => builder.v3(user(options)?),
+        version => return Err(format!("unknown version {}", version)),
+    };
+    if let Some(community) = &options.community {
+        builder = builder.community(community);
+    }
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(retries) = options.retries {
+        builder = builder.retries(retries);
+    }
+
+    builder.build().map_err(|err| err.to_string())
+}
+
+/// Parses an OID, by name when MIBs are loaded.
+fn parse_oid(mib: &Mib, text: &str) -> Result<Oid, String> {
+    mib.lookup(text.trim_start_matches('.'))
+        .or_else(|| text.parse().ok())
+        .ok_or_else(|| format!("unknown OID {}", text))
+}
+
+/// A value given as net-snmp's `TYPE VALUE` pair.
+fn parse_value(mib: &Mib, kind: &str, text: &str) -> Result<Value, String> {
+    let invalid = || format!("invalid {} value {}", kind, text);
+
+    Ok(match kind {
+        "i" => Value::Integer(text.parse().map_err(|_| invalid())?),
+        "u" => Value::Gauge32(text.parse().map_err(|_| invalid())?),
+        "c" => Value::Counter32(text.parse().map_err(|_| invalid())?),
+        "C" => Value::Counter64(text.parse().map_err(|_| invalid())?),
+        "t" => Value::TimeTicks(text.parse().map_err(|_| invalid())?),
+        "a" => Value::IpAddress(text.parse::<Ipv4Addr>().map_err(|_| invalid())?),
+        "o" => Value::Oid(parse_oid(mib, text)?.into()),
+        "s" => Value::OctetString(text.as_bytes().to_vec()),
+        "x" => {
+            // Octets may be grouped with spaces, colons or dashes.
+            let digits: String = text
+                .chars()
+                .filter(|c| !matches!(c, ' ' | ':' | '-'))
+                .collect();
+            if !digits.chars().all(|c| c.is_ascii_hexdigit()) || !digits.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+            let bytes = (0..digits.len() / 2)
+                .map(|i| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16))
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?;
+            Value::OctetString(bytes)
+        }
+        "n" => Value::Null,
+        _ => return Err(format!("unknown type {}", kind)),
+    })
+}
+
+fn parse_bindings(mib: &Mib, args: &[String]) -> Result<Vec<(Oid, Value)>, String> {
+    if !args.len().is_multiple_of(3) {
+        return Err("bindings are OID TYPE VALUE triples".to_string());
+    }
+
+    args.chunks(3)
+        .map(|binding| {
+            let oid = parse_oid(mib, &binding[0])?;
+            Ok((oid, parse_value(mib, &binding[1], &binding[2])?))
+        })
+        .collect()
+}
+
+/// The type net-snmp prints a value with, if any, and the value itself.
+fn typed(value: &Value) -> (Option<&'static str>, String) {
+    match value {
+        Value::Integer(int) => (Some("INTEGER"), int.to_string()),
+        Value::OctetString(bytes) if bytes.is_empty() => (None, "\"\"".to_string()),
+        Value::OctetString(bytes) => match value.as_str() {
+            Some(text) => (
+                Some("STRING"),
+                format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+            ),
+            None => (Some("Hex-STRING"), Hex(bytes).to_string()),
+        },
+        Value::Oid(oid) => (Some("OID"), format!(".{}", Oid::from(oid.clone()))),
+        Value::IpAddress(ip) => (Some("IpAddress"), ip.to_string()),
+        Value::Counter32(n) => (Some("Counter32"), n.to_string()),
+        Value::Counter64(n) => (Some("Counter64"), n.to_string()),
+        Value::Gauge32(n) => (Some("Gauge32"), n.to_string()),
+        Value::TimeTicks(n) => (Some("Timeticks"), Ticks(*n).to_string()),
+        Value::Opaque(bytes) => (Some("Opaque"), Hex(bytes).to_string()),
+        Value::Float(float) => (Some("Opaque: Float"), format!("{:.6}", float)),
+        Value::Double(double) => (Some("Opaque: Double"), format!("{:.6}", double)),
+        Value::Integer64(int) => (Some("Opaque: Int64"), int.to_string()),
+        Value::Unsigned64(int) => (Some("Opaque: UInt64"), int.to_string()),
+        Value::Null => (None, "NULL".to_string()),
+        Value::NoSuchObject => (
+            None,
+            "No Such Object available on this agent at this OID".to_string(),
+        ),
+        Value::NoSuchInstance => (
+            None,
+            "No Such Instance currently exists at this OID".to_string(),
+        ),
+        Value::EndOfMibView => (
+            None,
+            "No more variables left in this MIB View (It is past the end of the MIB tree)"
+                .to_string(),
+        ),
+    }
+}
+
+struct Printer<'a> {
+    options: &'a Options,
+    mib: &'a Mib,
+}
+
+impl Printer<'_> {
+    fn print(&self, oid: &Oid, value: &Value) {
+        let named = !self.options.numeric && !self.options.mib_dirs.is_empty();
+        let name = match named.then(|| self.mib.name_of(oid)).flatten() {
+            Some(name) => name,
+            None => format!(".{}", oid),
+        };
+
+        let (kind, mut text) = typed(value);
+        if named {
+            // Enumerations and DISPLAY-HINTs of the loaded MIBs.
+            let formatted = Formatter::new(self.mib).format(oid, value);
+            if formatted != value.to_string() {
+                text = formatted;
+            }
+        }
+        let value = match kind {
+            Some(kind) if !self.options.quick => format!("{}: {}", kind, text),
+            _ => text,
+        };
+
+        if self.options.values_only {
+            println!("{}", value);
+        } else if self.options.quick {
+            println!("{} {}", name, value);
+        } else {
+            println!("{} = {}", name, value);
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), String> {
+    let mut args = args.into_iter();
+    let command = args.next().ok_or_else(String::new)?;
+    let (options, rest) = parse_options(args.collect())?;
+    let (agent, args) = rest.split_first().ok_or("missing AGENT")?;
+
+    let mut mib = Mib::new();
+    for dir in &options.mib_dirs {
+        mib.load_dir(dir)
+            .map_err(|err| format!("{}: {}", dir, err))?;
+    }
+    let printer = Printer {
+        options: &options,
+        mib: &mib,
+    };
+    // Notifications go to the manager's trap port.
+    let port = if command == "trap" { 162 } else { 161 };
+    let sess = session(&options, agent, port)?;
+    let oids =
+        || -> Result<Vec<Oid>, String> { args.iter().map(|arg| parse_oid(&mib, arg)).collect() };
+    let root = || match args {
+        [] => parse_oid(&mib, MIB_2),
+        [root] => parse_oid(&mib, root),
+        _ => Err("walks take one OID".to_string()),
+    };
+    let failed = |err: yar_snmp::SnmpError| err.to_string();
+
+    match command.as_str() {
+        "get" | "getnext" => {
+            let oids = oids()?;
+            if oids.is_empty() {
+                return Err("missing OID".to_string());
+            }
+            let vars = match command.as_str() {
+                "get" => sess.get_many(&oids),
+                _ => sess.getnext_many(&oids),
+            };
+            for (oid, value) in vars.map_err(failed)? {
+                printer.print(&oid, &value);
+            }
+        }
+        "walk" | "bulkwalk" => {
+            let root = root()?;
+            let walked = match command.as_str() {
+                "walk" => sess.walk(&root),
+                _ => sess.bulk_walk(&root, options.max_repetitions),
+            };
+            for (suffix, value) in walked.map_err(failed)? {
+                let oid = Oid::from([root.as_slice(), &suffix].concat());
+                printer.print(&oid, &value);
+            }
+        }
+        "set" => {
+            let bindings = parse_bindings(&mib, args)?;
+            if bindings.is_empty() {
+                return Err("missing OID TYPE VALUE".to_string());
+            }
+            for (oid, value) in sess.set(&bindings).map_err(failed)? {
+                printer.print(&oid, &value);
+            }
+        }
+        "trap" if options.version.as_deref() == Some("1") => {
+            let [enterprise, generic, specific, bindings @ ..] = args else {
+                return Err("missing ENTERPRISE GENERIC SPECIFIC".to_string());
+            };
+            let generic = generic.parse().map_err(|_| "invalid generic trap")?;
+            let specific = specific.parse().map_err(|_| "invalid specific trap")?;
+            let bindings = parse_bindings(&mib, bindings)?;
+            sess.send_v1_trap(parse_oid(&mib, enterprise)?, generic, specific, &bindings)
+                .map_err(failed)?;
+        }
+        "trap" => {
+            let (trap_oid, bindings) = args.split_first().ok_or("missing TRAP-OID")?;
+            let bindings = parse_bindings(&mib, bindings)?;
+            sess.send_trap(parse_oid(&mib, trap_oid)?, &bindings)
+                .map_err(failed)?;
+        }
+        _ => return Err(format!("unknown command {}", command)),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if err.is_empty() => {
+            eprint!("{}", USAGE);
+            ExitCode::from(2)
+        }
+        Err(err) => {
+            eprintln!("yar-snmp: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn values_parse_by_type() {
+        let mib = Mib::new();
+        let parse = |kind, text| parse_value(&mib, kind, text);
+
+        assert_eq!(parse("i", "-5"), Ok(Value::Integer(-5)));
+        assert_eq!(parse("u", "7"), Ok(Value::Gauge32(7)));
+        assert_eq!(parse("C", "1099511627776"), Ok(Value::Counter64(1 << 40)));
+        assert_eq!(
+            parse("a", "10.0.0.1"),
+            Ok(Value::IpAddress(Ipv4Addr::new(10, 0, 0, 1)))
+        );
+        assert_eq!(parse("s", "eth0"), Ok(Value::OctetString(b"eth0".to_vec())));
+        assert_eq!(
+            parse("x", "00:1a 2B-3c"),
+            Ok(Value::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c]))
+        );
+
+        assert_eq!(parse("x", "abc"), Err("invalid x value abc".to_string()));
+        assert_eq!(parse("x", "0g"), Err("invalid x value 0g".to_string()));
+        assert_eq!(parse("x", "0x12"), Err("invalid x value 0x12".to_string()));
+        assert_eq!(parse("i", "ten"), Err("invalid i value ten".to_string()));
+        assert_eq!(parse("q", "1"), Err("unknown type q".to_string()));
+    }
+
+    #[test]
+    fn bindings_come_in_triples() {
+        let mib = Mib::new();
+        let bindings = parse_bindings(&mib, &strings(&["1.3.6.1.2.1.1.5.0", "s", "sw1"]));
+        assert_eq!(
+            bindings,
+            Ok(vec![(
+                "1.3.6.1.2.1.1.5.0".parse().unwrap(),
+                Value::OctetString(b"sw1".to_vec())
+            )])
+        );
+
+        assert!(parse_bindings(&mib, &strings(&["1.3.6.1.2.1.1.5.0", "s"])).is_err());
+        assert!(parse_bindings(&mib, &strings(&["sysName.0", "s", "sw1"])).is_err());
+        assert!(parse_bindings(&mib, &strings(&["1.3.6.1.2.1.1.5.0", "i", "x"])).is_err());
+    }
+
+    #[test]
+    fn timeouts_are_seconds() {
+        let timeout =
+            |args: &[&str]| parse_options(strings(args)).map(|(options, _)| options.timeout);
+
+        assert_eq!(
+            timeout(&["-t", "2.5"]),
+            Ok(Some(Duration::from_millis(2500)))
+        );
+        assert_eq!(timeout(&["-t0.1"]), Ok(Some(Duration::from_millis(100))));
+        assert_eq!(timeout(&[]), Ok(None));
+        for invalid in ["-1", "nan", "inf", "soon"] {
+            assert_eq!(
+                timeout(&["-t", invalid]),
+                Err("invalid timeout".to_string())
+            );
+        }
+        assert_eq!(timeout(&["-t"]), Err("-t needs a value".to_string()));
+    }
+
+    #[test]
+    fn agents_default_to_the_command_port() {
+        let options = Options::default();
+        let port = |agent, default_port| {
+            session(&options, agent, default_port)
+                .unwrap()
+                .peer_addr()
+                .unwrap()
+                .port()
+        };
+
+        assert_eq!(port("127.0.0.1", 162), 162);
+        assert_eq!(port("udp:127.0.0.1:1161", 162), 1161);
+    }
+}
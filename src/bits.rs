@@ -0,0 +1,100 @@
+//! The BITS construct of SMIv2 (RFC 2578 7.1.4): an OCTET STRING of named bits.
+
+use crate::{Mib, Oid, SnmpError, SnmpResult};
+
+/// A BITS value. Bit 0 is the most significant bit of the first octet; bits past the end
+/// of the string are clear.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bits(pub Vec<u8>);
+
+impl Bits {
+    /// The value with just `positions` set, in as few octets as hold the highest of them.
+    pub fn from_positions(positions: impl IntoIterator<Item = u32>) -> Self {
+        let mut bits = Bits::default();
+        for bit in positions {
+            bits.set(bit);
+        }
+        bits
+    }
+
+    /// The value of the BITS object `oid` with the bits named `names` set; fails with
+    /// [`SnmpError::UnknownName`] for a name the loaded `mib` does not define for it.
+    pub fn from_names<S: AsRef<str>>(
+        mib: &Mib,
+        oid: &Oid,
+        names: impl IntoIterator<Item = S>,
+    ) -> SnmpResult<Self> {
+        let defined = mib.bit_names(oid).unwrap_or_default();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let name = name.as_ref();
+                defined
+                    .iter()
+                    .find(|(_, defined)| defined == name)
+                    .and_then(|(bit, _)| u32::try_from(*bit).ok())
+                    .ok_or_else(|| SnmpError::UnknownName(name.to_string()))
+            })
+            .collect::<SnmpResult<Vec<_>>>()
+            .map(Bits::from_positions)
+    }
+
+    pub fn is_set(&self, bit: u32) -> bool {
+        self.0
+            .get(bit as usize / 8)
+            .is_some_and(|octet| octet & (0x80 >> (bit % 8)) != 0)
+    }
+
+    /// Sets `bit`, growing the string to reach it.
+    pub fn set(&mut self, bit: u32) {
+        let octet = bit as usize / 8;
+        if self.0.len() <= octet {
+            self.0.resize(octet + 1, 0);
+        }
+        self.0[octet] |= 0x80 >> (bit % 8);
+    }
+
+    pub fn clear(&mut self, bit: u32) {
+        if let Some(octet) = self.0.get_mut(bit as usize / 8) {
+            *octet &= !(0x80 >> (bit % 8));
+        }
+    }
+
+    /// The positions of the bits set, in increasing order.
+    pub fn positions(&self) -> Vec<u32> {
+        (0..self.0.len() as u32 * 8)
+            .filter(|bit| self.is_set(*bit))
+            .collect()
+    }
+
+    /// The bits set with the names the loaded `mib` gives them as bits of the object `oid`,
+    /// `None` for those it does not name.
+    pub fn names<'m>(&self, mib: &'m Mib, oid: &Oid) -> Vec<(u32, Option<&'m str>)> {
+        let defined = mib.bit_names(oid).unwrap_or_default();
+
+        self.positions()
+            .into_iter()
+            .map(|bit| {
+                let name = defined
+                    .iter()
+                    .find(|(defined, _)| *defined == i64::from(bit))
+                    .map(|(_, name)| name.as_str());
+                (bit, name)
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<u8>> for Bits {
+    fn from(octets: Vec<u8>) -> Self {
+        Bits(octets)
+    }
+}
+
+impl FromIterator<u32> for Bits {
+    fn from_iter<I: IntoIterator<Item = u32>>(positions: I) -> Self {
+        Bits::from_positions(positions)
+    }
+}
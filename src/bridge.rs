@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::{IndexDecoder, MacAddr, Table, Value};
+
+/// dot1dBasePortTable of BRIDGE-MIB (RFC 4188), mapping bridge ports to interfaces.
+pub(crate) const BASE_PORT_TABLE: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 1, 4];
+/// dot1dBasePortIfIndex.
+pub(crate) const BASE_PORT_COLUMNS: [u32; 1] = [2];
+/// dot1dTpFdbTable, indexed by MAC address.
+pub(crate) const TP_FDB_TABLE: [u32; 9] = [1, 3, 6, 1, 2, 1, 17, 4, 3];
+/// dot1qTpFdbTable of Q-BRIDGE-MIB (RFC 4363), indexed by filtering database and MAC.
+pub(crate) const Q_TP_FDB_TABLE: [u32; 11] = [1, 3, 6, 1, 2, 1, 17, 7, 1, 2, 2];
+/// The port and status columns, numbered alike in both forwarding tables.
+pub(crate) const FDB_COLUMNS: [u32; 2] = [2, 3];
+
+/// dot1dTpFdbStatus or dot1qTpFdbStatus: how the entry came about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FdbStatus {
+    Other,
+    Invalid,
+    Learned,
+    /// One of the bridge's own addresses.
+    SelfAddress,
+    /// Configured statically.
+    Mgmt,
+    Unknown(i64),
+}
+
+impl From<i64> for FdbStatus {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => FdbStatus::Other,
+            2 => FdbStatus::Invalid,
+            3 => FdbStatus::Learned,
+            4 => FdbStatus::SelfAddress,
+            5 => FdbStatus::Mgmt,
+            other => FdbStatus::Unknown(other),
+        }
+    }
+}
+
+/// Where a switch forwards frames for one MAC address, as returned by
+/// [`SyncSession::fdb_table`](crate::SyncSession::fdb_table).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FdbEntry {
+    /// The dot1qFdbId the entry was learned in, which most switches number after the VLAN;
+    /// `None` for entries from the VLAN-unaware dot1dTpFdbTable.
+    pub vlan: Option<u32>,
+    pub mac: MacAddr,
+    /// The bridge port, 0 when the address was not learned on a port.
+    pub port: u32,
+    /// The ifIndex of `port`, when dot1dBasePortTable maps it.
+    pub if_index: Option<u32>,
+    pub status: Option<FdbStatus>,
+}
+
+/// Decodes the rows of dot1qTpFdbTable, indexed by dot1qFdbId and MAC address, or of
+/// dot1dTpFdbTable, indexed by the MAC address alone.
+pub(crate) fn entries(fdb: Table, q_bridge: bool, base_ports: &Table) -> Vec<FdbEntry> {
+    let if_indexes: HashMap<u32, u32> = base_ports
+        .iter()
+        .filter_map(|(index, row)| match (&index[..], row.get(&2)) {
+            ([port], Some(Value::Integer(if_index))) => Some((*port, *if_index as u32)),
+            _ => None,
+        })
+        .collect();
+
+    fdb.into_iter()
+        .filter_map(|(index, row)| {
+            let mut index = IndexDecoder::new(&index);
+            let vlan = if q_bridge {
+                Some(index.integer().ok()?)
+            } else {
+                None
+            };
+            let mac = MacAddr(index.fixed_string(6).ok()?.try_into().ok()?);
+            index.finish().ok()?;
+
+            let port = match row.get(&2) {
+                Some(Value::Integer(port)) => *port as u32,
+                _ => 0,
+            };
+            let status = match row.get(&3) {
+                Some(Value::Integer(status)) => Some(FdbStatus::from(*status)),
+                _ => None,
+            };
+
+            Some(FdbEntry {
+                vlan,
+                mac,
+                port,
+                if_index: if_indexes.get(&port).copied(),
+                status,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! Message buffers kept between requests, so busy sessions stop allocating a receive
+//! buffer of the transport's maximum message size and an encode buffer for every request.
+
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError};
+
+/// How many idle buffers a pool keeps; requests in flight beyond that allocate their own.
+const MAX_IDLE: usize = 4;
+
+#[derive(Debug, Default)]
+pub(crate) struct BufferPool {
+    idle: Mutex<Vec<Vec<u8>>>,
+}
+
+impl BufferPool {
+    /// An empty buffer, with the capacity of one returned earlier if there is one.
+    pub(crate) fn get(&self) -> Buffer<'_> {
+        let buffer = self
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop()
+            .unwrap_or_default();
+
+        Buffer { buffer, pool: self }
+    }
+}
+
+/// A buffer that goes back to its pool when dropped.
+pub(crate) struct Buffer<'a> {
+    buffer: Vec<u8>,
+    pool: &'a BufferPool,
+}
+
+impl Buffer<'_> {
+    /// Fills the buffer with `len` zeroes to receive into.
+    pub(crate) fn zeroed(mut self, len: usize) -> Self {
+        self.buffer.resize(len, 0);
+        self
+    }
+}
+
+impl Deref for Buffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for Buffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for Buffer<'_> {
+    fn drop(&mut self) {
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.clear();
+
+        let mut idle = self
+            .pool
+            .idle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if idle.len() < MAX_IDLE {
+            idle.push(buffer);
+        }
+    }
+}
@@ -0,0 +1,506 @@
+use std::io;
+use std::iter;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rasn_snmp::v2;
+
+use crate::hooks::Hooks;
+use crate::options::Context;
+use crate::security::Security;
+use crate::transport::{self, Hosts, SocketOptions};
+#[cfg(feature = "pcap")]
+use crate::PcapWriter;
+use crate::{
+    Decoding, DispatchedTransport, Dispatcher, EngineId, RateLimiter, RetryPolicy, SyncSession,
+    TcpTransport, Transport, UdpTransport, UsmUser, UsmUserTable, BUFFER_SIZE,
+};
+
+/// The SNMP version a session speaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    V1,
+    V2c,
+    V3,
+}
+
+impl Version {
+    /// The msgVersion value on the wire.
+    pub fn wire(self) -> u8 {
+        match self {
+            Version::V1 => 0,
+            Version::V2c => 1,
+            Version::V3 => 3,
+        }
+    }
+}
+
+/// Transport settings shared by every way of opening a session.
+#[derive(Debug, Clone)]
+pub(crate) struct Config {
+    pub(crate) timeout: Duration,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) socket: SocketOptions,
+    pub(crate) max_message_size: usize,
+    pub(crate) recv_buffer_size: Option<usize>,
+    pub(crate) exceptions_as_errors: bool,
+    pub(crate) decoding: Decoding,
+    pub(crate) version_fallback: bool,
+    pub(crate) rate_limit: Option<RateLimiter>,
+    pub(crate) resolve_every: Option<Duration>,
+    pub(crate) resolve_on_failure: bool,
+    pub(crate) context: Context,
+    pub(crate) hooks: Hooks,
+    #[cfg(feature = "pcap")]
+    pub(crate) capture: Option<PcapWriter>,
+}
+
+impl Config {
+    pub(crate) fn with_timeout(timeout: Duration) -> Self {
+        Config {
+            timeout,
+            retry: RetryPolicy::default(),
+            socket: SocketOptions::default(),
+            max_message_size: BUFFER_SIZE,
+            recv_buffer_size: None,
+            exceptions_as_errors: false,
+            decoding: Decoding::default(),
+            version_fallback: false,
+            rate_limit: None,
+            resolve_every: None,
+            resolve_on_failure: false,
+            context: Context::default(),
+            hooks: Hooks::default(),
+            #[cfg(feature = "pcap")]
+            capture: None,
+        }
+    }
+}
+
+/// Structured configuration for [`SyncSession`] and `AsyncSession`, started with
+/// [`SyncSession::builder`]. Defaults to SNMPv2c with community `public` on port 161, a
+/// one second timeout and one retransmission.
+#[derive(Debug, Clone)]
+pub struct SessionBuilder {
+    host: String,
+    fallbacks: Vec<String>,
+    port: u16,
+    version: Version,
+    communities: Vec<Vec<u8>>,
+    user: Option<UsmUser>,
+    /// The table to look the user up in by name, when building with it.
+    users: Option<(UsmUserTable, Vec<u8>)>,
+    /// The engine SNMPv3 traps are sent from, and how often it restarted.
+    local_engine: Option<(EngineId, u32)>,
+    config: Config,
+}
+
+impl SessionBuilder {
+    /// `host` is a host name or IP address, such as `fe80::1%eth0` with a zone; a full
+    /// socket address overrides the port. Over UDP, every address a name resolves to is
+    /// tried in turn when requests go unanswered.
+    pub fn new(host: impl Into<String>) -> Self {
+        SessionBuilder {
+            host: host.into(),
+            fallbacks: Vec::new(),
+            port: 161,
+            version: Version::V2c,
+            communities: vec![b"public".to_vec()],
+            user: None,
+            users: None,
+            local_engine: None,
+            config: Config::with_timeout(Duration::from_secs(1)),
+        }
+    }
+
+    pub fn v1(self, community: impl AsRef<[u8]>) -> Self {
+        self.version(Version::V1).community(community)
+    }
+
+    pub fn v2c(self, community: impl AsRef<[u8]>) -> Self {
+        self.version(Version::V2c).community(community)
+    }
+
+    pub fn v3(mut self, user: UsmUser) -> Self {
+        self.user = Some(user);
+        self.users = None;
+        self.version(Version::V3)
+    }
+
+    /// SNMPv3 as the user named `name` in `users`, as it is when the session is built,
+    /// with its keys cached in the table.
+    pub fn v3_user(mut self, users: &UsmUserTable, name: impl AsRef<[u8]>) -> Self {
+        self.user = None;
+        self.users = Some((users.clone(), name.as_ref().to_vec()));
+        self.version(Version::V3)
+    }
+
+    /// The engine ID SNMPv3 traps are sent from, which receivers localize the user's keys
+    /// to, and how many times it was restarted. Boots should be kept and incremented on
+    /// every start, e.g. in a state file, since receivers reject messages whose boots went
+    /// back; the engine time counts from when the session is built. Informs are sent to
+    /// the receiver's engine instead, which is discovered like an agent's.
+    pub fn local_engine(mut self, engine_id: EngineId, boots: u32) -> Self {
+        self.local_engine = Some((engine_id, boots));
+        self
+    }
+
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn community(mut self, community: impl AsRef<[u8]>) -> Self {
+        self.communities = vec![community.as_ref().to_vec()];
+        self
+    }
+
+    /// Communities to try in order, for agents whose community is not known up front. A
+    /// request that times out with every retransmission is resent with the next one, and
+    /// the session keeps the first that is answered, as reported by
+    /// [`SyncSession::community`].
+    pub fn communities<C: AsRef<[u8]>>(mut self, communities: impl IntoIterator<Item = C>) -> Self {
+        self.communities = communities
+            .into_iter()
+            .map(|community| community.as_ref().to_vec())
+            .collect();
+        self
+    }
+
+    /// Adds another address of the same agent, e.g. its management VLAN address after its
+    /// loopback, given like the host. Over UDP, requests fail over to it once the addresses
+    /// before it leave one unanswered; over TCP, it is connected to if they refuse.
+    pub fn fallback(mut self, host: impl Into<String>) -> Self {
+        self.fallbacks.push(host.into());
+        self
+    }
+
+    /// Looks the host and fallbacks up again once `interval` has passed since they last
+    /// were, before the next request, and reconnects if their addresses changed; for
+    /// long-lived sessions to agents whose address DHCP or DNS may change. Only used for
+    /// UDP.
+    pub fn resolve_every(mut self, interval: Duration) -> Self {
+        self.config.resolve_every = Some(interval);
+        self
+    }
+
+    /// Looks the host and fallbacks up again when a request went unanswered at every
+    /// address, and resends it once if their addresses changed; only used for UDP.
+    pub fn resolve_on_failure(mut self, enabled: bool) -> Self {
+        self.config.resolve_on_failure = enabled;
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// How long to wait for each response before retransmitting.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// Number of retransmissions, keeping the rest of the retry policy.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retry = self.config.retry.with_retries(retries);
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+
+    /// Local address and port to bind instead of the unspecified address of the target's
+    /// family and any free port; only used for UDP.
+    pub fn local_addr(self, addr: SocketAddr) -> Self {
+        self.local_ip(addr.ip()).source_port(addr.port())
+    }
+
+    /// Sends from `ip`, one of the host's addresses, e.g. on multihomed pollers whose
+    /// agents only answer a management address; only used for UDP.
+    pub fn local_ip(mut self, ip: IpAddr) -> Self {
+        self.config.socket.local_ip = Some(ip);
+        self
+    }
+
+    /// Sends from a fixed source port, for firewalls that only let a known range through;
+    /// only used for UDP. Sessions cannot share a port, except through a
+    /// [`Dispatcher`] bound to it.
+    pub fn source_port(mut self, port: u16) -> Self {
+        self.config.socket.local_port = port;
+        self
+    }
+
+    /// Sends through the network interface `name`, e.g. `eth1` or a VRF device, whatever
+    /// the routing table says; only used for UDP, and only supported on Linux and
+    /// Android, where it usually needs `CAP_NET_RAW`.
+    pub fn interface(mut self, name: impl Into<String>) -> Self {
+        self.config.socket.device = Some(name.into());
+        self
+    }
+
+    /// Marks outgoing datagrams with the TOS octet `tos`, the traffic class in IPv6; only
+    /// used for UDP.
+    pub fn tos(mut self, tos: u8) -> Self {
+        self.config.socket.tos = Some(tos);
+        self
+    }
+
+    /// Marks outgoing datagrams with the DSCP code point `dscp`, e.g. 16 for CS2, which
+    /// management traffic is often expected to carry; only used for UDP.
+    pub fn dscp(self, dscp: u8) -> Self {
+        self.tos(dscp << 2)
+    }
+
+    /// The TTL of outgoing datagrams, the hop limit in IPv6; only used for UDP.
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.config.socket.ttl = Some(ttl);
+        self
+    }
+
+    /// Size of the kernel buffer responses wait in until received (SO_RCVBUF), for
+    /// sessions with many requests in flight; only used for UDP. The system may round it
+    /// or cap it.
+    pub fn socket_recv_buffer(mut self, size: usize) -> Self {
+        self.config.socket.recv_buffer = Some(size);
+        self
+    }
+
+    /// Largest message sent; also advertised as msgMaxSize in SNMPv3, which asks the agent
+    /// to keep its responses within it.
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.config.max_message_size = size;
+        self
+    }
+
+    /// Size of the buffer responses are received into; defaults to the largest message the
+    /// transport can carry. Larger responses fail with
+    /// [`SnmpError::Truncated`](crate::SnmpError::Truncated).
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.config.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Makes GETs fail with [`SnmpError::NoSuchObject`](crate::SnmpError::NoSuchObject) or
+    /// [`SnmpError::NoSuchInstance`](crate::SnmpError::NoSuchInstance) instead of returning
+    /// the exception as the [`Value`](crate::Value) of the binding.
+    pub fn exceptions_as_errors(mut self, enabled: bool) -> Self {
+        self.config.exceptions_as_errors = enabled;
+        self
+    }
+
+    /// Whether the deviations from the encoding rules some agents are known for are
+    /// repaired, the default, or make their responses fail; see [`Decoding`].
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.config.decoding = decoding;
+        self
+    }
+
+    /// The SNMPv3 contextName of every request, unless overridden with
+    /// [`RequestOptions::context`](crate::RequestOptions::context).
+    pub fn context(mut self, name: impl AsRef<[u8]>) -> Self {
+        self.config.context.name = Some(name.as_ref().to_vec());
+        self
+    }
+
+    /// The SNMPv3 contextEngineID of every request, unless overridden with
+    /// [`RequestOptions::context_engine_id`](crate::RequestOptions::context_engine_id).
+    pub fn context_engine_id(mut self, engine_id: EngineId) -> Self {
+        self.config.context.engine_id = Some(engine_id);
+        self
+    }
+
+    /// Retries a request that timed out with every retransmission once more in the other
+    /// of SNMPv1 and SNMPv2c, for fleets mixing both. When that is answered the session
+    /// stays with the other version, which [`SyncSession::version`] reports.
+    pub fn version_fallback(mut self, enabled: bool) -> Self {
+        self.config.version_fallback = enabled;
+        self
+    }
+
+    /// Paces the requests of the session with `limiter`, which may be shared with other
+    /// sessions for a limit across all of them.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.config.rate_limit = Some(limiter);
+        self
+    }
+
+    /// Calls `hook` with every message the session sends, retransmissions and SNMPv3
+    /// discovery and notifications included, and the PDU of the request it carries, which
+    /// discovery, SNMPv1 traps and [`SyncSession::send_raw`](crate::SyncSession::send_raw)
+    /// have none of. What the hook
+    /// leaves in the message is sent instead, e.g. to see how an agent takes a malformed
+    /// one.
+    pub fn on_send(
+        mut self,
+        hook: impl Fn(&mut Vec<u8>, Option<&v2::Pdus>) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.hooks.on_send = Some(Arc::new(hook));
+        self
+    }
+
+    /// Calls `hook` with every datagram the session receives, once it has been checked,
+    /// and the PDU of the response when it answers the request; for logging, timing
+    /// requests against [`SessionBuilder::on_send`] or keeping an audit trail.
+    pub fn on_receive(
+        mut self,
+        hook: impl Fn(&[u8], Option<&v2::Pdus>) + Send + Sync + 'static,
+    ) -> Self {
+        self.config.hooks.on_receive = Some(Arc::new(hook));
+        self
+    }
+
+    /// Writes every message the session sends and receives to `capture`, e.g. to see in
+    /// Wireshark what a misbehaving agent answers.
+    #[cfg(feature = "pcap")]
+    pub fn capture(mut self, capture: PcapWriter) -> Self {
+        self.config.capture = Some(capture);
+        self
+    }
+
+    /// The host if it is an address, IPv6 zones included; a socket address keeps its own
+    /// port.
+    fn socket_addr(&self) -> io::Result<Option<SocketAddr>> {
+        transport::literal(&self.host, self.port)
+    }
+
+    /// The host, then each fallback.
+    fn hosts(&self) -> Hosts {
+        let hosts = iter::once(&self.host).chain(&self.fallbacks).cloned();
+
+        Hosts::new(hosts.collect(), self.port)
+    }
+
+    fn security(&self) -> io::Result<Security> {
+        let security = self.credentials()?;
+
+        Ok(match &self.local_engine {
+            Some((engine_id, boots)) => security.with_local_engine(engine_id.clone(), *boots),
+            None => security,
+        })
+    }
+
+    fn credentials(&self) -> io::Result<Security> {
+        if let (Version::V3, Some((users, name))) = (self.version, &self.users) {
+            let user = users.get(name).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "no such SNMPv3 user")
+            })?;
+            return Security::usm(user, Some(users.clone()), self.config.max_message_size);
+        }
+
+        match (self.version, &self.user) {
+            (Version::V3, Some(user)) => {
+                Security::usm(user.clone(), None, self.config.max_message_size)
+            }
+            (Version::V3, None) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "SNMPv3 requires a user",
+            )),
+            (_, _) if self.communities.is_empty() => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no community given",
+            )),
+            (version, _) => Ok(Security::communities(
+                version.wire(),
+                self.communities.clone(),
+            )),
+        }
+    }
+
+    pub fn build(self) -> io::Result<SyncSession> {
+        let transport = UdpTransport::open_hosts(self.hosts(), &self.config.socket)?;
+
+        self.build_with(transport)
+    }
+
+    /// Connects over TCP (RFC 3430) instead of UDP.
+    pub fn build_tcp(self) -> io::Result<SyncSession<TcpTransport>> {
+        let transport = TcpTransport::connect(&self.hosts().resolve()?[..])?;
+
+        self.build_with(transport)
+    }
+
+    /// Sends over the shared socket of `dispatcher` instead of a socket of its own.
+    pub fn build_dispatched(
+        self,
+        dispatcher: &Dispatcher,
+    ) -> io::Result<SyncSession<DispatchedTransport>> {
+        let transport = match self.socket_addr()? {
+            Some(addr) => dispatcher.transport(addr)?,
+            None => dispatcher.transport((self.host.as_str(), self.port))?,
+        };
+
+        self.build_with(transport)
+    }
+
+    /// Runs the session over `transport`; the host and port are not used.
+    pub fn build_with<T: Transport>(self, transport: T) -> io::Result<SyncSession<T>> {
+        let security = self.security()?;
+
+        Ok(SyncSession::with_transport(
+            security,
+            transport,
+            self.config,
+        ))
+    }
+
+    /// Connects over TLS on TCP (RFC 6353) with the Transport Security Model; DTLS over UDP
+    /// is not supported. Any version, community or user set on the builder is ignored. The
+    /// host name is what the agent's certificate is verified against, so it should not be an
+    /// address when roots are used.
+    /// Agents listen on port 10161 (RFC 6353 10.), which has to be set with
+    /// [`SessionBuilder::port`].
+    #[cfg(feature = "tls")]
+    pub fn build_tls(self, tls: &crate::TlsConfig) -> io::Result<SyncSession<crate::TlsTransport>> {
+        let transport = match self.socket_addr()? {
+            Some(addr) => crate::TlsTransport::connect(addr, &addr.ip().to_string(), tls)?,
+            None => crate::TlsTransport::connect((self.host.as_str(), self.port), &self.host, tls)?,
+        };
+        let security = Security::tsm(self.config.max_message_size);
+
+        Ok(SyncSession::with_transport(
+            security,
+            transport,
+            self.config,
+        ))
+    }
+
+    #[cfg(feature = "tokio")]
+    pub async fn build_async(self) -> io::Result<crate::AsyncSession> {
+        let transport =
+            crate::AsyncUdpTransport::open_hosts(self.hosts(), &self.config.socket).await?;
+
+        self.build_async_with(transport)
+    }
+
+    /// Connects over TCP (RFC 3430) instead of UDP.
+    #[cfg(feature = "tokio")]
+    pub async fn build_async_tcp(
+        self,
+    ) -> io::Result<crate::AsyncSession<crate::AsyncTcpTransport>> {
+        let addrs = self.hosts().resolve_async().await?;
+        let transport = crate::AsyncTcpTransport::connect(&addrs[..]).await?;
+
+        self.build_async_with(transport)
+    }
+
+    /// Runs the session over `transport`; the host and port are not used.
+    #[cfg(feature = "tokio")]
+    pub fn build_async_with<T: crate::AsyncTransport>(
+        self,
+        transport: T,
+    ) -> io::Result<crate::AsyncSession<T>> {
+        let security = self.security()?;
+
+        Ok(crate::AsyncSession::with_transport(
+            security,
+            transport,
+            self.config,
+        ))
+    }
+}
@@ -0,0 +1,206 @@
+//! Answering repeated GETs and walks from memory, for data that changes slowly.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{
+    oid, IntoOid, Oid, RequestOptions, SnmpResult, SyncSession, Transport, UdpTransport, Value,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Get(Oid),
+    Walk(Oid),
+}
+
+#[derive(Debug)]
+enum Cached {
+    Value(Value),
+    Walk(BTreeMap<Vec<u32>, Value>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    expires: Instant,
+    cached: Cached,
+}
+
+/// A session whose GETs and walks are answered from memory until their result is older
+/// than its time to live, e.g. so dashboards refreshing every few seconds only ask for
+/// sysDescr or ifAlias once an hour. Errors are not cached.
+///
+/// GETs are cached per OID, so a GET of several OIDs only asks the agent for those not
+/// fresh in the cache; walks are cached per subtree. When `max_entries` are cached, the
+/// one closest to expiring makes room. Defaults to 1024 entries.
+pub struct Cache<T = UdpTransport> {
+    session: SyncSession<T>,
+    ttl: Duration,
+    /// Times to live of subtrees, overriding `ttl`.
+    ttls: Vec<(Oid, Duration)>,
+    max_entries: usize,
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl<T: Transport> Cache<T> {
+    /// Caches the results of `session` for `ttl`.
+    pub fn new(session: SyncSession<T>, ttl: Duration) -> Self {
+        Cache {
+            session,
+            ttl,
+            ttls: Vec::new(),
+            max_entries: 1024,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Caches instances in the subtree at `oid` for `ttl` instead, e.g. `Duration::ZERO`
+    /// for counters; the longest matching subtree wins.
+    pub fn ttl_for(mut self, oid: impl IntoOid, ttl: Duration) -> SnmpResult<Self> {
+        self.ttls.push((oid.into_oid()?, ttl));
+        Ok(self)
+    }
+
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.max_entries = max;
+        self
+    }
+
+    /// The session, for requests that should not be cached.
+    pub fn session(&self) -> &SyncSession<T> {
+        &self.session
+    }
+
+    fn ttl(&self, oid: &Oid) -> Duration {
+        self.ttls
+            .iter()
+            .filter(|(prefix, _)| oid.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.ttl, |(_, ttl)| *ttl)
+    }
+
+    fn lookup<R>(&self, key: &Key, read: impl FnOnce(&Cached) -> Option<R>) -> Option<R> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        entries
+            .get(key)
+            .filter(|entry| entry.expires > Instant::now())
+            .and_then(|entry| read(&entry.cached))
+    }
+
+    fn store(&self, key: Key, cached: Cached) {
+        let oid = match &key {
+            Key::Get(oid) | Key::Walk(oid) => oid,
+        };
+        let ttl = self.ttl(oid);
+        if ttl.is_zero() || self.max_entries == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.expires > now);
+        }
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.expires)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                expires: now + ttl,
+                cached,
+            },
+        );
+    }
+
+    pub fn get(&self, oid: impl IntoOid) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_many(&[oid.into_oid()?])
+    }
+
+    /// GETs `oids` like [`SyncSession::get_many`], asking the agent only for those not
+    /// cached.
+    pub fn get_many<O: IntoOid + Clone>(&self, oids: &[O]) -> SnmpResult<Vec<(Oid, Value)>> {
+        let oids = oid::into_oids(oids)?;
+        let mut vars: Vec<Option<(Oid, Value)>> = oids
+            .iter()
+            .map(|oid| {
+                self.lookup(&Key::Get(oid.clone()), |cached| match cached {
+                    Cached::Value(value) => Some((oid.clone(), value.clone())),
+                    Cached::Walk(_) => None,
+                })
+            })
+            .collect();
+
+        let missing: Vec<Oid> = oids
+            .iter()
+            .zip(&vars)
+            .filter(|(_, var)| var.is_none())
+            .map(|(oid, _)| oid.clone())
+            .collect();
+        if !missing.is_empty() {
+            let mut fetched = self.session.get_many(&missing)?.into_iter();
+
+            for var in vars.iter_mut().filter(|var| var.is_none()) {
+                let Some((oid, value)) = fetched.next() else {
+                    break;
+                };
+                self.store(Key::Get(oid.clone()), Cached::Value(value.clone()));
+                *var = Some((oid, value));
+            }
+        }
+
+        Ok(vars.into_iter().flatten().collect())
+    }
+
+    /// Walks the subtree at `oid` like [`SyncSession::bulk_walk`], unless it is cached.
+    pub fn walk(&self, oid: impl IntoOid) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        let oid = oid.into_oid()?;
+        let key = Key::Walk(oid.clone());
+
+        let cached = self.lookup(&key, |cached| match cached {
+            Cached::Walk(walk) => Some(walk.clone()),
+            Cached::Value(_) => None,
+        });
+        if let Some(walk) = cached {
+            return Ok(walk);
+        }
+
+        let walk = self
+            .session
+            .bulk_walk_with(&oid, &RequestOptions::default())?;
+        self.store(key, Cached::Walk(walk.clone()));
+
+        Ok(walk)
+    }
+
+    /// Forgets the GETs of instances in the subtree at `oid` and the walks of subtrees
+    /// that overlap it.
+    pub fn invalidate(&self, oid: impl IntoOid) -> SnmpResult<()> {
+        let oid = oid.into_oid()?;
+
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|key, _| match key {
+                Key::Get(cached) => !cached.starts_with(&oid),
+                Key::Walk(cached) => !cached.starts_with(&oid) && !oid.starts_with(cached),
+            });
+
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
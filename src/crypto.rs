@@ -0,0 +1,246 @@
+//! The cryptographic primitives of the User-based Security Model, behind a provider that
+//! can be swapped for one built on a validated module, e.g. in FIPS environments.
+
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use aes::cipher::block_padding::NoPadding;
+use aes::cipher::{
+    AsyncStreamCipher, BlockCipher, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit,
+};
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::{AuthProtocol, SnmpError, SnmpResult};
+
+/// Hashes, HMACs and ciphers for SNMPv3, selected with [`set_crypto_provider`].
+///
+/// Every method defaults to the RustCrypto implementation, so a provider only overrides
+/// what it implements itself; [`RustCrypto`] overrides nothing. Keys are as long as the
+/// algorithm needs: 16, 24 or 32 octets for AES, 8 for DES.
+pub trait CryptoProvider: fmt::Debug + Send + Sync {
+    /// The hash of `auth` over `parts`, one after the other.
+    fn digest(&self, auth: AuthProtocol, parts: &[&[u8]]) -> Vec<u8> {
+        rust_crypto::digest(auth, parts)
+    }
+
+    /// The hash of `auth` over one megabyte of `password` repeated (RFC 3414 A.2), which a
+    /// provider may want to stream rather than have buffered.
+    fn password_to_key(&self, auth: AuthProtocol, password: &[u8]) -> Vec<u8> {
+        rust_crypto::password_to_key(auth, password)
+    }
+
+    /// The full, untruncated HMAC of `data` with the hash of `auth`.
+    fn hmac(&self, auth: AuthProtocol, key: &[u8], data: &[u8]) -> Vec<u8> {
+        rust_crypto::hmac(auth, key, data)
+    }
+
+    /// Encrypts `data` in place with AES in CFB-128 mode.
+    fn aes_cfb_encrypt(&self, key: &[u8], iv: &[u8; 16], data: &mut [u8]) {
+        rust_crypto::aes_cfb(key, iv, data, true)
+    }
+
+    fn aes_cfb_decrypt(&self, key: &[u8], iv: &[u8; 16], data: &mut [u8]) {
+        rust_crypto::aes_cfb(key, iv, data, false)
+    }
+
+    /// Encrypts `data`, a multiple of 8 octets, in place with DES in CBC mode.
+    fn des_cbc_encrypt(&self, key: &[u8], iv: &[u8; 8], data: &mut [u8]) {
+        rust_crypto::des_cbc_encrypt(key, iv, data)
+    }
+
+    /// Fails when `data` is not a multiple of 8 octets.
+    fn des_cbc_decrypt(&self, key: &[u8], iv: &[u8; 8], data: &mut [u8]) -> SnmpResult<()> {
+        rust_crypto::des_cbc_decrypt(key, iv, data)
+    }
+}
+
+/// The pure Rust implementations of the RustCrypto crates, used unless another provider is
+/// set.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustCrypto;
+
+impl CryptoProvider for RustCrypto {}
+
+static PROVIDER: OnceLock<Arc<dyn CryptoProvider>> = OnceLock::new();
+
+/// Makes `provider` the one SNMPv3 uses in this process. Only works before the first
+/// SNMPv3 message or key is computed, after which the provider in use is returned back.
+pub fn set_crypto_provider(
+    provider: Arc<dyn CryptoProvider>,
+) -> Result<(), Arc<dyn CryptoProvider>> {
+    PROVIDER.set(provider)
+}
+
+pub(crate) fn provider() -> &'static dyn CryptoProvider {
+    PROVIDER.get_or_init(|| Arc::new(RustCrypto)).as_ref()
+}
+
+mod rust_crypto {
+    use super::*;
+
+    fn hash<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = D::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    pub(super) fn digest(auth: AuthProtocol, parts: &[&[u8]]) -> Vec<u8> {
+        match auth {
+            AuthProtocol::Md5 => hash::<Md5>(parts),
+            AuthProtocol::Sha1 => hash::<Sha1>(parts),
+            AuthProtocol::Sha224 => hash::<Sha224>(parts),
+            AuthProtocol::Sha256 => hash::<Sha256>(parts),
+            AuthProtocol::Sha384 => hash::<Sha384>(parts),
+            AuthProtocol::Sha512 => hash::<Sha512>(parts),
+        }
+    }
+
+    fn expand_password<D: Digest>(password: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        for chunk in super::password_chunks(password) {
+            hasher.update(chunk);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    pub(super) fn password_to_key(auth: AuthProtocol, password: &[u8]) -> Vec<u8> {
+        match auth {
+            AuthProtocol::Md5 => expand_password::<Md5>(password),
+            AuthProtocol::Sha1 => expand_password::<Sha1>(password),
+            AuthProtocol::Sha224 => expand_password::<Sha224>(password),
+            AuthProtocol::Sha256 => expand_password::<Sha256>(password),
+            AuthProtocol::Sha384 => expand_password::<Sha384>(password),
+            AuthProtocol::Sha512 => expand_password::<Sha512>(password),
+        }
+    }
+
+    fn mac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    pub(super) fn hmac(auth: AuthProtocol, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match auth {
+            AuthProtocol::Md5 => mac::<Hmac<Md5>>(key, data),
+            AuthProtocol::Sha1 => mac::<Hmac<Sha1>>(key, data),
+            AuthProtocol::Sha224 => mac::<Hmac<Sha224>>(key, data),
+            AuthProtocol::Sha256 => mac::<Hmac<Sha256>>(key, data),
+            AuthProtocol::Sha384 => mac::<Hmac<Sha384>>(key, data),
+            AuthProtocol::Sha512 => mac::<Hmac<Sha512>>(key, data),
+        }
+    }
+
+    fn cfb<C>(key: &[u8], iv: &[u8], data: &mut [u8], encrypt: bool)
+    where
+        C: BlockCipher + BlockEncryptMut + KeyInit,
+    {
+        if encrypt {
+            cfb_mode::Encryptor::<C>::new_from_slices(key, iv)
+                .expect("key and IV sizes match the cipher")
+                .encrypt(data);
+        } else {
+            cfb_mode::Decryptor::<C>::new_from_slices(key, iv)
+                .expect("key and IV sizes match the cipher")
+                .decrypt(data);
+        }
+    }
+
+    pub(super) fn aes_cfb(key: &[u8], iv: &[u8; 16], data: &mut [u8], encrypt: bool) {
+        match key.len() {
+            16 => cfb::<aes::Aes128>(key, iv, data, encrypt),
+            24 => cfb::<aes::Aes192>(key, iv, data, encrypt),
+            _ => cfb::<aes::Aes256>(key, iv, data, encrypt),
+        }
+    }
+
+    pub(super) fn des_cbc_encrypt(key: &[u8], iv: &[u8; 8], data: &mut [u8]) {
+        let len = data.len();
+        cbc::Encryptor::<des::Des>::new(key[..8].into(), iv.into())
+            .encrypt_padded_mut::<NoPadding>(data, len)
+            .expect("data is padded to the block size");
+    }
+
+    pub(super) fn des_cbc_decrypt(key: &[u8], iv: &[u8; 8], data: &mut [u8]) -> SnmpResult<()> {
+        cbc::Decryptor::<des::Des>::new(key[..8].into(), iv.into())
+            .decrypt_padded_mut::<NoPadding>(data)
+            .map_err(|_| SnmpError::AuthenticationError)?;
+        Ok(())
+    }
+}
+
+/// One megabyte of the repeated passphrase, in 64-octet chunks.
+fn password_chunks(password: &[u8]) -> impl Iterator<Item = [u8; 64]> + '_ {
+    let mut repeated = password.iter().copied().cycle();
+
+    (0..1_048_576 / 64).map(move |_| {
+        let mut chunk = [0; 64];
+        chunk.fill_with(|| repeated.next().unwrap_or_default());
+        chunk
+    })
+}
+
+/// HMACs and hashes from [ring](https://docs.rs/ring), for the SHA-1 and SHA-2 protocols
+/// it implements; MD5, SHA-224 and the ciphers, which ring does not expose, stay with
+/// RustCrypto.
+#[cfg(feature = "ring")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ring;
+
+#[cfg(feature = "ring")]
+impl Ring {
+    fn digest_algorithm(auth: AuthProtocol) -> Option<&'static ring::digest::Algorithm> {
+        match auth {
+            AuthProtocol::Sha1 => Some(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY),
+            AuthProtocol::Sha256 => Some(&ring::digest::SHA256),
+            AuthProtocol::Sha384 => Some(&ring::digest::SHA384),
+            AuthProtocol::Sha512 => Some(&ring::digest::SHA512),
+            AuthProtocol::Md5 | AuthProtocol::Sha224 => None,
+        }
+    }
+}
+
+#[cfg(feature = "ring")]
+impl CryptoProvider for Ring {
+    fn digest(&self, auth: AuthProtocol, parts: &[&[u8]]) -> Vec<u8> {
+        let Some(algorithm) = Ring::digest_algorithm(auth) else {
+            return rust_crypto::digest(auth, parts);
+        };
+
+        let mut context = ring::digest::Context::new(algorithm);
+        for part in parts {
+            context.update(part);
+        }
+        context.finish().as_ref().to_vec()
+    }
+
+    fn password_to_key(&self, auth: AuthProtocol, password: &[u8]) -> Vec<u8> {
+        let Some(algorithm) = Ring::digest_algorithm(auth) else {
+            return rust_crypto::password_to_key(auth, password);
+        };
+
+        let mut context = ring::digest::Context::new(algorithm);
+        for chunk in password_chunks(password) {
+            context.update(&chunk);
+        }
+        context.finish().as_ref().to_vec()
+    }
+
+    fn hmac(&self, auth: AuthProtocol, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let algorithm = match auth {
+            AuthProtocol::Sha1 => ring::hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY,
+            AuthProtocol::Sha256 => ring::hmac::HMAC_SHA256,
+            AuthProtocol::Sha384 => ring::hmac::HMAC_SHA384,
+            AuthProtocol::Sha512 => ring::hmac::HMAC_SHA512,
+            AuthProtocol::Md5 | AuthProtocol::Sha224 => return rust_crypto::hmac(auth, key, data),
+        };
+
+        let key = ring::hmac::Key::new(algorithm, key);
+        ring::hmac::sign(&key, data).as_ref().to_vec()
+    }
+}
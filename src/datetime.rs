@@ -0,0 +1,178 @@
+//! The DateAndTime textual convention of SNMPv2-TC (RFC 2579), e.g. hrSystemDate.
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+/// A DateAndTime as the agent sent it: a local time, with the offset from UTC the 11-octet
+/// form adds. Displays as ISO 8601, `2024-03-01T12:34:56.7+02:00`, leaving the offset out
+/// when there is none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DateAndTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    /// Up to 60, for leap seconds.
+    pub second: u8,
+    pub deci_seconds: u8,
+    /// Minutes east of UTC.
+    pub utc_offset: Option<i16>,
+}
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((i64::from(month) + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u8;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u8;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+impl DateAndTime {
+    /// Decodes the 8 or 11 octets of a DateAndTime; `None` when a field is out of range.
+    pub fn from_octets(octets: &[u8]) -> Option<Self> {
+        let (fields, offset) = match octets.len() {
+            8 => (octets, None),
+            11 => (&octets[..8], Some(&octets[8..])),
+            _ => return None,
+        };
+        let &[year_high, year_low, month, day, hour, minute, second, deci_seconds] = fields else {
+            return None;
+        };
+
+        let utc_offset = match offset {
+            Some(&[direction, hours, minutes]) if hours <= 13 && minutes <= 59 => {
+                let minutes = i16::from(hours) * 60 + i16::from(minutes);
+                match direction {
+                    b'+' => Some(minutes),
+                    b'-' => Some(-minutes),
+                    _ => return None,
+                }
+            }
+            Some(_) => return None,
+            None => None,
+        };
+        let valid = (1..=12).contains(&month)
+            && (1..=31).contains(&day)
+            && hour <= 23
+            && minute <= 59
+            && second <= 60
+            && deci_seconds <= 9;
+
+        valid.then_some(DateAndTime {
+            year: u16::from_be_bytes([year_high, year_low]),
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            deci_seconds,
+            utc_offset,
+        })
+    }
+
+    /// The octets to SET, 11 of them when there is an offset.
+    pub fn to_octets(&self) -> Vec<u8> {
+        let mut octets = self.year.to_be_bytes().to_vec();
+        octets.extend([
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            self.deci_seconds,
+        ]);
+        if let Some(offset) = self.utc_offset {
+            let direction = if offset < 0 { b'-' } else { b'+' };
+            let minutes = offset.unsigned_abs();
+            octets.extend([direction, (minutes / 60) as u8, (minutes % 60) as u8]);
+        }
+
+        octets
+    }
+
+    /// `time` in UTC, to the tenth of a second.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let (secs, tenths) = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_millis() / 100),
+            Err(before) => {
+                let before = before.duration();
+                let tenths = before.subsec_millis().div_ceil(100);
+                let secs = -(before.as_secs() as i64) - i64::from(tenths > 0);
+                (secs, (10 - tenths) % 10)
+            }
+        };
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let secs = secs.rem_euclid(86400);
+
+        DateAndTime {
+            year: year as u16,
+            month,
+            day,
+            hour: (secs / 3600) as u8,
+            minute: (secs / 60 % 60) as u8,
+            second: (secs % 60) as u8,
+            deci_seconds: tenths as u8,
+            utc_offset: Some(0),
+        }
+    }
+
+    /// The instant this is, taking a time without an offset as UTC.
+    pub fn to_system_time(&self) -> SystemTime {
+        let days = days_from_civil(self.year.into(), self.month, self.day);
+        let secs = days * 86400
+            + i64::from(self.hour) * 3600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second)
+            - i64::from(self.utc_offset.unwrap_or(0)) * 60;
+        let tenths = Duration::from_millis(u64::from(self.deci_seconds) * 100);
+
+        match u64::try_from(secs) {
+            Ok(secs) => SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + tenths,
+            Err(_) => SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + tenths,
+        }
+    }
+}
+
+impl fmt::Display for DateAndTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.deci_seconds
+        )?;
+
+        match self.utc_offset {
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let minutes = offset.unsigned_abs();
+                write!(f, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+//! Rendering SNMP messages for debugging: decoded, as a tree of their fields like
+//! net-snmp's packet dumps, or where they fail to decode, as a hex dump marking where.
+
+use std::fmt;
+
+use rasn_smi::v1 as smi1;
+use rasn_snmp::{v1, v2, v2c, v3};
+
+use crate::usm::{FLAG_AUTH, FLAG_PRIV, FLAG_REPORTABLE, SECURITY_MODEL_USM};
+use crate::{ber, pdu, ErrorStatus, Hex, Oid, SnmpError, SnmpResult, Ticks, Value};
+
+/// Renders `bytes` as the [`Message`] they decode to or, when they do not, as the error
+/// and a [`HexDump`] marking the element decoding broke at, if the framing is what broke.
+pub fn dump(bytes: &[u8]) -> String {
+    match Message::decode(bytes) {
+        Ok(message) => message.to_string(),
+        Err(err) => {
+            let mark = match &err {
+                SnmpError::Decode { offset, .. } => *offset,
+                _ => ber::invalid_offset(bytes),
+            };
+
+            format!("{}\n{}", err, HexDump { bytes, mark })
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Header {
+    Community {
+        version: u8,
+        community: Vec<u8>,
+    },
+    V3 {
+        message_id: String,
+        max_size: String,
+        flags: u8,
+        security_model: String,
+        /// `None` for security models other than the USM.
+        usm: Option<Box<v3::USMSecurityParameters>>,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Body {
+    Pdu(v2::Pdus),
+    Trap(v1::Trap),
+    Scoped(v3::ScopedPdu),
+    Encrypted(usize),
+}
+
+/// An SNMPv1, SNMPv2c or SNMPv3 message, decoded to be displayed as a tree:
+///
+/// ```text
+/// SNMPv2c message
+///   community: public
+///   Response-PDU
+///     request-id: 1788137437
+///     error-status: noError(0)
+///     error-index: 0
+///     variable-bindings:
+///       1.3.6.1.2.1.1.5.0 = OCTET STRING: sw1
+/// ```
+///
+/// The scoped PDU of an encrypted SNMPv3 message is only shown as its length.
+#[derive(Debug, Clone)]
+pub struct Message {
+    header: Header,
+    body: Body,
+}
+
+impl Message {
+    pub fn decode(bytes: &[u8]) -> SnmpResult<Message> {
+        match ber::message_version(bytes) {
+            Some(0) => {
+                let message: v1::Message<v1::Pdus> = pdu::decode(bytes)?;
+                let body = match message.data {
+                    v1::Pdus::Trap(trap) => Body::Trap(trap),
+                    data => Body::Pdu(crate::v1::from_pdus(data)?),
+                };
+
+                Ok(Message {
+                    header: Header::Community {
+                        version: 0,
+                        community: message.community.to_vec(),
+                    },
+                    body,
+                })
+            }
+            Some(1) => {
+                let message: v2c::Message<v2::Pdus> = pdu::decode(bytes)?;
+
+                Ok(Message {
+                    header: Header::Community {
+                        version: 1,
+                        community: message.community.to_vec(),
+                    },
+                    body: Body::Pdu(message.data),
+                })
+            }
+            Some(3) => {
+                let message: v3::Message = pdu::decode(bytes)?;
+                let global = &message.global_data;
+                let usm = if global.security_model == SECURITY_MODEL_USM.into() {
+                    Some(Box::new(pdu::decode(&message.security_parameters)?))
+                } else {
+                    None
+                };
+                let body = match message.scoped_data {
+                    v3::ScopedPduData::CleartextPdu(scoped) => Body::Scoped(scoped),
+                    v3::ScopedPduData::EncryptedPdu(encrypted) => Body::Encrypted(encrypted.len()),
+                };
+
+                Ok(Message {
+                    header: Header::V3 {
+                        message_id: global.message_id.to_string(),
+                        max_size: global.max_size.to_string(),
+                        flags: global.flags.first().copied().unwrap_or_default(),
+                        security_model: global.security_model.to_string(),
+                        usm,
+                    },
+                    body,
+                })
+            }
+            _ => Err(SnmpError::InvalidMessage("unknown SNMP version")),
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.header {
+            Header::Community { version, community } => {
+                let version = if *version == 0 { "SNMPv1" } else { "SNMPv2c" };
+                writeln!(f, "{} message", version)?;
+                writeln!(f, "  community: {}", text(community))?;
+            }
+            Header::V3 {
+                message_id,
+                max_size,
+                flags,
+                security_model,
+                usm,
+            } => {
+                writeln!(f, "SNMPv3 message")?;
+                writeln!(f, "  msgID: {}", message_id)?;
+                writeln!(f, "  msgMaxSize: {}", max_size)?;
+                let names = [
+                    (FLAG_AUTH, "auth"),
+                    (FLAG_PRIV, "priv"),
+                    (FLAG_REPORTABLE, "reportable"),
+                ];
+                let set: Vec<&str> = names
+                    .iter()
+                    .filter(|(flag, _)| flags & flag != 0)
+                    .map(|(_, name)| *name)
+                    .collect();
+                if set.is_empty() {
+                    writeln!(f, "  msgFlags: {:#04x}", flags)?;
+                } else {
+                    writeln!(f, "  msgFlags: {:#04x} ({})", flags, set.join(", "))?;
+                }
+                writeln!(f, "  msgSecurityModel: {}", security_model)?;
+
+                if let Some(usm) = usm {
+                    writeln!(
+                        f,
+                        "  msgAuthoritativeEngineID: {}",
+                        hex(&usm.authoritative_engine_id)
+                    )?;
+                    writeln!(
+                        f,
+                        "  msgAuthoritativeEngineBoots: {}",
+                        usm.authoritative_engine_boots
+                    )?;
+                    writeln!(
+                        f,
+                        "  msgAuthoritativeEngineTime: {}",
+                        usm.authoritative_engine_time
+                    )?;
+                    writeln!(f, "  msgUserName: {}", text(&usm.user_name))?;
+                    writeln!(
+                        f,
+                        "  msgAuthenticationParameters: {}",
+                        hex(&usm.authentication_parameters)
+                    )?;
+                    writeln!(
+                        f,
+                        "  msgPrivacyParameters: {}",
+                        hex(&usm.privacy_parameters)
+                    )?;
+                }
+            }
+        }
+
+        match &self.body {
+            Body::Pdu(data) => write_pdu(f, data, 1),
+            Body::Trap(trap) => write_trap(f, trap),
+            Body::Scoped(scoped) => {
+                writeln!(f, "  contextEngineID: {}", hex(&scoped.engine_id))?;
+                writeln!(f, "  contextName: {}", text(&scoped.name))?;
+                write_pdu(f, &scoped.data, 1)
+            }
+            Body::Encrypted(len) => writeln!(f, "  encryptedPDU: {} octets", len),
+        }
+    }
+}
+
+/// Displays a PDU as the tree [`Message`] shows it in, e.g. the one a
+/// [`SessionBuilder::on_receive`](crate::SessionBuilder::on_receive) hook is given.
+#[derive(Debug, Clone, Copy)]
+pub struct Pdu<'a>(pub &'a v2::Pdus);
+
+impl fmt::Display for Pdu<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_pdu(f, self.0, 0)
+    }
+}
+
+fn indent(f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+    write!(f, "{:width$}", "", width = depth * 2)
+}
+
+fn write_pdu(f: &mut fmt::Formatter<'_>, data: &v2::Pdus, depth: usize) -> fmt::Result {
+    let (name, pdu) = match data {
+        v2::Pdus::GetRequest(v2::GetRequest(pdu)) => ("GetRequest-PDU", pdu),
+        v2::Pdus::GetNextRequest(v2::GetNextRequest(pdu)) => ("GetNextRequest-PDU", pdu),
+        v2::Pdus::Response(v2::Response(pdu)) => ("Response-PDU", pdu),
+        v2::Pdus::SetRequest(v2::SetRequest(pdu)) => ("SetRequest-PDU", pdu),
+        v2::Pdus::InformRequest(v2::InformRequest(pdu)) => ("InformRequest-PDU", pdu),
+        v2::Pdus::Trap(v2::Trap(pdu)) => ("SNMPv2-Trap-PDU", pdu),
+        v2::Pdus::Report(v2::Report(pdu)) => ("Report-PDU", pdu),
+        v2::Pdus::GetBulkRequest(v2::GetBulkRequest(bulk)) => {
+            indent(f, depth)?;
+            writeln!(f, "GetBulkRequest-PDU")?;
+            indent(f, depth + 1)?;
+            writeln!(f, "request-id: {}", bulk.request_id)?;
+            indent(f, depth + 1)?;
+            writeln!(f, "non-repeaters: {}", bulk.non_repeaters)?;
+            indent(f, depth + 1)?;
+            writeln!(f, "max-repetitions: {}", bulk.max_repetitions)?;
+            let bindings = bulk
+                .variable_bindings
+                .iter()
+                .map(|var| (Oid::from_asn(&var.name), Value::from(var.value.clone())));
+            return write_bindings(f, bindings, depth + 1);
+        }
+    };
+
+    indent(f, depth)?;
+    writeln!(f, "{}", name)?;
+    indent(f, depth + 1)?;
+    writeln!(f, "request-id: {}", pdu.request_id)?;
+    indent(f, depth + 1)?;
+    writeln!(f, "error-status: {}", status(pdu.error_status))?;
+    indent(f, depth + 1)?;
+    writeln!(f, "error-index: {}", pdu.error_index)?;
+    let bindings = pdu
+        .variable_bindings
+        .iter()
+        .map(|var| (Oid::from_asn(&var.name), Value::from(var.value.clone())));
+    write_bindings(f, bindings, depth + 1)
+}
+
+fn write_trap(f: &mut fmt::Formatter<'_>, trap: &v1::Trap) -> fmt::Result {
+    let smi1::NetworkAddress::Internet(smi1::IpAddress(addr)) = &trap.agent_addr;
+    let addr = std::net::Ipv4Addr::from(**addr);
+
+    writeln!(f, "  Trap-PDU")?;
+    writeln!(f, "    enterprise: {}", Oid::from_asn(&trap.enterprise))?;
+    writeln!(f, "    agent-addr: {}", addr)?;
+    writeln!(f, "    generic-trap: {}", trap.generic_trap)?;
+    writeln!(f, "    specific-trap: {}", trap.specific_trap)?;
+    writeln!(f, "    time-stamp: {}", Ticks(trap.time_stamp.0))?;
+    let bindings = trap.variable_bindings.iter().map(|var| {
+        let value = crate::v1::from_syntax(var.value.clone());
+        (Oid::from_asn(&var.name), Value::from(value))
+    });
+    write_bindings(f, bindings, 2)
+}
+
+fn write_bindings(
+    f: &mut fmt::Formatter<'_>,
+    bindings: impl Iterator<Item = (Oid, Value)>,
+    depth: usize,
+) -> fmt::Result {
+    indent(f, depth)?;
+    writeln!(f, "variable-bindings:")?;
+
+    for (oid, value) in bindings {
+        indent(f, depth + 1)?;
+        match value {
+            Value::Null | Value::NoSuchObject | Value::NoSuchInstance | Value::EndOfMibView => {
+                writeln!(f, "{} = {}", oid, value.type_name())?
+            }
+            Value::TimeTicks(ticks) => writeln!(f, "{} = TimeTicks: {}", oid, Ticks(ticks))?,
+            value => writeln!(f, "{} = {}: {}", oid, value.type_name(), value)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn status(code: u32) -> String {
+    if code == 0 {
+        return "noError(0)".to_string();
+    }
+
+    match ErrorStatus::from(code) {
+        ErrorStatus::Other(_) => code.to_string(),
+        status => format!("{}({})", status, code),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "(empty)".to_string();
+    }
+
+    Hex(bytes).to_string()
+}
+
+/// Octets shown as text where they read as text, as hex otherwise.
+fn text(bytes: &[u8]) -> String {
+    match Value::OctetString(bytes.to_vec()).as_str() {
+        Some(text) if !text.is_empty() => text.to_string(),
+        _ => hex(bytes),
+    }
+}
+
+/// Octets as lines of sixteen, each with its offset and the octets as ASCII, e.g. for
+/// a message that does not decode. The octet at `mark` is pointed out on the line below
+/// its own.
+///
+/// ```text
+/// 0000  30 47 02 01 01 04 06 70  75 62 6c 69 63 a2 3a 02  0G.....public.:.
+/// 0010  01 2a 02 01 02 02 01 01  30 2f 30 7f 06 08 2b 06  .*......0/0...+.
+///                                      ^^ offset 0x1a
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a> {
+    pub bytes: &'a [u8],
+    pub mark: Option<usize>,
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, chunk) in self.bytes.chunks(16).enumerate() {
+            let offset = line * 16;
+            write!(f, "{:04x} ", offset)?;
+            for column in 0..16 {
+                if column == 8 {
+                    f.write_str(" ")?;
+                }
+                match chunk.get(column) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str("  ")?;
+            for byte in chunk {
+                let shown = if byte.is_ascii_graphic() || *byte == b' ' {
+                    *byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{}", shown)?;
+            }
+            writeln!(f)?;
+
+            if let Some(mark) = self
+                .mark
+                .filter(|mark| (offset..offset + 16).contains(mark))
+            {
+                let column = mark - offset;
+                let width = 6 + column * 3 + usize::from(column >= 8);
+                writeln!(f, "{:width$}^^ offset {:#x}", "", mark, width = width)?;
+            }
+        }
+
+        match self.mark {
+            Some(mark) if mark >= self.bytes.len() => {
+                writeln!(f, "offset {:#x} is past the end", mark)
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! How strictly sessions hold the responses of agents to the encoding rules.
+
+use rasn_snmp::v2;
+
+use crate::trace;
+use crate::{ber, ErrorStatus, SnmpError, SnmpResult, Version};
+
+/// The highest error-status of SNMPv1 (genErr) and of the later versions
+/// (inconsistentName).
+const V1_MAX_ERROR_STATUS: u32 = 5;
+const V2_MAX_ERROR_STATUS: u32 = 18;
+
+/// How a session takes the deviations from the encoding rules some agents are known for,
+/// set with [`SessionBuilder::decoding`](crate::SessionBuilder::decoding).
+///
+/// The deviations covered are Counter32, Gauge32, TimeTicks and Counter64 values whose
+/// first octet has the sign bit set, as if negative, because the leading zero octet was
+/// left out; SNMPv2 types, such as Counter64 or the exception values, in SNMPv1 responses;
+/// and error-status codes the version does not define.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Decoding {
+    /// Repairs them: unsigned values are read as the number their octets spell, the
+    /// SNMPv2 types are taken as they are, and an unknown error-status becomes `genErr`.
+    #[default]
+    Lenient,
+    /// Rejects the response with [`SnmpError::InvalidMessage`].
+    Strict,
+}
+
+impl Decoding {
+    /// Fails, when strict, if `message`, the encoding a PDU of `version` was decoded
+    /// from, holds an unsigned value with its sign bit set or, for SNMPv1, an SNMPv2 type.
+    pub(crate) fn check_encoding(self, message: &[u8], version: Version) -> SnmpResult<()> {
+        if self == Decoding::Lenient {
+            return Ok(());
+        }
+
+        for (tag, contents) in ber::primitives(message) {
+            match tag {
+                0x41..=0x43 | 0x46 if contents.first().is_some_and(|byte| byte & 0x80 != 0) => {
+                    return Err(SnmpError::InvalidMessage(
+                        "unsigned value encoded as negative",
+                    ));
+                }
+                // Counter64 and the exception values.
+                0x46 | 0x80..=0x82 if version == Version::V1 => {
+                    return Err(SnmpError::InvalidMessage(
+                        "SNMPv2 type in an SNMPv1 message",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks, or repairs, the error-status of `data`, received from a `version` agent.
+    pub(crate) fn check_pdu(self, data: &mut v2::Pdus, version: Version) -> SnmpResult<()> {
+        let pdu = match data {
+            v2::Pdus::Response(v2::Response(pdu)) | v2::Pdus::Report(v2::Report(pdu)) => pdu,
+            _ => return Ok(()),
+        };
+        let max = match version {
+            Version::V1 => V1_MAX_ERROR_STATUS,
+            Version::V2c | Version::V3 => V2_MAX_ERROR_STATUS,
+        };
+        if pdu.error_status <= max {
+            return Ok(());
+        }
+
+        match self {
+            Decoding::Lenient => {
+                trace::event!(
+                    debug,
+                    status = pdu.error_status,
+                    "repairing unknown error-status"
+                );
+                pdu.error_status = ErrorStatus::GenErr.into();
+                Ok(())
+            }
+            Decoding::Strict => Err(SnmpError::InvalidMessage("error-status out of range")),
+        }
+    }
+}
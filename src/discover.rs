@@ -0,0 +1,357 @@
+//! Sweeping address ranges for SNMP agents, or asking a whole segment at once.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::options::Context;
+use crate::security::Security;
+use crate::transport::{self, SocketOptions, UDP_MAX_MESSAGE_SIZE};
+use crate::trap;
+use crate::{
+    pdu, Decoding, Dispatcher, Oid, RateLimiter, SessionBuilder, SnmpError, SnmpResult, SystemInfo,
+    UsmUser, Value,
+};
+
+/// What to try an address with.
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    V1(Vec<u8>),
+    V2c(Vec<u8>),
+    V3(UsmUser),
+}
+
+impl Credentials {
+    fn apply(&self, builder: SessionBuilder) -> SessionBuilder {
+        match self {
+            Credentials::V1(community) => builder.v1(community),
+            Credentials::V2c(community) => builder.v2c(community),
+            Credentials::V3(user) => builder.v3(user.clone()),
+        }
+    }
+}
+
+/// An agent that answered a [`Scan`].
+#[derive(Debug, Clone)]
+pub struct Discovered {
+    pub addr: SocketAddr,
+    /// The first of the scan's credentials the agent answered.
+    pub credentials: Credentials,
+    pub system: SystemInfo,
+}
+
+/// Consecutive addresses, as offsets from `first`.
+#[derive(Debug, Clone, Copy)]
+struct Range {
+    first: IpAddr,
+    count: u64,
+}
+
+impl Range {
+    fn addr(&self, offset: u64) -> IpAddr {
+        match self.first {
+            IpAddr::V4(first) => Ipv4Addr::from(u32::from(first) + offset as u32).into(),
+            IpAddr::V6(first) => Ipv6Addr::from(u128::from(first) + u128::from(offset)).into(),
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Parses `192.0.2.0/24`, `2001:db8::/120` or a single address. IPv4 networks of more
+/// than two addresses leave out their network and broadcast addresses.
+fn range(network: &str) -> io::Result<Range> {
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (network, None),
+    };
+    let addr: IpAddr = addr
+        .trim()
+        .parse()
+        .map_err(|_| invalid("invalid address"))?;
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix: u32 = match prefix {
+        Some(prefix) => prefix
+            .trim()
+            .parse()
+            .map_err(|_| invalid("invalid prefix"))?,
+        None => bits,
+    };
+    if prefix > bits {
+        return Err(invalid("invalid prefix"));
+    }
+
+    let host_bits = bits - prefix;
+    if host_bits >= 64 {
+        return Err(invalid("network too large to scan"));
+    }
+    let count = 1u64 << host_bits;
+
+    Ok(match addr {
+        IpAddr::V4(addr) => {
+            let network = u32::from(addr) & !((count - 1) as u32);
+            let (first, count) = if count > 2 {
+                (network + 1, count - 2)
+            } else {
+                (network, count)
+            };
+            Range {
+                first: Ipv4Addr::from(first).into(),
+                count,
+            }
+        }
+        IpAddr::V6(addr) => Range {
+            first: Ipv6Addr::from(u128::from(addr) & !u128::from(count - 1)).into(),
+            count,
+        },
+    })
+}
+
+/// A sweep of address ranges with a GET of the system group, e.g.
+/// `Scan::new(["192.0.2.0/24"])?.credentials(vec![Credentials::V2c(b"public".to_vec())]).run()?`.
+///
+/// Addresses are probed by a fixed number of worker threads, sharing one socket per address
+/// family, with each credential in turn until one is answered. Defaults to SNMPv2c with
+/// community `public` on port 161, 64 workers, a one second timeout and no retransmission.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    ranges: Vec<Range>,
+    credentials: Vec<Credentials>,
+    port: u16,
+    timeout: Duration,
+    retries: u32,
+    concurrency: usize,
+    limiter: Option<RateLimiter>,
+}
+
+impl Scan {
+    pub fn new<S: AsRef<str>>(networks: impl IntoIterator<Item = S>) -> io::Result<Self> {
+        let ranges = networks
+            .into_iter()
+            .map(|network| range(network.as_ref()))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Scan {
+            ranges,
+            credentials: vec![Credentials::V2c(b"public".to_vec())],
+            port: 161,
+            timeout: Duration::from_secs(1),
+            retries: 0,
+            concurrency: 64,
+            limiter: None,
+        })
+    }
+
+    /// The credentials to try, in order.
+    pub fn credentials(mut self, credentials: Vec<Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// How many addresses are probed at once.
+    pub fn concurrency(mut self, workers: usize) -> Self {
+        self.concurrency = workers.max(1);
+        self
+    }
+
+    /// Sends at most `per_second` requests a second across the scan, counting each
+    /// credential tried and retransmission.
+    pub fn rate(self, per_second: u32) -> Self {
+        self.rate_limit(RateLimiter::new().per_second(per_second))
+    }
+
+    /// Paces the scan's requests with `limiter`, which may be shared with other work.
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// How many addresses the scan covers.
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(|range| range.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn addr(&self, mut index: u64) -> Option<IpAddr> {
+        for range in &self.ranges {
+            if index < range.count {
+                return Some(range.addr(index));
+            }
+            index -= range.count;
+        }
+
+        None
+    }
+
+    /// Probes every address and returns the agents that answered, in address order.
+    pub fn run(&self) -> io::Result<Vec<Discovered>> {
+        let family = |v4| self.ranges.iter().any(|range| range.first.is_ipv4() == v4);
+        let v4 = family(true)
+            .then(|| Dispatcher::bind((Ipv4Addr::UNSPECIFIED, 0)))
+            .transpose()?;
+        let v6 = family(false)
+            .then(|| Dispatcher::bind((Ipv6Addr::UNSPECIFIED, 0)))
+            .transpose()?;
+
+        let next = AtomicU64::new(0);
+        let found = Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            let workers = (self.concurrency as u64).min(self.len());
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some(ip) = self.addr(next.fetch_add(1, Ordering::Relaxed)) {
+                        let dispatcher = match ip {
+                            IpAddr::V4(_) => v4.as_ref(),
+                            IpAddr::V6(_) => v6.as_ref(),
+                        };
+                        let Some(dispatcher) = dispatcher else {
+                            continue;
+                        };
+
+                        if let Some(discovered) = self.probe(dispatcher, ip) {
+                            found
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .push(discovered);
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut found = found.into_inner().unwrap_or_else(PoisonError::into_inner);
+        found.sort_by_key(|discovered| discovered.addr);
+        Ok(found)
+    }
+
+    fn probe(&self, dispatcher: &Dispatcher, ip: IpAddr) -> Option<Discovered> {
+        let addr = SocketAddr::new(ip, self.port);
+
+        self.credentials.iter().find_map(|credentials| {
+            let mut builder = SessionBuilder::new(addr.to_string())
+                .timeout(self.timeout)
+                .retries(self.retries);
+            if let Some(limiter) = &self.limiter {
+                builder = builder.rate_limit(limiter.clone());
+            }
+            let session = credentials
+                .apply(builder)
+                .build_dispatched(dispatcher)
+                .ok()?;
+
+            session.system_info().ok().map(|system| Discovered {
+                addr,
+                credentials: credentials.clone(),
+                system,
+            })
+        })
+    }
+}
+
+/// A responder to [`broadcast_get`] and the bindings it answered with.
+pub type Response = (SocketAddr, Vec<(Oid, Value)>);
+
+/// Sends a GET of `oids` to a broadcast or multicast address, e.g. `255.255.255.255:161`,
+/// and returns what each distinct responder answered within `window`, in the order the
+/// answers arrived. Responders that answer more than once are listed once.
+///
+/// Only community-based credentials can be broadcast, since SNMPv3 needs a discovery
+/// exchange with every agent.
+pub fn broadcast_get(
+    dest: impl ToSocketAddrs,
+    credentials: &Credentials,
+    oids: &[Oid],
+    window: Duration,
+) -> SnmpResult<Vec<Response>> {
+    let security = match credentials {
+        Credentials::V1(community) => Security::community(0, community),
+        Credentials::V2c(community) => Security::community(1, community),
+        Credentials::V3(_) => return Err(SnmpError::Unsupported("broadcast requires a community")),
+    };
+    let dest = dest
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(transport::no_addrs)?;
+
+    let socket = UdpSocket::bind(transport::bind_addr(dest, &SocketOptions::default()))?;
+    if dest.is_ipv4() {
+        socket.set_broadcast(true)?;
+    }
+
+    let mut data = pdu::get(oids);
+    let request_id = pdu::initial_request_id();
+    pdu::set_request_id(&mut data, request_id);
+    let mut message = Vec::new();
+    security.encode(
+        data,
+        &Context::default(),
+        UDP_MAX_MESSAGE_SIZE,
+        &mut message,
+    )?;
+    socket.send_to(&message, dest)?;
+
+    let deadline = Instant::now() + window;
+    let mut responders: Vec<Response> = Vec::new();
+    let mut buf = vec![0; UDP_MAX_MESSAGE_SIZE];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break;
+            }
+            // An ICMP error from a host without an agent says nothing about the others.
+            Err(err) if trap::is_icmp_error(&err) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if responders.iter().any(|(addr, _)| *addr == from) {
+            continue;
+        }
+
+        // Anything a responder gets wrong is its own problem, not the others'.
+        let Ok(Some(response)) =
+            security.decode(&buf[..len], &message, request_id, Decoding::default())
+        else {
+            continue;
+        };
+        if let Ok(bindings) = pdu::parse_response(response) {
+            responders.push((from, bindings));
+        }
+    }
+
+    Ok(responders)
+}
@@ -0,0 +1,236 @@
+//! Many sessions over one UDP socket.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::transport::{no_addrs, UDP_MAX_MESSAGE_SIZE};
+use crate::{ber, is_timeout, trace, Transport};
+
+/// How often the receiving thread checks whether the dispatcher was dropped.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+/// Outstanding requests by destination and msgID or request-id.
+type Outstanding = Mutex<HashMap<(SocketAddr, i64), Sender<Vec<u8>>>>;
+
+#[derive(Debug)]
+struct Shared {
+    socket: UdpSocket,
+    outstanding: Outstanding,
+    stopped: AtomicBool,
+}
+
+/// Sends the requests of any number of sessions from one unconnected UDP socket and hands
+/// each response to the session waiting for it, matched by source address and msgID or
+/// request-id. Pollers talking to thousands of agents then need one file descriptor
+/// instead of one per agent.
+///
+/// Unlike a connected socket, the shared one receives datagrams from anywhere, so a
+/// response only counts if it comes from the address the request went to, or from one
+/// allowed with [`DispatchedTransport::accept_from`]. Anything else is dropped, so a
+/// datagram with a guessed request-id cannot be passed off as the agent's answer.
+///
+/// Sessions are created with [`SessionBuilder::build_dispatched`](crate::SessionBuilder::build_dispatched)
+/// or from [`Dispatcher::transport`].
+#[derive(Debug)]
+pub struct Dispatcher {
+    shared: Arc<Shared>,
+    receiver: Option<JoinHandle<()>>,
+}
+
+impl Dispatcher {
+    /// Binds the shared socket, e.g. to `0.0.0.0:0`, and starts the thread that receives
+    /// on it.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(SHUTDOWN_POLL))?;
+
+        let shared = Arc::new(Shared {
+            socket,
+            outstanding: Mutex::new(HashMap::new()),
+            stopped: AtomicBool::new(false),
+        });
+
+        let receiver = {
+            let shared = shared.clone();
+            thread::spawn(move || receive(&shared))
+        };
+
+        Ok(Dispatcher {
+            shared,
+            receiver: Some(receiver),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.shared.socket.local_addr()
+    }
+
+    /// A transport to `dest_addr` over the shared socket.
+    pub fn transport<A: ToSocketAddrs>(&self, dest_addr: A) -> io::Result<DispatchedTransport> {
+        let dest = dest_addr.to_socket_addrs()?.next().ok_or_else(no_addrs)?;
+        let (sender, responses) = mpsc::channel();
+
+        Ok(DispatchedTransport {
+            shared: self.shared.clone(),
+            dest,
+            sources: vec![dest],
+            sender,
+            responses: Mutex::new(responses),
+            outstanding: Mutex::new(None),
+        })
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+
+        if let Some(receiver) = self.receiver.take() {
+            let _ = receiver.join();
+        }
+    }
+}
+
+fn receive(shared: &Shared) {
+    let mut buf = vec![0; UDP_MAX_MESSAGE_SIZE];
+
+    while !shared.stopped.load(Ordering::Relaxed) {
+        let (len, source) = match shared.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if is_timeout(&err) => continue,
+            Err(_err) => {
+                trace::event!(debug, error = %_err, "dispatcher socket failed");
+                continue;
+            }
+        };
+
+        let message = &buf[..len];
+        let outstanding = shared
+            .outstanding
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let sender = ber::message_id(message).and_then(|id| outstanding.get(&(source, id)));
+
+        if let Some(sender) = sender {
+            let _ = sender.send(message.to_vec());
+        } else {
+            trace::event!(debug, %source, "dropping datagram from unexpected source");
+        }
+    }
+}
+
+/// A [`Transport`] that shares the socket of a [`Dispatcher`]. Only the response to the
+/// last request sent is delivered, which is all a session waits for.
+#[derive(Debug)]
+pub struct DispatchedTransport {
+    shared: Arc<Shared>,
+    dest: SocketAddr,
+    /// Where responses may come from: `dest` and any other addresses of the agent.
+    sources: Vec<SocketAddr>,
+    sender: Sender<Vec<u8>>,
+    responses: Mutex<Receiver<Vec<u8>>>,
+    /// The msgID or request-id registered for the last request.
+    outstanding: Mutex<Option<i64>>,
+}
+
+impl DispatchedTransport {
+    /// Also accepts responses from `addrs`, for multihomed agents that may answer from
+    /// an address other than the one they were asked on.
+    pub fn accept_from<A: ToSocketAddrs>(mut self, addrs: A) -> io::Result<Self> {
+        for addr in addrs.to_socket_addrs()? {
+            if !self.sources.contains(&addr) {
+                self.sources.push(addr);
+            }
+        }
+
+        Ok(self)
+    }
+
+    fn forget(&self, outstanding: &mut Option<i64>) {
+        if let Some(id) = outstanding.take() {
+            let mut registered = self
+                .shared
+                .outstanding
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            for source in &self.sources {
+                registered.remove(&(*source, id));
+            }
+        }
+    }
+}
+
+impl Transport for DispatchedTransport {
+    fn send(&self, data: &[u8]) -> io::Result<()> {
+        let id = ber::message_id(data).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "message without a request id")
+        })?;
+
+        let mut outstanding = self
+            .outstanding
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if *outstanding != Some(id) {
+            self.forget(&mut outstanding);
+
+            let mut registered = self
+                .shared
+                .outstanding
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+
+            for source in &self.sources {
+                registered.insert((*source, id), self.sender.clone());
+            }
+            *outstanding = Some(id);
+        }
+
+        self.shared.socket.send_to(data, self.dest).map(drop)
+    }
+
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let message = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .recv_timeout(timeout)
+            .map_err(|err| match err {
+                RecvTimeoutError::Timeout => io::ErrorKind::TimedOut.into(),
+                RecvTimeoutError::Disconnected => io::Error::from(io::ErrorKind::NotConnected),
+            })?;
+
+        // Cut short like a UDP socket would.
+        let len = message.len().min(buf.len());
+        buf[..len].copy_from_slice(&message[..len]);
+
+        Ok(len)
+    }
+
+    fn max_msg_size(&self) -> usize {
+        UDP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.shared.socket.local_addr()
+    }
+}
+
+impl Drop for DispatchedTransport {
+    fn drop(&mut self) {
+        let mut outstanding = self
+            .outstanding
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+
+        self.forget(&mut outstanding);
+    }
+}
@@ -0,0 +1,290 @@
+//! Reading captures of real devices: snmpsim `.snmprec` files and `snmpwalk -On` output.
+//!
+//! Both load into a `BTreeMap<Oid, Value>`, which an [`Agent`](crate::Agent) serves when
+//! registered as a [`Handler`](crate::Handler), and which
+//! [`MockAgent`](crate::testing::MockAgent) serves as is.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Oid, Value};
+
+type Parsed<T> = Result<T, &'static str>;
+
+fn invalid(source: &str, line: usize, message: &str) -> io::Error {
+    let message = format!("{}:{}: {}", source, line, message);
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn oid(text: &str) -> Parsed<Oid> {
+    text.trim().parse().map_err(|_| "invalid OID")
+}
+
+fn number<T: FromStr>(text: &str) -> Parsed<T> {
+    text.trim().parse().map_err(|_| "invalid number")
+}
+
+/// Hex digits in pairs, ignoring whitespace between them.
+fn hex(text: &str) -> Parsed<Vec<u8>> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err("odd number of hex digits");
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let high = pair[0].to_digit(16).ok_or("invalid hex digit")?;
+            let low = pair[1].to_digit(16).ok_or("invalid hex digit")?;
+            Ok((high * 16 + low) as u8)
+        })
+        .collect()
+}
+
+/// One `OID|TAG|VALUE` line of a `.snmprec` file. The tag is the BER tag of the value,
+/// with an `x` suffix when the value is hex-encoded.
+fn snmprec_line(line: &str) -> Parsed<(Oid, Value)> {
+    let mut fields = line.splitn(3, '|');
+    let (Some(name), Some(tag), Some(value)) = (fields.next(), fields.next(), fields.next()) else {
+        return Err("expected OID|TAG|VALUE");
+    };
+
+    if tag.contains(':') {
+        return Err("variation modules are not supported");
+    }
+    let (tag, hexed) = match tag.strip_suffix('x') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+    };
+    let bytes = if hexed {
+        hex(value)?
+    } else {
+        value.as_bytes().to_vec()
+    };
+    let text = || std::str::from_utf8(&bytes).map_err(|_| "invalid text");
+
+    let value = match number::<u8>(tag)? {
+        2 => Value::Integer(number(text()?)?),
+        4 => Value::OctetString(bytes.clone()),
+        5 => Value::Null,
+        6 => Value::Oid(oid(text()?)?.into()),
+        64 if hexed => Value::IpAddress(
+            <[u8; 4]>::try_from(&bytes[..])
+                .map_err(|_| "IpAddress is not four octets")?
+                .into(),
+        ),
+        64 => Value::IpAddress(text()?.parse().map_err(|_| "invalid IpAddress")?),
+        65 => Value::Counter32(number(text()?)?),
+        66 => Value::Gauge32(number(text()?)?),
+        67 => Value::TimeTicks(number(text()?)?),
+        68 => Value::from_opaque(bytes.clone()),
+        70 => Value::Counter64(number(text()?)?),
+        128 => Value::NoSuchObject,
+        129 => Value::NoSuchInstance,
+        130 => Value::EndOfMibView,
+        _ => return Err("unknown tag"),
+    };
+
+    Ok((oid(name)?, value))
+}
+
+/// Parses an snmpsim `.snmprec` file, skipping blank lines and `#` comments. Variation
+/// modules, e.g. `2:numeric`, are not supported.
+pub fn parse_snmprec(text: &str) -> io::Result<BTreeMap<Oid, Value>> {
+    snmprec(text, "snmprec")
+}
+
+fn snmprec(text: &str, source: &str) -> io::Result<BTreeMap<Oid, Value>> {
+    let mut instances = BTreeMap::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = snmprec_line(line).map_err(|err| invalid(source, i + 1, err))?;
+        instances.insert(name, value);
+    }
+
+    Ok(instances)
+}
+
+/// The contents of a quoted STRING, undoing net-snmp's escaping of `"` and `\`.
+fn unquote(text: &str) -> Vec<u8> {
+    let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+    else {
+        return text.as_bytes().to_vec();
+    };
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut escaped = false;
+    for byte in inner.bytes() {
+        if byte == b'\\' && !escaped {
+            escaped = true;
+            continue;
+        }
+        escaped = false;
+        bytes.push(byte);
+    }
+
+    bytes
+}
+
+/// Whether `text` opens a quote it does not close, so the STRING goes on on the next line.
+fn open_quote(text: &str) -> bool {
+    let mut open = false;
+    let mut escaped = false;
+    for byte in text.bytes() {
+        match byte {
+            b'\\' if open && !escaped => {
+                escaped = true;
+                continue;
+            }
+            b'"' if !escaped => open = !open,
+            _ => {}
+        }
+        escaped = false;
+    }
+
+    open
+}
+
+/// The first word of `text`, or the number in parentheses of enumerations like `up(1)`
+/// and of Timeticks like `(8640000) 1 day, 0:00:00.00`.
+fn leading_number<T: FromStr>(text: &str) -> Parsed<T> {
+    let text = text.trim();
+    let word = match (text.find('('), text.find(')')) {
+        (Some(open), Some(close)) if open < close => &text[open + 1..close],
+        _ => text.split_whitespace().next().unwrap_or(""),
+    };
+
+    number(word)
+}
+
+/// An Opaque as net-snmp prints it: its hex contents, or the float or 64-bit integer it
+/// wraps, e.g. `Float: 1.500000`.
+fn opaque(text: &str) -> Parsed<Value> {
+    let Some((kind, value)) = text.split_once(':') else {
+        return hex(text).map(Value::Opaque);
+    };
+    let value = value.trim();
+
+    Ok(match kind {
+        "Float" => Value::Float(number(value)?),
+        "Double" => Value::Double(number(value)?),
+        "Int64" => Value::Integer64(number(value)?),
+        "UInt64" => Value::Unsigned64(number(value)?),
+        _ => return Err("unknown Opaque type"),
+    })
+}
+
+/// The value printed after ` = ` by net-snmp.
+fn snmpwalk_value(text: &str) -> Parsed<Value> {
+    let text = text.trim();
+
+    if text == "\"\"" {
+        return Ok(Value::OctetString(Vec::new()));
+    } else if text == "NULL" {
+        return Ok(Value::Null);
+    } else if text.starts_with("No Such Object") {
+        return Ok(Value::NoSuchObject);
+    } else if text.starts_with("No Such Instance") {
+        return Ok(Value::NoSuchInstance);
+    } else if text.starts_with("No more variables") {
+        return Ok(Value::EndOfMibView);
+    } else if let Some(rest) = text.strip_prefix("Wrong Type (should be ") {
+        // The agent's type differs from the MIB's; what follows is as the agent sent it.
+        let (_, value) = rest.split_once("): ").ok_or("invalid Wrong Type")?;
+        return snmpwalk_value(value);
+    }
+
+    let (kind, value) = text.split_once(':').ok_or("expected TYPE: VALUE")?;
+    let value = value.trim();
+
+    Ok(match kind {
+        "INTEGER" => Value::Integer(leading_number(value)?),
+        "STRING" => Value::OctetString(unquote(value)),
+        "Hex-STRING" => Value::OctetString(hex(value)?),
+        // Named bits follow the octets, e.g. `BITS: 80 00 linkUp(0)`.
+        "BITS" => Value::OctetString(hex(&value
+            .split_whitespace()
+            .take_while(|word| word.len() == 2)
+            .collect::<String>())?),
+        "OID" => Value::Oid(oid(value)?.into()),
+        "IpAddress" => Value::IpAddress(value.parse().map_err(|_| "invalid IpAddress")?),
+        "Network Address" => Value::IpAddress(
+            <[u8; 4]>::try_from(&hex(&value.replace(':', ""))?[..])
+                .map_err(|_| "Network Address is not four octets")?
+                .into(),
+        ),
+        "Counter32" => Value::Counter32(leading_number(value)?),
+        "Gauge32" | "Unsigned32" | "UInteger32" => Value::Gauge32(leading_number(value)?),
+        "Counter64" => Value::Counter64(leading_number(value)?),
+        "Timeticks" => Value::TimeTicks(leading_number(value)?),
+        "Opaque" => opaque(value)?,
+        _ => return Err("unknown type"),
+    })
+}
+
+/// Parses the output of `snmpwalk -On`, i.e. lines of `.1.3.6.1.2.1.1.5.0 = STRING:
+/// "name"`. Values net-snmp wraps over several lines, such as long Hex-STRINGs and
+/// STRINGs with line breaks, are joined back together.
+pub fn parse_snmpwalk(text: &str) -> io::Result<BTreeMap<Oid, Value>> {
+    snmpwalk(text, "snmpwalk")
+}
+
+fn snmpwalk(text: &str, source: &str) -> io::Result<BTreeMap<Oid, Value>> {
+    // Each entry with the line it starts on.
+    let mut entries: Vec<(usize, String, String)> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        let continued = entries
+            .last()
+            .is_some_and(|(_, _, value)| open_quote(value));
+
+        match line.split_once(" = ") {
+            Some((name, value))
+                if !continued && name.trim_start().starts_with(['.', '0', '1', '2']) =>
+            {
+                entries.push((i + 1, name.to_string(), value.to_string()));
+            }
+            _ if line.trim().is_empty() && !continued => {}
+            _ => {
+                let (_, _, value) = entries
+                    .last_mut()
+                    .ok_or_else(|| invalid(source, i + 1, "expected OID = VALUE"))?;
+                value.push(if continued { '\n' } else { ' ' });
+                value.push_str(line);
+            }
+        }
+    }
+
+    entries
+        .into_iter()
+        .map(|(line, name, value)| {
+            let parsed = oid(&name).and_then(|name| Ok((name, snmpwalk_value(&value)?)));
+            parsed.map_err(|err| invalid(source, line, err))
+        })
+        .collect()
+}
+
+/// Loads a dump file: `.snmprec` files as snmpsim records, anything else as `snmpwalk -On`
+/// output.
+pub fn load_file(path: impl AsRef<Path>) -> io::Result<BTreeMap<Oid, Value>> {
+    let path = path.as_ref();
+    let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+    let source = path.display().to_string();
+
+    if path.extension().is_some_and(|ext| ext == "snmprec") {
+        snmprec(&text, &source)
+    } else {
+        snmpwalk(&text, &source)
+    }
+}
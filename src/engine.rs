@@ -0,0 +1,218 @@
+//! SNMP engine IDs (RFC 3411 SnmpEngineID), which SNMPv3 agents and notification senders
+//! are known by and their users' keys are localized to.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{MacAddr, SnmpError, SnmpResult};
+
+/// What the octets of an [`EngineId`] after its enterprise number are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EngineIdFormat<'a> {
+    /// The format from before RFC 3411, whose first bit is clear: eight octets the
+    /// enterprise defines.
+    Legacy(&'a [u8]),
+    Ipv4(Ipv4Addr),
+    Ipv6(Ipv6Addr),
+    Mac(MacAddr),
+    /// Administratively assigned text, e.g. a host name.
+    Text(&'a str),
+    /// Administratively assigned octets.
+    Octets(&'a [u8]),
+    /// RFC 5343's localEngineID, which addresses whichever engine receives it.
+    Local,
+    /// A reserved or enterprise-specific format, or one of the above whose data has the
+    /// wrong length, with its format octet.
+    Other(u8, &'a [u8]),
+}
+
+/// An SNMP engine ID: an enterprise number, a format octet and up to 27 octets of data,
+/// e.g. `80001f8803525400123456` for net-snmp's (8072) MAC-based ID.
+///
+/// Displays, and parses from, hex digits, which may be prefixed with `0x` and separated by
+/// colons or spaces. The alternate form `{:#}` describes the ID instead, as in
+/// `enterprise 8072, MAC 52:54:00:12:34:56`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EngineId(Vec<u8>);
+
+impl EngineId {
+    /// Checks the RFC 3411 length of 5 to 32 octets.
+    pub fn new(octets: impl Into<Vec<u8>>) -> SnmpResult<Self> {
+        let octets = octets.into();
+        if !(5..=32).contains(&octets.len()) {
+            return Err(SnmpError::InvalidEngineId(hex(&octets)));
+        }
+
+        Ok(EngineId(octets))
+    }
+
+    /// An ID as an agent reported it, which is used as is even if it breaks RFC 3411.
+    pub(crate) fn from_wire(octets: Vec<u8>) -> Self {
+        EngineId(octets)
+    }
+
+    fn with_format(enterprise: u32, format: u8, data: &[u8]) -> Self {
+        let mut octets = (enterprise | 0x8000_0000).to_be_bytes().to_vec();
+        octets.push(format);
+        octets.extend_from_slice(data);
+        EngineId(octets)
+    }
+
+    pub fn ipv4(enterprise: u32, addr: Ipv4Addr) -> Self {
+        EngineId::with_format(enterprise, 1, &addr.octets())
+    }
+
+    pub fn ipv6(enterprise: u32, addr: Ipv6Addr) -> Self {
+        EngineId::with_format(enterprise, 2, &addr.octets())
+    }
+
+    pub fn mac(enterprise: u32, mac: MacAddr) -> Self {
+        EngineId::with_format(enterprise, 3, &mac.octets())
+    }
+
+    /// Fails for text longer than 27 bytes.
+    pub fn text(enterprise: u32, text: &str) -> SnmpResult<Self> {
+        EngineId::new(EngineId::with_format(enterprise, 4, text.as_bytes()).0)
+    }
+
+    /// Fails for more than 27 octets.
+    pub fn octets(enterprise: u32, octets: &[u8]) -> SnmpResult<Self> {
+        EngineId::new(EngineId::with_format(enterprise, 5, octets).0)
+    }
+
+    /// RFC 5343's localEngineID, `8000000006`.
+    pub fn local() -> Self {
+        EngineId::with_format(0, 6, &[])
+    }
+
+    /// A new random ID of administratively assigned octets, for an agent or notification
+    /// sender of its own. It has to be kept, e.g. in configuration, since the keys of the
+    /// engine's users are localized to it.
+    pub fn generate(enterprise: u32) -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        let random = hasher.finish();
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() as u32);
+
+        let mut data = random.to_be_bytes().to_vec();
+        data.extend_from_slice(&secs.to_be_bytes());
+        EngineId::with_format(enterprise, 5, &data)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// The IANA enterprise number of whoever assigned the ID.
+    pub fn enterprise(&self) -> u32 {
+        let mut number = [0; 4];
+        for (digit, octet) in number.iter_mut().zip(&self.0) {
+            *digit = *octet;
+        }
+
+        u32::from_be_bytes(number) & 0x7fff_ffff
+    }
+
+    pub fn format(&self) -> EngineIdFormat<'_> {
+        let octets = &self.0;
+        if octets.first().is_some_and(|first| first & 0x80 == 0) {
+            return EngineIdFormat::Legacy(octets.get(4..).unwrap_or_default());
+        }
+        let Some(&format) = octets.get(4) else {
+            return EngineIdFormat::Other(0, &[]);
+        };
+
+        let data = &octets[5..];
+        match (format, data.len()) {
+            (1, 4) => EngineIdFormat::Ipv4(<[u8; 4]>::try_from(data).unwrap().into()),
+            (2, 16) => EngineIdFormat::Ipv6(<[u8; 16]>::try_from(data).unwrap().into()),
+            (3, 6) => EngineIdFormat::Mac(MacAddr(data.try_into().unwrap())),
+            (4, _) => match std::str::from_utf8(data) {
+                Ok(text) => EngineIdFormat::Text(text),
+                Err(_) => EngineIdFormat::Other(format, data),
+            },
+            (5, _) => EngineIdFormat::Octets(data),
+            (6, 0) if self.enterprise() == 0 => EngineIdFormat::Local,
+            _ => EngineIdFormat::Other(format, data),
+        }
+    }
+}
+
+fn hex(octets: &[u8]) -> String {
+    octets.iter().map(|octet| format!("{octet:02x}")).collect()
+}
+
+impl AsRef<[u8]> for EngineId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<EngineId> for Vec<u8> {
+    fn from(id: EngineId) -> Self {
+        id.0
+    }
+}
+
+impl TryFrom<Vec<u8>> for EngineId {
+    type Error = SnmpError;
+
+    fn try_from(octets: Vec<u8>) -> SnmpResult<Self> {
+        EngineId::new(octets)
+    }
+}
+
+impl fmt::Display for EngineId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return f.write_str(&hex(&self.0));
+        }
+
+        write!(f, "enterprise {}, ", self.enterprise())?;
+        match self.format() {
+            EngineIdFormat::Legacy(data) => write!(f, "legacy {}", hex(data)),
+            EngineIdFormat::Ipv4(addr) => write!(f, "IPv4 {addr}"),
+            EngineIdFormat::Ipv6(addr) => write!(f, "IPv6 {addr}"),
+            EngineIdFormat::Mac(mac) => write!(f, "MAC {mac}"),
+            EngineIdFormat::Text(text) => write!(f, "text {text:?}"),
+            EngineIdFormat::Octets(data) => write!(f, "octets {}", hex(data)),
+            EngineIdFormat::Local => f.write_str("local"),
+            EngineIdFormat::Other(format, data) => write!(f, "format {format} {}", hex(data)),
+        }
+    }
+}
+
+impl FromStr for EngineId {
+    type Err = SnmpError;
+
+    fn from_str(text: &str) -> SnmpResult<Self> {
+        let invalid = || SnmpError::InvalidEngineId(text.to_string());
+        let digits: String = text
+            .trim()
+            .trim_start_matches("0x")
+            .chars()
+            .filter(|c| !matches!(c, ':' | ' '))
+            .collect();
+        if !digits.len().is_multiple_of(2) || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let octets = (0..digits.len())
+            .step_by(2)
+            .map(|at| u8::from_str_radix(&digits[at..at + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        EngineId::new(octets).map_err(|_| invalid())
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::{error, fmt, io};
+
+use crate::{Oid, Value};
+use rasn::error::{DecodeError, EncodeError};
+
+/// The error-status of a response PDU (RFC 3416); v1 agents only use the first six.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorStatus {
+    TooBig,
+    NoSuchName,
+    BadValue,
+    ReadOnly,
+    GenErr,
+    NoAccess,
+    WrongType,
+    WrongLength,
+    WrongEncoding,
+    WrongValue,
+    NoCreation,
+    InconsistentValue,
+    ResourceUnavailable,
+    CommitFailed,
+    UndoFailed,
+    AuthorizationError,
+    NotWritable,
+    InconsistentName,
+    /// A code outside the ones defined by RFC 3416.
+    Other(u32),
+}
+
+impl From<u32> for ErrorStatus {
+    fn from(status: u32) -> Self {
+        match status {
+            1 => ErrorStatus::TooBig,
+            2 => ErrorStatus::NoSuchName,
+            3 => ErrorStatus::BadValue,
+            4 => ErrorStatus::ReadOnly,
+            5 => ErrorStatus::GenErr,
+            6 => ErrorStatus::NoAccess,
+            7 => ErrorStatus::WrongType,
+            8 => ErrorStatus::WrongLength,
+            9 => ErrorStatus::WrongEncoding,
+            10 => ErrorStatus::WrongValue,
+            11 => ErrorStatus::NoCreation,
+            12 => ErrorStatus::InconsistentValue,
+            13 => ErrorStatus::ResourceUnavailable,
+            14 => ErrorStatus::CommitFailed,
+            15 => ErrorStatus::UndoFailed,
+            16 => ErrorStatus::AuthorizationError,
+            17 => ErrorStatus::NotWritable,
+            18 => ErrorStatus::InconsistentName,
+            other => ErrorStatus::Other(other),
+        }
+    }
+}
+
+impl From<ErrorStatus> for u32 {
+    fn from(status: ErrorStatus) -> Self {
+        match status {
+            ErrorStatus::TooBig => 1,
+            ErrorStatus::NoSuchName => 2,
+            ErrorStatus::BadValue => 3,
+            ErrorStatus::ReadOnly => 4,
+            ErrorStatus::GenErr => 5,
+            ErrorStatus::NoAccess => 6,
+            ErrorStatus::WrongType => 7,
+            ErrorStatus::WrongLength => 8,
+            ErrorStatus::WrongEncoding => 9,
+            ErrorStatus::WrongValue => 10,
+            ErrorStatus::NoCreation => 11,
+            ErrorStatus::InconsistentValue => 12,
+            ErrorStatus::ResourceUnavailable => 13,
+            ErrorStatus::CommitFailed => 14,
+            ErrorStatus::UndoFailed => 15,
+            ErrorStatus::AuthorizationError => 16,
+            ErrorStatus::NotWritable => 17,
+            ErrorStatus::InconsistentName => 18,
+            ErrorStatus::Other(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorStatus::TooBig => "tooBig",
+            ErrorStatus::NoSuchName => "noSuchName",
+            ErrorStatus::BadValue => "badValue",
+            ErrorStatus::ReadOnly => "readOnly",
+            ErrorStatus::GenErr => "genErr",
+            ErrorStatus::NoAccess => "noAccess",
+            ErrorStatus::WrongType => "wrongType",
+            ErrorStatus::WrongLength => "wrongLength",
+            ErrorStatus::WrongEncoding => "wrongEncoding",
+            ErrorStatus::WrongValue => "wrongValue",
+            ErrorStatus::NoCreation => "noCreation",
+            ErrorStatus::InconsistentValue => "inconsistentValue",
+            ErrorStatus::ResourceUnavailable => "resourceUnavailable",
+            ErrorStatus::CommitFailed => "commitFailed",
+            ErrorStatus::UndoFailed => "undoFailed",
+            ErrorStatus::AuthorizationError => "authorizationError",
+            ErrorStatus::NotWritable => "notWritable",
+            ErrorStatus::InconsistentName => "inconsistentName",
+            ErrorStatus::Other(other) => return write!(f, "error-status {}", other),
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum SnmpError {
+    /// The socket failed while sending or receiving.
+    Io(io::Error),
+    /// No response arrived before the timeout, including retries.
+    Timeout,
+    /// Nothing listens on the agent's port: an ICMP port unreachable came back, which
+    /// connected UDP sockets report as a refused connection.
+    PortUnreachable,
+    /// The agent's host or network cannot be reached.
+    HostUnreachable,
+    /// A request could not be encoded.
+    Encode(EncodeError),
+    /// A response could not be decoded; `offset` points at the first malformed BER element
+    /// when the framing itself is broken. Truncated, corrupted or hostile messages end
+    /// up here, or in one of the errors about their contents, and never in a panic.
+    Decode {
+        offset: Option<usize>,
+        source: DecodeError,
+    },
+    /// The encoded request exceeds the maximum message size.
+    EncodingTooLarge { size: usize, max: usize },
+    /// A response of `size` bytes did not fit the receive buffer of `buffer` bytes; see
+    /// [`SessionBuilder::recv_buffer_size`](crate::SessionBuilder::recv_buffer_size).
+    Truncated { size: usize, buffer: usize },
+    /// The response decoded fine but violates the protocol.
+    InvalidMessage(&'static str),
+    /// An OID string is not dotted decimal or is not encodable.
+    InvalidOid(String),
+    /// A MAC address string is in none of the forms [`MacAddr`](crate::MacAddr) parses.
+    InvalidMacAddr(String),
+    /// An engine ID is not 5 to 32 octets, or not in hex digits.
+    InvalidEngineId(String),
+    /// A name the loaded MIB does not define where it was used, e.g. for a bit given to
+    /// [`Bits::from_names`](crate::Bits::from_names).
+    UnknownName(String),
+    /// The arcs of a table index do not decode as the index components asked for.
+    InvalidIndex(Oid),
+    /// A walk step returned an OID that does not follow the one requested.
+    OidNotIncreasing { previous: Oid, next: Oid },
+    /// The operation cannot be expressed in the session's SNMP version.
+    Unsupported(&'static str),
+    /// A response PDU was expected but something else arrived.
+    UnexpectedPdu,
+    /// A GET named an object the agent does not implement; only reported by sessions built
+    /// with [`SessionBuilder::exceptions_as_errors`](crate::SessionBuilder::exceptions_as_errors).
+    NoSuchObject(Oid),
+    /// A GET named an instance that does not exist of an object the agent implements.
+    NoSuchInstance(Oid),
+    /// The agent answered with a non-zero error-status. `index` is 1-based into `bindings`,
+    /// with 0 meaning the error is not tied to a binding.
+    AgentError {
+        status: ErrorStatus,
+        index: u32,
+        bindings: Vec<(Oid, Value)>,
+    },
+    /// A SET made with [`RequestOptions::verify`](crate::RequestOptions::verify) was
+    /// accepted, but the binding at `index`, 0-based into the request, read back with
+    /// another value. `bindings` are all of them as read back, e.g. to tell which took
+    /// effect and need rolling back.
+    Unverified {
+        index: usize,
+        bindings: Vec<(Oid, Value)>,
+    },
+    /// A value is not of the type it was asked for as, e.g. by
+    /// [`SyncSession::get_u64`](crate::SyncSession::get_u64).
+    WrongType { expected: &'static str, got: Value },
+    /// An SNMPv3 message failed authentication or decryption.
+    AuthenticationError,
+    /// The agent does not know the SNMPv3 user (usmStatsUnknownUserNames).
+    UnknownUserName,
+    /// The agent does not allow the user the security level asked for, e.g. privacy for a
+    /// user without a privacy key (usmStatsUnsupportedSecLevels).
+    UnsupportedSecLevel,
+    /// The agent rejected the message's digest: the user's authentication protocol or
+    /// passphrase is not the agent's (usmStatsWrongDigests).
+    WrongDigest,
+    /// The agent could not decrypt the message: the user's privacy protocol or passphrase is
+    /// not the agent's (usmStatsDecryptionErrors).
+    DecryptionError,
+    /// The agent's clock could not be synchronized with (usmStatsNotInTimeWindows); the
+    /// session adopts the clock of an authenticated report and resends once by itself.
+    NotInTimeWindow,
+    /// The agent did not know the engine ID it was sent (usmStatsUnknownEngineIDs) even
+    /// after the session discovered it again and resent.
+    UnknownEngineId,
+    /// The agent answered with a report PDU for the given counter, other than those above.
+    Report(Oid),
+}
+
+pub type SnmpResult<T> = Result<T, SnmpError>;
+
+/// A walk that failed part of the way, e.g. timing out on row 5000 of 8000, with what it
+/// collected up to then.
+#[derive(Debug)]
+pub struct PartialWalk {
+    /// The instances walked, keyed by their suffix under the walked OID.
+    pub collected: BTreeMap<Vec<u32>, Value>,
+    /// The last OID received, to resume the walk after; `None` when the first request
+    /// already failed.
+    pub last: Option<Oid>,
+    pub error: SnmpError,
+}
+
+impl From<SnmpError> for PartialWalk {
+    fn from(error: SnmpError) -> Self {
+        PartialWalk {
+            collected: BTreeMap::new(),
+            last: None,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for PartialWalk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "walk failed after {} instances: {}",
+            self.collected.len(),
+            self.error
+        )
+    }
+}
+
+impl error::Error for PartialWalk {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl fmt::Display for SnmpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnmpError::Io(err) => write!(f, "socket error: {}", err),
+            SnmpError::Timeout => f.write_str("request timed out"),
+            SnmpError::PortUnreachable => f.write_str("agent port unreachable"),
+            SnmpError::HostUnreachable => f.write_str("agent host unreachable"),
+            SnmpError::Encode(_) => f.write_str("failed to encode request"),
+            SnmpError::Decode {
+                offset: Some(offset),
+                ..
+            } => write!(f, "failed to decode response at offset {}", offset),
+            SnmpError::Decode { offset: None, .. } => f.write_str("failed to decode response"),
+            SnmpError::EncodingTooLarge { size, max } => {
+                write!(f, "encoded request is {} bytes, maximum is {}", size, max)
+            }
+            SnmpError::Truncated { size, buffer } => write!(
+                f,
+                "response is {} bytes, receive buffer is {}",
+                size, buffer
+            ),
+            SnmpError::InvalidMessage(reason) => write!(f, "invalid message: {}", reason),
+            SnmpError::InvalidOid(oid) => write!(f, "invalid OID {:?}", oid),
+            SnmpError::InvalidMacAddr(mac) => write!(f, "invalid MAC address {:?}", mac),
+            SnmpError::InvalidEngineId(id) => write!(f, "invalid engine ID {:?}", id),
+            SnmpError::UnknownName(name) => write!(f, "unknown name {:?}", name),
+            SnmpError::InvalidIndex(index) => write!(f, "invalid table index {}", index),
+            SnmpError::OidNotIncreasing { previous, next } => {
+                write!(f, "OID not increasing: {} after {}", next, previous)
+            }
+            SnmpError::Unsupported(reason) => write!(f, "unsupported: {}", reason),
+            SnmpError::UnexpectedPdu => f.write_str("unexpected PDU type in response"),
+            SnmpError::NoSuchObject(oid) => write!(f, "no such object {}", oid),
+            SnmpError::NoSuchInstance(oid) => write!(f, "no such instance {}", oid),
+            SnmpError::AgentError {
+                status,
+                index,
+                bindings,
+            } => match (*index as usize)
+                .checked_sub(1)
+                .and_then(|i| bindings.get(i))
+            {
+                Some((name, _)) => write!(f, "agent returned {} for {}", status, name),
+                None => write!(f, "agent returned {}", status),
+            },
+            SnmpError::Unverified { index, bindings } => match bindings.get(*index) {
+                Some((name, _)) => write!(f, "{} did not read back the value set", name),
+                None => f.write_str("SET did not read back the values set"),
+            },
+            SnmpError::WrongType { expected, got } => {
+                write!(f, "expected {}, got {}", expected, got.type_name())
+            }
+            SnmpError::AuthenticationError => f.write_str("message failed authentication"),
+            SnmpError::UnknownUserName => f.write_str("agent does not know the user"),
+            SnmpError::UnsupportedSecLevel => {
+                f.write_str("agent does not support the security level for the user")
+            }
+            SnmpError::WrongDigest => f.write_str("agent rejected the message digest"),
+            SnmpError::DecryptionError => f.write_str("agent could not decrypt the message"),
+            SnmpError::NotInTimeWindow => f.write_str("message outside the agent's time window"),
+            SnmpError::UnknownEngineId => f.write_str("agent does not know the engine ID"),
+            SnmpError::Report(oid) => write!(f, "agent sent report {}", oid),
+        }
+    }
+}
+
+impl SnmpError {
+    /// The binding an [`SnmpError::AgentError`] or [`SnmpError::Unverified`] is about, as
+    /// a 0-based index into the request; for a SET it is also an index into the slice it
+    /// was given.
+    pub fn binding_index(&self) -> Option<usize> {
+        match self {
+            SnmpError::AgentError { index, .. } => (*index as usize).checked_sub(1),
+            SnmpError::Unverified { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Whether the agent could not fit its response into one message, which smaller
+    /// requests may avoid.
+    pub(crate) fn is_too_big(&self) -> bool {
+        matches!(
+            self,
+            SnmpError::AgentError {
+                status: ErrorStatus::TooBig,
+                ..
+            }
+        )
+    }
+
+    /// Whether a received datagram was rejected for itself, being malformed or failing
+    /// authentication, rather than answering the request.
+    pub(crate) fn is_bad_datagram(&self) -> bool {
+        matches!(
+            self,
+            SnmpError::Decode { .. }
+                | SnmpError::Truncated { .. }
+                | SnmpError::InvalidMessage(_)
+                | SnmpError::UnexpectedPdu
+                | SnmpError::AuthenticationError
+        )
+    }
+}
+
+impl error::Error for SnmpError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SnmpError::Io(err) => Some(err),
+            SnmpError::Encode(err) => Some(err),
+            SnmpError::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnmpError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionRefused => SnmpError::PortUnreachable,
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => {
+                SnmpError::HostUnreachable
+            }
+            _ => SnmpError::Io(err),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Rendering values the way their MIB definitions ask for: DISPLAY-HINTs (RFC 2579 3.1)
+//! and the names of enumerated INTEGERs and BITS.
+
+use crate::mac::MAC_COLUMNS;
+use crate::mib::Rendering;
+use crate::{Bits, MacAddr, Mib, Oid, Value};
+
+/// Formats values using the textual conventions of a loaded [`Mib`], e.g. `up(1)` for
+/// ifOperStatus or `00:1a:2b:3c:4d:5e` for a MacAddress. Objects the MIB knows nothing
+/// about, and values that do not fit their hint, fall back to [`Value`]'s `Display`, except
+/// that the MAC addresses of a few well-known columns, such as ifPhysAddress, are shown as
+/// [`MacAddr`]s whether their MIB is loaded or not.
+#[derive(Debug, Clone, Copy)]
+pub struct Formatter<'a> {
+    mib: &'a Mib,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(mib: &'a Mib) -> Self {
+        Formatter { mib }
+    }
+
+    /// Renders `value`, received for the object instance `oid`.
+    pub fn format(&self, oid: &Oid, value: &Value) -> String {
+        self.mib
+            .rendering(oid)
+            .and_then(|rendering| render(&rendering, value))
+            .or_else(|| mac(oid, value))
+            .unwrap_or_else(|| value.to_string())
+    }
+
+    /// Renders a binding as `IF-MIB::ifOperStatus.3 = up(1)`.
+    pub fn format_binding(&self, oid: &Oid, value: &Value) -> String {
+        let name = self.mib.name_of(oid).unwrap_or_else(|| oid.to_string());
+
+        format!("{} = {}", name, self.format(oid, value))
+    }
+}
+
+fn mac(oid: &Oid, value: &Value) -> Option<String> {
+    if !MAC_COLUMNS
+        .iter()
+        .any(|column| oid.as_slice().starts_with(column))
+    {
+        return None;
+    }
+
+    match value {
+        Value::OctetString(bytes) => Some(MacAddr(bytes[..].try_into().ok()?).to_string()),
+        _ => None,
+    }
+}
+
+fn render(rendering: &Rendering<'_>, value: &Value) -> Option<String> {
+    match value {
+        Value::Integer(number) => {
+            let named = rendering.names.iter().find(|(named, _)| named == number);
+
+            match (named, rendering.hint) {
+                (Some((_, name)), _) => Some(format!("{}({})", name, number)),
+                (None, Some(hint)) => integer_hint(hint, *number),
+                (None, None) => None,
+            }
+        }
+        Value::OctetString(bytes) if rendering.bits && !rendering.names.is_empty() => {
+            Some(bits(rendering.names, bytes))
+        }
+        Value::OctetString(bytes) => octet_hint(rendering.hint?, bytes),
+        _ => None,
+    }
+}
+
+/// Applies an INTEGER hint: `d`, `d-N` for N implied decimal places, `x`, `o` or `b`.
+fn integer_hint(hint: &str, number: i64) -> Option<String> {
+    let mut chars = hint.chars();
+
+    match (chars.next()?, chars.as_str()) {
+        ('d', "") => Some(number.to_string()),
+        ('d', places) => {
+            let places: usize = places.strip_prefix('-')?.parse().ok()?;
+            if places == 0 {
+                return Some(number.to_string());
+            }
+
+            let digits = format!("{:0width$}", number.unsigned_abs(), width = places + 1);
+            let (whole, fraction) = digits.split_at(digits.len() - places);
+            let sign = if number < 0 { "-" } else { "" };
+
+            Some(format!("{}{}.{}", sign, whole, fraction))
+        }
+        ('x', "") => Some(format!("{:x}", number)),
+        ('o', "") => Some(format!("{:o}", number)),
+        ('b', "") => Some(format!("{:b}", number)),
+        _ => None,
+    }
+}
+
+/// One octet-format specification of an OCTET STRING hint.
+#[derive(Debug, PartialEq)]
+struct OctetFormat {
+    /// Whether the first octet of the value says how often to apply this specification.
+    repeat: bool,
+    len: usize,
+    format: char,
+    separator: Option<char>,
+    terminator: Option<char>,
+}
+
+fn octet_formats(hint: &str) -> Option<Vec<OctetFormat>> {
+    let mut chars = hint.chars().peekable();
+    let mut formats = Vec::new();
+    let delimiter = |c: &char| !c.is_ascii_digit() && *c != '*';
+
+    while chars.peek().is_some() {
+        let repeat = chars.next_if_eq(&'*').is_some();
+
+        let mut len = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            len.push(digit);
+        }
+
+        let format = chars.next().filter(|format| "xdoat".contains(*format))?;
+        let separator = chars.next_if(delimiter);
+        let terminator = repeat.then(|| chars.next_if(delimiter)).flatten();
+
+        formats.push(OctetFormat {
+            repeat,
+            // A length of zero would never consume the value.
+            len: len.parse().ok().filter(|len| *len > 0)?,
+            format,
+            separator,
+            terminator,
+        });
+    }
+
+    (!formats.is_empty()).then_some(formats)
+}
+
+/// Applies an OCTET STRING hint such as `1x:` or `2d-1d-1d,1d:1d:1d.1d`; the last
+/// specification is reused until the value runs out. Hex is rendered in lowercase with
+/// two digits per octet.
+fn octet_hint(hint: &str, bytes: &[u8]) -> Option<String> {
+    let formats = octet_formats(hint)?;
+    let mut out = String::new();
+    let mut pos = 0;
+    let mut index = 0;
+
+    while pos < bytes.len() {
+        let format = &formats[index.min(formats.len() - 1)];
+        index += 1;
+        let count = if format.repeat {
+            pos += 1;
+            bytes[pos - 1] as usize
+        } else {
+            1
+        };
+
+        for repetition in 0..count {
+            if pos >= bytes.len() {
+                break;
+            }
+
+            let chunk = &bytes[pos..bytes.len().min(pos + format.len)];
+            pos += chunk.len();
+
+            match format.format {
+                'a' | 't' => out.push_str(&String::from_utf8_lossy(chunk)),
+                _ if chunk.len() > 8 => return None,
+                format => {
+                    let number = chunk.iter().fold(0u64, |n, b| (n << 8) | *b as u64);
+                    out.push_str(&match format {
+                        'x' => format!("{:0width$x}", number, width = chunk.len() * 2),
+                        'o' => format!("{:o}", number),
+                        _ => number.to_string(),
+                    });
+                }
+            }
+
+            if pos < bytes.len() {
+                let last = repetition + 1 == count;
+                let delimiter = match format.terminator {
+                    Some(terminator) if last => Some(terminator),
+                    _ => format.separator,
+                };
+                out.extend(delimiter);
+            }
+        }
+    }
+
+    Some(out)
+}
+
+/// Names the bits set in a BITS value; bit 0 is the most significant bit of the first
+/// octet (RFC 2578 7.1.4).
+fn bits(names: &[(i64, String)], bytes: &[u8]) -> String {
+    let bits = Bits(bytes.to_vec());
+    let set = |bit: i64| u32::try_from(bit).is_ok_and(|bit| bits.is_set(bit));
+
+    names
+        .iter()
+        .filter(|(bit, _)| set(*bit))
+        .map(|(bit, name)| format!("{}({})", name, bit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,97 @@
+//! Relaying notifications from one network segment to receivers in others.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::trace;
+use crate::translate;
+use crate::{Notification, SnmpResult, SyncSession, TrapEvent, TrapListener, Version};
+
+/// Receives notifications with a [`TrapListener`] and sends each on to every destination,
+/// e.g. `TrapForwarder::new(listener).destination(collector).run()`.
+///
+/// Destinations are sessions, whose version and credentials the notifications are sent
+/// with instead of those they arrived with; informs are acknowledged by the listener and
+/// forwarded as traps. SNMPv1 traps only go to SNMPv1 destinations and v2 notifications
+/// to the others, unless [translated](TrapForwarder::translate).
+pub struct TrapForwarder {
+    listener: TrapListener,
+    destinations: Vec<SyncSession>,
+    translate: bool,
+}
+
+impl TrapForwarder {
+    pub fn new(listener: TrapListener) -> Self {
+        TrapForwarder {
+            listener,
+            destinations: Vec::new(),
+            translate: false,
+        }
+    }
+
+    pub fn destination(mut self, session: SyncSession) -> Self {
+        self.destinations.push(session);
+        self
+    }
+
+    /// Sends SNMPv1 traps to SNMPv2c and v3 destinations as SNMPv2-Traps, and v2
+    /// notifications to SNMPv1 destinations as v1 traps, translated with
+    /// [`translate::v1_to_v2`] and [`translate::v2_to_v1`]. The agent address of a
+    /// translated v2 notification is its source's, unless it binds snmpTrapAddress.0.
+    pub fn translate(mut self, translate: bool) -> Self {
+        self.translate = translate;
+        self
+    }
+
+    pub fn listener(&self) -> &TrapListener {
+        &self.listener
+    }
+
+    /// Sends `event` to every destination, returning how each send went, in the order the
+    /// destinations were added.
+    pub fn forward(&self, event: &TrapEvent) -> Vec<SnmpResult<()>> {
+        let v1 = matches!(event.notification, Notification::V1 { .. });
+        let translated = self.translate.then(|| {
+            if v1 {
+                translate::v1_to_v2(&event.notification, &event.community)
+            } else {
+                let agent_addr = match event.source.ip() {
+                    IpAddr::V4(addr) => addr,
+                    IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+                };
+                translate::v2_to_v1(&event.notification, agent_addr)
+            }
+        });
+
+        self.destinations
+            .iter()
+            .map(|destination| {
+                let notification = match &translated {
+                    Some(translated) if (destination.version() == Version::V1) != v1 => translated,
+                    _ => &event.notification,
+                };
+
+                destination.send_notification(notification)
+            })
+            .collect()
+    }
+
+    /// Forwards notifications until the listener fails, e.g. with `WouldBlock` or
+    /// `TimedOut` once its read timeout passes without one.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            let event = self.listener.recv()?;
+
+            for result in self.forward(&event) {
+                if let Err(_err) = result {
+                    trace::event!(
+                        debug,
+                        source = %event.source,
+                        error = %_err,
+                        "failed to forward notification"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Hooks sessions call with every message they send and receive, set with
+//! [`SessionBuilder::on_send`](crate::SessionBuilder::on_send) and
+//! [`SessionBuilder::on_receive`](crate::SessionBuilder::on_receive).
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use rasn_snmp::v2;
+
+type SendFn = dyn Fn(&mut Vec<u8>, Option<&v2::Pdus>) + Send + Sync;
+type ReceiveFn = dyn Fn(&[u8], Option<&v2::Pdus>) + Send + Sync;
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) on_send: Option<Arc<SendFn>>,
+    pub(crate) on_receive: Option<Arc<ReceiveFn>>,
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("on_send", &self.on_send.is_some())
+            .field("on_receive", &self.on_receive.is_some())
+            .finish()
+    }
+}
+
+impl Hooks {
+    /// A copy of `pdu` for the send hook, which as nothing else needs it once encoded is
+    /// only made when there is one.
+    pub(crate) fn request(&self, pdu: &v2::Pdus) -> Option<v2::Pdus> {
+        self.on_send.as_ref().map(|_| pdu.clone())
+    }
+
+    /// `message` as the send hook leaves it.
+    pub(crate) fn sending<'a>(&self, message: &'a [u8], pdu: Option<&v2::Pdus>) -> Cow<'a, [u8]> {
+        match &self.on_send {
+            Some(on_send) => {
+                let mut message = message.to_vec();
+                on_send(&mut message, pdu);
+                Cow::Owned(message)
+            }
+            None => Cow::Borrowed(message),
+        }
+    }
+
+    pub(crate) fn received<R: Answer>(&self, message: &[u8], answer: Option<&R>) {
+        if let Some(on_receive) = &self.on_receive {
+            on_receive(message, answer.and_then(Answer::pdu));
+        }
+    }
+}
+
+/// What a received message was accepted as, and the PDU, if any, the receive hook is
+/// given with it.
+pub(crate) trait Answer {
+    fn pdu(&self) -> Option<&v2::Pdus>;
+}
+
+impl Answer for v2::Pdus {
+    fn pdu(&self) -> Option<&v2::Pdus> {
+        Some(self)
+    }
+}
+
+impl Answer for Option<v2::Pdus> {
+    fn pdu(&self) -> Option<&v2::Pdus> {
+        self.as_ref()
+    }
+}
+
+impl Answer for () {
+    fn pdu(&self) -> Option<&v2::Pdus> {
+        None
+    }
+}
+
+impl Answer for Vec<u8> {
+    fn pdu(&self) -> Option<&v2::Pdus> {
+        None
+    }
+}
@@ -0,0 +1,158 @@
+//! Table indices as they appear in instance OIDs (RFC 2578 7.7).
+//!
+//! Each INDEX object contributes sub-identifiers to the OID of every column instance:
+//! integers one, IpAddresses four, fixed-size strings one per octet, and variable-length
+//! strings and OIDs a length followed by their contents. The length is left out when the
+//! last INDEX object is declared IMPLIED.
+
+use std::net::Ipv4Addr;
+
+use crate::{Oid, SnmpError, SnmpResult};
+
+/// Reads the components of an index one at a time, in INDEX clause order, e.g.
+/// `let mut index = IndexDecoder::new(suffix); let (if_index, addr) = (index.integer()?,
+/// index.ip_address()?); index.finish()?;`.
+#[derive(Debug, Clone)]
+pub struct IndexDecoder<'a> {
+    index: &'a [u32],
+    arcs: &'a [u32],
+}
+
+impl<'a> IndexDecoder<'a> {
+    pub fn new(index: &'a [u32]) -> Self {
+        IndexDecoder { index, arcs: index }
+    }
+
+    fn invalid(&self) -> SnmpError {
+        SnmpError::InvalidIndex(self.index.into())
+    }
+
+    fn take(&mut self, len: usize) -> SnmpResult<&'a [u32]> {
+        let (taken, rest) = self
+            .arcs
+            .split_at_checked(len)
+            .ok_or_else(|| self.invalid())?;
+        self.arcs = rest;
+
+        Ok(taken)
+    }
+
+    fn octets(&self, arcs: &[u32]) -> SnmpResult<Vec<u8>> {
+        arcs.iter()
+            .map(|arc| u8::try_from(*arc).map_err(|_| self.invalid()))
+            .collect()
+    }
+
+    fn len(&mut self) -> SnmpResult<usize> {
+        Ok(self.integer()? as usize)
+    }
+
+    /// An INTEGER, Unsigned32 or other integer-valued component.
+    pub fn integer(&mut self) -> SnmpResult<u32> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn ip_address(&mut self) -> SnmpResult<Ipv4Addr> {
+        let octets = self.fixed_string(4)?;
+
+        Ok(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+    }
+
+    /// A string of a fixed size, such as a MacAddress.
+    pub fn fixed_string(&mut self, len: usize) -> SnmpResult<Vec<u8>> {
+        let arcs = self.take(len)?;
+
+        self.octets(arcs)
+    }
+
+    /// A variable-length string, preceded by its length.
+    pub fn string(&mut self) -> SnmpResult<Vec<u8>> {
+        let len = self.len()?;
+
+        self.fixed_string(len)
+    }
+
+    /// An IMPLIED string, which runs to the end of the index.
+    pub fn implied_string(&mut self) -> SnmpResult<Vec<u8>> {
+        self.fixed_string(self.arcs.len())
+    }
+
+    /// An OBJECT IDENTIFIER, preceded by its number of arcs.
+    pub fn oid(&mut self) -> SnmpResult<Oid> {
+        let len = self.len()?;
+
+        Ok(self.take(len)?.into())
+    }
+
+    /// An IMPLIED OBJECT IDENTIFIER, which runs to the end of the index.
+    pub fn implied_oid(&mut self) -> SnmpResult<Oid> {
+        Ok(self.take(self.arcs.len())?.into())
+    }
+
+    /// The arcs not read yet.
+    pub fn rest(&self) -> &'a [u32] {
+        self.arcs
+    }
+
+    /// Fails if arcs are left over, which means the index was not what it was read as.
+    pub fn finish(self) -> SnmpResult<()> {
+        match self.arcs {
+            [] => Ok(()),
+            _ => Err(self.invalid()),
+        }
+    }
+}
+
+/// Builds an index from its components in INDEX clause order, for appending to a column
+/// OID, e.g. `IndexEncoder::new().integer(2).string(b"public").finish()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexEncoder {
+    arcs: Vec<u32>,
+}
+
+impl IndexEncoder {
+    pub fn new() -> Self {
+        IndexEncoder::default()
+    }
+
+    pub fn integer(mut self, value: u32) -> Self {
+        self.arcs.push(value);
+        self
+    }
+
+    pub fn ip_address(self, addr: Ipv4Addr) -> Self {
+        self.fixed_string(&addr.octets())
+    }
+
+    pub fn fixed_string(mut self, octets: &[u8]) -> Self {
+        self.arcs
+            .extend(octets.iter().map(|octet| u32::from(*octet)));
+        self
+    }
+
+    pub fn string(self, octets: &[u8]) -> Self {
+        self.integer(octets.len() as u32).fixed_string(octets)
+    }
+
+    pub fn implied_string(self, octets: &[u8]) -> Self {
+        self.fixed_string(octets)
+    }
+
+    pub fn oid(self, oid: &Oid) -> Self {
+        self.integer(oid.len() as u32).implied_oid(oid)
+    }
+
+    pub fn implied_oid(mut self, oid: &Oid) -> Self {
+        self.arcs.extend_from_slice(oid);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u32> {
+        self.arcs
+    }
+
+    /// Appends the index to the OID of a column, giving the OID of one of its instances.
+    pub fn instance_of(self, column: &Oid) -> Oid {
+        [column.as_slice(), &self.arcs].concat().into()
+    }
+}
@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use crate::{MacAddr, Table, Value};
+
+/// ifTable of IF-MIB (RFC 2863).
+pub(crate) const IF_TABLE: [u32; 8] = [1, 3, 6, 1, 2, 1, 2, 2];
+/// ifDescr, ifType, ifMtu, ifSpeed, ifPhysAddress, ifAdminStatus, ifOperStatus,
+/// ifLastChange, ifInOctets, ifInErrors, ifOutOctets and ifOutErrors.
+pub(crate) const IF_COLUMNS: [u32; 12] = [2, 3, 4, 5, 6, 7, 8, 9, 10, 14, 16, 20];
+/// ifXTable, which adds names, aliases and 64-bit counters.
+pub(crate) const IF_X_TABLE: [u32; 9] = [1, 3, 6, 1, 2, 1, 31, 1, 1];
+/// ifName, ifHCInOctets, ifHCOutOctets, ifHighSpeed and ifAlias.
+pub(crate) const IF_X_COLUMNS: [u32; 5] = [1, 6, 10, 15, 18];
+
+/// ifAdminStatus or ifOperStatus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum IfStatus {
+    Up,
+    Down,
+    Testing,
+    Unknown,
+    Dormant,
+    NotPresent,
+    LowerLayerDown,
+    Other(i64),
+}
+
+impl From<i64> for IfStatus {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => IfStatus::Up,
+            2 => IfStatus::Down,
+            3 => IfStatus::Testing,
+            4 => IfStatus::Unknown,
+            5 => IfStatus::Dormant,
+            6 => IfStatus::NotPresent,
+            7 => IfStatus::LowerLayerDown,
+            other => IfStatus::Other(other),
+        }
+    }
+}
+
+/// One row of ifTable joined with the ifXTable row of the same ifIndex, as returned by
+/// [`SyncSession::interfaces`](crate::SyncSession::interfaces). Columns the agent does
+/// not return are `None`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interface {
+    pub index: u32,
+    /// ifName, e.g. `Gi0/1`.
+    pub name: Option<String>,
+    pub descr: Option<String>,
+    pub alias: Option<String>,
+    /// The IANAifType, e.g. 6 for ethernetCsmacd.
+    pub if_type: Option<i64>,
+    pub mtu: Option<i64>,
+    /// Bits per second, from ifHighSpeed when ifSpeed is saturated at 4294967295.
+    pub speed: Option<u64>,
+    pub phys_address: Option<Vec<u8>>,
+    pub admin_status: Option<IfStatus>,
+    pub oper_status: Option<IfStatus>,
+    /// sysUpTime when the interface last changed its operational state.
+    pub last_change: Option<Duration>,
+    pub in_octets: Option<u32>,
+    pub out_octets: Option<u32>,
+    pub hc_in_octets: Option<u64>,
+    pub hc_out_octets: Option<u64>,
+    pub in_errors: Option<u32>,
+    pub out_errors: Option<u32>,
+}
+
+impl Interface {
+    /// ifPhysAddress as a MAC address, when it is one; tunnels and loopbacks have none.
+    pub fn mac(&self) -> Option<MacAddr> {
+        let octets = self.phys_address.as_deref()?.try_into().ok()?;
+
+        Some(MacAddr(octets))
+    }
+}
+
+fn text(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(Value::as_bytes)
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+}
+
+fn integer(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Integer(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn unsigned(value: Option<&Value>) -> Option<u32> {
+    match value? {
+        Value::Counter32(value) | Value::Gauge32(value) | Value::TimeTicks(value) => Some(*value),
+        _ => None,
+    }
+}
+
+fn counter64(value: Option<&Value>) -> Option<u64> {
+    match value? {
+        Value::Counter64(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Joins the rows of ifTable and ifXTable on ifIndex, in ifIndex order; interfaces only
+/// present in ifXTable are left out.
+pub(crate) fn join(if_table: Table, mut if_x_table: Table) -> Vec<Interface> {
+    if_table
+        .into_iter()
+        .filter_map(|(index, row)| {
+            let [if_index] = index[..] else {
+                return None;
+            };
+            let x_row = if_x_table.remove(&index).unwrap_or_default();
+
+            let high_speed = unsigned(x_row.get(&15)).map(|mbps| u64::from(mbps) * 1_000_000);
+            let speed = match unsigned(row.get(&5)) {
+                Some(u32::MAX) => high_speed.or(Some(u32::MAX.into())),
+                speed => speed.map(u64::from).or(high_speed),
+            };
+
+            Some(Interface {
+                index: if_index,
+                name: text(x_row.get(&1)),
+                descr: text(row.get(&2)),
+                alias: text(x_row.get(&18)),
+                if_type: integer(row.get(&3)),
+                mtu: integer(row.get(&4)),
+                speed,
+                phys_address: row.get(&6).and_then(Value::as_bytes).map(<[u8]>::to_vec),
+                admin_status: integer(row.get(&7)).map(IfStatus::from),
+                oper_status: integer(row.get(&8)).map(IfStatus::from),
+                last_change: unsigned(row.get(&9))
+                    .map(|ticks| Duration::from_millis(u64::from(ticks) * 10)),
+                in_octets: unsigned(row.get(&10)),
+                out_octets: unsigned(row.get(&16)),
+                hc_in_octets: counter64(x_row.get(&6)),
+                hc_out_octets: counter64(x_row.get(&10)),
+                in_errors: unsigned(row.get(&14)),
+                out_errors: unsigned(row.get(&20)),
+            })
+        })
+        .collect()
+}
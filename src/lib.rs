@@ -1,223 +1,1335 @@
-use std::borrow::Cow;
 use std::collections::BTreeMap;
-use std::time::Duration;
+use std::ops::ControlFlow;
+use std::slice;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{
     io,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs},
 };
 
-use rasn::types::{Integer, ObjectIdentifier, OctetString};
-use rasn_snmp::{v2, v2c};
+use rasn_snmp::v2;
 
-#[cfg(test)]
-mod tests;
+/// The ASN.1 crates whose PDU types [`SyncSession::send_pdu`] takes and returns.
+pub use {rasn, rasn_smi, rasn_snmp};
 
-#[derive(Debug, PartialEq)]
-pub enum SnmpError {
-    SendError,
-    ReceiveError,
-    ParseError,
-}
+mod agent;
+pub mod agentx;
+mod alarm;
+#[cfg(feature = "tokio")]
+mod async_session;
+#[cfg(feature = "tokio")]
+mod async_transport;
+#[cfg(feature = "tokio")]
+mod async_trap;
+mod ber;
+mod bits;
+mod bridge;
+mod buffers;
+mod builder;
+mod cache;
+mod crypto;
+mod datetime;
+pub mod debug;
+mod decoding;
+pub mod discover;
+mod dispatch;
+pub mod dump;
+mod engine;
+mod error;
+mod format;
+mod forward;
+mod hooks;
+pub mod index;
+mod interfaces;
+mod limit;
+mod mac;
+mod mib;
+mod neighbors;
+mod oid;
+mod options;
+#[cfg(feature = "pcap")]
+mod pcap;
+mod pdu;
+mod poller;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+mod rates;
+mod retry;
+mod row;
+mod scheduler;
+mod security;
+mod sink;
+mod stats;
+mod system;
+mod table;
+pub mod testing;
+#[cfg(feature = "tls")]
+mod tls;
+mod trace;
+pub mod translate;
+mod transport;
+mod trap;
+pub mod usm;
+mod v1;
+mod value;
+mod visit;
+mod walk;
+
+pub use agent::{Agent, AgentHandle, Handler};
+pub use alarm::{Alarm, Evaluator, Measure, Rule};
+#[cfg(feature = "tokio")]
+pub use async_session::AsyncSession;
+#[cfg(feature = "tokio")]
+pub use async_transport::{AsyncTcpTransport, AsyncTransport, AsyncUdpTransport};
+#[cfg(feature = "tokio")]
+pub use async_trap::AsyncTrapListener;
+pub use bits::Bits;
+pub use bridge::{FdbEntry, FdbStatus};
+pub use builder::{SessionBuilder, Version};
+pub use cache::Cache;
+#[cfg(feature = "ring")]
+pub use crypto::Ring;
+pub use crypto::{set_crypto_provider, CryptoProvider, RustCrypto};
+pub use datetime::DateAndTime;
+pub use decoding::Decoding;
+pub use dispatch::{DispatchedTransport, Dispatcher};
+pub use engine::{EngineId, EngineIdFormat};
+pub use error::{ErrorStatus, PartialWalk, SnmpError, SnmpResult};
+pub use format::Formatter;
+pub use forward::TrapForwarder;
+pub use index::{IndexDecoder, IndexEncoder};
+pub use interfaces::{IfStatus, Interface};
+pub use limit::RateLimiter;
+pub use mac::MacAddr;
+pub use mib::{Mib, MibNode};
+pub use neighbors::{Neighbor, NeighborKind};
+#[cfg(feature = "serde")]
+pub use oid::oid_keys;
+pub use oid::{IntoOid, Oid};
+pub use options::{CancelToken, RequestOptions};
+#[cfg(feature = "pcap")]
+pub use pcap::PcapWriter;
+#[cfg(feature = "tokio")]
+pub use poller::walk_many_async;
+pub use poller::{walk_many, PollResult, Poller, Target, WalkResult};
+pub use rates::CounterTracker;
+pub use retry::RetryPolicy;
+pub use row::RowStatus;
+pub use scheduler::{Collected, Job, Sample, Scheduler};
+pub use sink::TrapSink;
+#[cfg(feature = "webhook")]
+pub use sink::Webhook;
+pub use stats::SessionStats;
+pub use system::SystemInfo;
+pub use table::{ColumnWalk, Table};
+#[cfg(feature = "tls")]
+pub use tls::{TlsConfig, TlsTransport};
+pub use transport::{TcpTransport, Transport, UdpTransport};
+pub use trap::{NamedBinding, Notification, TrapEvent, TrapListener, TrapStats};
+pub use usm::{AuthProtocol, PrivProtocol, UsmUser, UsmUserTable};
+pub use value::{Hex, Ticks, Value};
+pub use visit::ValueRef;
+pub use walk::{Walk, WalkCursor};
+
+use bridge::{BASE_PORT_COLUMNS, BASE_PORT_TABLE, FDB_COLUMNS, Q_TP_FDB_TABLE, TP_FDB_TABLE};
+use buffers::BufferPool;
+use builder::Config;
+use hooks::{Answer, Hooks};
+use interfaces::{IF_COLUMNS, IF_TABLE, IF_X_COLUMNS, IF_X_TABLE};
+use limit::Permit;
+use neighbors::{
+    NET_TO_MEDIA_COLUMNS, NET_TO_MEDIA_TABLE, NET_TO_PHYSICAL_COLUMNS, NET_TO_PHYSICAL_TABLE,
+};
+use options::Context;
+use row::Row;
+use security::Security;
+use stats::Stats;
+use system::SYSTEM_OIDS;
+use table::TableWalk;
+use visit::VisitWalk;
 
-type SnmpResult<T> = Result<T, SnmpError>;
+#[cfg(test)]
+mod tests;
 
 const BUFFER_SIZE: usize = 4096;
 
-pub struct SyncSession {
-    community: OctetString,
-    socket: UdpSocket,
-    version: Integer,
+/// Read timeouts surface as `WouldBlock` on Unix and `TimedOut` on Windows.
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// A blocking session with one agent, over UDP unless another [`Transport`] is given to
+/// [`SessionBuilder::build_with`].
+pub struct SyncSession<T = UdpTransport> {
+    security: Security,
+    transport: T,
+    timeout: Duration,
+    retry: RetryPolicy,
+    max_message_size: usize,
+    recv_buffer_size: usize,
+    exceptions_as_errors: bool,
+    decoding: Decoding,
+    version_fallback: bool,
+    limiter: Option<RateLimiter>,
+    resolve_every: Option<Duration>,
+    resolve_on_failure: bool,
+    /// The SNMPv3 context of requests that do not give their own.
+    context: Context,
+    /// When the agent was last looked up, in milliseconds since `started`.
+    resolved: AtomicU64,
+    request_id: AtomicI32,
+    started: Instant,
+    stats: Stats,
+    buffers: BufferPool,
+    hooks: Hooks,
+    #[cfg(feature = "pcap")]
+    capture: Option<PcapWriter>,
 }
 
 impl SyncSession {
+    /// Starts configuring a session to `host`, e.g.
+    /// `SyncSession::builder("10.0.0.1").v2c("public").retries(3).build()`.
+    pub fn builder(host: impl Into<String>) -> SessionBuilder {
+        SessionBuilder::new(host)
+    }
+
+    /// Creates a community-based session; `version` is the wire value, 0 for SNMPv1 and 1
+    /// for SNMPv2c.
     pub fn new<A>(version: u8, dest_addr: A, community: &[u8], timeout: u64) -> io::Result<Self>
     where
         A: ToSocketAddrs,
     {
-        let socket = match dest_addr.to_socket_addrs()?.next() {
-            Some(SocketAddr::V4(_)) => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
-            Some(SocketAddr::V6(_)) => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
-            None => panic!("empty list of socket addrs"),
-        };
+        let config = Config::with_timeout(Duration::from_millis(timeout));
 
-        socket.set_read_timeout(Some(Duration::from_millis(timeout)))?;
-        socket.connect(dest_addr)?;
+        Self::open(Security::community(version, community), dest_addr, config)
+    }
 
-        Ok(SyncSession {
-            community: community.to_vec().into(),
-            socket,
-            version: version.into(),
-        })
+    /// Creates an SNMPv3 session; the agent's engine is discovered on the first request.
+    pub fn new_v3<A>(dest_addr: A, user: UsmUser, timeout: u64) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let config = Config::with_timeout(Duration::from_millis(timeout));
+        let security = Security::usm(user, None, config.max_message_size)?;
+
+        Self::open(security, dest_addr, config)
     }
 
-    fn send_and_recv(socket: &UdpSocket, send: Vec<u8>) -> SnmpResult<Vec<u8>> {
-        let mut recv: Box<[u8; BUFFER_SIZE]> = Box::new([0; BUFFER_SIZE]);
+    pub(crate) fn open<A>(security: Security, dest_addr: A, config: Config) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        let transport = UdpTransport::open(dest_addr, &config.socket)?;
+
+        Ok(Self::with_transport(security, transport, config))
+    }
+}
 
-        for _ in 0..2 {
-            if let Ok(_) = socket.send(&send) {
-                match socket.recv(recv.as_mut_slice()) {
-                    Ok(_) => return Ok(recv.to_vec()),
-                    Err(_) => continue,
+impl<T: Transport> SyncSession<T> {
+    pub(crate) fn with_transport(security: Security, transport: T, config: Config) -> Self {
+        SyncSession {
+            security,
+            max_message_size: config.max_message_size.min(transport.max_msg_size()),
+            recv_buffer_size: config
+                .recv_buffer_size
+                .unwrap_or_else(|| transport.max_msg_size()),
+            exceptions_as_errors: config.exceptions_as_errors,
+            decoding: config.decoding,
+            version_fallback: config.version_fallback,
+            limiter: config.rate_limit,
+            resolve_every: config.resolve_every,
+            resolve_on_failure: config.resolve_on_failure,
+            context: config.context,
+            resolved: AtomicU64::new(0),
+            transport,
+            timeout: config.timeout,
+            retry: config.retry,
+            request_id: AtomicI32::new(pdu::initial_request_id()),
+            started: Instant::now(),
+            stats: Stats::default(),
+            buffers: BufferPool::default(),
+            hooks: config.hooks,
+            #[cfg(feature = "pcap")]
+            capture: config.capture,
+        }
+    }
+
+    /// Sends `message`, the encoding of `request`, as the send hook leaves it, once the rate
+    /// limit allows, and captures what was sent; the permit counts the request as
+    /// outstanding until dropped.
+    fn transmit(&self, message: &[u8], request: Option<&v2::Pdus>) -> SnmpResult<Option<Permit>> {
+        let permit = self.limiter.as_ref().map(RateLimiter::acquire);
+        let message = self.hooks.sending(message, request);
+        self.transport.send(&message)?;
+        #[cfg(feature = "pcap")]
+        if let Some(capture) = &self.capture {
+            let (local, peer) = (self.transport.local_addr(), self.transport.peer_addr());
+            capture.record(local, peer, &message, true);
+        }
+        trace::event!(trace, len = message.len(), "sent message");
+        Ok(permit)
+    }
+
+    /// Sends `send` and feeds received datagrams to `accept` until it returns a value,
+    /// retransmitting according to the retry policy whenever the timeout runs out first.
+    /// Malformed or unauthentic datagrams are dropped; the request fails with why only if
+    /// nothing else answers it.
+    fn send_and_recv<R>(
+        &self,
+        send: &[u8],
+        request: Option<&v2::Pdus>,
+        opts: &RequestOptions,
+        mut accept: impl FnMut(&[u8]) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R>
+    where
+        R: Answer,
+    {
+        let mut recv = self.buffers.get().zeroed(self.recv_buffer_size);
+        let (timeout, retry) = (opts.timeout_or(self.timeout), opts.retry_or(&self.retry));
+        // Why the last datagram was dropped, should no other answer the request.
+        let mut rejected = None;
+
+        for attempt in 0..=retry.retries() {
+            if attempt > 0 {
+                let pause = retry.pause(attempt);
+                trace::event!(debug, attempt, ?pause, "retransmitting request");
+                thread::sleep(pause);
+            }
+
+            let _permit = self.transmit(send, request)?;
+            self.stats.sent(attempt);
+            let sent = Instant::now();
+
+            let deadline = Instant::now() + timeout;
+
+            while let Some(remaining) = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|remaining| !remaining.is_zero())
+            {
+                match self.transport.recv(recv.as_mut_slice(), remaining) {
+                    Ok(len) => {
+                        trace::event!(trace, len, "received message");
+                        #[cfg(feature = "pcap")]
+                        if let Some(capture) = &self.capture {
+                            let (local, peer) =
+                                (self.transport.local_addr(), self.transport.peer_addr());
+                            capture.record(local, peer, &recv[..len], false);
+                        }
+                        let accepted = pdu::check_truncated(&recv[..len], recv.len())
+                            .and_then(|()| accept(&recv[..len]));
+                        let answer = accepted.as_ref().ok().and_then(Option::as_ref);
+                        self.hooks.received(&recv[..len], answer);
+                        match accepted {
+                            Ok(Some(value)) => {
+                                self.stats.answered(attempt, sent.elapsed());
+                                return Ok(value);
+                            }
+                            Ok(None) => {}
+                            Err(err) => {
+                                self.stats.rejected(&err);
+                                if !err.is_bad_datagram() {
+                                    return Err(err);
+                                }
+                                trace::event!(debug, error = %err, "dropping rejected message");
+                                rejected = Some(err);
+                            }
+                        }
+                    }
+                    Err(err) if is_timeout(&err) => break,
+                    Err(err) => return Err(err.into()),
                 }
-            } else {
-                return Err(SnmpError::SendError);
             }
         }
 
-        Err(SnmpError::ReceiveError)
+        trace::event!(debug, attempts = retry.retries() + 1, "request timed out");
+        self.stats.timed_out();
+        Err(rejected.unwrap_or(SnmpError::Timeout))
+    }
+
+    /// The agent address requests currently go to, which changes when the session fails
+    /// over to another of the agent's addresses.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.transport.peer_addr()
+    }
+
+    /// Counters of the requests sent so far and how they were answered, for reporting
+    /// reachability and latency alongside the polled values.
+    pub fn stats(&self) -> SessionStats {
+        self.stats.snapshot()
     }
 
-    fn parse_oid(value: &String) -> ObjectIdentifier {
-        let oid: Cow<'static, [u32]> = value
-            .split('.')
-            .filter_map(|part| part.parse::<u32>().ok())
-            .collect();
+    /// The version requests are sent in, which changes when
+    /// [`SessionBuilder::version_fallback`] finds the agent only answers the other one.
+    pub fn version(&self) -> Version {
+        self.security.version()
+    }
 
-        ObjectIdentifier::new_unchecked(oid)
+    /// The community requests are sent with, which changes when one of
+    /// [`SessionBuilder::communities`] after the first is answered; `None` for SNMPv3.
+    pub fn community(&self) -> Option<&[u8]> {
+        self.security.current_community()
     }
 
-    fn parse_response(message: v2c::Message<v2::Pdus>) -> SnmpResult<v2::VarBindList> {
-        if let v2::Pdus::Response(response) = message.data {
-            println!(
-                "Error: status: {}, index: {}",
-                response.0.error_status, response.0.error_index
-            );
+    /// The engine ID the agent reported in SNMPv3 discovery, e.g. to check it against the
+    /// expected one or to configure its notifications; `None` before the first request and
+    /// for community-based or TLS sessions.
+    pub fn engine_id(&self) -> Option<EngineId> {
+        self.security.engine_id()
+    }
 
-            Ok(response.0.variable_bindings)
-        } else {
-            Err(SnmpError::ParseError)
+    /// Replaces the retry policy used for every request from now on.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry = policy;
+    }
+
+    fn next_request_id(&self) -> i32 {
+        self.request_id.fetch_add(1, Ordering::Relaxed) & i32::MAX
+    }
+
+    fn request(&self, data: v2::Pdus, opts: &RequestOptions) -> SnmpResult<v2::Pdus> {
+        self.request_with(data, opts, |response, message, request_id| {
+            self.security
+                .decode(response, message, request_id, self.decoding)
+        })
+    }
+
+    /// Sends `data` like [`Self::request`], with the responses taken by `accept`, given
+    /// the message and request-id they should answer.
+    fn request_with<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        mut accept: impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        self.resolve_if_due();
+
+        let result = match self.exchange(data.clone(), opts, &mut accept) {
+            // The agent rebooted or was replaced; resend once against the relearned engine.
+            Err(SnmpError::NotInTimeWindow | SnmpError::UnknownEngineId)
+                if self.security.resync() =>
+            {
+                trace::event!(debug, "resending request after engine resync");
+                self.exchange(data, opts, &mut accept)
+            }
+            Err(SnmpError::Timeout) => self.fail_over(data, opts, &mut accept),
+            result => result,
+        };
+
+        if let Err(_err) = &result {
+            trace::event!(debug, error = %_err, "request failed");
         }
+        result
     }
 
-    fn parse_value(var: v2::VarBindValue) -> String {
-        match var {
-            v2::VarBindValue::Value(value) => match value {
-                v2::ObjectSyntax::Simple(simple) => match simple {
-                    rasn_smi::v2::SimpleSyntax::Integer(int) => int.to_string(),
-                    rasn_smi::v2::SimpleSyntax::String(str) => {
-                        String::from_utf8(str.to_vec()).unwrap()
-                    }
-                    rasn_smi::v2::SimpleSyntax::ObjectId(oid) => oid
-                        .iter()
-                        .map(ToString::to_string)
-                        .collect::<Vec<_>>()
-                        .join("."),
-                },
-                v2::ObjectSyntax::ApplicationWide(wide) => match wide {
-                    rasn_smi::v2::ApplicationSyntax::Address(ip) => {
-                        ip.0.iter()
-                            .map(ToString::to_string)
-                            .collect::<Vec<_>>()
-                            .join(".")
-                    }
-                    rasn_smi::v2::ApplicationSyntax::Counter(counter) => counter.0.to_string(),
-                    rasn_smi::v2::ApplicationSyntax::Ticks(tick) => tick.0.to_string(),
-                    rasn_smi::v2::ApplicationSyntax::BigCounter(counter) => counter.0.to_string(),
-                    rasn_smi::v2::ApplicationSyntax::Unsigned(gauge) => gauge.0.to_string(),
-                    rasn_smi::v2::ApplicationSyntax::Arbitrary(opaque) => {
-                        println!("{:?}", opaque);
-                        "".to_string()
-                    }
-                },
-            },
-            _ => "".to_string(),
-        }
-    }
-
-    pub fn get(&self, oid: &String) {
-        let message = v2c::Message {
-            version: self.version.clone(),
-            community: self.community.clone(),
-            data: v2::GetRequest(v2::Pdu {
-                request_id: 1,
-                error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
-                error_index: 0,
-                variable_bindings: vec![v2::VarBind {
-                    name: Self::parse_oid(oid),
-                    value: v2::VarBindValue::Unspecified,
-                }],
-            }),
+    /// Resends a request that timed out to each of the agent's other addresses, staying
+    /// with the first that answers, then with other credentials.
+    fn fail_over<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        for _ in 0..self.transport.alternatives() {
+            self.transport.fail_over()?;
+            trace::event!(debug, peer = ?self.transport.peer_addr().ok(), "failing over");
+
+            match self.exchange(data.clone(), opts, accept) {
+                Err(SnmpError::Timeout) => continue,
+                result => return result,
+            }
+        }
+
+        if self.resolve_on_failure && self.resolve() {
+            match self.exchange(data.clone(), opts, accept) {
+                Err(SnmpError::Timeout) => {}
+                result => return result,
+            }
+        }
+
+        self.fall_back(data, opts, accept)
+    }
+
+    /// Looks the agent up again once [`SessionBuilder::resolve_every`](crate::SessionBuilder::resolve_every)
+    /// has passed since it last was.
+    fn resolve_if_due(&self) {
+        let Some(every) = self.resolve_every else {
+            return;
         };
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.resolved.load(Ordering::Relaxed);
 
-        let message = rasn::ber::encode(&message).unwrap();
-        let message = Self::send_and_recv(&self.socket, message).unwrap();
-        let message: v2c::Message<v2::Response>= rasn::ber::decode(&message).unwrap();
-
-        // let vars = Self::parse_response(message).unwrap();
-        for var in message.data.0.variable_bindings {
-            println!("{} = {:#?}", var.name, var.value);
-        }
-    }
-
-    pub fn getnext(&self, oid: &String) -> SnmpResult<v2::VarBindList> {
-        let message = v2c::Message {
-            version: v2c::Message::<v2::GetNextRequest>::VERSION.into(),
-            community: self.community.clone(),
-            data: v2::GetNextRequest(v2::Pdu {
-                request_id: 1,
-                error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
-                error_index: 0,
-                variable_bindings: vec![v2::VarBind {
-                    name: Self::parse_oid(oid),
-                    value: v2::VarBindValue::Unspecified,
-                }],
-            }),
+        // Of requests sent at once, only one looks the agent up.
+        if now.saturating_sub(last) >= every.as_millis() as u64
+            && self
+                .resolved
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.resolve();
+        }
+    }
+
+    /// Looks the agent up again and returns whether its addresses changed.
+    fn resolve(&self) -> bool {
+        match self.transport.resolve() {
+            Ok(changed) => {
+                if changed {
+                    trace::event!(debug, peer = ?self.transport.peer_addr().ok(), "agent moved");
+                }
+                changed
+            }
+            Err(_err) => {
+                trace::event!(debug, error = %_err, "looking the agent up again failed");
+                false
+            }
+        }
+    }
+
+    /// Resends a request that timed out with each of the other communities, and versions
+    /// with version fallback, keeping the first that is answered.
+    fn fall_back<R: Answer>(
+        &self,
+        data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        let Some(original) = self.security.credentials() else {
+            return Err(SnmpError::Timeout);
         };
 
-        let message = rasn::ber::encode(&message).unwrap();
-        let message = Self::send_and_recv(&self.socket, message).unwrap();
-        let message = rasn::ber::decode(&message).unwrap();
-
-        Self::parse_response(message)
-    }
-
-    pub fn getbulk(&self, oid: &String, non_repeaters: u32, max_repetitions: u32) {
-        let message = v2c::Message {
-            version: self.version.clone(),
-            community: self.community.clone(),
-            data: v2::GetBulkRequest(v2::BulkPdu {
-                request_id: 1,
-                non_repeaters,
-                max_repetitions,
-                variable_bindings: vec![v2::VarBind {
-                    name: Self::parse_oid(oid),
-                    value: v2::VarBindValue::Unspecified,
-                }],
-            }),
+        for credentials in self.security.fallbacks(self.version_fallback) {
+            self.security.set_credentials(credentials);
+            trace::event!(debug, ?credentials, "retrying with other credentials");
+
+            if let Ok(answer) = self.exchange(data.clone(), opts, accept) {
+                return Ok(answer);
+            }
+        }
+
+        self.security.set_credentials(original);
+        Err(SnmpError::Timeout)
+    }
+
+    fn exchange<R: Answer>(
+        &self,
+        mut data: v2::Pdus,
+        opts: &RequestOptions,
+        accept: &mut impl FnMut(&[u8], &[u8], i32) -> SnmpResult<Option<R>>,
+    ) -> SnmpResult<R> {
+        while let Some(message) = self.security.handshake()? {
+            self.send_and_recv(&message, None, opts, |response| {
+                self.security.complete_handshake(response).map(Some)
+            })?;
+        }
+
+        let request_id = self.next_request_id();
+        pdu::set_request_id(&mut data, request_id);
+        let request = self.hooks.request(&data);
+
+        let mut message = self.buffers.get();
+        let context = opts.context_or(&self.context);
+        self.security
+            .encode(data, &context, self.max_message_size, &mut message)?;
+
+        self.send_and_recv(&message, request.as_ref(), opts, |response| {
+            accept(response, &message, request_id)
+        })
+    }
+
+    pub fn get(&self, oid: impl IntoOid) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_with(oid, &RequestOptions::default())
+    }
+
+    /// [`SyncSession::get`] with the session's settings overridden by `opts`; the other
+    /// `_with` methods work the same way.
+    pub fn get_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_many_with(&[oid.into_oid()?], opts)
+    }
+
+    pub fn getnext(&self, oid: impl IntoOid) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_with(oid, &RequestOptions::default())
+    }
+
+    pub fn getnext_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_many_with(&[oid.into_oid()?], opts)
+    }
+
+    /// Fetches several OIDs in one GET; results are in request order. When the agent
+    /// answers tooBig, the OIDs are fetched in smaller batches instead.
+    pub fn get_many<O: IntoOid + Clone>(&self, oids: &[O]) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.get_many_with(oids, &RequestOptions::default())
+    }
+
+    pub fn get_many_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let vars = self.fetch(&oid::into_oids(oids)?, opts)?;
+
+        if self.exceptions_as_errors {
+            pdu::check_exceptions(&vars)?;
+        }
+        Ok(vars)
+    }
+
+    /// GETs `oid` as text; anything that is not UTF-8 is replaced. Typed GETs fail with
+    /// [`SnmpError::NoSuchObject`] or [`SnmpError::NoSuchInstance`] whatever
+    /// [`SessionBuilder::exceptions_as_errors`] says, and with [`SnmpError::WrongType`] when
+    /// the value is of another type.
+    pub fn get_string(&self, oid: impl IntoOid) -> SnmpResult<String> {
+        self.get_value(oid)?.try_into()
+    }
+
+    /// GETs `oid` as a Counter32, Gauge32, TimeTicks or INTEGER, which has to fit.
+    pub fn get_u32(&self, oid: impl IntoOid) -> SnmpResult<u32> {
+        self.get_value(oid)?.try_into()
+    }
+
+    /// GETs `oid` as any unsigned integer, Counter64 included, or a non-negative INTEGER.
+    pub fn get_u64(&self, oid: impl IntoOid) -> SnmpResult<u64> {
+        self.get_value(oid)?.try_into()
+    }
+
+    /// GETs `oid` as an IpAddress, or an InetAddress of four or sixteen octets.
+    pub fn get_ip(&self, oid: impl IntoOid) -> SnmpResult<IpAddr> {
+        self.get_value(oid)?.try_into()
+    }
+
+    /// GETs `oid` as an OBJECT IDENTIFIER.
+    pub fn get_oid(&self, oid: impl IntoOid) -> SnmpResult<Oid> {
+        self.get_value(oid)?.try_into()
+    }
+
+    /// GETs `oid` alone, failing on exceptions and on a response for another OID.
+    fn get_value(&self, oid: impl IntoOid) -> SnmpResult<Value> {
+        let oid = oid.into_oid()?;
+        let vars = self.fetch(slice::from_ref(&oid), &RequestOptions::default())?;
+        pdu::check_exceptions(&vars)?;
+
+        match vars.into_iter().next() {
+            Some((name, value)) if name == oid => Ok(value),
+            _ => Err(SnmpError::InvalidMessage(
+                "response OID does not match the request",
+            )),
+        }
+    }
+
+    /// GETs `oids`, returning exceptions as values regardless of the session's setting.
+    fn fetch(&self, oids: &[Oid], opts: &RequestOptions) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut batches = pdu::Batches::new(oids);
+        let mut vars = Vec::with_capacity(oids.len());
+
+        while let Some(batch) = batches.next() {
+            match pdu::parse_aligned_response(self.request(pdu::get(batch), opts)?, batch) {
+                Ok(batch) => {
+                    vars.extend(batch);
+                    batches.advance();
+                }
+                Err(err) => batches.shrink(err)?,
+            }
+        }
+
+        Ok(vars)
+    }
+
+    /// Fetches the successors of several OIDs in one GETNEXT; results are in request order.
+    /// Split into smaller batches like [`SyncSession::get_many`].
+    pub fn getnext_many<O: IntoOid + Clone>(&self, oids: &[O]) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.getnext_many_with(oids, &RequestOptions::default())
+    }
+
+    pub fn getnext_many_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let oids = oid::into_oids(oids)?;
+
+        let mut batches = pdu::Batches::new(&oids);
+        let mut vars = Vec::with_capacity(oids.len());
+
+        while let Some(batch) = batches.next() {
+            match self.getnext_batch(batch.to_vec(), opts) {
+                Ok(batch) => {
+                    vars.extend(batch);
+                    batches.advance();
+                }
+                Err(err) => batches.shrink(err)?,
+            }
+        }
+
+        Ok(vars)
+    }
+
+    fn getnext_batch(
+        &self,
+        oids: Vec<Oid>,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut next = pdu::NextRequest::new(oids);
+
+        while let Some(data) = next.request() {
+            if let Some(vars) = next.response(self.request(data, opts)?)? {
+                return Ok(vars);
+            }
+        }
+
+        Ok(next.finish())
+    }
+
+    /// Issues a GETBULK; the first `non_repeaters` OIDs are fetched once, the rest up to
+    /// `max_repetitions` times each, interleaved row by row. When the agent answers
+    /// tooBig, the request is repeated with half the repetitions.
+    pub fn getbulk<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        non_repeaters: u32,
+        max_repetitions: u32,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let opts = RequestOptions::new().max_repetitions(max_repetitions);
+
+        self.getbulk_with(oids, non_repeaters, &opts)
+    }
+
+    /// [`SyncSession::getbulk`] with max-repetitions taken from `opts`.
+    pub fn getbulk_with<O: IntoOid + Clone>(
+        &self,
+        oids: &[O],
+        non_repeaters: u32,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let mut max_repetitions = opts.max_repetitions_or_default();
+
+        self.bulk(
+            &oid::into_oids(oids)?,
+            non_repeaters,
+            &mut max_repetitions,
+            opts,
+        )
+    }
+
+    /// A GETBULK that leaves `max_repetitions` at the value the agent last accepted.
+    fn bulk(
+        &self,
+        oids: &[Oid],
+        non_repeaters: u32,
+        max_repetitions: &mut u32,
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        loop {
+            let data = pdu::getbulk(oids, non_repeaters, *max_repetitions);
+
+            match self.request(data, opts).and_then(|data| {
+                pdu::parse_bulk_response(data, oids, non_repeaters, *max_repetitions)
+            }) {
+                Err(err) if err.is_too_big() && *max_repetitions > 1 => {
+                    *max_repetitions /= 2;
+                    trace::event!(
+                        debug,
+                        max_repetitions,
+                        "reducing max-repetitions after tooBig"
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn set<O: IntoOid + Clone>(
+        &self,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        self.set_with(bindings, &RequestOptions::default())
+    }
+
+    pub fn set_with<O: IntoOid + Clone>(
+        &self,
+        bindings: &[(O, Value)],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Vec<(Oid, Value)>> {
+        let bindings = oid::into_bindings(bindings)?;
+        let data = self.request(pdu::set(&bindings), opts)?;
+        let response = pdu::parse_set_response(data, &bindings)?;
+
+        if opts.verifies() {
+            let oids: Vec<Oid> = bindings.iter().map(|(oid, _)| oid.clone()).collect();
+            pdu::verify_set(&bindings, self.fetch(&oids, opts)?)?;
+        }
+
+        Ok(response)
+    }
+
+    /// Creates the row `index` of the table whose entry is `entry`, with `columns` set and
+    /// the RowStatus column `status_column` active. The row is first created with a single
+    /// createAndGo; when the agent refuses that with wrongValue or inconsistentValue, it is
+    /// created with createAndWait, its columns set, and then made active, destroying it
+    /// again if a step after its creation fails.
+    pub fn create_row(
+        &self,
+        entry: impl IntoOid,
+        status_column: u32,
+        index: &[u32],
+        columns: &[(u32, Value)],
+    ) -> SnmpResult<()> {
+        let row = Row::new(entry.into_oid()?, status_column, index);
+
+        match self.set(&row.with_status(columns, RowStatus::CreateAndGo)) {
+            Err(err) if row::create_in_steps(&err) => {}
+            result => return result.map(drop),
+        }
+
+        self.set(&[row.status(RowStatus::CreateAndWait)])?;
+        let mut result = Ok(Vec::new());
+        if !columns.is_empty() {
+            result = self.set(&row.columns(columns));
+        }
+        if result.is_ok() {
+            result = self.set(&[row.status(RowStatus::Active)]);
+        }
+        if result.is_err() {
+            // The error that matters is the one that left the row unfinished.
+            let _ = self.set(&[row.status(RowStatus::Destroy)]);
+        }
+
+        result.map(drop)
+    }
+
+    /// Deletes the row `index` of the table whose entry is `entry`.
+    pub fn destroy_row(
+        &self,
+        entry: impl IntoOid,
+        status_column: u32,
+        index: &[u32],
+    ) -> SnmpResult<()> {
+        let row = Row::new(entry.into_oid()?, status_column, index);
+
+        self.set(&[row.status(RowStatus::Destroy)]).map(drop)
+    }
+
+    /// Hundredths of a second since the session was created, reported as sysUpTime.0.
+    fn uptime(&self) -> u32 {
+        (self.started.elapsed().as_millis() / 10) as u32
+    }
+
+    /// Sends an SNMPv2-Trap; no acknowledgement is expected. SNMPv3 sessions with USM send
+    /// it from their [`SessionBuilder::local_engine`].
+    pub fn send_trap<O: IntoOid + Clone>(
+        &self,
+        trap_oid: impl IntoOid,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let bindings = oid::into_bindings(bindings)?;
+
+        let mut data = pdu::trap(self.uptime(), &trap_oid.into_oid()?, &bindings);
+        pdu::set_request_id(&mut data, self.next_request_id());
+        let request = self.hooks.request(&data);
+
+        let mut message = self.buffers.get();
+        self.security
+            .encode(data, &self.context, self.max_message_size, &mut message)?;
+
+        self.transmit(&message, request.as_ref())?;
+
+        Ok(())
+    }
+
+    /// Sends an SNMPv1 Trap-PDU; only available on SNMPv1 sessions. The agent address is the
+    /// session's local IPv4 address.
+    pub fn send_v1_trap<O: IntoOid + Clone>(
+        &self,
+        enterprise: impl IntoOid,
+        generic_trap: u32,
+        specific_trap: u32,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let agent_addr = match self.transport.local_addr() {
+            Ok(SocketAddr::V4(addr)) => *addr.ip(),
+            _ => Ipv4Addr::UNSPECIFIED,
         };
 
-        let message = rasn::ber::encode(&message).unwrap();
-        let message = Self::send_and_recv(&self.socket, message).unwrap();
-        let message = rasn::ber::decode(&message).unwrap();
+        let trap = v1::trap(
+            &enterprise.into_oid()?,
+            agent_addr,
+            generic_trap,
+            specific_trap,
+            self.uptime(),
+            &oid::into_bindings(bindings)?,
+        )?;
+        let message = self.security.encode_v1_trap(trap)?;
+
+        self.transmit(&message, None)?;
 
-        let vars = Self::parse_response(message).unwrap();
-        for var in vars {
-            println!("{} = {}", var.name, Self::parse_value(var.value));
+        Ok(())
+    }
+
+    /// Sends `notification` as it was received, e.g. by a [`TrapListener`], with its uptime
+    /// and, for SNMPv1, its agent fields: SNMPv1 notifications only on SNMPv1 sessions,
+    /// v2 ones on the others.
+    pub fn send_notification(&self, notification: &Notification) -> SnmpResult<()> {
+        match notification {
+            Notification::V1 {
+                enterprise,
+                agent_addr,
+                generic_trap,
+                specific_trap,
+                timestamp,
+                bindings,
+            } => {
+                let trap = v1::trap(
+                    enterprise,
+                    *agent_addr,
+                    *generic_trap,
+                    *specific_trap,
+                    *timestamp,
+                    bindings,
+                )?;
+                let message = self.security.encode_v1_trap(trap)?;
+
+                self.transmit(&message, None)?;
+            }
+            Notification::V2 {
+                uptime,
+                trap_oid,
+                bindings,
+            } => {
+                let mut data = pdu::trap(*uptime, trap_oid, bindings);
+                pdu::set_request_id(&mut data, self.next_request_id());
+                let request = self.hooks.request(&data);
+
+                let mut message = self.buffers.get();
+                self.security
+                    .encode(data, &self.context, self.max_message_size, &mut message)?;
+
+                self.transmit(&message, request.as_ref())?;
+            }
         }
+
+        Ok(())
     }
 
-    pub fn walk(&self, oid: &String) -> SnmpResult<BTreeMap<Vec<u32>, v2::VarBindValue>> {
-        let start = Self::parse_oid(oid);
+    /// Sends an InformRequest, retransmitting until the receiver acknowledges it.
+    pub fn send_inform<O: IntoOid + Clone>(
+        &self,
+        trap_oid: impl IntoOid,
+        bindings: &[(O, Value)],
+    ) -> SnmpResult<()> {
+        let bindings = oid::into_bindings(bindings)?;
+        let data = pdu::inform(self.uptime(), &trap_oid.into_oid()?, &bindings);
 
-        let mut current = oid.clone();
+        pdu::parse_response(self.request(data, &RequestOptions::default())?).map(drop)
+    }
+
+    /// Sends a PDU the rest of the API does not cover, e.g. of an experimental type, and
+    /// returns the PDU the agent answered with as it is, unchecked, with the session's
+    /// security, retransmission, fail-over and request-id management. The request-id of
+    /// `data` is replaced; SNMPv1 sessions only take the PDUs SNMPv1 has.
+    pub fn send_pdu(&self, data: v2::Pdus) -> SnmpResult<v2::Pdus> {
+        self.send_pdu_with(data, &RequestOptions::default())
+    }
+
+    pub fn send_pdu_with(&self, data: v2::Pdus, opts: &RequestOptions) -> SnmpResult<v2::Pdus> {
+        self.request(data, opts)
+    }
+
+    /// Sends an encoded message as it is, retransmitting it like a request, and returns
+    /// the first datagram the agent sends back, whatever it is.
+    pub fn send_raw(&self, message: &[u8]) -> SnmpResult<Vec<u8>> {
+        self.send_and_recv(message, None, &RequestOptions::default(), |response| {
+            Ok(Some(response.to_vec()))
+        })
+    }
+
+    pub fn walk(&self, oid: impl IntoOid) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.walk_with(oid, &RequestOptions::default())
+    }
+
+    /// [`SyncSession::walk`] with every GETNEXT of the walk sent with `opts`.
+    pub fn walk_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.walk_partial(oid, opts)
+            .map_err(|partial| partial.error)
+    }
+
+    /// [`SyncSession::walk_with`], returning what was collected along with the error when
+    /// the walk fails part of the way.
+    pub fn walk_partial(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        let start = oid.into_oid()?;
+
+        let mut current = start.clone();
         let mut result = BTreeMap::new();
 
         loop {
-            match self.getnext(&current) {
-                Ok(vars) => {
-                    let var = vars[0].clone();
+            if opts.cancelled() {
+                return Ok(result);
+            }
+            let step = self
+                .getnext_with(&current, opts)
+                .and_then(|vars| pdu::collect_subtree(&start, &current, vars, &mut result));
+
+            match step {
+                Ok(Some(next)) => {
+                    opts.report(result.len(), &next);
+                    current = next;
+                }
+                Ok(None) => return Ok(result),
+                Err(error) => return Err(pdu::partial_walk(&start, current, result, error)),
+            }
+        }
+    }
 
-                    if var.name.starts_with(&start) {
-                        let (_, right) = var.name.split_at(start.len());
+    /// Continues the walk of `cursor` with at most `max_requests` GETBULKs, or GETNEXTs in
+    /// SNMPv1, and returns the instances they collected, keyed by their suffix under the
+    /// walked OID. The cursor is left where the walk stopped, also when it failed.
+    pub fn walk_resume(
+        &self,
+        cursor: &mut WalkCursor,
+        max_requests: usize,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        let mut max_repetitions = opts.max_repetitions_or_default();
+        let mut result = BTreeMap::new();
+
+        for _ in 0..max_requests {
+            let Some(current) = cursor.current() else {
+                break;
+            };
+            if opts.cancelled() {
+                break;
+            }
+
+            let vars = if self.security.is_v1() {
+                self.getnext_with(&current, opts)
+            } else {
+                self.bulk(slice::from_ref(&current), 0, &mut max_repetitions, opts)
+            };
+            let step = vars
+                .and_then(|vars| pdu::collect_subtree(cursor.start(), &current, vars, &mut result));
 
-                        result.insert(right.to_vec(), var.value);
+            match step {
+                Ok(next) => {
+                    if let Some(next) = &next {
+                        opts.report(result.len(), next);
+                    }
+                    cursor.advance(next);
+                }
+                Err(error) => {
+                    return Err(PartialWalk {
+                        collected: result,
+                        last: cursor.after().cloned(),
+                        error,
+                    })
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Walks a subtree lazily, one GETNEXT per step as the iterator is advanced.
+    pub fn walk_iter(&self, oid: impl IntoOid) -> Walk<'_, T> {
+        Walk::new(self, oid.into_oid())
+    }
+
+    /// Iterates over the instances after `oid` with one GETNEXT per step, like
+    /// [`SyncSession::walk_iter`] but not confined to a subtree: it only ends at the end of
+    /// the agent's MIB view, so stop it once past what is of interest.
+    pub fn next_iter(&self, oid: impl IntoOid) -> Walk<'_, T> {
+        Walk::after(self, oid.into_oid())
+    }
+
+    /// Fetches the system group in one GET.
+    pub fn system_info(&self) -> SnmpResult<SystemInfo> {
+        let oids = SYSTEM_OIDS.map(Oid::from);
+        let vars = self.fetch(&oids, &RequestOptions::default())?;
+
+        Ok(SystemInfo::from_bindings(vars))
+    }
+
+    /// Walks ifTable and ifXTable and joins their rows on ifIndex.
+    pub fn interfaces(&self) -> SnmpResult<Vec<Interface>> {
+        let if_table = self.get_table(IF_TABLE, &IF_COLUMNS)?;
+        let if_x_table = self.get_table(IF_X_TABLE, &IF_X_COLUMNS)?;
+
+        Ok(interfaces::join(if_table, if_x_table))
+    }
+
+    /// Reads the MAC forwarding table of a switch from dot1qTpFdbTable, or from
+    /// dot1dTpFdbTable for VLAN-unaware bridges, with bridge ports mapped to ifIndex.
+    pub fn fdb_table(&self) -> SnmpResult<Vec<FdbEntry>> {
+        let base_ports = self.get_table(BASE_PORT_TABLE, &BASE_PORT_COLUMNS)?;
+
+        let fdb = self.get_table(Q_TP_FDB_TABLE, &FDB_COLUMNS)?;
+        if !fdb.is_empty() {
+            return Ok(bridge::entries(fdb, true, &base_ports));
+        }
+
+        let fdb = self.get_table(TP_FDB_TABLE, &FDB_COLUMNS)?;
+        Ok(bridge::entries(fdb, false, &base_ports))
+    }
+
+    /// Reads the ARP and IPv6 neighbor caches from ipNetToPhysicalTable, or the ARP cache
+    /// from ipNetToMediaTable on agents without it.
+    pub fn neighbor_table(&self) -> SnmpResult<Vec<Neighbor>> {
+        let table = self.get_table(NET_TO_PHYSICAL_TABLE, &NET_TO_PHYSICAL_COLUMNS)?;
+        if !table.is_empty() {
+            return Ok(neighbors::entries(table, false));
+        }
+
+        let table = self.get_table(NET_TO_MEDIA_TABLE, &NET_TO_MEDIA_COLUMNS)?;
+        Ok(neighbors::entries(table, true))
+    }
+
+    /// Fetches the given `columns` of a table such as ifTable, row by row; an empty slice
+    /// fetches every column.
+    pub fn get_table(&self, table: impl IntoOid, columns: &[u32]) -> SnmpResult<Table> {
+        self.get_table_with(table, columns, &RequestOptions::default())
+    }
+
+    /// [`SyncSession::get_table`] with every GETNEXT sent with `opts`.
+    pub fn get_table_with(
+        &self,
+        table: impl IntoOid,
+        columns: &[u32],
+        opts: &RequestOptions,
+    ) -> SnmpResult<Table> {
+        let mut walk = TableWalk::new(table.into_oid()?, columns);
+
+        while let Some(oids) = walk.request() {
+            if opts.cancelled() {
+                break;
+            }
+            walk.response(self.getnext_many_with(&oids, opts)?)?;
+            if let Some((rows, last)) = walk.progress() {
+                opts.report(rows, last);
+            }
+        }
+
+        Ok(walk.finish())
+    }
+
+    /// Fetches a table like [`SyncSession::get_table`], but with a GETBULK of
+    /// `max_repetitions` rows of every column at a time, fewer once the agent answers
+    /// tooBig. SNMPv1 sessions fall back to [`SyncSession::get_table`].
+    pub fn get_table_bulk(
+        &self,
+        table: impl IntoOid,
+        columns: &[u32],
+        mut max_repetitions: u32,
+    ) -> SnmpResult<Table> {
+        if self.security.is_v1() {
+            return self.get_table(table, columns);
+        }
+
+        let mut walk = TableWalk::new(table.into_oid()?, columns);
+        let opts = RequestOptions::default();
+
+        while let Some(oids) = walk.request() {
+            let vars = self.bulk(&oids, 0, &mut max_repetitions, &opts)?;
+            walk.bulk_response(vars)?;
+        }
+
+        Ok(walk.finish())
+    }
+
+    /// Walks a subtree with GETBULK, `max_repetitions` rows at a time, fewer once the agent
+    /// answers tooBig. SNMPv1 sessions fall back to [`SyncSession::walk`].
+    pub fn bulk_walk(
+        &self,
+        oid: impl IntoOid,
+        max_repetitions: u32,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        let opts = RequestOptions::new().max_repetitions(max_repetitions);
+
+        self.bulk_walk_with(oid, &opts)
+    }
+
+    /// [`SyncSession::bulk_walk`] with max-repetitions taken from `opts`.
+    pub fn bulk_walk_with(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>> {
+        self.bulk_walk_partial(oid, opts)
+            .map_err(|partial| partial.error)
+    }
+
+    /// [`SyncSession::bulk_walk_with`], returning what was collected along with the error
+    /// when the walk fails part of the way.
+    pub fn bulk_walk_partial(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+    ) -> Result<BTreeMap<Vec<u32>, Value>, PartialWalk> {
+        if self.security.is_v1() {
+            return self.walk_partial(oid, opts);
+        }
+
+        let start = oid.into_oid()?;
+        let mut max_repetitions = opts.max_repetitions_or_default();
+
+        let mut current = start.clone();
+        let mut result = BTreeMap::new();
+
+        loop {
+            if opts.cancelled() {
+                return Ok(result);
+            }
+            let step = self
+                .bulk(slice::from_ref(&current), 0, &mut max_repetitions, opts)
+                .and_then(|vars| pdu::collect_subtree(&start, &current, vars, &mut result));
+
+            match step {
+                Ok(Some(next)) => {
+                    opts.report(result.len(), &next);
+                    current = next;
+                }
+                Ok(None) => return Ok(result),
+                Err(error) => return Err(pdu::partial_walk(&start, current, result, error)),
+            }
+        }
+    }
+
+    /// Walks a subtree like [`SyncSession::bulk_walk`], keeping only the instances `keep`
+    /// returns true for, given their suffix under `oid` and value. Instances are checked
+    /// as they arrive, so those left out are never collected.
+    pub fn walk_filtered<F>(
+        &self,
+        oid: impl IntoOid,
+        mut keep: F,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, Value>>
+    where
+        F: FnMut(&[u32], &Value) -> bool,
+    {
+        self.walk_filter_map(oid, |suffix, value| keep(suffix, &value).then_some(value))
+    }
+
+    /// Walks a subtree like [`SyncSession::bulk_walk`], collecting what `f` maps each
+    /// instance to, given its suffix under `oid` and value, and skipping those it maps to
+    /// `None`, e.g. the ifIndex of every interface that is up from ifOperStatus.
+    pub fn walk_filter_map<U, F>(
+        &self,
+        oid: impl IntoOid,
+        mut f: F,
+    ) -> SnmpResult<BTreeMap<Vec<u32>, U>>
+    where
+        F: FnMut(&[u32], Value) -> Option<U>,
+    {
+        let start = oid.into_oid()?;
+        let mut result = BTreeMap::new();
+
+        self.bulk_walk_visit(&start, |name, value| {
+            let suffix = &name[start.len()..];
+            if let Some(mapped) = f(suffix, value.into()) {
+                result.insert(suffix.to_vec(), mapped);
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        Ok(result)
+    }
+
+    /// Walks a subtree like [`SyncSession::bulk_walk`], handing each varbind to `visit`
+    /// instead of collecting them, until the subtree ends or `visit` breaks.
+    ///
+    /// SNMPv2c responses are decoded in the receive buffer, so names and values borrow
+    /// from it and walking allocates nothing per varbind. SNMPv1 and SNMPv3 responses are
+    /// decoded as usual and then visited.
+    pub fn bulk_walk_visit<F>(&self, oid: impl IntoOid, visit: F) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        self.bulk_walk_visit_with(oid, &RequestOptions::default(), visit)
+    }
+
+    pub fn bulk_walk_visit_with<F>(
+        &self,
+        oid: impl IntoOid,
+        opts: &RequestOptions,
+        mut visit: F,
+    ) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        let mut walk = VisitWalk::new(oid.into_oid()?, opts.max_repetitions_or_default());
+
+        while let Some(current) = walk.current() {
+            if self.security.is_v1() {
+                let vars = self.getnext_with(&current, opts)?;
+                walk.accept_owned(&current, vars, &mut visit)?;
+            } else if let Some(community) = self.security.v2c_community() {
+                self.visit_bulk(&mut walk, community, &current, opts, &mut visit)?;
+            } else {
+                let oids = slice::from_ref(&current);
+                let vars = self.bulk(oids, 0, &mut walk.max_repetitions, opts)?;
+                walk.accept_owned(&current, vars, &mut visit)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// One GETBULK of a [`SyncSession::bulk_walk_visit`] over SNMPv2c, sent like any other
+    /// request, fail-over and community fallback included.
+    fn visit_bulk<F>(
+        &self,
+        walk: &mut VisitWalk,
+        community: &[u8],
+        current: &Oid,
+        opts: &RequestOptions,
+        visit: &mut F,
+    ) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        loop {
+            let data = walk.request(current);
+            let response =
+                self.request_with(data, opts, |response, message, request_id| {
+                    match walk.accept(response, community, request_id, visit) {
+                        Some(visited) => visited.map(|()| Some(None)),
+                        None => self
+                            .security
+                            .decode(response, message, request_id, self.decoding)
+                            .map(|data| data.map(Some)),
+                    }
+                })?;
 
-                        current = var.name.to_string();
-                    } else {
-                        return Ok(result);
-                    };
+            // Anything the in-place decoding does not cover, tooBig included.
+            let Some(data) = response else {
+                return Ok(());
+            };
+            let oids = slice::from_ref(current);
+            match pdu::parse_bulk_response(data, oids, 0, walk.max_repetitions) {
+                Err(err) if err.is_too_big() && walk.max_repetitions > 1 => {
+                    walk.max_repetitions /= 2;
+                    trace::event!(
+                        debug,
+                        max_repetitions = walk.max_repetitions,
+                        "reducing max-repetitions after tooBig"
+                    );
                 }
-                Err(_) => {}
+                result => return walk.accept_owned(current, result?, visit),
             }
         }
     }
@@ -0,0 +1,139 @@
+//! Pacing requests, so low-end agents are not sent more than they can answer.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a request waiting for another to finish checks again.
+const OUTSTANDING_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Debug)]
+struct State {
+    next: Instant,
+    outstanding: usize,
+}
+
+/// Limits how fast, and how many at once, the sessions it is given to with
+/// [`SessionBuilder::rate_limit`](crate::SessionBuilder::rate_limit) send requests,
+/// retransmissions and SNMPv3 discovery included.
+///
+/// Clones share their limit: give each target's session a limiter of its own for a limit
+/// per target, or clones of one limiter to every session for a global one. For both, make
+/// the per-target limiters [`within`](RateLimiter::within) the global one.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    max_outstanding: Option<usize>,
+    state: Arc<Mutex<State>>,
+    parent: Option<Box<RateLimiter>>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        RateLimiter::new()
+    }
+}
+
+impl RateLimiter {
+    /// A limiter that does not limit anything until configured.
+    pub fn new() -> Self {
+        RateLimiter {
+            interval: Duration::ZERO,
+            max_outstanding: None,
+            state: Arc::new(Mutex::new(State {
+                next: Instant::now(),
+                outstanding: 0,
+            })),
+            parent: None,
+        }
+    }
+
+    /// Spaces requests at least `1 / per_second` apart.
+    pub fn per_second(mut self, per_second: u32) -> Self {
+        self.interval = Duration::from_secs(1) / per_second.max(1);
+        self
+    }
+
+    /// Sends no request while `max` others are waiting for their response.
+    pub fn max_outstanding(mut self, max: usize) -> Self {
+        self.max_outstanding = Some(max.max(1));
+        self
+    }
+
+    /// Also keeps to the limits of `parent`, typically shared by every target.
+    pub fn within(mut self, parent: &RateLimiter) -> Self {
+        self.parent = Some(Box::new(parent.clone()));
+        self
+    }
+
+    /// A permit to send now from this limiter and every one it is within, or how long to
+    /// wait before asking again. Limiters are always locked child first, so no two
+    /// requests can each hold a lock the other waits for.
+    fn try_acquire(&self) -> Result<Permit, Duration> {
+        let chain: Vec<&RateLimiter> =
+            std::iter::successors(Some(self), |limiter| limiter.parent.as_deref()).collect();
+        let mut states: Vec<MutexGuard<'_, State>> = chain
+            .iter()
+            .map(|limiter| limiter.state.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+
+        let now = Instant::now();
+        let mut wait = Duration::ZERO;
+        for (limiter, state) in chain.iter().zip(&states) {
+            if limiter
+                .max_outstanding
+                .is_some_and(|max| state.outstanding >= max)
+            {
+                wait = wait.max(OUTSTANDING_POLL);
+            }
+            wait = wait.max(state.next.saturating_duration_since(now));
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        for (limiter, state) in chain.iter().zip(&mut states) {
+            state.next = now + limiter.interval;
+            state.outstanding += 1;
+        }
+
+        Ok(Permit {
+            states: chain.iter().map(|limiter| limiter.state.clone()).collect(),
+        })
+    }
+
+    pub(crate) fn acquire(&self) -> Permit {
+        loop {
+            match self.try_acquire() {
+                Ok(permit) => return permit,
+                Err(wait) => thread::sleep(wait),
+            }
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn acquire_async(&self) -> Permit {
+        loop {
+            match self.try_acquire() {
+                Ok(permit) => return permit,
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+}
+
+/// A request in flight, counted against the limit until dropped.
+pub(crate) struct Permit {
+    states: Vec<Arc<Mutex<State>>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        for state in &self.states {
+            state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .outstanding -= 1;
+        }
+    }
+}
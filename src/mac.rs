@@ -0,0 +1,113 @@
+//! MAC addresses, as ifPhysAddress, forwarding tables, neighbor caches and LLDP hold them.
+
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use crate::{SnmpError, SnmpResult};
+
+/// Columns of six-octet MAC addresses, which [`Formatter`](crate::Formatter) shows as such
+/// without their MIB loaded: ifPhysAddress, dot1dTpFdbAddress, ipNetToPhysicalPhysAddress,
+/// ipNetToMediaPhysAddress and lldpRemChassisId.
+pub(crate) const MAC_COLUMNS: [&[u32]; 5] = [
+    &[1, 3, 6, 1, 2, 1, 2, 2, 1, 6],
+    &[1, 3, 6, 1, 2, 1, 17, 4, 3, 1, 1],
+    &[1, 3, 6, 1, 2, 1, 4, 35, 1, 4],
+    &[1, 3, 6, 1, 2, 1, 4, 22, 1, 2],
+    &[1, 0, 8802, 1, 1, 2, 1, 4, 1, 1, 5],
+];
+
+/// A 48-bit MAC address, displayed as `00:1a:2b:3c:4d:5e`. Parses that form, the
+/// `00-1A-2B-3C-4D-5E` of Windows, Cisco's `001a.2b3c.4d5e` and bare hex digits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    pub fn octets(self) -> [u8; 6] {
+        self.0
+    }
+
+    /// `00-1A-2B-3C-4D-5E`.
+    pub fn dashed(self) -> String {
+        let [a, b, c, d, e, f] = self.0;
+
+        format!("{a:02X}-{b:02X}-{c:02X}-{d:02X}-{e:02X}-{f:02X}")
+    }
+
+    /// `001a.2b3c.4d5e`.
+    pub fn dotted(self) -> String {
+        let [a, b, c, d, e, f] = self.0;
+
+        format!("{a:02x}{b:02x}.{c:02x}{d:02x}.{e:02x}{f:02x}")
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        MacAddr(octets)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(mac: MacAddr) -> Self {
+        mac.0
+    }
+}
+
+impl Deref for MacAddr {
+    type Target = [u8; 6];
+
+    fn deref(&self) -> &[u8; 6] {
+        &self.0
+    }
+}
+
+impl PartialEq<[u8; 6]> for MacAddr {
+    fn eq(&self, other: &[u8; 6]) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+
+        write!(f, "{a:02x}:{b:02x}:{c:02x}:{d:02x}:{e:02x}:{g:02x}")
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = SnmpError;
+
+    fn from_str(text: &str) -> SnmpResult<Self> {
+        let invalid = || SnmpError::InvalidMacAddr(text.to_string());
+        let groups: Vec<&str> = text.split([':', '-', '.']).collect();
+
+        let digits = match groups.len() {
+            1 => text.to_string(),
+            3 if groups.iter().all(|group| group.len() == 4) && !text.contains([':', '-']) => {
+                groups.concat()
+            }
+            // Octets may drop their leading zero, as in `0:1a:2b:3c:4d:5e`.
+            6 if groups.iter().all(|group| (1..=2).contains(&group.len()))
+                && !(text.contains(':') && text.contains('-'))
+                && !text.contains('.') =>
+            {
+                groups.iter().map(|group| format!("{group:0>2}")).collect()
+            }
+            _ => return Err(invalid()),
+        };
+        if digits.len() != 12 || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let mut octets = [0; 6];
+        for (octet, pair) in octets.iter_mut().zip(digits.as_bytes().chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            *octet = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+        }
+
+        Ok(MacAddr(octets))
+    }
+}
@@ -0,0 +1,838 @@
+//! Loading SMI MIB modules to translate between names such as `IF-MIB::ifDescr.3` and
+//! numeric OIDs.
+//!
+//! The parser only understands as much of ASN.1 as it takes to find the OID of every
+//! definition: the module header, IMPORTS, and the `::= { parent arc }` value of each
+//! object, identity, notification, group and compliance. Of types it keeps the SYNTAX of
+//! objects and textual conventions, with their enumerations and DISPLAY-HINTs, for
+//! [`Formatter`](crate::Formatter). MACRO bodies are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::Oid;
+
+/// Macros whose invocations assign an OID to a lowercase name.
+const MACROS: &[&str] = &[
+    "OBJECT-TYPE",
+    "OBJECT-IDENTITY",
+    "MODULE-IDENTITY",
+    "NOTIFICATION-TYPE",
+    "TRAP-TYPE",
+    "OBJECT-GROUP",
+    "NOTIFICATION-GROUP",
+    "MODULE-COMPLIANCE",
+    "AGENT-CAPABILITIES",
+];
+
+/// The nodes SNMPv2-SMI defines, so modules resolve without it being loaded.
+const SMI: &[(&str, &[u32])] = &[
+    ("zeroDotZero", &[0, 0]),
+    ("ccitt", &[0]),
+    ("iso", &[1]),
+    ("joint-iso-ccitt", &[2]),
+    ("org", &[1, 3]),
+    ("dod", &[1, 3, 6]),
+    ("internet", &[1, 3, 6, 1]),
+    ("directory", &[1, 3, 6, 1, 1]),
+    ("mgmt", &[1, 3, 6, 1, 2]),
+    ("mib-2", &[1, 3, 6, 1, 2, 1]),
+    ("transmission", &[1, 3, 6, 1, 2, 1, 10]),
+    ("experimental", &[1, 3, 6, 1, 3]),
+    ("private", &[1, 3, 6, 1, 4]),
+    ("enterprises", &[1, 3, 6, 1, 4, 1]),
+    ("security", &[1, 3, 6, 1, 5]),
+    ("snmpV2", &[1, 3, 6, 1, 6]),
+    ("snmpDomains", &[1, 3, 6, 1, 6, 1]),
+    ("snmpProxys", &[1, 3, 6, 1, 6, 2]),
+    ("snmpModules", &[1, 3, 6, 1, 6, 3]),
+];
+
+const SMI_MODULE: &str = "SNMPv2-SMI";
+
+type NamedNumbers = &'static [(i64, &'static str)];
+
+/// The most used textual conventions of SNMPv2-TC (RFC 2579): name, hint, type and named
+/// numbers.
+const TC: &[(&str, Option<&str>, &str, NamedNumbers)] = &[
+    ("DisplayString", Some("255a"), "OCTET STRING", &[]),
+    ("PhysAddress", Some("1x:"), "OCTET STRING", &[]),
+    ("MacAddress", Some("1x:"), "OCTET STRING", &[]),
+    (
+        "DateAndTime",
+        Some("2d-1d-1d,1d:1d:1d.1d,1a1d:1d"),
+        "OCTET STRING",
+        &[],
+    ),
+    ("TruthValue", None, "INTEGER", &[(1, "true"), (2, "false")]),
+    (
+        "RowStatus",
+        None,
+        "INTEGER",
+        &[
+            (1, "active"),
+            (2, "notInService"),
+            (3, "notReady"),
+            (4, "createAndGo"),
+            (5, "createAndWait"),
+            (6, "destroy"),
+        ],
+    ),
+    (
+        "StorageType",
+        None,
+        "INTEGER",
+        &[
+            (1, "other"),
+            (2, "volatile"),
+            (3, "nonVolatile"),
+            (4, "permanent"),
+            (5, "readOnly"),
+        ],
+    ),
+];
+
+const TC_MODULE: &str = "SNMPv2-TC";
+
+/// A named node of the OID tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MibNode {
+    pub name: String,
+    pub module: String,
+    pub oid: Oid,
+}
+
+/// A definition whose OID is known relative to a parent that may not be resolved yet.
+#[derive(Debug, Clone)]
+struct Definition {
+    module: String,
+    name: String,
+    /// `None` for OIDs spelled out from the root, like `{ iso(1) org(3) }`.
+    parent: Option<String>,
+    /// Arcs below the parent, named when written as `name(number)`.
+    arcs: Vec<(Option<String>, u32)>,
+    /// The SYNTAX of an OBJECT-TYPE.
+    syntax: Option<Syntax>,
+}
+
+/// A SYNTAX clause: the type, e.g. `INTEGER`, `OCTET STRING` or a textual convention, and
+/// the named numbers of an enumeration or BITS.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Syntax {
+    pub(crate) name: String,
+    pub(crate) names: Vec<(i64, String)>,
+}
+
+/// A type assignment, usually a TEXTUAL-CONVENTION.
+#[derive(Debug, Clone)]
+struct Convention {
+    hint: Option<String>,
+    syntax: Syntax,
+}
+
+/// How to render the values of an object, gathered from its SYNTAX and the textual
+/// conventions it refers to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Rendering<'a> {
+    pub(crate) hint: Option<&'a str>,
+    pub(crate) names: &'a [(i64, String)],
+    pub(crate) bits: bool,
+}
+
+#[derive(Debug)]
+struct Module {
+    name: String,
+    /// Imported symbol to the module it comes from.
+    imports: HashMap<String, String>,
+    definitions: Vec<Definition>,
+    conventions: Vec<(String, Convention)>,
+}
+
+/// An OID tree built from MIB modules. Definitions are resolved as soon as the modules they
+/// depend on are loaded, so files can be loaded in any order.
+#[derive(Debug, Clone)]
+pub struct Mib {
+    nodes: BTreeMap<Vec<u32>, MibNode>,
+    /// Every module defining a name, in load order.
+    names: HashMap<String, Vec<(String, Vec<u32>)>>,
+    imports: HashMap<String, HashMap<String, String>>,
+    pending: Vec<Definition>,
+    /// The module and SYNTAX of every OBJECT-TYPE.
+    syntaxes: HashMap<Vec<u32>, (String, Syntax)>,
+    /// Every module defining a type, in load order.
+    conventions: HashMap<String, Vec<(String, Convention)>>,
+}
+
+impl Default for Mib {
+    fn default() -> Self {
+        let mut mib = Mib {
+            nodes: BTreeMap::new(),
+            names: HashMap::new(),
+            imports: HashMap::new(),
+            pending: Vec::new(),
+            syntaxes: HashMap::new(),
+            conventions: HashMap::new(),
+        };
+
+        for (name, arcs) in SMI {
+            mib.insert(SMI_MODULE, name, arcs.to_vec());
+        }
+        for (name, hint, syntax, names) in TC {
+            let convention = Convention {
+                hint: hint.map(str::to_string),
+                syntax: Syntax {
+                    name: syntax.to_string(),
+                    names: names
+                        .iter()
+                        .map(|(number, name)| (*number, name.to_string()))
+                        .collect(),
+                },
+            };
+            mib.conventions
+                .insert(name.to_string(), vec![(TC_MODULE.to_string(), convention)]);
+        }
+
+        mib
+    }
+}
+
+impl Mib {
+    /// A tree with just the SNMPv2-SMI nodes such as `enterprises`.
+    pub fn new() -> Self {
+        Mib::default()
+    }
+
+    /// Loads every file in `dir` that contains a module, the way net-snmp's mibs directory
+    /// is laid out; hidden files and files without `DEFINITIONS` are skipped.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.sort();
+
+        for path in paths {
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+            if path.is_file() && !hidden {
+                let text = String::from_utf8_lossy(&fs::read(&path)?).into_owned();
+
+                if text.contains("DEFINITIONS") {
+                    self.load(&text, &path.display().to_string())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+
+        self.load(&text, &path.display().to_string())
+    }
+
+    /// Loads the modules in `text`, the contents of a MIB file.
+    pub fn load_str(&mut self, text: &str) -> io::Result<()> {
+        self.load(text, "MIB")
+    }
+
+    fn load(&mut self, text: &str, source: &str) -> io::Result<()> {
+        let modules = Parser::new(text)
+            .and_then(|mut parser| parser.modules())
+            .map_err(|(line, message)| {
+                let message = format!("{}:{}: {}", source, line, message);
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            })?;
+
+        for module in modules {
+            for (name, convention) in module.conventions {
+                let modules = self.conventions.entry(name).or_default();
+                modules.retain(|(defined, _)| *defined != module.name);
+                modules.push((module.name.clone(), convention));
+            }
+
+            self.imports.insert(module.name.clone(), module.imports);
+            self.pending.extend(module.definitions);
+        }
+        self.resolve();
+
+        Ok(())
+    }
+
+    fn insert(&mut self, module: &str, name: &str, arcs: Vec<u32>) {
+        self.nodes.entry(arcs.clone()).or_insert_with(|| MibNode {
+            name: name.to_string(),
+            module: module.to_string(),
+            oid: arcs.clone().into(),
+        });
+
+        let modules = self.names.entry(name.to_string()).or_default();
+        if !modules.iter().any(|(defined, _)| defined == module) {
+            modules.push((module.to_string(), arcs));
+        }
+    }
+
+    /// Picks the definition of `name` that `module` refers to: its own first, then the one
+    /// it imports, then whichever module defines the name, since MIBs often forget imports.
+    fn scope<'m, T>(
+        &self,
+        defined: &'m [(String, T)],
+        module: &str,
+        name: &str,
+    ) -> Option<&'m (String, T)> {
+        let imported = self
+            .imports
+            .get(module)
+            .and_then(|imports| imports.get(name));
+
+        defined
+            .iter()
+            .find(|(defined, _)| defined == module)
+            .or_else(|| {
+                defined
+                    .iter()
+                    .find(|(defined, _)| Some(defined) == imported)
+            })
+            .or_else(|| defined.first())
+    }
+
+    /// Resolves pending definitions until no more parents become known.
+    fn resolve(&mut self) {
+        loop {
+            let pending = std::mem::take(&mut self.pending);
+            let before = pending.len();
+
+            for definition in pending {
+                let parent = match &definition.parent {
+                    Some(parent) => match self
+                        .names
+                        .get(parent)
+                        .and_then(|defined| self.scope(defined, &definition.module, parent))
+                    {
+                        Some((_, arcs)) => arcs.clone(),
+                        None => {
+                            self.pending.push(definition);
+                            continue;
+                        }
+                    },
+                    None => Vec::new(),
+                };
+
+                let mut arcs = parent;
+                for (label, arc) in &definition.arcs {
+                    arcs.push(*arc);
+                    if let Some(label) = label {
+                        self.insert(&definition.module, label, arcs.clone());
+                    }
+                }
+                if let Some(syntax) = definition.syntax {
+                    self.syntaxes
+                        .entry(arcs.clone())
+                        .or_insert((definition.module.clone(), syntax));
+                }
+                self.insert(&definition.module, &definition.name, arcs);
+            }
+
+            if self.pending.len() == before {
+                return;
+            }
+        }
+    }
+
+    /// Definitions whose parents are not defined by any loaded module, as `MODULE::name`.
+    pub fn unresolved(&self) -> Vec<String> {
+        self.pending
+            .iter()
+            .map(|definition| format!("{}::{}", definition.module, definition.name))
+            .collect()
+    }
+
+    /// Translates `IF-MIB::ifDescr.3`, `ifDescr.3` or `ifDescr` to a numeric OID; dotted
+    /// decimal passes through. Without a module, the first module loaded with the name wins.
+    pub fn lookup(&self, name: &str) -> Option<Oid> {
+        let (module, name) = match name.split_once("::") {
+            Some((module, name)) => (Some(module), name),
+            None => (None, name),
+        };
+
+        if name.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return name.parse().ok();
+        }
+
+        let (label, suffix) = name.split_once('.').unwrap_or((name, ""));
+        let defined = self.names.get(label)?;
+        let (_, arcs) = match module {
+            Some(module) => defined.iter().find(|(defined, _)| defined == module)?,
+            None => defined.first()?,
+        };
+
+        let mut arcs = arcs.clone();
+        if !suffix.is_empty() {
+            for arc in suffix.split('.') {
+                arcs.push(arc.parse().ok()?);
+            }
+        }
+
+        Some(arcs.into())
+    }
+
+    /// The node `oid` is or lies under, i.e. the one with the longest matching prefix.
+    pub fn node(&self, oid: &Oid) -> Option<&MibNode> {
+        (1..=oid.len())
+            .rev()
+            .find_map(|len| self.nodes.get(&oid[..len]))
+    }
+
+    /// How values of the object `oid` is or lies under are rendered, following its SYNTAX
+    /// through textual conventions until a DISPLAY-HINT and enumeration are found.
+    pub(crate) fn rendering(&self, oid: &Oid) -> Option<Rendering<'_>> {
+        let (mut module, mut syntax) = (1..=oid.len())
+            .rev()
+            .find_map(|len| self.syntaxes.get(&oid[..len]))
+            .map(|(module, syntax)| (module, syntax))?;
+
+        let mut rendering = Rendering::default();
+
+        // Conventions may refine one another, but not endlessly.
+        for _ in 0..8 {
+            if rendering.names.is_empty() {
+                rendering.names = &syntax.names;
+            }
+            rendering.bits |= syntax.name == "BITS";
+
+            let Some((defined, convention)) = self
+                .conventions
+                .get(&syntax.name)
+                .and_then(|defined| self.scope(defined, module, &syntax.name))
+            else {
+                break;
+            };
+
+            rendering.hint = rendering.hint.or(convention.hint.as_deref());
+            module = defined;
+            syntax = &convention.syntax;
+        }
+
+        Some(rendering)
+    }
+
+    /// The named bits of the object `oid` is or lies under, if its SYNTAX is BITS.
+    pub(crate) fn bit_names(&self, oid: &Oid) -> Option<&[(i64, String)]> {
+        self.rendering(oid)
+            .filter(|rendering| rendering.bits)
+            .map(|rendering| rendering.names)
+    }
+
+    /// Renders `oid` as `MODULE::name.index`, net-snmp style, using the closest named
+    /// ancestor; `None` when not even its first arc is known.
+    pub fn name_of(&self, oid: &Oid) -> Option<String> {
+        let node = self.node(oid)?;
+        let mut name = format!("{}::{}", node.module, node.name);
+
+        for arc in &oid[node.oid.len()..] {
+            name.push_str(&format!(".{}", arc));
+        }
+
+        Some(name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+type ParseResult<T> = Result<T, (usize, String)>;
+
+/// Splits ASN.1 source into identifiers, numbers, quoted strings and punctuation, dropping
+/// `--` comments, which end at the next `--` or the end of the line.
+fn tokenize(text: &str) -> ParseResult<Vec<Token<'_>>> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let byte = bytes[pos];
+
+        match byte {
+            b'\n' => {
+                line += 1;
+                pos += 1;
+                continue;
+            }
+            _ if byte.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            b'-' if bytes.get(pos + 1) == Some(&b'-') => {
+                pos += 2;
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    if bytes[pos..].starts_with(b"--") {
+                        pos += 2;
+                        break;
+                    }
+                    pos += 1;
+                }
+                continue;
+            }
+            b'"' | b'\'' => {
+                let first_line = line;
+                pos += 1;
+                while pos < bytes.len() && bytes[pos] != byte {
+                    line += usize::from(bytes[pos] == b'\n');
+                    pos += 1;
+                }
+                if pos == bytes.len() {
+                    return Err((first_line, "unterminated string".to_string()));
+                }
+                pos += 1;
+                // The B or H of '0101'B and '1F'H.
+                if byte == b'\'' && pos < bytes.len() && bytes[pos].is_ascii_alphabetic() {
+                    pos += 1;
+                }
+                tokens.push(Token {
+                    text: &text[start..pos],
+                    line: first_line,
+                });
+                continue;
+            }
+            _ if byte.is_ascii_alphanumeric() => {
+                while pos < bytes.len()
+                    && (bytes[pos].is_ascii_alphanumeric()
+                        || bytes[pos] == b'_'
+                        || (bytes[pos] == b'-' && bytes.get(pos + 1) != Some(&b'-')))
+                {
+                    pos += 1;
+                }
+            }
+            b'-' if bytes.get(pos + 1).is_some_and(u8::is_ascii_digit) => {
+                pos += 1;
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            b':' if bytes[pos..].starts_with(b"::=") => pos += 3,
+            b'.' if bytes[pos..].starts_with(b"..") => pos += 2,
+            _ => pos += text[pos..].chars().next().map_or(1, char::len_utf8),
+        }
+
+        tokens.push(Token {
+            text: &text[start..pos],
+            line,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> ParseResult<Self> {
+        Ok(Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.tokens.get(self.pos + offset).map(|token| token.text)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |token| token.line)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        Err((self.line(), message.into()))
+    }
+
+    fn next(&mut self) -> ParseResult<&'a str> {
+        match self.peek(0) {
+            Some(text) => {
+                self.pos += 1;
+                Ok(text)
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> ParseResult<()> {
+        match self.next()? {
+            text if text == expected => Ok(()),
+            text => {
+                self.pos -= 1;
+                self.error(format!("expected {}, found {}", expected, text))
+            }
+        }
+    }
+
+    /// Skips tokens up to and including `end`.
+    fn skip_past(&mut self, end: &str) -> ParseResult<()> {
+        while self.next()? != end {}
+        Ok(())
+    }
+
+    /// Skips a `{ }` or `( )` group, including nested ones, after its opening token.
+    fn skip_group(&mut self, open: &str) -> ParseResult<()> {
+        let close = if open == "{" { "}" } else { ")" };
+        let mut depth = 1;
+
+        while depth > 0 {
+            match self.next()? {
+                text if text == open => depth += 1,
+                text if text == close => depth -= 1,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn modules(&mut self) -> ParseResult<Vec<Module>> {
+        let mut modules = Vec::new();
+
+        while self.peek(0).is_some() {
+            modules.push(self.module()?);
+        }
+
+        if modules.is_empty() {
+            return self.error("no module definition");
+        }
+        Ok(modules)
+    }
+
+    /// `NAME DEFINITIONS ::= BEGIN ... END`.
+    fn module(&mut self) -> ParseResult<Module> {
+        let name = self.next()?;
+        if !is_identifier(name) {
+            return self.error(format!("expected a module name, found {}", name));
+        }
+        self.expect("DEFINITIONS")?;
+        self.skip_past("BEGIN")?;
+
+        let mut module = Module {
+            name: name.to_string(),
+            imports: HashMap::new(),
+            definitions: Vec::new(),
+            conventions: Vec::new(),
+        };
+
+        loop {
+            match self.next()? {
+                "END" => return Ok(module),
+                "IMPORTS" => self.imports(&mut module)?,
+                "EXPORTS" => self.skip_past(";")?,
+                open @ ("{" | "(") => self.skip_group(open)?,
+                "MACRO" => self.skip_past("END")?,
+                name => {
+                    if let Some(convention) = self.convention(name)? {
+                        module.conventions.push((name.to_string(), convention));
+                    } else if let Some(definition) = self.definition(&module.name, name)? {
+                        module.definitions.push(definition);
+                    }
+                }
+            }
+        }
+    }
+
+    /// `a, b FROM MODULE-A c FROM MODULE-B ;`
+    fn imports(&mut self, module: &mut Module) -> ParseResult<()> {
+        let mut symbols = Vec::new();
+
+        loop {
+            match self.next()? {
+                ";" => return Ok(()),
+                "," => {}
+                "FROM" => {
+                    let from = self.next()?;
+                    for symbol in symbols.drain(..) {
+                        module.imports.insert(symbol, from.to_string());
+                    }
+                    // Some modules follow the name with its OID.
+                    if self.peek(0) == Some("{") {
+                        self.pos += 1;
+                        self.skip_group("{")?;
+                    }
+                }
+                symbol => symbols.push(symbol.to_string()),
+            }
+        }
+    }
+
+    /// Parses the type assignment starting with `name`, just consumed, if this is one that
+    /// names a textual convention or refines a simple type. SEQUENCE and CHOICE types are
+    /// left to be skipped.
+    fn convention(&mut self, name: &str) -> ParseResult<Option<Convention>> {
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) || self.peek(0) != Some("::=") {
+            return Ok(None);
+        }
+        self.pos += 1;
+
+        match self.peek(0) {
+            Some("SEQUENCE" | "CHOICE") => Ok(None),
+            Some("TEXTUAL-CONVENTION") => {
+                let mut hint = None;
+                loop {
+                    match self.next()? {
+                        "SYNTAX" => break,
+                        "DISPLAY-HINT" => hint = Some(self.next()?.trim_matches('"').to_string()),
+                        open @ ("{" | "(") => self.skip_group(open)?,
+                        _ => {}
+                    }
+                }
+
+                let syntax = self.syntax()?;
+                Ok(Some(Convention { hint, syntax }))
+            }
+            _ => {
+                let syntax = self.syntax()?;
+                Ok(Some(Convention { hint: None, syntax }))
+            }
+        }
+    }
+
+    /// A type after SYNTAX or `::=`: the tag of SMI application types, the type name, and
+    /// its named numbers or constraint, e.g. `INTEGER { up(1), down(2) }`.
+    fn syntax(&mut self) -> ParseResult<Syntax> {
+        if self.peek(0) == Some("[") {
+            self.skip_past("]")?;
+            if self.peek(0) == Some("IMPLICIT") {
+                self.pos += 1;
+            }
+        }
+
+        let mut name = self.next()?.to_string();
+        if name == "OCTET" || name == "OBJECT" {
+            name = format!("{} {}", name, self.next()?);
+        }
+
+        let mut names = Vec::new();
+        if self.peek(0) == Some("{") {
+            self.pos += 1;
+
+            if matches!(name.as_str(), "INTEGER" | "Integer32" | "BITS") {
+                loop {
+                    match self.next()? {
+                        "}" => break,
+                        "," => {}
+                        label => {
+                            self.expect("(")?;
+                            names.push((self.number()?, label.to_string()));
+                            self.expect(")")?;
+                        }
+                    }
+                }
+            } else {
+                self.skip_group("{")?;
+            }
+        }
+        if self.peek(0) == Some("(") {
+            self.pos += 1;
+            self.skip_group("(")?;
+        }
+
+        Ok(Syntax { name, names })
+    }
+
+    /// Parses the value assignment starting with `name`, just consumed, if this is one:
+    /// a lowercase name followed by `OBJECT IDENTIFIER` or one of the [`MACROS`]. Type
+    /// assignments leave their body to be skipped token by token.
+    fn definition(&mut self, module: &str, name: &'a str) -> ParseResult<Option<Definition>> {
+        let assigns_oid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && match self.peek(0) {
+                Some("OBJECT") => self.peek(1) == Some("IDENTIFIER"),
+                Some(other) => MACROS.contains(&other),
+                None => false,
+            };
+        if !assigns_oid {
+            return Ok(None);
+        }
+
+        let object_type = self.peek(0) == Some("OBJECT-TYPE");
+        let mut enterprise = None;
+        let mut syntax = None;
+        loop {
+            match self.next()? {
+                "::=" => break,
+                "ENTERPRISE" => enterprise = Some(self.next()?),
+                "SYNTAX" if object_type && syntax.is_none() => syntax = Some(self.syntax()?),
+                open @ ("{" | "(") => self.skip_group(open)?,
+                _ => {}
+            }
+        }
+
+        let mut definition = Definition {
+            module: module.to_string(),
+            name: name.to_string(),
+            parent: None,
+            arcs: Vec::new(),
+            syntax,
+        };
+
+        // SNMPv1 traps are numbered under their enterprise (RFC 3584 3.1).
+        if let Some(enterprise) = enterprise {
+            let number = self.number()?;
+            definition.parent = Some(enterprise.to_string());
+            definition.arcs = vec![(None, 0), (None, number)];
+
+            return Ok(Some(definition));
+        }
+
+        self.expect("{")?;
+        loop {
+            let text = self.next()?;
+
+            if text == "}" {
+                break;
+            } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+                self.pos -= 1;
+                definition.arcs.push((None, self.number()?));
+            } else if !is_identifier(text) {
+                return self.error(format!("unexpected {} in OID value", text));
+            } else if self.peek(0) == Some("(") {
+                self.pos += 1;
+                let number = self.number()?;
+                self.expect(")")?;
+                definition.arcs.push((Some(text.to_string()), number));
+            } else if definition.parent.is_none() && definition.arcs.is_empty() {
+                definition.parent = Some(text.to_string());
+            } else {
+                return self.error(format!("unexpected {} in OID value", text));
+            }
+        }
+
+        Ok(Some(definition))
+    }
+
+    fn number<N: FromStr>(&mut self) -> ParseResult<N> {
+        let text = self.next()?;
+
+        match text.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => {
+                self.pos -= 1;
+                self.error(format!("expected a number, found {}", text))
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::net::IpAddr;
+
+use crate::{IndexDecoder, MacAddr, SnmpResult, Table, Value};
+
+/// ipNetToPhysicalTable of IP-MIB (RFC 4293), covering ARP and IPv6 neighbor discovery.
+pub(crate) const NET_TO_PHYSICAL_TABLE: [u32; 8] = [1, 3, 6, 1, 2, 1, 4, 35];
+/// ipNetToPhysicalPhysAddress and ipNetToPhysicalType.
+pub(crate) const NET_TO_PHYSICAL_COLUMNS: [u32; 2] = [4, 6];
+/// ipNetToMediaTable, the IPv4-only table of RFC 1213 that older agents still serve.
+pub(crate) const NET_TO_MEDIA_TABLE: [u32; 8] = [1, 3, 6, 1, 2, 1, 4, 22];
+/// ipNetToMediaPhysAddress and ipNetToMediaType.
+pub(crate) const NET_TO_MEDIA_COLUMNS: [u32; 2] = [2, 4];
+
+/// ipNetToPhysicalType or ipNetToMediaType: how the mapping came about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NeighborKind {
+    Other,
+    Invalid,
+    Dynamic,
+    Static,
+    /// One of the agent's own addresses; only in ipNetToPhysicalTable.
+    Local,
+    Unknown(i64),
+}
+
+impl From<i64> for NeighborKind {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => NeighborKind::Other,
+            2 => NeighborKind::Invalid,
+            3 => NeighborKind::Dynamic,
+            4 => NeighborKind::Static,
+            5 => NeighborKind::Local,
+            other => NeighborKind::Unknown(other),
+        }
+    }
+}
+
+/// An ARP or neighbor cache entry, as returned by
+/// [`SyncSession::neighbor_table`](crate::SyncSession::neighbor_table).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Neighbor {
+    pub if_index: u32,
+    pub ip: IpAddr,
+    pub mac: MacAddr,
+    pub kind: Option<NeighborKind>,
+}
+
+/// The address in an ipNetToPhysicalTable index: an InetAddressType, then the
+/// InetAddress. Zoned addresses drop their four-octet zone index.
+fn inet_address(index: &mut IndexDecoder<'_>) -> SnmpResult<Option<IpAddr>> {
+    let kind = index.integer()?;
+    let address = index.string()?;
+
+    Ok(match (kind, address.len()) {
+        (1, 4) | (3, 8) => <[u8; 4]>::try_from(&address[..4]).ok().map(IpAddr::from),
+        (2, 16) | (4, 20) => <[u8; 16]>::try_from(&address[..16]).ok().map(IpAddr::from),
+        _ => None,
+    })
+}
+
+/// Decodes ipNetToPhysicalTable rows, or ipNetToMediaTable rows when `legacy`. Entries
+/// whose physical address is not a six-octet MAC, e.g. incomplete ones, are left out.
+pub(crate) fn entries(table: Table, legacy: bool) -> Vec<Neighbor> {
+    let [phys_column, kind_column] = if legacy {
+        NET_TO_MEDIA_COLUMNS
+    } else {
+        NET_TO_PHYSICAL_COLUMNS
+    };
+
+    table
+        .into_iter()
+        .filter_map(|(index, row)| {
+            let mut index = IndexDecoder::new(&index);
+            let if_index = index.integer().ok()?;
+            let ip = if legacy {
+                index.ip_address().ok()?.into()
+            } else {
+                inet_address(&mut index).ok()??
+            };
+            index.finish().ok()?;
+            let mac = MacAddr(row.get(&phys_column)?.as_bytes()?.try_into().ok()?);
+            let kind = match row.get(&kind_column) {
+                Some(Value::Integer(kind)) => Some(NeighborKind::from(*kind)),
+                _ => None,
+            };
+
+            Some(Neighbor {
+                if_index,
+                ip,
+                mac,
+                kind,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,243 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use rasn::types::ObjectIdentifier;
+
+use crate::{SnmpError, SnmpResult, Value};
+
+/// An object identifier such as `1.3.6.1.2.1.1.1.0`. Ordering is lexicographic by arc,
+/// which is the order agents walk the MIB in.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Oid(Vec<u32>);
+
+impl Oid {
+    pub fn as_slice(&self) -> &[u32] {
+        &self.0
+    }
+
+    /// Returns `true` if this OID lies inside the subtree rooted at `prefix`.
+    pub fn starts_with(&self, prefix: &Oid) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// The arcs after `prefix`, or `None` if this OID is not inside it.
+    pub fn suffix(&self, prefix: &Oid) -> Option<&[u32]> {
+        self.0.strip_prefix(prefix.as_slice())
+    }
+
+    pub(crate) fn to_asn(&self) -> ObjectIdentifier {
+        ObjectIdentifier::new_unchecked(Cow::Owned(self.0.clone()))
+    }
+
+    pub(crate) fn from_asn(oid: &ObjectIdentifier) -> Self {
+        Oid(oid.to_vec())
+    }
+}
+
+impl FromStr for Oid {
+    type Err = SnmpError;
+
+    /// Parses dotted decimal notation; a leading dot, as printed by net-snmp, is accepted.
+    fn from_str(value: &str) -> SnmpResult<Self> {
+        let arcs = value.strip_prefix('.').unwrap_or(value);
+
+        let arcs = arcs
+            .split('.')
+            .map(|arc| arc.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| SnmpError::InvalidOid(value.to_string()))?;
+
+        // BER packs the first two arcs into one subidentifier (X.690 8.19.4).
+        match arcs.as_slice() {
+            [0..=1, 0..=39, ..] | [2, _, ..] => Ok(Oid(arcs)),
+            _ => Err(SnmpError::InvalidOid(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut arcs = self.0.iter();
+
+        if let Some(first) = arcs.next() {
+            write!(f, "{}", first)?;
+        }
+        for arc in arcs {
+            write!(f, ".{}", arc)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Deref for Oid {
+    type Target = [u32];
+
+    fn deref(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl AsRef<[u32]> for Oid {
+    fn as_ref(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+impl From<Vec<u32>> for Oid {
+    fn from(arcs: Vec<u32>) -> Self {
+        Oid(arcs)
+    }
+}
+
+impl From<&[u32]> for Oid {
+    fn from(arcs: &[u32]) -> Self {
+        Oid(arcs.to_vec())
+    }
+}
+
+impl<const N: usize> From<[u32; N]> for Oid {
+    fn from(arcs: [u32; N]) -> Self {
+        Oid(arcs.to_vec())
+    }
+}
+
+impl From<Oid> for Vec<u32> {
+    fn from(oid: Oid) -> Self {
+        oid.0
+    }
+}
+
+/// Serialized in dotted decimal notation, so it reads naturally and can key JSON objects.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Oid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Oid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let oid = <Cow<'de, str>>::deserialize(deserializer)?;
+
+        oid.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serializes the maps returned by walks and [`Table`](crate::Table)s, keyed by arcs, with
+/// dotted decimal keys instead, since formats like JSON only allow string keys. Use it as
+/// `#[serde(with = "yar_snmp::oid_keys")]` on a field holding such a map.
+#[cfg(feature = "serde")]
+pub mod oid_keys {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Oid;
+
+    pub fn serialize<V, S>(map: &BTreeMap<Vec<u32>, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_map(
+            map.iter()
+                .map(|(arcs, value)| (Oid::from(&arcs[..]), value)),
+        )
+    }
+
+    pub fn deserialize<'de, V, D>(deserializer: D) -> Result<BTreeMap<Vec<u32>, V>, D::Error>
+    where
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let map = BTreeMap::<Oid, V>::deserialize(deserializer)?;
+
+        Ok(map
+            .into_iter()
+            .map(|(oid, value)| (oid.into(), value))
+            .collect())
+    }
+}
+
+/// Anything a session accepts where an OID is expected: dotted strings, arc slices and
+/// [`Oid`]s, by value or by reference.
+pub trait IntoOid {
+    fn into_oid(self) -> SnmpResult<Oid>;
+}
+
+impl IntoOid for Oid {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self)
+    }
+}
+
+impl IntoOid for &Oid {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.clone())
+    }
+}
+
+impl IntoOid for &str {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        self.parse()
+    }
+}
+
+impl IntoOid for String {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        self.parse()
+    }
+}
+
+impl IntoOid for &String {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        self.parse()
+    }
+}
+
+impl IntoOid for &[u32] {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.into())
+    }
+}
+
+impl<const N: usize> IntoOid for [u32; N] {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.into())
+    }
+}
+
+impl<const N: usize> IntoOid for &[u32; N] {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.as_slice().into())
+    }
+}
+
+impl IntoOid for Vec<u32> {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.into())
+    }
+}
+
+impl IntoOid for &Vec<u32> {
+    fn into_oid(self) -> SnmpResult<Oid> {
+        Ok(self.as_slice().into())
+    }
+}
+
+pub(crate) fn into_oids<O: IntoOid + Clone>(oids: &[O]) -> SnmpResult<Vec<Oid>> {
+    oids.iter().cloned().map(IntoOid::into_oid).collect()
+}
+
+pub(crate) fn into_bindings<O: IntoOid + Clone>(
+    bindings: &[(O, Value)],
+) -> SnmpResult<Vec<(Oid, Value)>> {
+    bindings
+        .iter()
+        .map(|(oid, value)| Ok((oid.clone().into_oid()?, value.clone())))
+        .collect()
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{EngineId, Oid, RetryPolicy};
+
+/// Repetitions per GETBULK when [`RequestOptions::max_repetitions`] is not given.
+pub(crate) const DEFAULT_MAX_REPETITIONS: u32 = 10;
+
+/// Stops a walk between two of its requests when cancelled, from any thread; the walk
+/// then returns what it collected so far. Clones cancel together.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+type ProgressFn = dyn Fn(usize, &Oid) + Send + Sync;
+
+#[derive(Clone)]
+struct Progress(Arc<ProgressFn>);
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+impl PartialEq for Progress {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The SNMPv3 context a request is for, where not the agent's default one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Context {
+    pub(crate) engine_id: Option<EngineId>,
+    pub(crate) name: Option<Vec<u8>>,
+}
+
+impl Context {
+    pub(crate) fn is_default(&self) -> bool {
+        self.engine_id.is_none() && self.name.is_none()
+    }
+
+    /// This context, with what it leaves unset taken from `session`.
+    pub(crate) fn or(&self, session: &Context) -> Context {
+        Context {
+            engine_id: self.engine_id.clone().or_else(|| session.engine_id.clone()),
+            name: self.name.clone().or_else(|| session.name.clone()),
+        }
+    }
+}
+
+/// Overrides of the session's settings for a single call, e.g.
+/// `session.get_with(oid, &RequestOptions::new().timeout(Duration::from_secs(5)))`.
+/// Anything not set falls back to what the session was built with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOptions {
+    timeout: Option<Duration>,
+    retries: Option<u32>,
+    retry: Option<RetryPolicy>,
+    max_repetitions: Option<u32>,
+    progress: Option<Progress>,
+    cancel: Option<CancelToken>,
+    verify: bool,
+    context: Context,
+}
+
+impl RequestOptions {
+    pub fn new() -> Self {
+        RequestOptions::default()
+    }
+
+    /// How long to wait for each response before retransmitting.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Number of retransmissions, keeping the rest of the retry policy.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Repetitions per GETBULK, 10 unless given; still halved when the agent answers
+    /// tooBig.
+    pub fn max_repetitions(mut self, max_repetitions: u32) -> Self {
+        self.max_repetitions = Some(max_repetitions);
+        self
+    }
+
+    /// Called after every response of a walk or table fetch with how many instances, or
+    /// rows of a table, it has collected and the last OID received.
+    pub fn progress(mut self, progress: impl Fn(usize, &Oid) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Progress(Arc::new(progress)));
+        self
+    }
+
+    /// Checked by walks and table fetches before each request.
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Reads the bindings of a SET back once the agent accepted it, failing with
+    /// [`SnmpError::Unverified`](crate::SnmpError::Unverified) when one has another value.
+    /// Objects that are write-only, or that agents rewrite, like a RowStatus set to
+    /// createAndGo, do not read back what was set.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// The SNMPv3 contextName, e.g. `vlan-10` for the BRIDGE-MIB of one VLAN on Cisco
+    /// switches.
+    pub fn context(mut self, name: impl AsRef<[u8]>) -> Self {
+        self.context.name = Some(name.as_ref().to_vec());
+        self
+    }
+
+    /// The SNMPv3 contextEngineID, for proxies forwarding to the engine it names; the
+    /// agent's own engine ID otherwise.
+    pub fn context_engine_id(mut self, engine_id: EngineId) -> Self {
+        self.context.engine_id = Some(engine_id);
+        self
+    }
+
+    pub(crate) fn context_or(&self, session: &Context) -> Context {
+        self.context.or(session)
+    }
+
+    pub(crate) fn verifies(&self) -> bool {
+        self.verify
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    pub(crate) fn report(&self, count: usize, last: &Oid) {
+        if let Some(Progress(progress)) = &self.progress {
+            progress(count, last);
+        }
+    }
+
+    pub(crate) fn timeout_or(&self, session: Duration) -> Duration {
+        self.timeout.unwrap_or(session)
+    }
+
+    pub(crate) fn retry_or(&self, session: &RetryPolicy) -> RetryPolicy {
+        let policy = self.retry.as_ref().unwrap_or(session).clone();
+
+        match self.retries {
+            Some(retries) => policy.with_retries(retries),
+            None => policy,
+        }
+    }
+
+    pub(crate) fn max_repetitions_or_default(&self) -> u32 {
+        self.max_repetitions.unwrap_or(DEFAULT_MAX_REPETITIONS)
+    }
+}
@@ -0,0 +1,201 @@
+//! Capturing the messages of sessions to a pcap file, for reading in Wireshark or tcpdump.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// LINKTYPE_RAW: packets start with their IPv4 or IPv6 header.
+const LINKTYPE_RAW: u32 = 101;
+const SNAPLEN: u32 = 65_535;
+const UDP: u8 = 17;
+/// The largest payload of a UDP datagram over IPv4.
+const MAX_PAYLOAD: usize = 65_507;
+
+/// A pcap capture the messages of the sessions it is given to with
+/// [`SessionBuilder::capture`](crate::SessionBuilder::capture) are written to, as UDP
+/// datagrams between the session's local address and the agent's, with their IP and UDP
+/// headers and checksums.
+///
+/// Clones write to the same capture, so one file can take every session of a
+/// [`Poller`](crate::Poller). Each datagram is written as it is sent or received; failing
+/// to write one does not fail the request.
+#[derive(Clone)]
+pub struct PcapWriter {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl fmt::Debug for PcapWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PcapWriter")
+    }
+}
+
+impl PcapWriter {
+    /// Creates, or truncates, the capture file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        PcapWriter::new(File::create(path)?)
+    }
+
+    /// Starts a capture in `out`, writing the pcap file header.
+    pub fn new(mut out: impl Write + Send + 'static) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        out.write_all(&header)?;
+
+        Ok(PcapWriter {
+            out: Arc::new(Mutex::new(Box::new(out))),
+        })
+    }
+
+    /// Writes `payload` as a UDP datagram from `from` to `to`, timestamped now. An IPv4
+    /// address paired with an IPv6 one is written as IPv4-mapped.
+    pub fn write_datagram(
+        &self,
+        from: SocketAddr,
+        to: SocketAddr,
+        payload: &[u8],
+    ) -> io::Result<()> {
+        if payload.len() > MAX_PAYLOAD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "message too large for a UDP datagram",
+            ));
+        }
+
+        let datagram = udp(from, to, payload);
+        let packet = match (from.ip(), to.ip()) {
+            (IpAddr::V4(source), IpAddr::V4(dest)) => ipv4(source, dest, datagram),
+            (source, dest) => ipv6(mapped(source), mapped(dest), datagram),
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(now.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&now.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+
+        let mut out = self.out.lock().unwrap_or_else(PoisonError::into_inner);
+        out.write_all(&record)?;
+        out.flush()
+    }
+
+    /// Writes a message a session sent, or received, over a transport with these
+    /// addresses; unknown ones are written as unspecified.
+    pub(crate) fn record(
+        &self,
+        local: io::Result<SocketAddr>,
+        peer: io::Result<SocketAddr>,
+        message: &[u8],
+        sent: bool,
+    ) {
+        let unspecified = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        let (local, peer) = (local.unwrap_or(unspecified), peer.unwrap_or(unspecified));
+        let (from, to) = if sent { (local, peer) } else { (peer, local) };
+
+        if let Err(_err) = self.write_datagram(from, to, message) {
+            crate::trace::event!(debug, error = %_err, "writing to the capture failed");
+        }
+    }
+}
+
+/// The one's complement of the one's complement sum of `parts` as 16-bit words, each
+/// part but the last of an even length.
+fn checksum(parts: &[&[u8]]) -> u16 {
+    let mut sum: u32 = 0;
+    for part in parts {
+        for word in part.chunks(2) {
+            let high = u32::from(word[0]) << 8;
+            sum += high | word.get(1).copied().map_or(0, u32::from);
+        }
+    }
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+/// The UDP header and payload, with the checksum left to fill in.
+fn udp(from: SocketAddr, to: SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(8 + payload.len());
+    datagram.extend_from_slice(&from.port().to_be_bytes());
+    datagram.extend_from_slice(&to.port().to_be_bytes());
+    datagram.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    datagram.extend_from_slice(&[0, 0]);
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/// Fills in the checksum of `datagram` over it and its pseudo header; a zero checksum is
+/// sent as all ones, since zero means none.
+fn set_udp_checksum(datagram: &mut [u8], pseudo: &[u8]) {
+    let sum = match checksum(&[pseudo, datagram]) {
+        0 => 0xffff,
+        sum => sum,
+    };
+    datagram[6..8].copy_from_slice(&sum.to_be_bytes());
+}
+
+fn ipv4(source: Ipv4Addr, dest: Ipv4Addr, mut datagram: Vec<u8>) -> Vec<u8> {
+    let len = datagram.len() as u16;
+
+    let mut pseudo = Vec::with_capacity(12);
+    pseudo.extend_from_slice(&source.octets());
+    pseudo.extend_from_slice(&dest.octets());
+    pseudo.extend_from_slice(&[0, UDP]);
+    pseudo.extend_from_slice(&len.to_be_bytes());
+    set_udp_checksum(&mut datagram, &pseudo);
+
+    let mut packet = Vec::with_capacity(20 + datagram.len());
+    packet.extend_from_slice(&[0x45, 0]);
+    packet.extend_from_slice(&(20 + len).to_be_bytes());
+    // No identification, don't fragment, a TTL of 64.
+    packet.extend_from_slice(&[0, 0, 0x40, 0, 64, UDP, 0, 0]);
+    packet.extend_from_slice(&source.octets());
+    packet.extend_from_slice(&dest.octets());
+    let sum = checksum(&[&packet]);
+    packet[10..12].copy_from_slice(&sum.to_be_bytes());
+
+    packet.extend_from_slice(&datagram);
+    packet
+}
+
+fn mapped(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+fn ipv6(source: Ipv6Addr, dest: Ipv6Addr, mut datagram: Vec<u8>) -> Vec<u8> {
+    let len = datagram.len() as u32;
+
+    let mut pseudo = Vec::with_capacity(40);
+    pseudo.extend_from_slice(&source.octets());
+    pseudo.extend_from_slice(&dest.octets());
+    pseudo.extend_from_slice(&len.to_be_bytes());
+    pseudo.extend_from_slice(&[0, 0, 0, UDP]);
+    set_udp_checksum(&mut datagram, &pseudo);
+
+    let mut packet = Vec::with_capacity(40 + datagram.len());
+    packet.extend_from_slice(&[0x60, 0, 0, 0]);
+    packet.extend_from_slice(&(len as u16).to_be_bytes());
+    packet.extend_from_slice(&[UDP, 64]);
+    packet.extend_from_slice(&source.octets());
+    packet.extend_from_slice(&dest.octets());
+    packet.extend_from_slice(&datagram);
+    packet
+}
@@ -0,0 +1,461 @@
+//! PDU construction and response parsing shared by the sync and async sessions.
+
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hasher};
+
+use rasn::ber::enc::{Encoder, EncoderOptions};
+use rasn_snmp::v2;
+
+use crate::trace;
+use crate::{ber, ErrorStatus, Oid, PartialWalk, SnmpError, SnmpResult, Value};
+
+pub(crate) fn encode<T: rasn::Encode>(value: &T) -> SnmpResult<Vec<u8>> {
+    encode_into(value, Vec::new())
+}
+
+/// Encodes `value` into `buffer`, replacing its contents but reusing its allocation.
+pub(crate) fn encode_into<T: rasn::Encode>(value: &T, buffer: Vec<u8>) -> SnmpResult<Vec<u8>> {
+    let mut encoder = Encoder::new_with_buffer(EncoderOptions::ber(), buffer);
+    value.encode(&mut encoder).map_err(SnmpError::Encode)?;
+
+    Ok(encoder.output())
+}
+
+pub(crate) fn decode<T: rasn::Decode>(bytes: &[u8]) -> SnmpResult<T> {
+    rasn::ber::decode(bytes).map_err(|source| SnmpError::Decode {
+        offset: ber::invalid_offset(bytes),
+        source,
+    })
+}
+
+/// A random starting point, so sessions to the same agent do not reuse each other's IDs.
+pub(crate) fn initial_request_id() -> i32 {
+    let seed = RandomState::new().build_hasher().finish();
+
+    (seed as i32) & i32::MAX
+}
+
+pub(crate) fn request_id(data: &v2::Pdus) -> i32 {
+    match data {
+        v2::Pdus::GetRequest(v2::GetRequest(pdu))
+        | v2::Pdus::GetNextRequest(v2::GetNextRequest(pdu))
+        | v2::Pdus::Response(v2::Response(pdu))
+        | v2::Pdus::SetRequest(v2::SetRequest(pdu))
+        | v2::Pdus::InformRequest(v2::InformRequest(pdu))
+        | v2::Pdus::Trap(v2::Trap(pdu))
+        | v2::Pdus::Report(v2::Report(pdu)) => pdu.request_id,
+        v2::Pdus::GetBulkRequest(v2::GetBulkRequest(bulk)) => bulk.request_id,
+    }
+}
+
+pub(crate) fn set_request_id(data: &mut v2::Pdus, request_id: i32) {
+    match data {
+        v2::Pdus::GetRequest(v2::GetRequest(pdu))
+        | v2::Pdus::GetNextRequest(v2::GetNextRequest(pdu))
+        | v2::Pdus::Response(v2::Response(pdu))
+        | v2::Pdus::SetRequest(v2::SetRequest(pdu))
+        | v2::Pdus::InformRequest(v2::InformRequest(pdu))
+        | v2::Pdus::Trap(v2::Trap(pdu))
+        | v2::Pdus::Report(v2::Report(pdu)) => pdu.request_id = request_id,
+        v2::Pdus::GetBulkRequest(v2::GetBulkRequest(bulk)) => bulk.request_id = request_id,
+    }
+}
+
+pub(crate) fn var_binds(bindings: &[(Oid, Value)]) -> Vec<v2::VarBind> {
+    bindings
+        .iter()
+        .map(|(oid, value)| v2::VarBind {
+            name: oid.to_asn(),
+            value: value.clone().into(),
+        })
+        .collect()
+}
+
+fn pdu(oids: &[Oid]) -> v2::Pdu {
+    v2::Pdu {
+        request_id: 1,
+        error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+        error_index: 0,
+        variable_bindings: oids
+            .iter()
+            .map(|oid| v2::VarBind {
+                name: oid.to_asn(),
+                value: v2::VarBindValue::Unspecified,
+            })
+            .collect(),
+    }
+}
+
+pub(crate) fn get(oids: &[Oid]) -> v2::Pdus {
+    v2::Pdus::GetRequest(v2::GetRequest(pdu(oids)))
+}
+
+pub(crate) fn getnext(oids: &[Oid]) -> v2::Pdus {
+    v2::Pdus::GetNextRequest(v2::GetNextRequest(pdu(oids)))
+}
+
+pub(crate) fn getbulk(oids: &[Oid], non_repeaters: u32, max_repetitions: u32) -> v2::Pdus {
+    v2::Pdus::GetBulkRequest(v2::GetBulkRequest(v2::BulkPdu {
+        request_id: 1,
+        // RFC 3416 4.2.3: N is capped at the number of requested variables.
+        non_repeaters: non_repeaters.min(oids.len() as u32),
+        max_repetitions,
+        variable_bindings: oids
+            .iter()
+            .map(|oid| v2::VarBind {
+                name: oid.to_asn(),
+                value: v2::VarBindValue::Unspecified,
+            })
+            .collect(),
+    }))
+}
+
+pub(crate) fn set(bindings: &[(Oid, Value)]) -> v2::Pdus {
+    v2::Pdus::SetRequest(v2::SetRequest(v2::Pdu {
+        request_id: 1,
+        error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+        error_index: 0,
+        variable_bindings: var_binds(bindings),
+    }))
+}
+
+pub(crate) const SYS_UP_TIME: [u32; 9] = [1, 3, 6, 1, 2, 1, 1, 3, 0];
+pub(crate) const SNMP_TRAP_OID: [u32; 11] = [1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0];
+
+/// Prepends the sysUpTime.0 and snmpTrapOID.0 bindings every notification starts with.
+fn notification(uptime: u32, trap_oid: &Oid, bindings: &[(Oid, Value)]) -> v2::Pdu {
+    let mut variable_bindings = var_binds(&[
+        (SYS_UP_TIME.into(), Value::TimeTicks(uptime)),
+        (SNMP_TRAP_OID.into(), Value::Oid(trap_oid.to_vec())),
+    ]);
+    variable_bindings.extend(var_binds(bindings));
+
+    v2::Pdu {
+        request_id: 1,
+        error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+        error_index: 0,
+        variable_bindings,
+    }
+}
+
+pub(crate) fn trap(uptime: u32, trap_oid: &Oid, bindings: &[(Oid, Value)]) -> v2::Pdus {
+    v2::Pdus::Trap(v2::Trap(notification(uptime, trap_oid, bindings)))
+}
+
+pub(crate) fn inform(uptime: u32, trap_oid: &Oid, bindings: &[(Oid, Value)]) -> v2::Pdus {
+    v2::Pdus::InformRequest(v2::InformRequest(notification(uptime, trap_oid, bindings)))
+}
+
+pub(crate) fn parse_response(data: v2::Pdus) -> SnmpResult<Vec<(Oid, Value)>> {
+    let v2::Pdus::Response(response) = data else {
+        return Err(SnmpError::UnexpectedPdu);
+    };
+
+    let status = response.0.error_status;
+    let bindings = response
+        .0
+        .variable_bindings
+        .into_iter()
+        .map(|var| (Oid::from_asn(&var.name), var.value.into()))
+        .collect();
+
+    if status != v2::Pdu::ERROR_STATUS_NO_ERROR {
+        return Err(SnmpError::AgentError {
+            status: status.into(),
+            index: response.0.error_index,
+            bindings,
+        });
+    }
+
+    Ok(bindings)
+}
+
+/// Parses the response to a SET of `sent`. When the agent refuses it without echoing the
+/// bindings, as some do, the error carries `sent` instead, so its index always points into
+/// them.
+pub(crate) fn parse_set_response(
+    data: v2::Pdus,
+    sent: &[(Oid, Value)],
+) -> SnmpResult<Vec<(Oid, Value)>> {
+    match parse_response(data) {
+        Err(SnmpError::AgentError {
+            status,
+            index,
+            bindings,
+        }) => {
+            let echoed = bindings.len() == sent.len()
+                && bindings
+                    .iter()
+                    .zip(sent)
+                    .all(|((name, _), (sent, _))| name == sent);
+
+            Err(SnmpError::AgentError {
+                status,
+                index,
+                bindings: if echoed { bindings } else { sent.to_vec() },
+            })
+        }
+        result => result,
+    }
+}
+
+/// Compares what a SET sent with what was read back after it.
+pub(crate) fn verify_set(sent: &[(Oid, Value)], read: Vec<(Oid, Value)>) -> SnmpResult<()> {
+    match sent
+        .iter()
+        .zip(&read)
+        .position(|((_, sent), (_, read))| sent != read)
+    {
+        Some(index) => Err(SnmpError::Unverified {
+            index,
+            bindings: read,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Fails when a received message filled the whole buffer yet its framing claims more, which
+/// is how an oversized UDP datagram shows up after the kernel cut it short.
+pub(crate) fn check_truncated(message: &[u8], buffer: usize) -> SnmpResult<()> {
+    match ber::message_len(message) {
+        Some(size) if message.len() == buffer && size > buffer => {
+            Err(SnmpError::Truncated { size, buffer })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parses a GET or GETNEXT response, which must answer every requested OID in order.
+pub(crate) fn parse_aligned_response(
+    data: v2::Pdus,
+    oids: &[Oid],
+) -> SnmpResult<Vec<(Oid, Value)>> {
+    let vars = parse_response(data)?;
+
+    if vars.len() != oids.len() {
+        return Err(SnmpError::InvalidMessage(
+            "response bindings do not match the request",
+        ));
+    }
+
+    Ok(vars)
+}
+
+/// Fails on the first noSuchObject or noSuchInstance in the response to a GET.
+pub(crate) fn check_exceptions(vars: &[(Oid, Value)]) -> SnmpResult<()> {
+    for (oid, value) in vars {
+        match value {
+            Value::NoSuchObject => return Err(SnmpError::NoSuchObject(oid.clone())),
+            Value::NoSuchInstance => return Err(SnmpError::NoSuchInstance(oid.clone())),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// A GETNEXT over several OIDs. SNMPv1 agents fail the whole PDU with `noSuchName` when one
+/// OID is past the end of the MIB, so that OID is reported as `EndOfMibView` and the request
+/// is retried without it (RFC 3584 4.4).
+pub(crate) struct NextRequest {
+    oids: Vec<Oid>,
+    ended: Vec<bool>,
+}
+
+impl NextRequest {
+    pub(crate) fn new(oids: Vec<Oid>) -> Self {
+        let ended = vec![false; oids.len()];
+
+        NextRequest { oids, ended }
+    }
+
+    fn pending(&self) -> Vec<Oid> {
+        self.oids
+            .iter()
+            .zip(&self.ended)
+            .filter(|(_, ended)| !**ended)
+            .map(|(oid, _)| oid.clone())
+            .collect()
+    }
+
+    /// The next PDU to send, or `None` once every OID has ended.
+    pub(crate) fn request(&self) -> Option<v2::Pdus> {
+        let pending = self.pending();
+
+        (!pending.is_empty()).then(|| getnext(&pending))
+    }
+
+    /// Feeds the response to [`NextRequest::request`]; `None` means send the next request.
+    pub(crate) fn response(&mut self, data: v2::Pdus) -> SnmpResult<Option<Vec<(Oid, Value)>>> {
+        let pending = self.pending();
+
+        let vars = match parse_aligned_response(data, &pending) {
+            Ok(vars) => vars,
+            Err(SnmpError::AgentError {
+                status: ErrorStatus::NoSuchName,
+                index,
+                ..
+            }) if (1..=pending.len()).contains(&(index as usize)) => {
+                let ended = self.ended.iter_mut().filter(|ended| !**ended);
+                if let Some(ended) = ended.take(index as usize).last() {
+                    *ended = true;
+                }
+
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut vars = vars.into_iter();
+
+        Ok(Some(self.merge(|| vars.next())))
+    }
+
+    /// The results once every OID has ended.
+    pub(crate) fn finish(self) -> Vec<(Oid, Value)> {
+        self.merge(|| None)
+    }
+
+    fn merge(&self, mut next: impl FnMut() -> Option<(Oid, Value)>) -> Vec<(Oid, Value)> {
+        self.oids
+            .iter()
+            .zip(&self.ended)
+            .map(|(oid, ended)| {
+                let var = if *ended { None } else { next() };
+
+                var.unwrap_or_else(|| (oid.clone(), Value::EndOfMibView))
+            })
+            .collect()
+    }
+}
+
+/// Consecutive batches of a request's OIDs, halved whenever the agent answers tooBig so
+/// that oversized GET and GETNEXT requests are split until each response fits.
+pub(crate) struct Batches<'a> {
+    oids: &'a [Oid],
+    size: usize,
+}
+
+impl<'a> Batches<'a> {
+    pub(crate) fn new(oids: &'a [Oid]) -> Self {
+        Batches {
+            oids,
+            size: oids.len(),
+        }
+    }
+
+    /// The OIDs to request next, or `None` once every batch has been answered.
+    pub(crate) fn next(&self) -> Option<&'a [Oid]> {
+        let size = self.size.min(self.oids.len());
+
+        (size > 0).then(|| &self.oids[..size])
+    }
+
+    /// Moves on after the current batch was answered.
+    pub(crate) fn advance(&mut self) {
+        let size = self.size.min(self.oids.len());
+
+        self.oids = &self.oids[size..];
+    }
+
+    /// Halves the batch after a tooBig; any other error, or tooBig for a single OID, ends
+    /// the request.
+    pub(crate) fn shrink(&mut self, err: SnmpError) -> SnmpResult<()> {
+        let size = self.size.min(self.oids.len());
+
+        if !err.is_too_big() || size <= 1 {
+            return Err(err);
+        }
+
+        self.size = size / 2;
+        trace::event!(debug, batch = self.size, "splitting request after tooBig");
+
+        Ok(())
+    }
+}
+
+/// Parses a GETBULK response: `N` non-repeater results, then up to `max_repetitions` rows
+/// of one result per repeating variable, in request order.
+pub(crate) fn parse_bulk_response(
+    data: v2::Pdus,
+    oids: &[Oid],
+    non_repeaters: u32,
+    max_repetitions: u32,
+) -> SnmpResult<Vec<(Oid, Value)>> {
+    let non_repeaters = (non_repeaters as usize).min(oids.len());
+    let repeaters = oids.len() - non_repeaters;
+
+    let mut vars = parse_response(data)?;
+    vars.truncate(non_repeaters + repeaters * max_repetitions as usize);
+
+    Ok(vars)
+}
+
+/// Appends the varbinds of one walk step from `current` that fall inside `start` to `out`.
+/// Returns the OID to continue from, or `None` once the subtree is exhausted; an agent that
+/// does not move forward would otherwise be walked forever.
+pub(crate) fn walk_step(
+    start: &Oid,
+    current: &Oid,
+    vars: Vec<(Oid, Value)>,
+    out: &mut impl Extend<(Oid, Value)>,
+) -> SnmpResult<Option<Oid>> {
+    let mut previous = current;
+    let mut next = None;
+
+    for (name, value) in vars {
+        if value == Value::EndOfMibView {
+            return Ok(None);
+        }
+
+        if name <= *previous {
+            return Err(SnmpError::OidNotIncreasing {
+                previous: previous.clone(),
+                next: name,
+            });
+        }
+
+        if !name.starts_with(start) {
+            return Ok(None);
+        }
+
+        out.extend([(name.clone(), value)]);
+
+        previous = &*next.insert(name);
+    }
+
+    Ok(next)
+}
+
+/// The [`PartialWalk`] of a walk of `start` that failed with `error` while continuing
+/// from `current`.
+pub(crate) fn partial_walk(
+    start: &Oid,
+    current: Oid,
+    collected: BTreeMap<Vec<u32>, Value>,
+    error: SnmpError,
+) -> PartialWalk {
+    PartialWalk {
+        last: (current != *start).then_some(current),
+        collected,
+        error,
+    }
+}
+
+/// Like [`walk_step`], but keys the varbinds in `result` by their suffix under `start`.
+pub(crate) fn collect_subtree(
+    start: &Oid,
+    current: &Oid,
+    vars: Vec<(Oid, Value)>,
+    result: &mut BTreeMap<Vec<u32>, Value>,
+) -> SnmpResult<Option<Oid>> {
+    let mut step = Vec::new();
+    let next = walk_step(start, current, vars, &mut step)?;
+
+    for (name, value) in step {
+        result.insert(name[start.len()..].to_vec(), value);
+    }
+
+    Ok(next)
+}
@@ -0,0 +1,334 @@
+//! Polling many agents concurrently on a fixed schedule.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::net::SocketAddr;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::trace;
+use crate::{oid, IntoOid, Oid, RequestOptions, SessionBuilder, SnmpResult, SyncSession, Value};
+
+/// The outcome of walking one of the targets of [`walk_many`], as
+/// [`SyncSession::bulk_walk`] returns it.
+pub type WalkResult = SnmpResult<BTreeMap<Vec<u32>, Value>>;
+
+#[derive(Debug, Clone)]
+enum Request {
+    Get(Vec<Oid>),
+    Walk(Oid),
+}
+
+/// An agent to poll: where and how to reach it, what to fetch, and how often.
+#[derive(Debug, Clone)]
+pub struct Target {
+    builder: SessionBuilder,
+    request: Request,
+    interval: Duration,
+}
+
+impl Target {
+    /// Fetches `oids` with one GET every `interval`.
+    pub fn get<O: IntoOid + Clone>(
+        builder: SessionBuilder,
+        oids: &[O],
+        interval: Duration,
+    ) -> SnmpResult<Self> {
+        Ok(Target {
+            builder,
+            request: Request::Get(oid::into_oids(oids)?),
+            interval,
+        })
+    }
+
+    /// Walks the subtree at `oid` every `interval`.
+    pub fn walk(
+        builder: SessionBuilder,
+        oid: impl IntoOid,
+        interval: Duration,
+    ) -> SnmpResult<Self> {
+        Ok(Target {
+            builder,
+            request: Request::Walk(oid.into_oid()?),
+            interval,
+        })
+    }
+}
+
+/// The outcome of one poll of a target.
+#[derive(Debug)]
+pub struct PollResult {
+    /// Index of the target in the list given to [`Poller::start`].
+    pub target: usize,
+    /// When the poll began, for computing rates with
+    /// [`CounterTracker::record_at`](crate::CounterTracker::record_at).
+    pub started: Instant,
+    /// The address of the agent the poll went to last, which is no longer the first one
+    /// after the session failed over to a [`SessionBuilder::fallback`]; `None` when the
+    /// session could not be opened.
+    pub peer: Option<SocketAddr>,
+    pub result: SnmpResult<Vec<(Oid, Value)>>,
+}
+
+/// A target and the session to it, opened on first use and reopened after it fails to open.
+struct Slot {
+    target: Target,
+    session: Mutex<Option<SyncSession>>,
+}
+
+impl Slot {
+    fn poll(&self, index: usize, results: &Sender<PollResult>) {
+        // A poll that takes longer than the interval makes the next one wait its turn
+        // instead of queueing up behind it.
+        let Ok(mut session) = self.session.try_lock() else {
+            trace::event!(
+                debug,
+                target = index,
+                "skipping poll, previous one still running"
+            );
+            return;
+        };
+
+        let started = Instant::now();
+        let result = match &mut *session {
+            Some(session) => Self::request(session, &self.target.request),
+            None => match self.target.builder.clone().build() {
+                Ok(opened) => Self::request(session.insert(opened), &self.target.request),
+                Err(err) => Err(err.into()),
+            },
+        };
+
+        let peer = session
+            .as_ref()
+            .and_then(|session| session.peer_addr().ok());
+
+        let _ = results.send(PollResult {
+            target: index,
+            started,
+            peer,
+            result,
+        });
+    }
+
+    fn request(session: &SyncSession, request: &Request) -> SnmpResult<Vec<(Oid, Value)>> {
+        match request {
+            Request::Get(oids) => session.get_many(oids),
+            Request::Walk(oid) => Ok(session
+                .walk(oid)?
+                .into_iter()
+                .map(|(oid, value)| (oid.into(), value))
+                .collect()),
+        }
+    }
+}
+
+/// Polls a set of [`Target`]s on their intervals with a bounded pool of worker threads,
+/// delivering every [`PollResult`] to [`Poller::results`]. Each target is polled at most
+/// once at a time. Dropping the poller stops it and waits for running polls to finish.
+pub struct Poller {
+    stop: Option<Sender<()>>,
+    results: Receiver<PollResult>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Poller {
+    /// Starts polling every target right away with `workers` threads, at least one.
+    pub fn start(targets: Vec<Target>, workers: usize) -> Self {
+        let slots: Arc<Vec<Slot>> = Arc::new(
+            targets
+                .into_iter()
+                .map(|target| Slot {
+                    target,
+                    session: Mutex::new(None),
+                })
+                .collect(),
+        );
+
+        let (stop, stopped) = mpsc::channel();
+        let (results_tx, results) = mpsc::channel();
+        let (jobs_tx, jobs) = mpsc::channel::<usize>();
+        let jobs = Arc::new(Mutex::new(jobs));
+
+        let mut threads: Vec<_> = (0..workers.max(1))
+            .map(|_| {
+                let (slots, jobs, results) = (slots.clone(), jobs.clone(), results_tx.clone());
+
+                thread::spawn(move || loop {
+                    let job = jobs.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    match job {
+                        Ok(index) => slots[index].poll(index, &results),
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+
+        threads.push(thread::spawn(move || schedule(&slots, &jobs_tx, &stopped)));
+
+        Poller {
+            stop: Some(stop),
+            results,
+            threads,
+        }
+    }
+
+    pub fn results(&self) -> &Receiver<PollResult> {
+        &self.results
+    }
+}
+
+/// Walks the subtree at `oid` on every agent of `targets`, `concurrency` at a time, each
+/// with a session of its own and GETBULK unless it is SNMPv1. Returns the outcome for each
+/// target in the order given; only an invalid `oid` fails the whole call.
+pub fn walk_many(
+    targets: &[SessionBuilder],
+    oid: impl IntoOid,
+    concurrency: usize,
+) -> SnmpResult<Vec<WalkResult>> {
+    let oid = oid.into_oid()?;
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..targets.len()).map(|_| None).collect::<Vec<_>>());
+
+    let worker = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(target) = targets.get(index) else {
+            break;
+        };
+        let result = target
+            .clone()
+            .build()
+            .map_err(Into::into)
+            .and_then(|session| session.bulk_walk_with(&oid, &RequestOptions::default()));
+
+        results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..concurrency.clamp(1, targets.len().max(1)))
+            .map(|_| scope.spawn(worker))
+            .collect();
+
+        for worker in workers {
+            worker
+                .join()
+                .unwrap_or_else(|panic| panic::resume_unwind(panic));
+        }
+    });
+
+    Ok(finish(results))
+}
+
+/// The non-blocking counterpart of [`walk_many`]: the walks run concurrently within the
+/// calling task, so no runtime is needed beyond what sessions need.
+#[cfg(feature = "tokio")]
+pub async fn walk_many_async(
+    targets: &[SessionBuilder],
+    oid: impl IntoOid,
+    concurrency: usize,
+) -> SnmpResult<Vec<WalkResult>> {
+    let oid = oid.into_oid()?;
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..targets.len()).map(|_| None).collect::<Vec<_>>());
+
+    let workers = (0..concurrency.clamp(1, targets.len().max(1))).map(|_| async {
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(target) = targets.get(index) else {
+                break;
+            };
+            let result = match target.clone().build_async().await {
+                Ok(session) => {
+                    session
+                        .bulk_walk_with(&oid, &RequestOptions::default())
+                        .await
+                }
+                Err(err) => Err(err.into()),
+            };
+
+            results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+        }
+    });
+    join_all(workers.collect()).await;
+
+    Ok(finish(results))
+}
+
+/// Polls `futures` together until all are done.
+#[cfg(feature = "tokio")]
+async fn join_all<F: std::future::Future<Output = ()>>(futures: Vec<F>) {
+    let mut futures: Vec<_> = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future)))
+        .collect();
+
+    std::future::poll_fn(|cx| {
+        let mut pending = false;
+        for slot in &mut futures {
+            if let Some(future) = slot {
+                match future.as_mut().poll(cx) {
+                    std::task::Poll::Ready(()) => *slot = None,
+                    std::task::Poll::Pending => pending = true,
+                }
+            }
+        }
+
+        if pending {
+            std::task::Poll::Pending
+        } else {
+            std::task::Poll::Ready(())
+        }
+    })
+    .await
+}
+
+fn finish(results: Mutex<Vec<Option<WalkResult>>>) -> Vec<WalkResult> {
+    results
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .map(|result| result.expect("every target is walked"))
+        .collect()
+}
+
+/// Hands each target to the workers whenever it is due, until told to stop. Polls are due
+/// at fixed multiples of the interval from the start, so slow polls do not shift the
+/// schedule.
+fn schedule(slots: &[Slot], jobs: &Sender<usize>, stopped: &Receiver<()>) {
+    let start = Instant::now();
+    let mut due: BinaryHeap<_> = (0..slots.len())
+        .map(|index| Reverse((start, index)))
+        .collect();
+
+    while let Some(Reverse((at, index))) = due.pop() {
+        let wait = at.saturating_duration_since(Instant::now());
+        match stopped.recv_timeout(wait) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return,
+        }
+
+        if jobs.send(index).is_err() {
+            return;
+        }
+
+        let mut next = at + slots[index].target.interval;
+        // Skip polls that were missed altogether, e.g. while the machine was suspended.
+        while next < Instant::now() {
+            next += slots[index].target.interval.max(Duration::from_millis(1));
+        }
+        due.push(Reverse((next, index)));
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Exposing polled values as Prometheus metrics, for exporters built on this crate.
+//!
+//! [`Metrics`] collects walks and tables into metric families and renders them in the text
+//! exposition format; [`serve`] answers scrapes over HTTP.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::trace;
+use crate::{IntoOid, Mib, Oid, SnmpResult, Table, Value};
+
+/// How long waking the server thread up on drop may take.
+const WAKE_TIMEOUT: Duration = Duration::from_millis(100);
+/// How long a scraper gets to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// How the instances under an object become samples: the metric name, and the labels the
+/// arcs of each instance's index are put in.
+#[derive(Debug, Clone)]
+pub struct Metric {
+    oid: Oid,
+    name: String,
+    labels: Vec<String>,
+    help: Option<String>,
+}
+
+impl Metric {
+    /// Samples the instances under `oid`, a column or scalar, or a whole table for
+    /// [`Metrics::add_table`]. The metric is named after the object in the MIB.
+    pub fn new(oid: impl IntoOid) -> SnmpResult<Self> {
+        Ok(Metric {
+            oid: oid.into_oid()?,
+            name: "{name}".to_string(),
+            labels: Vec::new(),
+            help: None,
+        })
+    }
+
+    /// Names the metric after `template`, in which `{name}` stands for the object's MIB
+    /// name, e.g. `snmp_{name}_total`. Objects the MIB does not know are named after their
+    /// OID, like `oid_1_3_6_1_2_1_2_2_1_10`.
+    pub fn name(mut self, template: impl Into<String>) -> Self {
+        self.name = template.into();
+        self
+    }
+
+    /// Puts the first arc of an instance's index in the first label, the second in the
+    /// second, and so on, with the last label taking the remaining arcs dotted, such as an
+    /// IP address. Without labels, a non-zero index goes in an `index` label.
+    pub fn labels(mut self, labels: &[&str]) -> Self {
+        self.labels = labels.iter().map(|label| label.to_string()).collect();
+        self
+    }
+
+    /// The `# HELP` text, the object's MIB name by default.
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    fn label_values(&self, index: &[u32]) -> Vec<(String, String)> {
+        let dotted = |arcs: &[u32]| {
+            arcs.iter()
+                .map(|arc| arc.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        };
+
+        if self.labels.is_empty() {
+            return match index {
+                [] | [0] => Vec::new(),
+                _ => vec![("index".to_string(), dotted(index))],
+            };
+        }
+
+        let mut values = Vec::new();
+        for (i, label) in self.labels.iter().enumerate() {
+            let value = if i + 1 == self.labels.len() {
+                index.get(i..).map(dotted)
+            } else {
+                index.get(i).map(|arc| arc.to_string())
+            };
+            match value {
+                Some(value) if !value.is_empty() => values.push((label.clone(), value)),
+                _ => break,
+            }
+        }
+
+        values
+    }
+}
+
+#[derive(Debug)]
+struct Family {
+    help: String,
+    kind: &'static str,
+    samples: BTreeMap<Vec<(String, String)>, String>,
+}
+
+/// Metric families gathered from walks and tables, rendered with [`Metrics::render`].
+///
+/// Counter32 and Counter64 values become counters; INTEGERs, Gauge32s and TimeTicks
+/// become gauges. Other values, such as strings, are left out, and so are values of
+/// another kind than the first of their family, which has only one type.
+#[derive(Debug, Default)]
+pub struct Metrics<'a> {
+    mib: Option<&'a Mib>,
+    families: BTreeMap<String, Family>,
+}
+
+impl<'a> Metrics<'a> {
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Names metrics and their help after the objects of `mib`.
+    pub fn with_mib(mib: &'a Mib) -> Self {
+        Metrics {
+            mib: Some(mib),
+            families: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the result of walking the object of `metric`, whose keys are relative to it.
+    pub fn add_walk(&mut self, metric: &Metric, walk: &BTreeMap<Vec<u32>, Value>) {
+        for (index, value) in walk {
+            self.add_sample(metric, &metric.oid, index, value);
+        }
+    }
+
+    /// Adds the bindings under the object of `metric`, e.g. a
+    /// [`PollResult`](crate::PollResult), ignoring any others.
+    pub fn add_bindings(&mut self, metric: &Metric, bindings: &[(Oid, Value)]) {
+        for (name, value) in bindings {
+            if let Some(index) = name.suffix(&metric.oid) {
+                self.add_sample(metric, &metric.oid, index, value);
+            }
+        }
+    }
+
+    /// Adds every column of `table`, fetched from the table of `metric`, as a metric of
+    /// its own named after the column, so the name template should contain `{name}`.
+    pub fn add_table(&mut self, metric: &Metric, table: &Table) {
+        for (index, row) in table {
+            for (column, value) in row {
+                let object = Oid::from([metric.oid.as_slice(), &[1, *column]].concat());
+                self.add_sample(metric, &object, index, value);
+            }
+        }
+    }
+
+    fn add_sample(&mut self, metric: &Metric, object: &Oid, index: &[u32], value: &Value) {
+        let (kind, sample) = match value {
+            Value::Counter32(value) => ("counter", value.to_string()),
+            Value::Counter64(value) => ("counter", value.to_string()),
+            Value::Integer(value) => ("gauge", value.to_string()),
+            Value::Gauge32(value) | Value::TimeTicks(value) => ("gauge", value.to_string()),
+            _ => return,
+        };
+
+        let node = self
+            .mib
+            .and_then(|mib| mib.node(object))
+            .filter(|node| node.oid == *object);
+        let name = match node {
+            Some(node) => sanitize(&metric.name.replace("{name}", &node.name)),
+            None => sanitize(&metric.name.replace("{name}", &format!("oid_{}", object))),
+        };
+
+        let family = self.families.entry(name).or_insert_with(|| Family {
+            help: metric.help.clone().unwrap_or_else(|| match node {
+                Some(node) => format!("{}::{}", node.module, node.name),
+                None => object.to_string(),
+            }),
+            kind,
+            samples: BTreeMap::new(),
+        });
+        if family.kind != kind {
+            trace::event!(debug, %object, kind, "leaving out a sample of another kind");
+            return;
+        }
+        family.samples.insert(metric.label_values(index), sample);
+    }
+
+    /// Drops every sample, to collect afresh for the next scrape.
+    pub fn clear(&mut self) {
+        self.families.clear();
+    }
+
+    /// The metrics in the Prometheus text exposition format, version 0.0.4.
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+
+        for (name, family) in &self.families {
+            let _ = writeln!(text, "# HELP {} {}", name, escape(&family.help, false));
+            let _ = writeln!(text, "# TYPE {} {}", name, family.kind);
+
+            for (labels, value) in &family.samples {
+                text.push_str(name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(label, value)| format!("{}=\"{}\"", label, escape(value, true)))
+                        .collect();
+                    let _ = write!(text, "{{{}}}", labels.join(","));
+                }
+                let _ = writeln!(text, " {}", value);
+            }
+        }
+
+        text
+    }
+}
+
+/// Replaces the characters metric names may not contain with underscores.
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':' => c,
+            _ => '_',
+        })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+
+    sanitized
+}
+
+/// Escapes backslashes and line feeds, and in label values double quotes as well.
+fn escape(text: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+struct Shared {
+    listener: TcpListener,
+    stopped: AtomicBool,
+}
+
+/// A running [`serve`]r, which stops when dropped.
+pub struct MetricsServer {
+    shared: Arc<Shared>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.shared.listener.local_addr()
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+
+        // Wake the server thread up from accepting.
+        if let Ok(mut addr) = self.local_addr() {
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr.ip() {
+                    IpAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    IpAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
+            }
+            let _ = TcpStream::connect_timeout(&addr, WAKE_TIMEOUT);
+        }
+
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+/// Answers HTTP GET requests on `addr` from a background thread, one at a time, with what
+/// `render` returns for the request's path and query, e.g. `/metrics?target=10.0.0.1`, or
+/// 404 Not Found for `None`.
+///
+/// `render` typically polls the agents and returns [`Metrics::render`], so the values are
+/// as fresh as the scrape.
+pub fn serve<F>(addr: impl ToSocketAddrs, render: F) -> io::Result<MetricsServer>
+where
+    F: Fn(&str) -> Option<String> + Send + 'static,
+{
+    let shared = Arc::new(Shared {
+        listener: TcpListener::bind(addr)?,
+        stopped: AtomicBool::new(false),
+    });
+
+    let server = {
+        let shared = shared.clone();
+        thread::spawn(move || {
+            for stream in shared.listener.incoming() {
+                if shared.stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let _ = answer(stream, &render);
+                }
+            }
+        })
+    };
+
+    Ok(MetricsServer {
+        shared,
+        server: Some(server),
+    })
+}
+
+fn answer(mut stream: TcpStream, render: impl Fn(&str) -> Option<String>) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let len = stream.read(&mut buf)?;
+        if len == 0 || request.len() + len > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..len]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.split_whitespace();
+    let (status, allow, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some(target)) => match render(target) {
+            Some(body) => ("200 OK", "", body),
+            None => ("404 Not Found", "", String::new()),
+        },
+        _ => ("405 Method Not Allowed", "Allow: GET\r\n", String::new()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n{}Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        allow,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
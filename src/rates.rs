@@ -0,0 +1,97 @@
+//! Turning successive Counter32 and Counter64 samples into per-second rates.
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::{Oid, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Counter {
+    Counter32(u32),
+    Counter64(u64),
+}
+
+impl Counter {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Counter32(value) => Some(Counter::Counter32(*value)),
+            Value::Counter64(value) => Some(Counter::Counter64(*value)),
+            _ => None,
+        }
+    }
+
+    /// The increase since `previous`, assuming the counter wrapped at most once; `None`
+    /// when the two are of different widths.
+    fn delta(self, previous: Counter) -> Option<u64> {
+        match (previous, self) {
+            (Counter::Counter32(old), Counter::Counter32(new)) => {
+                Some(new.wrapping_sub(old).into())
+            }
+            (Counter::Counter64(old), Counter::Counter64(new)) => Some(new.wrapping_sub(old)),
+            _ => None,
+        }
+    }
+}
+
+/// The previous sample of every counter, keyed by OID, for computing rates between polls.
+///
+/// A counter that went down is taken to have wrapped around (RFC 2578 7.1.6). Counters also
+/// restart from zero when the agent reboots, which looks the same; call
+/// [`CounterTracker::reset`] when sysUpTime goes backwards.
+#[derive(Debug, Clone, Default)]
+pub struct CounterTracker {
+    samples: HashMap<Oid, (Counter, Instant)>,
+}
+
+impl CounterTracker {
+    pub fn new() -> Self {
+        CounterTracker::default()
+    }
+
+    /// Records a sample taken now; see [`CounterTracker::record_at`].
+    pub fn record(&mut self, oid: impl Into<Oid>, value: &Value) -> Option<f64> {
+        self.record_at(oid, value, Instant::now())
+    }
+
+    /// Records a sample taken `at` and returns the per-second rate since the previous one.
+    /// There is no rate for the first sample of an OID, for values that are not counters,
+    /// for a counter that changed width, or when no time has passed.
+    pub fn record_at(&mut self, oid: impl Into<Oid>, value: &Value, at: Instant) -> Option<f64> {
+        let counter = Counter::from_value(value)?;
+        let (previous, then) = self.samples.insert(oid.into(), (counter, at))?;
+
+        let delta = counter.delta(previous)?;
+        let elapsed = at.checked_duration_since(then)?.as_secs_f64();
+
+        (elapsed > 0.0).then(|| delta as f64 / elapsed)
+    }
+
+    /// Records every counter in `vars`, e.g. the result of a walk, and returns the rates
+    /// that could be computed.
+    pub fn record_all<I, O>(&mut self, vars: I) -> Vec<(Oid, f64)>
+    where
+        I: IntoIterator<Item = (O, Value)>,
+        O: Into<Oid>,
+    {
+        let now = Instant::now();
+
+        vars.into_iter()
+            .filter_map(|(oid, value)| {
+                let oid = oid.into();
+                let rate = self.record_at(oid.clone(), &value, now)?;
+
+                Some((oid, rate))
+            })
+            .collect()
+    }
+
+    /// Forgets the previous sample of `oid`.
+    pub fn remove(&mut self, oid: &Oid) {
+        self.samples.remove(oid);
+    }
+
+    /// Forgets every previous sample, so the next poll starts afresh.
+    pub fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
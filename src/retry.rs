@@ -0,0 +1,76 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How often a request is retransmitted when no response arrives within the timeout, and
+/// how long to pause before each retransmission. Retransmissions reuse the request-id, so
+/// a late answer to an earlier attempt is still accepted (RFC 3416 4.1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    retries: u32,
+    delay: Duration,
+    backoff: f64,
+    jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    /// One retransmission without pause, which is what sessions always did.
+    fn default() -> Self {
+        RetryPolicy::new(1)
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            delay: Duration::ZERO,
+            backoff: 1.0,
+            jitter: 0.0,
+        }
+    }
+
+    /// Never retransmit.
+    pub fn none() -> Self {
+        RetryPolicy::new(0)
+    }
+
+    pub(crate) fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Pause before the first retransmission.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Factor the pause grows by with each further retransmission.
+    pub fn backoff(mut self, multiplier: f64) -> Self {
+        self.backoff = multiplier.max(1.0);
+        self
+    }
+
+    /// Randomizes each pause by up to `fraction` in either direction, so pollers that
+    /// started together drift apart.
+    pub fn jitter(mut self, fraction: f64) -> Self {
+        self.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The pause before retransmission `attempt`, counting from 1.
+    pub(crate) fn pause(&self, attempt: u32) -> Duration {
+        let base = self.delay.as_secs_f64() * self.backoff.powi(attempt as i32 - 1);
+
+        // A uniform sample in [-1, 1] from the randomly keyed std hasher.
+        let sample = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        let factor = 1.0 + self.jitter * (2.0 * sample - 1.0);
+
+        Duration::try_from_secs_f64(base * factor).unwrap_or(Duration::MAX)
+    }
+}
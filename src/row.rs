@@ -0,0 +1,112 @@
+//! Creating and destroying conceptual rows through their RowStatus column (RFC 2579).
+
+use std::fmt;
+
+use crate::{ErrorStatus, Oid, SnmpError, Value};
+
+/// The RowStatus textual convention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RowStatus {
+    Active = 1,
+    NotInService = 2,
+    NotReady = 3,
+    CreateAndGo = 4,
+    CreateAndWait = 5,
+    Destroy = 6,
+}
+
+impl TryFrom<i64> for RowStatus {
+    type Error = i64;
+
+    fn try_from(status: i64) -> Result<Self, i64> {
+        Ok(match status {
+            1 => RowStatus::Active,
+            2 => RowStatus::NotInService,
+            3 => RowStatus::NotReady,
+            4 => RowStatus::CreateAndGo,
+            5 => RowStatus::CreateAndWait,
+            6 => RowStatus::Destroy,
+            other => return Err(other),
+        })
+    }
+}
+
+impl From<RowStatus> for Value {
+    fn from(status: RowStatus) -> Self {
+        Value::Integer(status as i64)
+    }
+}
+
+impl fmt::Display for RowStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RowStatus::Active => "active",
+            RowStatus::NotInService => "notInService",
+            RowStatus::NotReady => "notReady",
+            RowStatus::CreateAndGo => "createAndGo",
+            RowStatus::CreateAndWait => "createAndWait",
+            RowStatus::Destroy => "destroy",
+        })
+    }
+}
+
+/// A row of a table, addressed by the OID of its entry, the column of its RowStatus and
+/// its index.
+#[derive(Debug)]
+pub(crate) struct Row {
+    entry: Oid,
+    status_column: u32,
+    index: Vec<u32>,
+}
+
+impl Row {
+    pub(crate) fn new(entry: Oid, status_column: u32, index: &[u32]) -> Self {
+        Row {
+            entry,
+            status_column,
+            index: index.to_vec(),
+        }
+    }
+
+    fn instance(&self, column: u32) -> Oid {
+        [self.entry.as_slice(), &[column], &self.index]
+            .concat()
+            .into()
+    }
+
+    pub(crate) fn status(&self, status: RowStatus) -> (Oid, Value) {
+        (self.instance(self.status_column), status.into())
+    }
+
+    pub(crate) fn columns(&self, columns: &[(u32, Value)]) -> Vec<(Oid, Value)> {
+        columns
+            .iter()
+            .map(|(column, value)| (self.instance(*column), value.clone()))
+            .collect()
+    }
+
+    /// The columns and then the status, to set in one request.
+    pub(crate) fn with_status(
+        &self,
+        columns: &[(u32, Value)],
+        status: RowStatus,
+    ) -> Vec<(Oid, Value)> {
+        let mut bindings = self.columns(columns);
+        bindings.push(self.status(status));
+        bindings
+    }
+}
+
+/// Whether a createAndGo was refused in a way that creating the row in steps may get
+/// around: agents that do not support createAndGo answer wrongValue, and those that need
+/// columns set before the row can be active answer inconsistentValue.
+pub(crate) fn create_in_steps(err: &SnmpError) -> bool {
+    matches!(
+        err,
+        SnmpError::AgentError {
+            status: ErrorStatus::InconsistentValue | ErrorStatus::WrongValue,
+            ..
+        }
+    )
+}
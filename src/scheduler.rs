@@ -0,0 +1,384 @@
+//! Collecting samples from many agents, each on an interval of its own.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::trace;
+use crate::{oid, IntoOid, Oid, SessionBuilder, SnmpError, SnmpResult, SyncSession, Table, Value};
+
+/// How many intervals a job backs off to at most while its agent is down, unless set.
+const DEFAULT_BACKOFF_INTERVALS: u32 = 8;
+
+#[derive(Debug, Clone)]
+enum Collect {
+    Get(Vec<Oid>),
+    Walk(Oid),
+    Table(Oid, Vec<u32>),
+}
+
+/// What a [`Scheduler`] collects from an agent, and how often.
+#[derive(Debug, Clone)]
+pub struct Job {
+    builder: SessionBuilder,
+    collect: Collect,
+    interval: Duration,
+    jitter: Duration,
+    max_backoff: Duration,
+}
+
+impl Job {
+    fn new(builder: SessionBuilder, collect: Collect, interval: Duration) -> Self {
+        Job {
+            builder,
+            collect,
+            interval,
+            jitter: Duration::ZERO,
+            max_backoff: interval * DEFAULT_BACKOFF_INTERVALS,
+        }
+    }
+
+    /// Fetches `oids` with one GET every `interval`.
+    pub fn get<O: IntoOid + Clone>(
+        builder: SessionBuilder,
+        oids: &[O],
+        interval: Duration,
+    ) -> SnmpResult<Self> {
+        Ok(Job::new(
+            builder,
+            Collect::Get(oid::into_oids(oids)?),
+            interval,
+        ))
+    }
+
+    /// Walks the subtree at `oid` every `interval`.
+    pub fn walk(
+        builder: SessionBuilder,
+        oid: impl IntoOid,
+        interval: Duration,
+    ) -> SnmpResult<Self> {
+        Ok(Job::new(builder, Collect::Walk(oid.into_oid()?), interval))
+    }
+
+    /// Fetches `columns` of `table` like [`SyncSession::get_table`] every `interval`.
+    pub fn table(
+        builder: SessionBuilder,
+        table: impl IntoOid,
+        columns: &[u32],
+        interval: Duration,
+    ) -> SnmpResult<Self> {
+        let collect = Collect::Table(table.into_oid()?, columns.to_vec());
+
+        Ok(Job::new(builder, collect, interval))
+    }
+
+    /// Delays each collection by a random amount up to `jitter`, so jobs with the same
+    /// interval do not all hit the network at once.
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// While the agent does not answer, waits twice as long after each failed collection,
+    /// up to `max`, before trying again; eight intervals unless set. `Duration::ZERO`
+    /// keeps to the interval.
+    pub fn max_backoff(mut self, max: Duration) -> Self {
+        self.max_backoff = max;
+        self
+    }
+}
+
+/// What one collection returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Collected {
+    /// The bindings of a GET, or the instances of a walk.
+    Bindings(Vec<(Oid, Value)>),
+    /// The rows of the table at `table`.
+    Table { table: Oid, rows: Table },
+}
+
+impl Collected {
+    /// Every instance collected, with table cells named by their OID.
+    pub fn bindings(&self) -> Vec<(Oid, Value)> {
+        match self {
+            Collected::Bindings(vars) => vars.clone(),
+            Collected::Table { table, rows } => rows
+                .iter()
+                .flat_map(|(index, row)| {
+                    row.iter().map(move |(column, value)| {
+                        let oid = [table.as_slice(), &[1, *column], index].concat();
+                        (oid.into(), value.clone())
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The outcome of one collection of a job.
+#[derive(Debug)]
+pub struct Sample {
+    /// Index of the job in the list given to [`Scheduler::start`].
+    pub job: usize,
+    /// When the collection began.
+    pub time: SystemTime,
+    /// The same instant, for computing rates with
+    /// [`CounterTracker::record_at`](crate::CounterTracker::record_at).
+    pub started: Instant,
+    /// Intervals since the previous sample of the job that went without one, because a
+    /// collection ran long or the job was backing off.
+    pub missed: u64,
+    /// The agent address the collection went to last; `None` when the session could not
+    /// be opened.
+    pub peer: Option<SocketAddr>,
+    pub result: SnmpResult<Collected>,
+}
+
+#[derive(Default)]
+struct State {
+    session: Option<SyncSession>,
+    /// Collections that failed in a row.
+    failures: u32,
+    /// The interval of the last collection, counted from the start.
+    last_tick: Option<u64>,
+}
+
+/// A job and the session to it, opened on first use and reopened after it fails to open.
+struct Slot {
+    job: Job,
+    state: Mutex<State>,
+}
+
+struct Shared {
+    slots: Vec<Slot>,
+    origin: Instant,
+    random: RandomState,
+}
+
+impl Shared {
+    /// The random delay of the `tick`th collection of job `index`.
+    fn jitter(&self, index: usize, tick: u64) -> Duration {
+        let jitter = self.slots[index].job.jitter;
+        if jitter.is_zero() {
+            return Duration::ZERO;
+        }
+
+        let nanos = self.random.hash_one((index, tick)) % jitter.as_nanos().max(1) as u64;
+        Duration::from_nanos(nanos)
+    }
+
+    /// Which interval since the start `at` falls in.
+    fn tick(&self, index: usize, at: Instant) -> u64 {
+        let interval = self.slots[index].job.interval.max(Duration::from_millis(1));
+        (at.saturating_duration_since(self.origin).as_nanos() / interval.as_nanos()) as u64
+    }
+
+    /// When the `tick`th collection of job `index` is due.
+    fn due(&self, index: usize, tick: u64) -> Instant {
+        let interval = self.slots[index].job.interval.max(Duration::from_millis(1));
+        let since = interval.as_nanos().saturating_mul(u128::from(tick));
+        self.origin
+            + Duration::from_nanos(since.try_into().unwrap_or(u64::MAX))
+            + self.jitter(index, tick)
+    }
+
+    fn collect(&self, index: usize, samples: &Sender<Sample>, events: &Sender<Event>) {
+        let slot = &self.slots[index];
+        let mut state = slot.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let (time, started) = (SystemTime::now(), Instant::now());
+        let tick = self.tick(index, started);
+        let missed = state
+            .last_tick
+            .map_or(0, |last| tick.saturating_sub(last + 1));
+        state.last_tick = Some(tick);
+
+        let result = match &state.session {
+            Some(session) => collect(session, &slot.job.collect),
+            None => match slot.job.builder.clone().build() {
+                Ok(opened) => collect(state.session.insert(opened), &slot.job.collect),
+                Err(err) => Err(err.into()),
+            },
+        };
+        let peer = state
+            .session
+            .as_ref()
+            .and_then(|session| session.peer_addr().ok());
+
+        let next = if result.as_ref().is_err_and(is_down) && !slot.job.max_backoff.is_zero() {
+            state.failures = state.failures.saturating_add(1);
+            let backoff = slot
+                .job
+                .interval
+                .saturating_mul(1 << state.failures.min(16))
+                .min(slot.job.max_backoff);
+            trace::event!(debug, job = index, ?backoff, "agent down, backing off");
+            Instant::now() + backoff
+        } else {
+            state.failures = 0;
+            // Ticks that went by while collecting are skipped rather than made up for.
+            let mut next = tick + 1;
+            while self.due(index, next) < Instant::now() {
+                next += 1;
+            }
+            self.due(index, next)
+        };
+        drop(state);
+
+        let _ = samples.send(Sample {
+            job: index,
+            time,
+            started,
+            missed,
+            peer,
+            result,
+        });
+        let _ = events.send(Event::Due(index, next));
+    }
+}
+
+/// Whether a collection failed because the agent could not be reached.
+fn is_down(err: &SnmpError) -> bool {
+    matches!(
+        err,
+        SnmpError::Timeout
+            | SnmpError::PortUnreachable
+            | SnmpError::HostUnreachable
+            | SnmpError::Io(_)
+    )
+}
+
+fn collect(session: &SyncSession, collect: &Collect) -> SnmpResult<Collected> {
+    match collect {
+        Collect::Get(oids) => session.get_many(oids).map(Collected::Bindings),
+        Collect::Walk(oid) => Ok(Collected::Bindings(
+            session
+                .walk(oid)?
+                .into_iter()
+                .map(|(suffix, value)| ([oid.as_slice(), &suffix].concat().into(), value))
+                .collect(),
+        )),
+        Collect::Table(table, columns) => Ok(Collected::Table {
+            table: table.clone(),
+            rows: session.get_table(table, columns)?,
+        }),
+    }
+}
+
+enum Event {
+    Due(usize, Instant),
+    Stop,
+}
+
+/// Collects [`Job`]s on their intervals with a bounded pool of worker threads, delivering
+/// every [`Sample`] to [`Scheduler::samples`].
+///
+/// Collections are due at fixed multiples of a job's interval from the start, plus its
+/// jitter; those missed while a collection ran long are skipped and counted in the next
+/// sample. A job whose agent does not answer backs off, and returns to its schedule
+/// after the first collection that is answered. Dropping the scheduler stops it and
+/// waits for running collections to finish.
+pub struct Scheduler {
+    events: Sender<Event>,
+    samples: Receiver<Sample>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    /// Starts collecting every job with `workers` threads, at least one.
+    pub fn start(jobs: Vec<Job>, workers: usize) -> Self {
+        let shared = Arc::new(Shared {
+            slots: jobs
+                .into_iter()
+                .map(|job| Slot {
+                    job,
+                    state: Mutex::default(),
+                })
+                .collect(),
+            origin: Instant::now(),
+            random: RandomState::new(),
+        });
+
+        let (events, received) = mpsc::channel();
+        let (samples_tx, samples) = mpsc::channel();
+        let (work_tx, work) = mpsc::channel::<usize>();
+        let work = Arc::new(Mutex::new(work));
+
+        let mut threads: Vec<_> = (0..workers.max(1))
+            .map(|_| {
+                let (shared, work) = (shared.clone(), work.clone());
+                let (samples, events) = (samples_tx.clone(), events.clone());
+
+                thread::spawn(move || loop {
+                    let index = work.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    match index {
+                        Ok(index) => shared.collect(index, &samples, &events),
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+
+        threads.push(thread::spawn(move || {
+            schedule(&shared, &work_tx, &received)
+        }));
+
+        Scheduler {
+            events,
+            samples,
+            threads,
+        }
+    }
+
+    pub fn samples(&self) -> &Receiver<Sample> {
+        &self.samples
+    }
+}
+
+/// Hands each job to the workers when it is due, and takes it back with its next due time
+/// once collected, until told to stop.
+fn schedule(shared: &Shared, work: &Sender<usize>, events: &Receiver<Event>) {
+    let mut due: BinaryHeap<_> = (0..shared.slots.len())
+        .map(|index| Reverse((shared.due(index, 0), index)))
+        .collect();
+
+    loop {
+        let wait = match due.peek() {
+            Some(Reverse((at, _))) => at.saturating_duration_since(Instant::now()),
+            None => Duration::MAX,
+        };
+
+        match events.recv_timeout(wait) {
+            Ok(Event::Due(index, at)) => due.push(Reverse((at, index))),
+            Ok(Event::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {
+                if let Some(Reverse((_, index))) = due.pop() {
+                    if work.send(index).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        let _ = self.events.send(Event::Stop);
+
+        // The scheduling thread is last; once it stops, the workers see the end of the
+        // work queue.
+        if let Some(scheduler) = self.threads.pop() {
+            let _ = scheduler.join();
+        }
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Message processing for community-based (v1/v2c) and user-based (v3) security.
+
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicI32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use rasn::types::OctetString;
+use rasn_snmp::{v1, v2, v2c, v3};
+
+use crate::options::Context;
+use crate::pdu::{self, decode, encode, encode_into};
+use crate::trace;
+use crate::usm::{self, Usm, UsmUser, UsmUserTable};
+use crate::{ber, Decoding, EngineId, Oid, SnmpError, SnmpResult, Version};
+
+/// A panic elsewhere cannot leave the USM state half-updated, so poisoning is ignored.
+fn lock(usm: &Mutex<Usm>) -> MutexGuard<'_, Usm> {
+    usm.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+pub(crate) enum Security {
+    Community {
+        /// The msgVersion, switched between SNMPv1 and SNMPv2c by version fallback.
+        version: AtomicU8,
+        /// The communities to try in order, and the one in use.
+        communities: Vec<OctetString>,
+        current: AtomicUsize,
+    },
+    Usm(Mutex<Usm>),
+    /// The Transport Security Model (RFC 5591): the transport authenticates and encrypts,
+    /// so messages carry no security parameters.
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    Tsm {
+        msg_id: AtomicI32,
+        max_size: usize,
+    },
+}
+
+const SECURITY_MODEL_TSM: u32 = 4;
+
+/// authPriv and reportable; TLS always provides both.
+const TSM_FLAGS: u8 = 0x07;
+
+impl Security {
+    pub(crate) fn community(version: u8, community: &[u8]) -> Self {
+        Security::communities(version, vec![community.to_vec()])
+    }
+
+    /// `communities` must not be empty.
+    pub(crate) fn communities(version: u8, communities: Vec<Vec<u8>>) -> Self {
+        Security::Community {
+            version: AtomicU8::new(version),
+            communities: communities.into_iter().map(OctetString::from).collect(),
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn usm(
+        user: UsmUser,
+        users: Option<UsmUserTable>,
+        max_size: usize,
+    ) -> io::Result<Self> {
+        if !user.is_valid() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "privacy requires authentication",
+            ));
+        }
+
+        let mut usm = Usm::new(user, max_size);
+        if let Some(users) = users {
+            usm = usm.with_users(users);
+        }
+
+        Ok(Security::Usm(Mutex::new(usm)))
+    }
+
+    /// Makes an SNMPv3 session with USM send its traps from the engine `id`.
+    pub(crate) fn with_local_engine(self, id: EngineId, boots: u32) -> Self {
+        match self {
+            Security::Usm(usm) => {
+                let usm = usm.into_inner().unwrap_or_else(PoisonError::into_inner);
+                Security::Usm(Mutex::new(usm.with_local_engine(id, boots)))
+            }
+            security => security,
+        }
+    }
+
+    #[cfg_attr(not(feature = "tls"), allow(dead_code))]
+    pub(crate) fn tsm(max_size: usize) -> Self {
+        Security::Tsm {
+            msg_id: AtomicI32::new(pdu::initial_request_id()),
+            max_size,
+        }
+    }
+
+    pub(crate) fn is_v1(&self) -> bool {
+        self.version() == Version::V1
+    }
+
+    pub(crate) fn version(&self) -> Version {
+        match self {
+            Security::Community { version, .. } if version.load(Ordering::Relaxed) == 0 => {
+                Version::V1
+            }
+            Security::Community { .. } => Version::V2c,
+            Security::Usm(_) | Security::Tsm { .. } => Version::V3,
+        }
+    }
+
+    /// The community requests are sent with; `None` for SNMPv3.
+    pub(crate) fn current_community(&self) -> Option<&[u8]> {
+        match self {
+            Security::Community {
+                communities,
+                current,
+                ..
+            } => Some(&communities[current.load(Ordering::Relaxed)]),
+            _ => None,
+        }
+    }
+
+    /// The agent's engine ID, once an SNMPv3 session with USM has discovered it.
+    pub(crate) fn engine_id(&self) -> Option<EngineId> {
+        match self {
+            Security::Usm(usm) => lock(usm).engine_id().cloned(),
+            _ => None,
+        }
+    }
+
+    /// The community of an SNMPv2c session, whose responses can be read without decoding
+    /// them into owned PDUs.
+    pub(crate) fn v2c_community(&self) -> Option<&[u8]> {
+        self.current_community().filter(|_| !self.is_v1())
+    }
+
+    /// The msgVersion and index of the community in use; `None` for SNMPv3.
+    pub(crate) fn credentials(&self) -> Option<(u8, usize)> {
+        match self {
+            Security::Community {
+                version, current, ..
+            } => Some((
+                version.load(Ordering::Relaxed),
+                current.load(Ordering::Relaxed),
+            )),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set_credentials(&self, (wire, index): (u8, usize)) {
+        if let Security::Community {
+            version, current, ..
+        } = self
+        {
+            version.store(wire, Ordering::Relaxed);
+            current.store(index, Ordering::Relaxed);
+        }
+    }
+
+    /// The credentials to try after the ones in use went unanswered, in order: the other
+    /// communities, then with `versions` every community in the other of SNMPv1 and SNMPv2c.
+    pub(crate) fn fallbacks(&self, versions: bool) -> Vec<(u8, usize)> {
+        let (Security::Community { communities, .. }, Some((wire, index))) =
+            (self, self.credentials())
+        else {
+            return Vec::new();
+        };
+
+        let other = if wire == 0 { 1 } else { 0 };
+        let mut fallbacks: Vec<(u8, usize)> = (0..communities.len())
+            .filter(|other| *other != index)
+            .map(|other| (wire, other))
+            .collect();
+        if versions {
+            fallbacks.extend((0..communities.len()).map(|index| (other, index)));
+        }
+
+        fallbacks
+    }
+
+    /// The next message to exchange before requests can be sent (v3 discovery), if any.
+    pub(crate) fn handshake(&self) -> SnmpResult<Option<Vec<u8>>> {
+        match self {
+            Security::Community { .. } | Security::Tsm { .. } => Ok(None),
+            Security::Usm(usm) => lock(usm).handshake(),
+        }
+    }
+
+    pub(crate) fn complete_handshake(&self, response: &[u8]) -> SnmpResult<()> {
+        match self {
+            Security::Community { .. } | Security::Tsm { .. } => Ok(()),
+            Security::Usm(usm) => lock(usm).complete_handshake(response),
+        }
+    }
+
+    /// Whether the request that just failed with a report may be resent because the report
+    /// refreshed the v3 engine state.
+    pub(crate) fn resync(&self) -> bool {
+        match self {
+            Security::Community { .. } | Security::Tsm { .. } => false,
+            Security::Usm(usm) => lock(usm).resync(),
+        }
+    }
+
+    /// Encodes a request into `buffer`, refusing messages larger than `max_size`.
+    pub(crate) fn encode(
+        &self,
+        data: v2::Pdus,
+        context: &Context,
+        max_size: usize,
+        buffer: &mut Vec<u8>,
+    ) -> SnmpResult<()> {
+        *buffer = self.encode_message(data, context, mem::take(buffer))?;
+
+        if buffer.len() > max_size {
+            return Err(SnmpError::EncodingTooLarge {
+                size: buffer.len(),
+                max: max_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn encode_message(
+        &self,
+        data: v2::Pdus,
+        context: &Context,
+        buffer: Vec<u8>,
+    ) -> SnmpResult<Vec<u8>> {
+        if !context.is_default() && matches!(self, Security::Community { .. }) {
+            return Err(SnmpError::Unsupported("contexts require SNMPv3"));
+        }
+
+        match self {
+            Security::Community {
+                version,
+                communities,
+                current,
+            } if self.is_v1() => {
+                let community = &communities[current.load(Ordering::Relaxed)];
+                let message = v1::Message {
+                    version: version.load(Ordering::Relaxed).into(),
+                    community: community.clone(),
+                    data: crate::v1::to_pdus(data)?,
+                };
+
+                encode_into(&message, buffer)
+            }
+            Security::Community {
+                version,
+                communities,
+                current,
+            } => {
+                let community = &communities[current.load(Ordering::Relaxed)];
+                let message = v2c::Message {
+                    version: version.load(Ordering::Relaxed).into(),
+                    community: community.clone(),
+                    data,
+                };
+
+                encode_into(&message, buffer)
+            }
+            Security::Usm(usm) => lock(usm).encode_into(data, context, buffer),
+            Security::Tsm { msg_id, max_size } => {
+                let message = v3::Message {
+                    version: 3.into(),
+                    global_data: v3::HeaderData {
+                        message_id: (msg_id.fetch_add(1, Ordering::Relaxed) & i32::MAX).into(),
+                        max_size: (*max_size).into(),
+                        flags: vec![TSM_FLAGS].into(),
+                        security_model: SECURITY_MODEL_TSM.into(),
+                    },
+                    security_parameters: OctetString::new(),
+                    // By default the contextEngineID that addresses whichever engine
+                    // answers, so TSM needs no discovery.
+                    scoped_data: v3::ScopedPduData::CleartextPdu(v3::ScopedPdu {
+                        engine_id: context
+                            .engine_id
+                            .clone()
+                            .unwrap_or_else(EngineId::local)
+                            .into_bytes()
+                            .into(),
+                        name: context.name.clone().unwrap_or_default().into(),
+                        data,
+                    }),
+                };
+
+                encode_into(&message, buffer)
+            }
+        }
+    }
+
+    /// Encodes a v1 Trap-PDU, which only SNMPv1 sessions can send.
+    pub(crate) fn encode_v1_trap(&self, trap: v1::Trap) -> SnmpResult<Vec<u8>> {
+        match self {
+            Security::Community {
+                version,
+                communities,
+                current,
+            } if self.is_v1() => {
+                let community = &communities[current.load(Ordering::Relaxed)];
+                let message = v1::Message {
+                    version: version.load(Ordering::Relaxed).into(),
+                    community: community.clone(),
+                    data: v1::Pdus::Trap(trap),
+                };
+
+                encode(&message)
+            }
+            _ => Err(SnmpError::Unsupported("Trap-PDU requires SNMPv1")),
+        }
+    }
+
+    /// Decodes the response to request `request_id`, sent as `request`, held to the encoding
+    /// rules as `decoding` says. Datagrams that belong to another request or carry a
+    /// different community yield `None` so the caller can keep waiting; so do Reports on
+    /// another message, which would otherwise fail this request.
+    pub(crate) fn decode(
+        &self,
+        response: &[u8],
+        request: &[u8],
+        request_id: i32,
+        decoding: Decoding,
+    ) -> SnmpResult<Option<v2::Pdus>> {
+        let mut data = match self {
+            Security::Community {
+                communities,
+                current,
+                ..
+            } if self.is_v1() => {
+                let community = &communities[current.load(Ordering::Relaxed)];
+                decoding.check_encoding(response, Version::V1)?;
+
+                // Every v1 PDU but the Trap-PDU is encoded as its v2 counterpart, which
+                // also takes the v2 types some v1 agents answer with.
+                let lenient = match decoding {
+                    Decoding::Lenient => decode::<v2c::Message<v2::Pdus>>(response).ok(),
+                    Decoding::Strict => None,
+                };
+                let (received, data) = match lenient {
+                    Some(message) => (message.community, Ok(message.data)),
+                    None => {
+                        let message: v1::Message<v1::Pdus> = decode(response)?;
+                        (message.community, crate::v1::from_pdus(message.data))
+                    }
+                };
+                if received != *community {
+                    trace::event!(debug, "discarding message with another community");
+                    return Ok(None);
+                }
+
+                data?
+            }
+            Security::Community {
+                communities,
+                current,
+                ..
+            } => {
+                let community = &communities[current.load(Ordering::Relaxed)];
+                decoding.check_encoding(response, Version::V2c)?;
+                let message: v2c::Message<v2::Pdus> = decode(response)?;
+                if message.community != *community {
+                    trace::event!(debug, "discarding message with another community");
+                    return Ok(None);
+                }
+
+                message.data
+            }
+            Security::Usm(usm) => {
+                let data = lock(usm).decode_with(response, decoding)?;
+
+                if let Some(oid) = usm::report_oid(&data) {
+                    return report(oid, response, request);
+                }
+
+                data
+            }
+            Security::Tsm { .. } => {
+                decoding.check_encoding(response, Version::V3)?;
+                let message: v3::Message = decode(response)?;
+                if message.global_data.security_model != SECURITY_MODEL_TSM.into() {
+                    return Err(SnmpError::InvalidMessage("unsupported security model"));
+                }
+
+                let v3::ScopedPduData::CleartextPdu(scoped) = message.scoped_data else {
+                    return Err(SnmpError::InvalidMessage("encrypted PDU under TSM"));
+                };
+
+                if let Some(oid) = usm::report_oid(&scoped.data) {
+                    return report(oid, response, request);
+                }
+
+                scoped.data
+            }
+        };
+
+        let received = pdu::request_id(&data);
+        if received != request_id {
+            trace::event!(
+                debug,
+                expected = request_id,
+                received,
+                "discarding response to another request"
+            );
+            return Ok(None);
+        }
+
+        decoding.check_pdu(&mut data, self.version())?;
+        Ok(Some(data))
+    }
+}
+
+/// The error of a Report carrying the `oid` counter, if it answers `request`: Reports are
+/// paired by msgID, as their request-id may not be that of the request.
+fn report(oid: Oid, response: &[u8], request: &[u8]) -> SnmpResult<Option<v2::Pdus>> {
+    if ber::message_id(response) != ber::message_id(request) {
+        trace::event!(debug, "discarding report on another message");
+        return Ok(None);
+    }
+
+    Err(usm::report_error(oid))
+}
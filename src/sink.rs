@@ -0,0 +1,179 @@
+//! Destinations a trap listener hands its events to, for wiring notifications into an
+//! application or an alerting system without a trap daemon in between.
+
+use std::io;
+use std::sync::mpsc;
+
+use crate::TrapEvent;
+
+/// Receives every event a [`TrapListener`](crate::TrapListener) returns, added with
+/// [`TrapListener::sink`](crate::TrapListener::sink).
+///
+/// Implemented for closures, for the senders of `std` channels and, with the `tokio`
+/// feature, of tokio's, and by [`Webhook`] with the `webhook` feature. Sinks are called on
+/// the listener's thread, or task, as events are received, so slow ones hold up the
+/// listener.
+pub trait TrapSink: Send {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()>;
+}
+
+impl<F: FnMut(&TrapEvent) -> io::Result<()> + Send> TrapSink for F {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+        self(event)
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "channel receiver dropped")
+}
+
+impl TrapSink for mpsc::Sender<TrapEvent> {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+        self.send(event.clone()).map_err(|_| disconnected())
+    }
+}
+
+/// Blocks while the channel is full.
+impl TrapSink for mpsc::SyncSender<TrapEvent> {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+        self.send(event.clone()).map_err(|_| disconnected())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl TrapSink for tokio::sync::mpsc::UnboundedSender<TrapEvent> {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+        self.send(event.clone()).map_err(|_| disconnected())
+    }
+}
+
+/// Drops the event when the channel is full rather than block the listener's task.
+#[cfg(feature = "tokio")]
+impl TrapSink for tokio::sync::mpsc::Sender<TrapEvent> {
+    fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+        self.try_send(event.clone()).map_err(|err| match err {
+            tokio::sync::mpsc::error::TrySendError::Full(_) => {
+                io::Error::new(io::ErrorKind::WouldBlock, "channel full")
+            }
+            tokio::sync::mpsc::error::TrySendError::Closed(_) => disconnected(),
+        })
+    }
+}
+
+#[cfg(feature = "webhook")]
+pub use webhook::Webhook;
+
+#[cfg(feature = "webhook")]
+mod webhook {
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::time::Duration;
+
+    use super::TrapSink;
+    use crate::TrapEvent;
+
+    fn invalid(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }
+
+    /// POSTs every event as a JSON object, serialized as with the `serde` feature, to an
+    /// `http://` URL, e.g. `Webhook::new("http://alerts.example.net:8080/snmp")?`. Each event
+    /// is sent on a connection of its own, and any status but 2xx is a failed delivery.
+    /// HTTPS is left to a local proxy.
+    #[derive(Debug, Clone)]
+    pub struct Webhook {
+        host: String,
+        port: u16,
+        path: String,
+        headers: Vec<(String, String)>,
+        timeout: Duration,
+    }
+
+    impl Webhook {
+        pub fn new(url: &str) -> io::Result<Self> {
+            let rest = url
+                .strip_prefix("http://")
+                .ok_or_else(|| invalid("webhook URL must start with http://"))?;
+            let (authority, path) = match rest.find('/') {
+                Some(at) => (&rest[..at], &rest[at..]),
+                None => (rest, "/"),
+            };
+            let (host, port) = match authority.rsplit_once(':') {
+                Some((host, port)) if !port.contains(']') => (
+                    host,
+                    port.parse()
+                        .map_err(|_| invalid("invalid port in webhook URL"))?,
+                ),
+                _ => (authority, 80),
+            };
+            if host.is_empty() {
+                return Err(invalid("webhook URL without host"));
+            }
+
+            Ok(Webhook {
+                host: host.to_string(),
+                port,
+                path: path.to_string(),
+                headers: Vec::new(),
+                timeout: Duration::from_secs(5),
+            })
+        }
+
+        /// Sends `name: value` with every request, e.g. an `Authorization` header.
+        pub fn header(mut self, name: &str, value: &str) -> Self {
+            self.headers.push((name.to_string(), value.to_string()));
+            self
+        }
+
+        /// How long connecting, sending and waiting for the status may each take; five
+        /// seconds by default.
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        fn post(&self, body: &[u8]) -> io::Result<()> {
+            let host = self.host.trim_start_matches('[').trim_end_matches(']');
+            let addr = (host, self.port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| invalid("webhook host has no address"))?;
+
+            let mut stream = TcpStream::connect_timeout(&addr, self.timeout)?;
+            stream.set_read_timeout(Some(self.timeout))?;
+            stream.set_write_timeout(Some(self.timeout))?;
+
+            let mut request = format!(
+                "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n",
+                self.path,
+                self.host,
+                self.port,
+                body.len()
+            );
+            for (name, value) in &self.headers {
+                request.push_str(&format!("{name}: {value}\r\n"));
+            }
+            request.push_str("\r\n");
+            stream.write_all(request.as_bytes())?;
+            stream.write_all(body)?;
+
+            let mut status = String::new();
+            BufReader::new(stream).read_line(&mut status)?;
+            match status.split(' ').nth(1) {
+                Some(code) if code.starts_with('2') => Ok(()),
+                _ => Err(io::Error::other(format!(
+                    "webhook answered {:?}",
+                    status.trim_end()
+                ))),
+            }
+        }
+    }
+
+    impl TrapSink for Webhook {
+        fn deliver(&mut self, event: &TrapEvent) -> io::Result<()> {
+            let body = serde_json::to_vec(event)?;
+            self.post(&body)
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Per-session counters of how requests fared on the wire.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::SnmpError;
+
+/// A snapshot of a session's [`SyncSession::stats`](crate::SyncSession::stats), counted
+/// from when it was opened.
+///
+/// Round-trip times are only sampled from requests answered without a retransmission,
+/// since the answer to a retransmitted one may be to any of its copies (Karn's algorithm).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionStats {
+    /// Messages sent, retransmissions and v3 discovery included.
+    pub requests: u64,
+    /// Of those, the retransmissions.
+    pub retries: u64,
+    /// Requests that went unanswered after every retransmission.
+    pub timeouts: u64,
+    /// Responses that were malformed, truncated, or not responses at all.
+    pub decode_errors: u64,
+    pub rtt_min: Option<Duration>,
+    pub rtt_avg: Option<Duration>,
+    pub rtt_max: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    stats: SessionStats,
+    rtt_total: Duration,
+    rtt_samples: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Stats(Mutex<Counters>);
+
+impl Stats {
+    fn update(&self, update: impl FnOnce(&mut Counters)) {
+        update(&mut self.0.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    pub(crate) fn sent(&self, attempt: u32) {
+        self.update(|counters| {
+            counters.stats.requests += 1;
+            if attempt > 0 {
+                counters.stats.retries += 1;
+            }
+        });
+    }
+
+    pub(crate) fn answered(&self, attempt: u32, rtt: Duration) {
+        if attempt > 0 {
+            return;
+        }
+
+        self.update(|counters| {
+            let stats = &mut counters.stats;
+            stats.rtt_min = Some(stats.rtt_min.map_or(rtt, |min| min.min(rtt)));
+            stats.rtt_max = Some(stats.rtt_max.map_or(rtt, |max| max.max(rtt)));
+
+            counters.rtt_total += rtt;
+            counters.rtt_samples += 1;
+            let avg = counters.rtt_total.as_nanos() / u128::from(counters.rtt_samples);
+            stats.rtt_avg = Some(Duration::from_nanos(avg as u64));
+        });
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.update(|counters| counters.stats.timeouts += 1);
+    }
+
+    /// Counts `err`, which a received message was rejected with, if it is a decode error.
+    pub(crate) fn rejected(&self, err: &SnmpError) {
+        if matches!(
+            err,
+            SnmpError::Decode { .. }
+                | SnmpError::Truncated { .. }
+                | SnmpError::InvalidMessage(_)
+                | SnmpError::UnexpectedPdu
+        ) {
+            self.update(|counters| counters.stats.decode_errors += 1);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> SessionStats {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner).stats
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use crate::{Oid, Ticks, Value};
+
+/// sysDescr.0 through sysServices.0 of the SNMPv2-MIB system group, in field order.
+pub(crate) const SYSTEM_OIDS: [[u32; 9]; 7] = [
+    [1, 3, 6, 1, 2, 1, 1, 1, 0],
+    [1, 3, 6, 1, 2, 1, 1, 2, 0],
+    [1, 3, 6, 1, 2, 1, 1, 3, 0],
+    [1, 3, 6, 1, 2, 1, 1, 4, 0],
+    [1, 3, 6, 1, 2, 1, 1, 5, 0],
+    [1, 3, 6, 1, 2, 1, 1, 6, 0],
+    [1, 3, 6, 1, 2, 1, 1, 7, 0],
+];
+
+/// The system group of an agent (RFC 3418), as returned by
+/// [`SyncSession::system_info`](crate::SyncSession::system_info). Objects the agent does
+/// not implement keep their default, and text that is not UTF-8 is converted lossily.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemInfo {
+    pub descr: String,
+    /// The vendor's identification of the device, e.g. under `1.3.6.1.4.1.9` for Cisco.
+    pub object_id: Oid,
+    /// Time since the agent's network management portion was last re-initialized.
+    pub uptime: Duration,
+    pub contact: String,
+    pub name: String,
+    pub location: String,
+    /// The OSI layers the device offers services at, as a bit per layer.
+    pub services: u8,
+}
+
+impl SystemInfo {
+    /// Builds the info from the response to a GET of [`SYSTEM_OIDS`].
+    pub(crate) fn from_bindings(vars: Vec<(Oid, Value)>) -> Self {
+        let mut info = SystemInfo::default();
+
+        for (oid, value) in vars {
+            let Some(index) = SYSTEM_OIDS.iter().position(|arcs| oid.as_slice() == arcs) else {
+                continue;
+            };
+
+            match (index, value) {
+                (1, Value::Oid(arcs)) => info.object_id = arcs.into(),
+                (2, Value::TimeTicks(ticks)) => info.uptime = Ticks(ticks).duration(),
+                (6, Value::Integer(services)) => info.services = services as u8,
+                (index, Value::OctetString(text)) => {
+                    let text = String::from_utf8_lossy(&text).into_owned();
+
+                    match index {
+                        0 => info.descr = text,
+                        3 => info.contact = text,
+                        4 => info.name = text,
+                        5 => info.location = text,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        info
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::{panic, thread};
+
+use crate::options::DEFAULT_MAX_REPETITIONS;
+use crate::{
+    pdu, Dispatcher, IntoOid, Oid, SessionBuilder, SnmpResult, SyncSession, Transport, Value,
+};
+
+/// Rows of a conceptual table keyed by their index arcs, each mapping column sub-IDs to
+/// values. Sparse rows simply lack the columns the agent did not return.
+pub type Table = BTreeMap<Vec<u32>, HashMap<u32, Value>>;
+
+/// Fetches the columns of a table at the same time over several sessions, e.g.
+/// `ColumnWalk::new(SessionBuilder::new("192.0.2.1"), "1.3.6.1.2.1.2.2", &[2, 8, 10])?.run()?`.
+///
+/// Each column is walked on its own, with GETBULK unless the session is SNMPv1, by one of
+/// up to [`concurrency`](ColumnWalk::concurrency) sessions built from the builder, and the
+/// rows are joined by index. On high-latency links the table then takes about as long as
+/// its longest column rather than all of them. The sessions have a socket each, or share
+/// that of a [`Dispatcher`] with [`ColumnWalk::run_dispatched`]. Defaults to four sessions.
+#[derive(Debug, Clone)]
+pub struct ColumnWalk {
+    builder: SessionBuilder,
+    table: Oid,
+    columns: Vec<u32>,
+    concurrency: usize,
+    max_repetitions: u32,
+}
+
+impl ColumnWalk {
+    /// With no `columns`, the whole table is walked by a single session.
+    pub fn new(builder: SessionBuilder, table: impl IntoOid, columns: &[u32]) -> SnmpResult<Self> {
+        Ok(ColumnWalk {
+            builder,
+            table: table.into_oid()?,
+            columns: columns.to_vec(),
+            concurrency: 4,
+            max_repetitions: DEFAULT_MAX_REPETITIONS,
+        })
+    }
+
+    /// How many columns are walked at once, each by a session of its own.
+    pub fn concurrency(mut self, sessions: usize) -> Self {
+        self.concurrency = sessions.max(1);
+        self
+    }
+
+    /// Rows of a column per GETBULK; still halved when the agent answers tooBig.
+    pub fn max_repetitions(mut self, max_repetitions: u32) -> Self {
+        self.max_repetitions = max_repetitions;
+        self
+    }
+
+    pub fn run(&self) -> SnmpResult<Table> {
+        self.walk(|| self.builder.clone().build())
+    }
+
+    /// Runs the sessions over the shared socket of `dispatcher`.
+    pub fn run_dispatched(&self, dispatcher: &Dispatcher) -> SnmpResult<Table> {
+        self.walk(|| self.builder.clone().build_dispatched(dispatcher))
+    }
+
+    /// Walks the columns with sessions from `open`, until all are done or one fails; the
+    /// first error is returned.
+    fn walk<T, F>(&self, open: F) -> SnmpResult<Table>
+    where
+        T: Transport,
+        F: Fn() -> io::Result<SyncSession<T>> + Sync,
+    {
+        if self.columns.is_empty() {
+            return open()?.get_table_bulk(&self.table, &[], self.max_repetitions);
+        }
+
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let table = Mutex::new(Table::new());
+
+        let worker = || -> SnmpResult<()> {
+            let session = open()?;
+
+            while let Some(column) = self.columns.get(next.fetch_add(1, Ordering::Relaxed)) {
+                if failed.load(Ordering::Relaxed) {
+                    break;
+                }
+                let rows = session
+                    .get_table_bulk(&self.table, &[*column], self.max_repetitions)
+                    .inspect_err(|_| failed.store(true, Ordering::Relaxed))?;
+
+                let mut table = table.lock().unwrap_or_else(PoisonError::into_inner);
+                for (index, values) in rows {
+                    table.entry(index).or_default().extend(values);
+                }
+            }
+
+            Ok(())
+        };
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.concurrency.min(self.columns.len()))
+                .map(|_| scope.spawn(worker))
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .fold(Ok(()), SnmpResult::and)
+        })?;
+
+        Ok(table.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Walks the columns of a table side by side, one GETNEXT per row with a binding for each
+/// column that has not run out yet.
+pub(crate) struct TableWalk {
+    entry: Oid,
+    /// The subtree each cursor walks and the OID it has reached.
+    cursors: Vec<(Oid, Option<Oid>)>,
+    table: Table,
+    last: Option<Oid>,
+}
+
+impl TableWalk {
+    /// With no `columns`, the whole entry is walked as a single column.
+    pub(crate) fn new(table: Oid, columns: &[u32]) -> Self {
+        // Tables are SEQUENCE OF an entry type registered as `table.1`.
+        let entry: Oid = [table.as_slice(), &[1]].concat().into();
+
+        let mut prefixes: Vec<Oid> = columns
+            .iter()
+            .map(|column| [entry.as_slice(), &[*column]].concat().into())
+            .collect();
+        if prefixes.is_empty() {
+            prefixes.push(entry.clone());
+        }
+
+        TableWalk {
+            entry,
+            cursors: prefixes
+                .into_iter()
+                .map(|prefix| (prefix.clone(), Some(prefix)))
+                .collect(),
+            table: Table::new(),
+            last: None,
+        }
+    }
+
+    /// The OIDs to send in the next GETNEXT, or `None` once every column has ended.
+    pub(crate) fn request(&self) -> Option<Vec<Oid>> {
+        let oids: Vec<Oid> = self
+            .cursors
+            .iter()
+            .filter_map(|(_, current)| current.clone())
+            .collect();
+
+        (!oids.is_empty()).then_some(oids)
+    }
+
+    /// The cursors that have not ended, in request order.
+    fn active(&self) -> Vec<usize> {
+        (0..self.cursors.len())
+            .filter(|cursor| self.cursors[*cursor].1.is_some())
+            .collect()
+    }
+
+    /// Feeds the response to [`TableWalk::request`], in the same order.
+    pub(crate) fn response(&mut self, vars: Vec<(Oid, Value)>) -> SnmpResult<()> {
+        for (cursor, var) in self.active().into_iter().zip(vars) {
+            self.step(cursor, vec![var])?;
+        }
+
+        Ok(())
+    }
+
+    /// Feeds the response to a GETBULK of [`TableWalk::request`] without non-repeaters:
+    /// rows of one binding per column, in the same order. A column that ends early in a
+    /// sparse table stops there, and the bindings it wandered into the next column with
+    /// are dropped.
+    pub(crate) fn bulk_response(&mut self, vars: Vec<(Oid, Value)>) -> SnmpResult<()> {
+        let active = self.active();
+
+        // Nothing at all means no progress either; give up rather than ask again forever.
+        if vars.is_empty() {
+            for cursor in active {
+                self.cursors[cursor].1 = None;
+            }
+            return Ok(());
+        }
+
+        let mut columns = vec![Vec::new(); active.len()];
+        for (i, var) in vars.into_iter().enumerate() {
+            columns[i % active.len()].push(var);
+        }
+
+        for (cursor, vars) in active.into_iter().zip(columns) {
+            // A column the response was cut short before is simply asked for again.
+            if !vars.is_empty() {
+                self.step(cursor, vars)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn step(&mut self, cursor: usize, vars: Vec<(Oid, Value)>) -> SnmpResult<()> {
+        let (prefix, current) = &mut self.cursors[cursor];
+        let Some(from) = current.take() else {
+            return Ok(());
+        };
+
+        let mut step = Vec::new();
+        *current = pdu::walk_step(prefix, &from, vars, &mut step)?;
+
+        for (name, value) in step {
+            if let Some((column, index)) = name[self.entry.len()..].split_first() {
+                let row = self.table.entry(index.to_vec()).or_default();
+                row.insert(*column, value);
+            }
+            self.last = Some(name);
+        }
+
+        Ok(())
+    }
+
+    /// How many rows were collected so far, and the last OID received in them.
+    pub(crate) fn progress(&self) -> Option<(usize, &Oid)> {
+        Some((self.table.len(), self.last.as_ref()?))
+    }
+
+    pub(crate) fn finish(self) -> Table {
+        self.table
+    }
+}
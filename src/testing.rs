@@ -0,0 +1,158 @@
+//! Helpers for testing code that talks SNMP, without a device on the network.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::transport::UDP_MAX_MESSAGE_SIZE;
+use crate::{dump, Agent, IntoOid, Oid, Value};
+
+/// How often the serving thread checks whether the mock agent was dropped.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+struct Faults {
+    delay: Duration,
+    drops: usize,
+}
+
+struct Shared {
+    agent: Agent,
+    socket: UdpSocket,
+    stopped: AtomicBool,
+    faults: Mutex<Faults>,
+    received: AtomicUsize,
+}
+
+/// An [`Agent`] on a random port of 127.0.0.1 serving a fixed set of instances, such as
+/// those returned by a [`SyncSession::walk`](crate::SyncSession::walk) of a real device,
+/// e.g. `let agent = MockAgent::from_walk("1.3.6.1.2.1", walk)?; SyncSession::new(1,
+/// agent.local_addr()?, b"public", 1000)?`.
+///
+/// It answers SNMPv1 and SNMPv2c requests with the community `public`, and sets of
+/// existing instances with `private`. Requests are answered one at a time, in order, from
+/// a background thread that stops when the mock agent is dropped.
+pub struct MockAgent {
+    shared: Arc<Shared>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl MockAgent {
+    pub fn new<K: Into<Oid>>(instances: impl IntoIterator<Item = (K, Value)>) -> io::Result<Self> {
+        let instances: BTreeMap<Oid, Value> = instances
+            .into_iter()
+            .map(|(name, value)| (name.into(), value))
+            .collect();
+
+        let mut agent = Agent::new().write_community("private");
+        agent
+            .register(Oid::from(Vec::new()), RwLock::new(instances))
+            .map_err(io::Error::other)?;
+
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.set_read_timeout(Some(SHUTDOWN_POLL))?;
+
+        let shared = Arc::new(Shared {
+            agent,
+            socket,
+            stopped: AtomicBool::new(false),
+            faults: Mutex::new(Faults::default()),
+            received: AtomicUsize::new(0),
+        });
+
+        let server = {
+            let shared = shared.clone();
+            thread::spawn(move || serve(&shared))
+        };
+
+        Ok(MockAgent {
+            shared,
+            server: Some(server),
+        })
+    }
+
+    /// Serves the result of walking `root`, whose keys are relative to it.
+    pub fn from_walk(root: impl IntoOid, walk: BTreeMap<Vec<u32>, Value>) -> io::Result<Self> {
+        let root = root.into_oid().map_err(io::Error::other)?;
+
+        MockAgent::new(
+            walk.into_iter()
+                .map(|(suffix, value)| (Oid::from([root.as_slice(), &suffix].concat()), value)),
+        )
+    }
+
+    /// Serves a capture loaded with [`dump::load_file`]: an snmpsim `.snmprec` file, or
+    /// the output of `snmpwalk -On`.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        MockAgent::new(dump::load_file(path)?)
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.shared.socket.local_addr()
+    }
+
+    fn faults(&self) -> MutexGuard<'_, Faults> {
+        self.shared
+            .faults
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Waits `delay` before answering each request from now on, e.g. longer than a
+    /// session's timeout to exercise retransmission.
+    pub fn set_delay(&self, delay: Duration) {
+        self.faults().delay = delay;
+    }
+
+    /// Leaves the next `count` requests unanswered, as if they were lost.
+    pub fn drop_next(&self, count: usize) {
+        self.faults().drops = count;
+    }
+
+    /// How many datagrams arrived so far, dropped ones included.
+    pub fn received(&self) -> usize {
+        self.shared.received.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for MockAgent {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::Relaxed);
+
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+fn serve(shared: &Shared) {
+    let mut buf = vec![0; UDP_MAX_MESSAGE_SIZE];
+
+    while !shared.stopped.load(Ordering::Relaxed) {
+        let (len, source) = match shared.socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            // Timeouts and ICMP errors for earlier responses alike.
+            Err(_) => continue,
+        };
+        shared.received.fetch_add(1, Ordering::Relaxed);
+
+        let delay = {
+            let mut faults = shared.faults.lock().unwrap_or_else(PoisonError::into_inner);
+            if faults.drops > 0 {
+                faults.drops -= 1;
+                continue;
+            }
+            faults.delay
+        };
+        thread::sleep(delay);
+
+        if let Some(response) = shared.agent.respond(&buf[..len]) {
+            let _ = shared.socket.send_to(&response, source);
+        }
+    }
+}
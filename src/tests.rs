@@ -1,15 +1,5571 @@
-use super::SyncSession;
+use rasn::types::ObjectIdentifier;
+use rasn_smi::v2::{ApplicationSyntax, ObjectSyntax, SimpleSyntax};
+use rasn_snmp::{v2, v3};
+
+use super::usm::Usm;
+use super::{AuthProtocol, Decoding, Oid, PrivProtocol, SnmpError, SyncSession, UsmUser, Value};
 
 #[test]
+#[ignore = "requires a live agent at 10.123.0.20"]
 fn function_name_test() {
     let agent_addr = "10.123.0.20:161";
     let community = "CampUs".as_bytes();
 
     let sess = SyncSession::new(1, agent_addr, community, 10000).unwrap();
 
-    let walk = sess.get(&String::from(".1.3.6.1.2.1.2.2.1.6.16"));
+    let vars = sess.get(".1.3.6.1.2.1.2.2.1.6.16").unwrap();
+
+    for (name, value) in vars {
+        println!("{} = {}", name, value)
+    }
+}
+
+#[test]
+fn value_from_varbind_value() {
+    let int = v2::VarBindValue::Value(ObjectSyntax::Simple(SimpleSyntax::Integer((-42).into())));
+    assert_eq!(Value::from(int), Value::Integer(-42));
+
+    let ticks = v2::VarBindValue::Value(ObjectSyntax::ApplicationWide(ApplicationSyntax::Ticks(
+        rasn_smi::v1::TimeTicks(1234),
+    )));
+    assert_eq!(Value::from(ticks), Value::TimeTicks(1234));
+
+    assert_eq!(
+        Value::from(v2::VarBindValue::NoSuchInstance),
+        Value::NoSuchInstance
+    );
+    assert!(Value::from(v2::VarBindValue::EndOfMibView).is_exception());
+}
+
+#[test]
+fn value_round_trips_through_varbind_value() {
+    let values = [
+        Value::Integer(7),
+        Value::OctetString(b"sysContact".to_vec()),
+        Value::Oid(vec![1, 3, 6, 1, 4, 1, 9]),
+        Value::IpAddress([10, 0, 0, 1].into()),
+        Value::Counter32(1),
+        Value::Counter64(u64::MAX),
+        Value::Gauge32(100),
+        Value::TimeTicks(360000),
+        Value::Opaque(vec![0x04, 0x02, 0xca, 0xfe]),
+        Value::Float(123.0),
+    ];
+
+    for value in values {
+        let var: v2::VarBindValue = value.clone().into();
+        assert_eq!(Value::from(var), value);
+    }
+}
+
+#[test]
+fn binary_octet_strings_display_as_hex() {
+    let mac = Value::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+    assert_eq!(mac.to_string(), "00 1A 2B 3C 4D 5E");
+    assert_eq!(mac.as_str(), None);
+    assert_eq!(
+        mac.as_bytes(),
+        Some(&[0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e][..])
+    );
+
+    let descr = Value::OctetString(b"Linux router 6.1\r\n".to_vec());
+    assert_eq!(descr.to_string(), "Linux router 6.1\r\n");
+    assert_eq!(Value::OctetString(b"eth0\0".to_vec()).to_string(), "eth0");
+    assert_eq!(Value::OctetString(vec![0xc3, 0x28]).to_string(), "C3 28");
+    assert_eq!(Value::Opaque(vec![0x9f, 0x78]).to_string(), "9F 78");
+}
+
+fn oid(value: &str) -> Oid {
+    value.parse().unwrap()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn usm_key_localization_matches_rfc3414() {
+    let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+    let key = AuthProtocol::Md5.password_to_key(b"maplesyrup");
+    assert_eq!(hex(&key), "9faf3283884e92834ebc9847d8edd963");
+    assert_eq!(
+        hex(&AuthProtocol::Md5.localize_key(&key, &engine_id)),
+        "526f5eed9fcce26f8964c2930787d82b"
+    );
+
+    let key = AuthProtocol::Sha1.password_to_key(b"maplesyrup");
+    assert_eq!(hex(&key), "9fb5cc0381497b3793528939ff788d5d79145211");
+    assert_eq!(
+        hex(&AuthProtocol::Sha1.localize_key(&key, &engine_id)),
+        "6695febc9288e36282235fc7151f128497b38f3f"
+    );
+}
+
+#[test]
+fn short_privacy_keys_are_extended() {
+    let engine_id = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+    let key = AuthProtocol::Md5.localize_key(
+        &AuthProtocol::Md5.password_to_key(b"maplesyrup"),
+        &engine_id,
+    );
+
+    let blumenthal = PrivProtocol::Aes256.extend_key(AuthProtocol::Md5, &key, &engine_id);
+    let reeder = PrivProtocol::Aes256Cisco.extend_key(AuthProtocol::Md5, &key, &engine_id);
+
+    assert_eq!(blumenthal.len(), 32);
+    assert_eq!(reeder.len(), 32);
+    assert_eq!(blumenthal[..16], key[..]);
+    assert_eq!(reeder[..16], key[..]);
+    assert_ne!(blumenthal, reeder);
+
+    // Keys that are long enough already are left alone.
+    let key = AuthProtocol::Sha256.localize_key(
+        &AuthProtocol::Sha256.password_to_key(b"maplesyrup"),
+        &engine_id,
+    );
+    assert_eq!(
+        PrivProtocol::Aes256.extend_key(AuthProtocol::Sha256, &key, &engine_id),
+        key
+    );
+}
+
+fn discovered_usm(user: UsmUser) -> Usm {
+    discovered_usm_at(user, 7)
+}
+
+fn discovered_usm_at(user: UsmUser, boots: u32) -> Usm {
+    discover(Usm::new(user, 4096), boots)
+}
+
+fn discover(mut usm: Usm, boots: u32) -> Usm {
+    let report = v3::Message {
+        version: 3.into(),
+        global_data: v3::HeaderData {
+            message_id: 1.into(),
+            max_size: 65507.into(),
+            flags: vec![0].into(),
+            security_model: 3.into(),
+        },
+        security_parameters: rasn::ber::encode(&v3::USMSecurityParameters {
+            authoritative_engine_id: vec![0x80, 0, 0x1f, 0x88, 4, 1, 2, 3].into(),
+            authoritative_engine_boots: boots.into(),
+            authoritative_engine_time: 1200.into(),
+            user_name: Default::default(),
+            authentication_parameters: Default::default(),
+            privacy_parameters: Default::default(),
+        })
+        .unwrap()
+        .into(),
+        scoped_data: v3::ScopedPduData::CleartextPdu(v3::ScopedPdu {
+            engine_id: Default::default(),
+            name: Default::default(),
+            data: v2::Pdus::Report(v2::Report(v2::Pdu {
+                request_id: 0,
+                error_status: 0,
+                error_index: 0,
+                variable_bindings: Vec::new(),
+            })),
+        }),
+    };
+
+    usm.discover(&rasn::ber::encode(&report).unwrap()).unwrap();
+    usm
+}
+
+#[test]
+fn usm_round_trips_authenticated_encrypted_messages() {
+    for (auth, privacy) in [
+        (AuthProtocol::Md5, PrivProtocol::Des),
+        (AuthProtocol::Sha1, PrivProtocol::Aes128),
+        (AuthProtocol::Sha224, PrivProtocol::Aes192),
+        (AuthProtocol::Sha256, PrivProtocol::Aes256),
+        (AuthProtocol::Md5, PrivProtocol::Aes256Cisco),
+        (AuthProtocol::Sha384, PrivProtocol::Aes192Cisco),
+        (AuthProtocol::Sha512, PrivProtocol::Aes256),
+    ] {
+        let user = UsmUser::new(b"admin")
+            .auth(auth, b"authpassword")
+            .privacy(privacy, b"privpassword");
+        let mut usm = discovered_usm(user);
+
+        let pdus = v2::Pdus::GetRequest(v2::GetRequest(v2::Pdu {
+            request_id: 42,
+            error_status: 0,
+            error_index: 0,
+            variable_bindings: vec![v2::VarBind {
+                name: ObjectIdentifier::new_unchecked(vec![1, 3, 6, 1, 2, 1, 1, 5, 0].into()),
+                value: v2::VarBindValue::Unspecified,
+            }],
+        }));
+
+        let mut encoded = usm.encode(pdus.clone()).unwrap();
+        assert_eq!(usm.decode(&encoded).unwrap(), pdus);
+
+        // Flipping a bit in the payload must break the digest.
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0x01;
+        assert!(matches!(
+            usm.decode(&encoded),
+            Err(SnmpError::AuthenticationError)
+        ));
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_session_get() {
+    use rasn_snmp::v2c;
+
+    let agent = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let mut buf = [0; 1500];
+        let (len, peer) = agent.recv_from(&mut buf).await.unwrap();
+        let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+
+        let v2::Pdus::GetRequest(get) = request.data else {
+            panic!("expected a GetRequest");
+        };
+
+        let mut response = get.0;
+        response.variable_bindings[0].value = Value::OctetString(b"yar-snmp".to_vec()).into();
+
+        let response = v2c::Message {
+            version: request.version,
+            community: request.community,
+            data: v2::Pdus::Response(v2::Response(response)),
+        };
+        let response = rasn::ber::encode(&response).unwrap();
+        agent.send_to(&response, peer).await.unwrap();
+    });
+
+    let sess = super::AsyncSession::new(1, agent_addr, b"public", 1000)
+        .await
+        .unwrap();
+    let vars = sess.get("1.3.6.1.2.1.1.5.0").await.unwrap();
+
+    assert_eq!(vars.len(), 1);
+    assert_eq!(vars[0].0.to_string(), "1.3.6.1.2.1.1.5.0");
+    assert_eq!(vars[0].1, Value::OctetString(b"yar-snmp".to_vec()));
+}
+
+#[test]
+fn trap_starts_with_uptime_and_trap_oid() {
+    let link_down = oid("1.3.6.1.6.3.1.1.5.3");
+    let bindings = [(oid("1.3.6.1.2.1.2.2.1.1.7"), Value::Integer(7))];
+
+    let v2::Pdus::Trap(trap) = super::pdu::trap(4200, &link_down, &bindings) else {
+        panic!("expected an SNMPv2-Trap");
+    };
+
+    let vars: Vec<_> = trap
+        .0
+        .variable_bindings
+        .into_iter()
+        .map(|var| (var.name.to_string(), Value::from(var.value)))
+        .collect();
+
+    assert_eq!(
+        vars,
+        [
+            ("1.3.6.1.2.1.1.3.0".to_string(), Value::TimeTicks(4200)),
+            (
+                "1.3.6.1.6.3.1.1.4.1.0".to_string(),
+                Value::Oid(vec![1, 3, 6, 1, 6, 3, 1, 1, 5, 3])
+            ),
+            ("1.3.6.1.2.1.2.2.1.1.7".to_string(), Value::Integer(7)),
+        ]
+    );
+}
+
+#[test]
+fn getbulk_caps_non_repeaters() {
+    let oids = [oid("1.3.6.1.2.1.1.3.0"), oid("1.3.6.1.2.1.2.2.1.2")];
+
+    let v2::Pdus::GetBulkRequest(bulk) = super::pdu::getbulk(&oids, 5, 10) else {
+        panic!("expected a GetBulkRequest");
+    };
+
+    assert_eq!(bulk.0.non_repeaters, 2);
+    assert_eq!(bulk.0.max_repetitions, 10);
+    assert_eq!(bulk.0.variable_bindings.len(), 2);
+}
+
+#[test]
+fn collect_subtree_stops_at_boundary() {
+    let start = oid("1.3.6.1.2.1.2.2.1.2");
+    let vars = vec![
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.1"),
+            Value::OctetString(b"lo".to_vec()),
+        ),
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.2"),
+            Value::OctetString(b"eth0".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.2.2.1.3.1"), Value::Integer(24)),
+    ];
+
+    let mut result = std::collections::BTreeMap::new();
+    let next = super::pdu::collect_subtree(&start, &start, vars, &mut result).unwrap();
+    assert_eq!(next, None);
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[&vec![2]], Value::OctetString(b"eth0".to_vec()));
+
+    let vars = vec![(
+        oid("1.3.6.1.2.1.2.2.1.2.1"),
+        Value::OctetString(b"lo".to_vec()),
+    )];
+    assert_eq!(
+        super::pdu::collect_subtree(&start, &start, vars, &mut result).unwrap(),
+        Some(oid("1.3.6.1.2.1.2.2.1.2.1"))
+    );
+}
+
+#[test]
+fn collect_subtree_rejects_non_increasing_oids() {
+    let start = oid("1.3.6.1.2.1.2.2.1.2");
+    let current = oid("1.3.6.1.2.1.2.2.1.2.2");
+    let mut result = std::collections::BTreeMap::new();
+
+    for name in ["1.3.6.1.2.1.2.2.1.2.2", "1.3.6.1.2.1.2.2.1.2.1"] {
+        let vars = vec![(oid(name), Value::Integer(1))];
+
+        assert!(matches!(
+            super::pdu::collect_subtree(&start, &current, vars, &mut result),
+            Err(SnmpError::OidNotIncreasing { next, .. }) if next == oid(name)
+        ));
+    }
+
+    let vars = vec![(current.clone(), Value::EndOfMibView)];
+    let next = super::pdu::collect_subtree(&start, &current, vars, &mut result).unwrap();
+    assert_eq!(next, None);
+    assert!(result.is_empty());
+}
+
+#[test]
+fn new_rejects_empty_address_list() {
+    let addrs: &[std::net::SocketAddr] = &[];
+    let err = SyncSession::new(1, addrs, b"public", 1000).err().unwrap();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn usm_rejects_truncated_messages() {
+    let user = UsmUser::new(b"admin").auth(AuthProtocol::Sha1, b"authpassword");
+    let mut usm = discovered_usm(user);
+
+    let pdus = v2::Pdus::GetRequest(v2::GetRequest(v2::Pdu {
+        request_id: 1,
+        error_status: 0,
+        error_index: 0,
+        variable_bindings: Vec::new(),
+    }));
+    let encoded = usm.encode(pdus).unwrap();
+
+    for len in 0..encoded.len() {
+        assert!(usm.decode(&encoded[..len]).is_err());
+    }
+}
+
+#[test]
+fn decode_errors_report_truncation_offset() {
+    let message = rasn_snmp::v2c::Message {
+        version: 1.into(),
+        community: b"public".to_vec().into(),
+        data: super::pdu::get(&[oid("1.3.6.1.2.1.1.1.0")]),
+    };
+    let encoded = rasn::ber::encode(&message).unwrap();
+
+    let err =
+        super::pdu::decode::<rasn_snmp::v2c::Message<v2::Pdus>>(&encoded[..encoded.len() - 3])
+            .unwrap_err();
+
+    match &err {
+        SnmpError::Decode { offset, .. } => assert!(offset.is_some()),
+        other => panic!("unexpected error {:?}", other),
+    }
+    assert!(err
+        .to_string()
+        .starts_with("failed to decode response at offset"));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn v1_walk_ends_on_no_such_name() {
+    use rasn_smi::v1::{ObjectSyntax as Syntax1, SimpleSyntax as Simple1};
+    use rasn_snmp::v1;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+
+        for step in 0..2 {
+            let (len, peer) = agent.recv_from(&mut buf).unwrap();
+            let request: v1::Message<v1::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+            assert_eq!(request.version, 0.into());
+
+            let v1::Pdus::GetNextRequest(next) = request.data else {
+                panic!("expected a GetNextRequest");
+            };
+
+            let mut response = next.0;
+            if step == 0 {
+                response.variable_bindings[0] = v1::VarBind {
+                    name: ObjectIdentifier::new_unchecked(vec![1, 3, 6, 1, 2, 1, 1, 1, 0].into()),
+                    value: Syntax1::Simple(Simple1::String(b"ups".to_vec().into())),
+                };
+            } else {
+                response.error_status = 2.into();
+                response.error_index = 1.into();
+            }
+
+            let response = v1::Message {
+                version: request.version,
+                community: request.community,
+                data: v1::Pdus::GetResponse(v1::GetResponse(response)),
+            };
+            let response = rasn::ber::encode(&response).unwrap();
+            agent.send_to(&response, peer).unwrap();
+        }
+    });
+
+    let sess = SyncSession::new(0, agent_addr, b"public", 1000).unwrap();
+    let result = sess.bulk_walk([1, 3, 6, 1, 2, 1, 1], 10).unwrap();
+
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[&vec![1, 0]], Value::OctetString(b"ups".to_vec()));
+
+    assert!(matches!(
+        sess.getbulk(&["1.3.6.1.2.1.1"], 0, 10),
+        Err(SnmpError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn v1_trap_encodes_with_bindings() {
+    use rasn_snmp::v1;
+
+    let security = super::security::Security::community(0, b"public");
+    let trap = super::v1::trap(
+        &oid("1.3.6.1.4.1.8072"),
+        "10.11.12.13".parse().unwrap(),
+        6,
+        2,
+        11_932,
+        &[(oid("1.3.6.1.4.1.8072.2.1.7"), Value::Gauge32(1))],
+    )
+    .unwrap();
+
+    let encoded = security.encode_v1_trap(trap).unwrap();
+    let decoded: v1::Message<v1::Pdus> = rasn::ber::decode(&encoded).unwrap();
+
+    let v1::Pdus::Trap(trap) = decoded.data else {
+        panic!("expected a Trap-PDU");
+    };
+    assert_eq!(trap.specific_trap, 2.into());
+    assert_eq!(trap.variable_bindings.len(), 1);
+
+    let v2c = super::security::Security::community(1, b"public");
+    let trap = super::v1::trap(&oid("1.3.6.1.4.1.8072"), [0; 4].into(), 0, 0, 0, &[]).unwrap();
+    assert!(matches!(
+        v2c.encode_v1_trap(trap),
+        Err(SnmpError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn error_status_becomes_agent_error() {
+    let response = v2::Pdus::Response(v2::Response(v2::Pdu {
+        request_id: 1,
+        error_status: 17,
+        error_index: 2,
+        variable_bindings: ["1.3.6.1.2.1.1.4.0", "1.3.6.1.2.1.1.5.0"]
+            .iter()
+            .map(|name| v2::VarBind {
+                name: oid(name).to_asn(),
+                value: Value::OctetString(b"x".to_vec()).into(),
+            })
+            .collect(),
+    }));
+
+    let err = super::pdu::parse_response(response).unwrap_err();
+
+    let SnmpError::AgentError {
+        status,
+        index,
+        bindings,
+    } = &err
+    else {
+        panic!("expected an agent error, got {:?}", err);
+    };
+    assert_eq!(*status, super::ErrorStatus::NotWritable);
+    assert_eq!(*index, 2);
+    assert_eq!(bindings.len(), 2);
+    assert_eq!(
+        err.to_string(),
+        "agent returned notWritable for 1.3.6.1.2.1.1.5.0"
+    );
+    assert_eq!(u32::from(super::ErrorStatus::from(42)), 42);
+}
+
+#[test]
+fn stale_responses_are_discarded() {
+    use rasn_snmp::v2c;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, peer) = agent.recv_from(&mut buf).unwrap();
+        let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+
+        let v2::Pdus::GetRequest(get) = request.data else {
+            panic!("expected a GetRequest");
+        };
+
+        let respond = |request_id: i32, community: &[u8], value: &[u8]| {
+            let mut response = get.0.clone();
+            response.request_id = request_id;
+            response.variable_bindings[0].value = Value::OctetString(value.to_vec()).into();
+
+            let response = v2c::Message {
+                version: request.version.clone(),
+                community: community.to_vec().into(),
+                data: v2::Pdus::Response(v2::Response(response)),
+            };
+            let response = rasn::ber::encode(&response).unwrap();
+            agent.send_to(&response, peer).unwrap();
+        };
+
+        respond(get.0.request_id.wrapping_sub(1), b"public", b"stale");
+        respond(get.0.request_id, b"private", b"spoofed");
+        respond(get.0.request_id, b"public", b"fresh");
+    });
+
+    let sess = SyncSession::new(1, agent_addr, b"public", 1000).unwrap();
+    let vars = sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+
+    assert_eq!(vars[0].1, Value::OctetString(b"fresh".to_vec()));
+}
+
+#[test]
+fn oid_parsing_rejects_malformed_input() {
+    let sys_descr = oid(".1.3.6.1.2.1.1.1.0");
+    assert_eq!(sys_descr.to_string(), "1.3.6.1.2.1.1.1.0");
+    assert_eq!(sys_descr, Oid::from([1, 3, 6, 1, 2, 1, 1, 1, 0]));
+    assert_eq!(sys_descr.suffix(&oid("1.3.6.1.2.1.1")), Some(&[1, 0][..]));
+
+    for bad in ["", "1.3..6", "1.3.six", "3.1", "1.40"] {
+        assert!(matches!(
+            bad.parse::<Oid>(),
+            Err(SnmpError::InvalidOid(input)) if input == bad
+        ));
+    }
+}
+
+#[test]
+fn getnext_many_drops_exhausted_v1_bindings() {
+    let response = |error_status, error_index, names: &[&str]| {
+        v2::Pdus::Response(v2::Response(v2::Pdu {
+            request_id: 1,
+            error_status,
+            error_index,
+            variable_bindings: names
+                .iter()
+                .map(|name| v2::VarBind {
+                    name: oid(name).to_asn(),
+                    value: Value::Integer(1).into(),
+                })
+                .collect(),
+        }))
+    };
+
+    let mut next =
+        super::pdu::NextRequest::new(vec![oid("1.3.6.1.2.1.1.1"), oid("1.3.6.1.2.1.99")]);
+
+    let reply = response(2, 2, &["1.3.6.1.2.1.1.1", "1.3.6.1.2.1.99"]);
+    assert_eq!(next.response(reply).unwrap(), None);
+
+    let Some(v2::Pdus::GetNextRequest(retry)) = next.request() else {
+        panic!("expected a GetNextRequest");
+    };
+    assert_eq!(retry.0.variable_bindings.len(), 1);
+
+    let reply = response(0, 0, &["1.3.6.1.2.1.1.1.0"]);
+    assert_eq!(
+        next.response(reply).unwrap().unwrap(),
+        [
+            (oid("1.3.6.1.2.1.1.1.0"), Value::Integer(1)),
+            (oid("1.3.6.1.2.1.99"), Value::EndOfMibView),
+        ]
+    );
+}
+
+#[test]
+fn walk_iter_fetches_lazily() {
+    use rasn_snmp::v2c;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+    let (seen, requests) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let names = [
+            "1.3.6.1.2.1.1.1.0",
+            "1.3.6.1.2.1.1.2.0",
+            "1.3.6.1.2.1.2.1.0",
+        ];
+
+        for name in names {
+            let (len, peer) = agent.recv_from(&mut buf).unwrap();
+            let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+            seen.send(()).unwrap();
+
+            let v2::Pdus::GetNextRequest(next) = request.data else {
+                panic!("expected a GetNextRequest");
+            };
+
+            let mut response = next.0;
+            response.variable_bindings[0] = v2::VarBind {
+                name: oid(name).to_asn(),
+                value: Value::Integer(1).into(),
+            };
+
+            let response = v2c::Message {
+                version: request.version,
+                community: request.community,
+                data: v2::Pdus::Response(v2::Response(response)),
+            };
+            let response = rasn::ber::encode(&response).unwrap();
+            agent.send_to(&response, peer).unwrap();
+        }
+    });
+
+    let sess = SyncSession::new(1, agent_addr, b"public", 1000).unwrap();
+    let mut walk = sess.walk_iter("1.3.6.1.2.1.1");
+
+    let (name, _) = walk.next().unwrap().unwrap();
+    assert_eq!(name, oid("1.3.6.1.2.1.1.1.0"));
+    assert_eq!(requests.try_iter().count(), 1);
+
+    let rest: Vec<_> = walk.map(|var| var.unwrap().0).collect();
+    assert_eq!(rest, [oid("1.3.6.1.2.1.1.2.0")]);
+    assert_eq!(requests.try_iter().count(), 2);
+
+    let mut walk = sess.walk_iter("1.3.six");
+    assert!(matches!(walk.next(), Some(Err(SnmpError::InvalidOid(_)))));
+    assert!(walk.next().is_none());
+}
+
+#[test]
+fn table_walk_builds_sparse_rows() {
+    let mut walk = super::table::TableWalk::new(oid("1.3.6.1.2.1.2.2"), &[2, 8]);
+
+    assert_eq!(
+        walk.request().unwrap(),
+        [oid("1.3.6.1.2.1.2.2.1.2"), oid("1.3.6.1.2.1.2.2.1.8")]
+    );
+    walk.response(vec![
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.1"),
+            Value::OctetString(b"lo".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.2.2.1.8.2"), Value::Integer(1)),
+    ])
+    .unwrap();
+
+    walk.response(vec![
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.2"),
+            Value::OctetString(b"eth0".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.2.2.1.9.1"), Value::TimeTicks(0)),
+    ])
+    .unwrap();
+
+    assert_eq!(walk.request().unwrap(), [oid("1.3.6.1.2.1.2.2.1.2.2")]);
+    walk.response(vec![(oid("1.3.6.1.2.1.2.2.1.3.1"), Value::Integer(24))])
+        .unwrap();
+    assert_eq!(walk.request(), None);
+
+    let table = walk.finish();
+    assert_eq!(table.len(), 2);
+    assert_eq!(table[&vec![1]].len(), 1);
+    assert_eq!(table[&vec![2]][&2], Value::OctetString(b"eth0".to_vec()));
+    assert_eq!(table[&vec![2]][&8], Value::Integer(1));
+}
+
+#[test]
+fn retry_policy_backs_off_with_bounded_jitter() {
+    use std::time::Duration;
+
+    let policy = super::RetryPolicy::new(3)
+        .delay(Duration::from_millis(100))
+        .backoff(2.0);
+    assert_eq!(policy.pause(1), Duration::from_millis(100));
+    assert_eq!(policy.pause(3), Duration::from_millis(400));
+
+    let policy = policy.jitter(0.5);
+    for _ in 0..100 {
+        let pause = policy.pause(1);
+        assert!(pause >= Duration::from_millis(50) && pause <= Duration::from_millis(150));
+    }
+}
+
+#[test]
+fn counter_tracker_handles_wrap_around() {
+    use std::time::{Duration, Instant};
+
+    let mut tracker = super::CounterTracker::new();
+    let start = Instant::now();
+    let later = start + Duration::from_secs(10);
+    let in_octets = oid("1.3.6.1.2.1.2.2.1.10.1");
+    let hc_in_octets = oid("1.3.6.1.2.1.31.1.1.1.6.1");
+
+    let first = Value::Counter32(u32::MAX - 99);
+    assert_eq!(tracker.record_at(in_octets.clone(), &first, start), None);
+    let wrapped = Value::Counter32(900);
+    assert_eq!(tracker.record_at(in_octets, &wrapped, later), Some(100.0));
+
+    let first = Value::Counter64(u64::MAX);
+    assert_eq!(tracker.record_at(hc_in_octets.clone(), &first, start), None);
+    let wrapped = Value::Counter64(49);
+    assert_eq!(tracker.record_at(hc_in_octets, &wrapped, later), Some(5.0));
+
+    let descr = oid("1.3.6.1.2.1.2.2.1.2.1");
+    let name = Value::OctetString(b"eth0".to_vec());
+    assert_eq!(tracker.record_at(descr.clone(), &name, start), None);
+    assert_eq!(tracker.record_at(descr, &name, later), None);
+}
+
+const IF_MIB: &str = r#"
+IF-MIB DEFINITIONS ::= BEGIN
+
+IMPORTS
+    MODULE-IDENTITY, OBJECT-TYPE, Counter32, mib-2 FROM SNMPv2-SMI
+    DisplayString FROM SNMPv2-TC;
+
+ifMIB MODULE-IDENTITY
+    LAST-UPDATED "200006140000Z"
+    ORGANIZATION "IETF Interfaces MIB Working Group"
+    CONTACT-INFO "-- not a comment --"
+    DESCRIPTION  "The MIB module to describe generic objects for network
+                  interface sub-layers."
+    ::= { mib-2 31 }
+
+interfaces   OBJECT IDENTIFIER ::= { mib-2 2 }
+
+ifTable OBJECT-TYPE
+    SYNTAX      SEQUENCE OF IfEntry
+    MAX-ACCESS  not-accessible
+    STATUS      current
+    DESCRIPTION "A list of interface entries."
+    ::= { interfaces 2 }
+
+ifEntry OBJECT-TYPE
+    SYNTAX      IfEntry
+    MAX-ACCESS  not-accessible
+    STATUS      current
+    DESCRIPTION "An entry containing management information."
+    INDEX   { ifIndex }
+    ::= { ifTable 1 }
+
+IfEntry ::=
+    SEQUENCE {
+        ifIndex   InterfaceIndex,
+        ifDescr   DisplayString
+    }
+
+ifDescr OBJECT-TYPE
+    SYNTAX      DisplayString (SIZE (0..255)) -- trailing comment
+    MAX-ACCESS  read-only
+    STATUS      current
+    DESCRIPTION "A textual string containing information about the interface."
+    ::= { ifEntry 2 }
+
+END
+"#;
+
+#[test]
+fn mib_resolves_names_across_modules() {
+    let mut mib = super::Mib::new();
+
+    // The vendor module comes first and is resolved once IF-MIB is loaded.
+    mib.load_str(
+        "ACME-MIB DEFINITIONS ::= BEGIN
+         IMPORTS enterprises FROM SNMPv2-SMI ifEntry FROM IF-MIB;
+         acme OBJECT IDENTIFIER ::= { enterprises 99999 }
+         acmeIfExtension OBJECT IDENTIFIER ::= { ifEntry 99 }
+         acmeTraps OBJECT IDENTIFIER ::= { acme products(1) 2 }
+         END",
+    )
+    .unwrap();
+    assert_eq!(mib.unresolved(), ["ACME-MIB::acmeIfExtension"]);
+
+    mib.load_str(IF_MIB).unwrap();
+    assert!(mib.unresolved().is_empty());
+
+    let if_descr = oid("1.3.6.1.2.1.2.2.1.2.3");
+    assert_eq!(mib.lookup("IF-MIB::ifDescr.3"), Some(if_descr.clone()));
+    assert_eq!(mib.lookup("ifDescr.3"), Some(if_descr.clone()));
+    assert_eq!(mib.lookup("ACME-MIB::ifDescr"), None);
+    assert_eq!(mib.lookup("acmeIfExtension"), mib.lookup("ifEntry.99"));
+    assert_eq!(mib.lookup("products"), Some(oid("1.3.6.1.4.1.99999.1")));
+    assert_eq!(mib.name_of(&if_descr).as_deref(), Some("IF-MIB::ifDescr.3"));
+    assert_eq!(
+        mib.name_of(&oid("1.3.6.1.4.1.9.1.1")).as_deref(),
+        Some("SNMPv2-SMI::enterprises.9.1.1")
+    );
+
+    let err =
+        mib.load_str("BROKEN-MIB DEFINITIONS ::= BEGIN\nfoo OBJECT IDENTIFIER ::= { bar x }\nEND");
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        "MIB:2: unexpected x in OID value"
+    );
+}
+
+#[test]
+fn formatter_applies_display_hints_and_enumerations() {
+    let mut mib = super::Mib::new();
+    mib.load_str(IF_MIB).unwrap();
+    mib.load_str(
+        r#"ACME-MIB DEFINITIONS ::= BEGIN
+        IMPORTS enterprises, OBJECT-TYPE FROM SNMPv2-SMI
+                TEXTUAL-CONVENTION, MacAddress, DateAndTime FROM SNMPv2-TC;
+
+        Celsius ::= TEXTUAL-CONVENTION
+            DISPLAY-HINT "d-2"
+            STATUS current
+            DESCRIPTION "Hundredths of a degree."
+            SYNTAX Integer32 (-10000..10000)
+
+        acme OBJECT IDENTIFIER ::= { enterprises 99999 }
+        acmeMac OBJECT-TYPE SYNTAX MacAddress MAX-ACCESS read-only STATUS current
+            DESCRIPTION "" ::= { acme 1 }
+        acmeClock OBJECT-TYPE SYNTAX DateAndTime MAX-ACCESS read-only STATUS current
+            DESCRIPTION "" ::= { acme 2 }
+        acmeTemperature OBJECT-TYPE SYNTAX Celsius MAX-ACCESS read-only STATUS current
+            DESCRIPTION "" ::= { acme 3 }
+        acmeStatus OBJECT-TYPE
+            SYNTAX INTEGER { up(1), down(2), testing(3) }
+            MAX-ACCESS read-only
+            STATUS current
+            DESCRIPTION ""
+            DEFVAL { up }
+            ::= { acme 4 }
+        acmeFlags OBJECT-TYPE SYNTAX BITS { fan(0), psu(1), overheat(9) } MAX-ACCESS read-only
+            STATUS current DESCRIPTION "" ::= { acme 5 }
+        END"#,
+    )
+    .unwrap();
+    let formatter = super::Formatter::new(&mib);
+    let format = |name: &str, value: Value| formatter.format(&mib.lookup(name).unwrap(), &value);
+
+    let mac = Value::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+    assert_eq!(format("acmeMac.0", mac), "00:1a:2b:3c:4d:5e");
+
+    let clock = Value::OctetString(vec![0x07, 0xe8, 10, 14, 13, 30, 15, 0, b'+', 2, 0]);
+    assert_eq!(format("acmeClock.0", clock), "2024-10-14,13:30:15.0,+2:0");
+
+    assert_eq!(format("acmeTemperature.0", Value::Integer(2150)), "21.50");
+    assert_eq!(format("acmeTemperature.0", Value::Integer(-5)), "-0.05");
+    assert_eq!(format("acmeStatus.0", Value::Integer(2)), "down(2)");
+    assert_eq!(format("acmeStatus.0", Value::Integer(7)), "7");
+    assert_eq!(
+        format("acmeFlags.0", Value::OctetString(vec![0x80, 0x40])),
+        "fan(0) overheat(9)"
+    );
+    assert_eq!(
+        format("ifDescr.1", Value::OctetString(b"eth0".to_vec())),
+        "eth0"
+    );
+
+    let oper = mib.lookup("acmeStatus.0").unwrap();
+    assert_eq!(
+        formatter.format_binding(&oper, &Value::Integer(1)),
+        "ACME-MIB::acmeStatus.0 = up(1)"
+    );
+}
+
+#[test]
+fn formatter_ignores_display_hints_with_a_zero_length() {
+    let mut mib = super::Mib::new();
+    mib.load_str(
+        r#"ACME-MIB DEFINITIONS ::= BEGIN
+        IMPORTS enterprises, OBJECT-TYPE FROM SNMPv2-SMI
+                TEXTUAL-CONVENTION FROM SNMPv2-TC;
+
+        Broken ::= TEXTUAL-CONVENTION
+            DISPLAY-HINT "0x"
+            STATUS current
+            DESCRIPTION ""
+            SYNTAX OCTET STRING
+
+        acme OBJECT IDENTIFIER ::= { enterprises 99999 }
+        acmeBroken OBJECT-TYPE SYNTAX Broken MAX-ACCESS read-only STATUS current
+            DESCRIPTION "" ::= { acme 1 }
+        END"#,
+    )
+    .unwrap();
+    let formatter = super::Formatter::new(&mib);
+    let value = Value::OctetString(b"ok".to_vec());
+
+    assert_eq!(
+        formatter.format(&mib.lookup("acmeBroken.0").unwrap(), &value),
+        "ok"
+    );
+}
+
+#[test]
+fn retransmissions_reuse_the_request_id() {
+    use rasn_snmp::v2c;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    let responder = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let mut ids = Vec::new();
+
+        for _ in 0..3 {
+            let (len, peer) = agent.recv_from(&mut buf).unwrap();
+            let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+            ids.push(super::pdu::request_id(&request.data));
+
+            if ids.len() < 3 {
+                continue;
+            }
+
+            let v2::Pdus::GetRequest(get) = request.data else {
+                panic!("expected a GetRequest");
+            };
+            let response = v2c::Message {
+                version: request.version,
+                community: request.community,
+                data: v2::Pdus::Response(v2::Response(get.0)),
+            };
+            let response = rasn::ber::encode(&response).unwrap();
+            agent.send_to(&response, peer).unwrap();
+        }
+
+        ids
+    });
+
+    let mut sess = SyncSession::new(1, agent_addr, b"public", 50).unwrap();
+    sess.set_retry_policy(super::RetryPolicy::new(2).delay(std::time::Duration::from_millis(10)));
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+
+    let ids = responder.join().unwrap();
+    assert!(ids.iter().all(|id| *id == ids[0]));
+}
+
+#[test]
+fn request_options_override_timeout_and_retries() {
+    use std::time::{Duration, Instant};
+
+    use super::RequestOptions;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    agent
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    let silent = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let mut received = 0;
+
+        while agent.recv_from(&mut buf).is_ok() {
+            received += 1;
+        }
+
+        received
+    });
+
+    let sess = SyncSession::builder(agent_addr.to_string())
+        .timeout(Duration::from_secs(5))
+        .retries(3)
+        .build()
+        .unwrap();
+
+    let opts = RequestOptions::new()
+        .timeout(Duration::from_millis(30))
+        .retries(1);
+    let started = Instant::now();
+    assert!(matches!(
+        sess.get_with("1.3.6.1.2.1.1.5.0", &opts),
+        Err(SnmpError::Timeout)
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    assert_eq!(silent.join().unwrap(), 2);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn closed_ports_fail_fast_as_unreachable() {
+    use std::time::{Duration, Instant};
+
+    let closed = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let closed_addr = closed.local_addr().unwrap();
+    drop(closed);
+
+    let sess = SyncSession::builder(closed_addr.to_string())
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let started = Instant::now();
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::PortUnreachable)
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn builder_applies_version_community_and_port() {
+    use rasn_snmp::v1 as snmp_v1;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = agent.local_addr().unwrap().port();
+
+    let responder = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, peer) = agent.recv_from(&mut buf).unwrap();
+        let request: snmp_v1::Message<snmp_v1::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+
+        let snmp_v1::Pdus::GetRequest(get) = &request.data else {
+            panic!("expected a GetRequest");
+        };
+        let response = snmp_v1::Message {
+            version: request.version.clone(),
+            community: request.community.clone(),
+            data: snmp_v1::Pdus::GetResponse(snmp_v1::GetResponse(get.0.clone())),
+        };
+        agent
+            .send_to(&rasn::ber::encode(&response).unwrap(), peer)
+            .unwrap();
+
+        request
+    });
+
+    let sess = SyncSession::builder("127.0.0.1")
+        .port(port)
+        .v1("private")
+        .timeout(std::time::Duration::from_secs(1))
+        .retries(0)
+        .build()
+        .unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+
+    let request = responder.join().unwrap();
+    assert_eq!(request.version, 0.into());
+    assert_eq!(request.community.as_ref(), b"private");
+}
+
+#[test]
+fn builder_requires_a_user_for_v3() {
+    let err = SyncSession::builder("127.0.0.1")
+        .version(super::Version::V3)
+        .build()
+        .err()
+        .unwrap();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+fn usm_report(counter: &[u32]) -> v2::Pdus {
+    v2::Pdus::Report(v2::Report(v2::Pdu {
+        request_id: 0,
+        error_status: 0,
+        error_index: 0,
+        variable_bindings: vec![v2::VarBind {
+            name: ObjectIdentifier::new_unchecked(counter.to_vec().into()),
+            value: v2::VarBindValue::Value(ObjectSyntax::ApplicationWide(
+                ApplicationSyntax::Counter(rasn_smi::v1::Counter(1)),
+            )),
+        }],
+    }))
+}
+
+#[test]
+fn usm_rediscovers_after_unknown_engine_id_report() {
+    let mut usm = discovered_usm(UsmUser::new(b"public"));
+    assert!(usm.handshake().unwrap().is_none());
+
+    let report = usm
+        .encode(usm_report(super::usm::USM_STATS_UNKNOWN_ENGINE_IDS))
+        .unwrap();
+    usm.decode(&report).unwrap();
+
+    assert!(usm.resync());
+    assert!(!usm.resync());
+    assert!(usm.handshake().unwrap().is_some());
+}
+
+#[test]
+fn usm_adopts_clock_from_authenticated_time_window_report() {
+    let user = UsmUser::new(b"admin").auth(AuthProtocol::Sha1, b"authpassword");
+    let mut usm = discovered_usm(user.clone());
+    let mut rebooted = discovered_usm_at(user, 8);
+
+    let report = rebooted
+        .encode(usm_report(super::usm::USM_STATS_NOT_IN_TIME_WINDOWS))
+        .unwrap();
+    usm.decode(&report).unwrap();
+    assert!(usm.resync());
+
+    let request = usm.encode(usm_report(&[1, 3, 6, 1])).unwrap();
+    let message: v3::Message = rasn::ber::decode(&request).unwrap();
+    let params: v3::USMSecurityParameters =
+        rasn::ber::decode(&message.security_parameters).unwrap();
+    assert_eq!(params.authoritative_engine_boots, 8.into());
+}
+
+#[test]
+fn usm_keeps_its_clock_against_older_messages() {
+    let user = UsmUser::new(b"admin").auth(AuthProtocol::Sha1, b"authpassword");
+    let mut usm = discovered_usm_at(user.clone(), 8);
+    let mut stale = discovered_usm_at(user, 7);
+
+    let response = stale.encode(usm_report(&[1, 3, 6, 1])).unwrap();
+    usm.decode(&response).unwrap();
+
+    let request = usm.encode(usm_report(&[1, 3, 6, 1])).unwrap();
+    let message: v3::Message = rasn::ber::decode(&request).unwrap();
+    let params: v3::USMSecurityParameters =
+        rasn::ber::decode(&message.security_parameters).unwrap();
+    assert_eq!(params.authoritative_engine_boots, 8.into());
+}
+
+#[test]
+fn usm_ignores_unauthenticated_time_window_reports() {
+    let user = UsmUser::new(b"admin").auth(AuthProtocol::Md5, b"authpassword");
+    let mut usm = discovered_usm(user);
+    let mut forged = discovered_usm_at(UsmUser::new(b"admin"), 8);
+
+    let report = forged
+        .encode(usm_report(super::usm::USM_STATS_NOT_IN_TIME_WINDOWS))
+        .unwrap();
+    usm.decode(&report).unwrap();
+
+    assert!(!usm.resync());
+}
+
+/// Answers every GetRequest with its own bindings, the way the agent threads do above.
+fn echo_response(request: &[u8]) -> Vec<u8> {
+    use rasn_snmp::v2c;
+
+    let request: v2c::Message<v2::Pdus> = rasn::ber::decode(request).unwrap();
+    let v2::Pdus::GetRequest(get) = request.data else {
+        panic!("expected a GetRequest");
+    };
+
+    rasn::ber::encode(&v2c::Message {
+        version: request.version,
+        community: request.community,
+        data: v2::Pdus::Response(v2::Response(get.0)),
+    })
+    .unwrap()
+}
+
+/// Echoes GetRequests over UDP until no request arrives for a while.
+fn echo_agent() -> (std::net::SocketAddr, std::thread::JoinHandle<usize>) {
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(std::time::Duration::from_millis(500)))
+        .unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    let agent = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let mut answered = 0;
+
+        while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+            socket.send_to(&echo_response(&buf[..len]), peer).unwrap();
+            answered += 1;
+        }
+
+        answered
+    });
+
+    (addr, agent)
+}
+
+#[test]
+fn poller_polls_targets_on_their_intervals() {
+    use std::time::Duration;
+
+    use super::{Poller, Target};
+
+    let (fast_addr, fast_agent) = echo_agent();
+    let (slow_addr, slow_agent) = echo_agent();
+
+    let targets = vec![
+        Target::get(
+            SyncSession::builder(fast_addr.to_string()),
+            &["1.3.6.1.2.1.1.3.0"],
+            Duration::from_millis(20),
+        )
+        .unwrap(),
+        Target::get(
+            SyncSession::builder(slow_addr.to_string()),
+            &["1.3.6.1.2.1.1.5.0", "1.3.6.1.2.1.1.6.0"],
+            Duration::from_secs(60),
+        )
+        .unwrap(),
+    ];
+    let poller = Poller::start(targets, 2);
+
+    let mut polls = [0, 0];
+    while polls[0] < 3 {
+        let poll = poller
+            .results()
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap();
+        let vars = poll.result.unwrap();
+
+        assert_eq!(vars.len(), poll.target + 1);
+        polls[poll.target] += 1;
+    }
+    drop(poller);
+
+    assert_eq!(polls[1], 1);
+    assert!(fast_agent.join().unwrap() >= 3);
+    assert_eq!(slow_agent.join().unwrap(), 1);
+}
+
+#[test]
+fn dispatcher_shares_one_socket_between_sessions() {
+    let (first, first_agent) = echo_agent();
+    let (second, second_agent) = echo_agent();
+    let dispatcher = super::Dispatcher::bind("127.0.0.1:0").unwrap();
+
+    let sessions: Vec<_> = [first, first, second]
+        .iter()
+        .map(|agent| {
+            SyncSession::builder(agent.to_string())
+                .build_dispatched(&dispatcher)
+                .unwrap()
+        })
+        .collect();
+
+    std::thread::scope(|scope| {
+        for (i, sess) in sessions.iter().enumerate() {
+            scope.spawn(move || {
+                for j in 0..20 {
+                    let name = format!("1.3.6.1.2.1.2.2.1.2.{}", i * 100 + j);
+                    let vars = sess.get(name.as_str()).unwrap();
+                    assert_eq!(vars[0].0, oid(&name));
+                }
+            });
+        }
+    });
+
+    for sess in &sessions {
+        assert_eq!(
+            super::Transport::local_addr(&sess.transport).unwrap(),
+            dispatcher.local_addr().unwrap()
+        );
+    }
+    drop(sessions);
+    drop(dispatcher);
+
+    assert_eq!(first_agent.join().unwrap(), 40);
+    assert_eq!(second_agent.join().unwrap(), 20);
+}
+
+#[test]
+fn dispatcher_only_accepts_responses_from_allowed_sources() {
+    use std::net::UdpSocket;
+    use std::time::Duration;
+
+    // A multihomed agent that answers from an address other than the one it was asked on.
+    let asked = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let answering = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let (asked_addr, answering_addr) =
+        (asked.local_addr().unwrap(), answering.local_addr().unwrap());
+
+    let agent = std::thread::spawn(move || {
+        asked
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let mut buf = [0; 1500];
+
+        while let Ok((len, peer)) = asked.recv_from(&mut buf) {
+            answering
+                .send_to(&echo_response(&buf[..len]), peer)
+                .unwrap();
+        }
+    });
+
+    let dispatcher = super::Dispatcher::bind("127.0.0.1:0").unwrap();
+    let builder = SyncSession::builder(asked_addr.to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0);
+
+    let strict = builder.clone().build_dispatched(&dispatcher).unwrap();
+    assert!(matches!(
+        strict.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+
+    let transport = dispatcher
+        .transport(asked_addr)
+        .unwrap()
+        .accept_from(answering_addr)
+        .unwrap();
+    let multihomed = builder.build_with(transport).unwrap();
+    assert!(multihomed.get("1.3.6.1.2.1.1.5.0").is_ok());
+
+    drop((strict, multihomed, dispatcher));
+    agent.join().unwrap();
+}
+
+#[test]
+fn tcp_transport_reassembles_framed_messages() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let agent = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0; 1500];
+        let len = stream.read(&mut buf).unwrap();
+
+        // Split the response to make sure the session waits for the whole message.
+        let response = echo_response(&buf[..len]);
+        let (head, tail) = response.split_at(3);
+        stream.write_all(head).unwrap();
+        stream.flush().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        stream.write_all(tail).unwrap();
+    });
+
+    let sess = SyncSession::builder("127.0.0.1")
+        .port(port)
+        .build_tcp()
+        .unwrap();
+    let vars = sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+
+    assert_eq!(vars[0].0, oid("1.3.6.1.2.1.1.5.0"));
+    agent.join().unwrap();
+}
+
+#[test]
+fn sessions_run_over_custom_transports() {
+    use std::cell::RefCell;
+    use std::io;
+    use std::time::Duration;
+
+    /// An agent that lives in memory and answers on the next `recv`.
+    struct Loopback(RefCell<Vec<Vec<u8>>>);
+
+    impl super::Transport for Loopback {
+        fn send(&self, data: &[u8]) -> io::Result<()> {
+            self.0.borrow_mut().push(echo_response(data));
+            Ok(())
+        }
+
+        fn recv(&self, buf: &mut [u8], _timeout: Duration) -> io::Result<usize> {
+            let message = self.0.borrow_mut().pop().ok_or(io::ErrorKind::TimedOut)?;
+            buf[..message.len()].copy_from_slice(&message);
+            Ok(message.len())
+        }
+
+        fn max_msg_size(&self) -> usize {
+            1500
+        }
+    }
+
+    let sess = SyncSession::builder("unused")
+        .build_with(Loopback(RefCell::new(Vec::new())))
+        .unwrap();
+
+    let vars = sess.get("1.3.6.1.2.1.1.1.0").unwrap();
+    assert_eq!(vars[0].0, oid("1.3.6.1.2.1.1.1.0"));
+}
+
+/// An in-memory agent that answers tooBig to GETs of more than two OIDs and GETBULKs of
+/// more than two repetitions, and cuts responses short like a UDP socket would.
+#[derive(Default)]
+struct TooBigAgent {
+    requests: std::cell::RefCell<Vec<usize>>,
+    response: std::cell::RefCell<Option<Vec<u8>>>,
+}
+
+impl super::Transport for TooBigAgent {
+    fn send(&self, data: &[u8]) -> std::io::Result<()> {
+        use rasn_snmp::v2c;
+
+        let request: v2c::Message<v2::Pdus> = rasn::ber::decode(data).unwrap();
+        let (request_id, size, bindings) = match request.data {
+            v2::Pdus::GetRequest(get) => {
+                let size = get.0.variable_bindings.len();
+                (get.0.request_id, size, get.0.variable_bindings)
+            }
+            v2::Pdus::GetBulkRequest(bulk) => {
+                let rows = (1..=bulk.0.max_repetitions).flat_map(|row| {
+                    bulk.0.variable_bindings.iter().map(move |var| v2::VarBind {
+                        name: ObjectIdentifier::new_unchecked(
+                            [&var.name[..], &[row]].concat().into(),
+                        ),
+                        value: v2::VarBindValue::Value(ObjectSyntax::Simple(
+                            SimpleSyntax::Integer(row.into()),
+                        )),
+                    })
+                });
+                let size = bulk.0.max_repetitions as usize;
+                (bulk.0.request_id, size, rows.collect())
+            }
+            _ => panic!("expected a GetRequest or GetBulkRequest"),
+        };
+        self.requests.borrow_mut().push(size);
+
+        let response = v2::Pdu {
+            request_id,
+            error_status: if size > 2 { 1 } else { 0 },
+            error_index: 0,
+            variable_bindings: bindings,
+        };
+        *self.response.borrow_mut() = Some(
+            rasn::ber::encode(&v2c::Message {
+                version: request.version,
+                community: request.community,
+                data: v2::Pdus::Response(v2::Response(response)),
+            })
+            .unwrap(),
+        );
+
+        Ok(())
+    }
+
+    fn recv(&self, buf: &mut [u8], _timeout: std::time::Duration) -> std::io::Result<usize> {
+        let message = self.response.borrow_mut().take();
+        let message = message.ok_or(std::io::ErrorKind::TimedOut)?;
+
+        let len = message.len().min(buf.len());
+        buf[..len].copy_from_slice(&message[..len]);
+        Ok(len)
+    }
+
+    fn max_msg_size(&self) -> usize {
+        1500
+    }
+}
+
+#[test]
+fn too_big_responses_split_the_request() {
+    let sess = SyncSession::builder("unused")
+        .build_with(TooBigAgent::default())
+        .unwrap();
+
+    let oids: Vec<_> = (1..=5)
+        .map(|i| format!("1.3.6.1.2.1.2.2.1.10.{}", i))
+        .collect();
+    let vars = sess.get_many(&oids).unwrap();
+    assert_eq!(
+        vars.iter()
+            .map(|(name, _)| name.to_string())
+            .collect::<Vec<_>>(),
+        oids
+    );
+
+    let vars = sess.getbulk(&["1.3.6.1.2.1.2.2.1.10"], 0, 8).unwrap();
+    assert_eq!(vars.len(), 2);
+    assert_eq!(vars[1].0, oid("1.3.6.1.2.1.2.2.1.10.2"));
+
+    // 5 OIDs fail, then batches of 2, 2 and 1; 8 repetitions fail, then 4, then 2.
+    let requests = sess.transport.requests.borrow();
+    assert_eq!(*requests, [5, 2, 2, 1, 8, 4, 2]);
+}
+
+#[test]
+fn truncated_responses_are_reported() {
+    let sess = SyncSession::builder("unused")
+        .recv_buffer_size(32)
+        .build_with(TooBigAgent::default())
+        .unwrap();
+
+    match sess.get("1.3.6.1.2.1.1.1.0") {
+        Err(SnmpError::Truncated { size, buffer: 32 }) => assert!(size > 32),
+        other => panic!("expected a truncated response, got {:?}", other),
+    }
+}
+
+#[cfg(feature = "tls")]
+fn tls_agent(
+    server: rcgen::CertifiedKey,
+    client: rustls::pki_types::CertificateDer<'static>,
+) -> (u16, std::thread::JoinHandle<Option<v3::Message>>) {
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+    use std::sync::Arc;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(client).unwrap();
+    let verifier =
+        rustls::server::WebPkiClientVerifier::builder_with_provider(roots.into(), provider.clone())
+            .build()
+            .unwrap();
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(
+            vec![server.cert.der().clone()],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(server.key_pair.serialize_der())),
+        )
+        .unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let agent = std::thread::spawn(move || {
+        let (socket, _) = listener.accept().unwrap();
+        let connection = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+        let mut stream = rustls::StreamOwned::new(connection, socket);
+
+        let mut buf = [0; 1500];
+        let len = super::transport::read_message(&mut stream, &mut buf).ok()?;
+        let request: v3::Message = rasn::ber::decode(&buf[..len]).unwrap();
+
+        let v3::ScopedPduData::CleartextPdu(scoped) = &request.scoped_data else {
+            panic!("expected a plaintext scoped PDU");
+        };
+        let v2::Pdus::GetRequest(get) = &scoped.data else {
+            panic!("expected a GetRequest");
+        };
+        let mut response = request.clone();
+        response.scoped_data = v3::ScopedPduData::CleartextPdu(v3::ScopedPdu {
+            data: v2::Pdus::Response(v2::Response(get.0.clone())),
+            ..scoped.clone()
+        });
+
+        std::io::Write::write_all(&mut stream, &rasn::ber::encode(&response).unwrap()).unwrap();
+        std::io::Write::flush(&mut stream).unwrap();
+
+        Some(request)
+    });
+
+    (port, agent)
+}
+
+#[cfg(feature = "tls")]
+fn tls_client(
+    fingerprint: &[u8],
+) -> (super::TlsConfig, rustls::pki_types::CertificateDer<'static>) {
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+
+    let client = rcgen::generate_simple_self_signed(vec!["manager".to_string()]).unwrap();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(client.key_pair.serialize_der()));
+    let config = super::TlsConfig::new(vec![client.cert.der().clone()], key)
+        .server_fingerprint(fingerprint)
+        .unwrap();
+
+    (config, client.cert.der().clone())
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls_session_uses_the_transport_security_model() {
+    use sha2::{Digest, Sha256};
+
+    let server = rcgen::generate_simple_self_signed(vec!["agent".to_string()]).unwrap();
+    let fingerprint = [&[4][..], &Sha256::digest(server.cert.der())].concat();
+    let (config, client_cert) = tls_client(&fingerprint);
+    let (port, agent) = tls_agent(server, client_cert);
+
+    let sess = SyncSession::builder("127.0.0.1")
+        .port(port)
+        .build_tls(&config)
+        .unwrap();
+    let vars = sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(vars[0].0, oid("1.3.6.1.2.1.1.5.0"));
+
+    let request = agent.join().unwrap().unwrap();
+    assert_eq!(request.global_data.security_model, 4.into());
+    assert!(request.security_parameters.is_empty());
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn tls_session_rejects_unexpected_agent_certificates() {
+    let server = rcgen::generate_simple_self_signed(vec!["agent".to_string()]).unwrap();
+    let (config, client_cert) = tls_client(&[[4].as_slice(), &[0; 32]].concat());
+    let (port, agent) = tls_agent(server, client_cert);
+
+    let sess = SyncSession::builder("127.0.0.1")
+        .port(port)
+        .build_tls(&config)
+        .unwrap();
+
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Io(_))
+    ));
+    assert!(agent.join().unwrap().is_none());
+}
+
+#[test]
+fn trap_listener_receives_and_acknowledges_notifications() {
+    use super::{Notification, TrapListener, Version};
+
+    let mut listener = TrapListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let receiver = std::thread::spawn(move || {
+        let events: Vec<_> = listener.by_ref().take(3).map(Result::unwrap).collect();
+        events
+    });
+
+    let v2c = SyncSession::new(1, addr, b"public", 1000).unwrap();
+    v2c.send_trap(
+        "1.3.6.1.6.3.1.1.5.3",
+        &[("1.3.6.1.2.1.2.2.1.1.2", Value::Integer(2))],
+    )
+    .unwrap();
+    v2c.send_inform("1.3.6.1.6.3.1.1.5.4", &[] as &[(Oid, Value)])
+        .unwrap();
+
+    let v1 = SyncSession::new(0, addr, b"traps", 1000).unwrap();
+    v1.send_v1_trap("1.3.6.1.4.1.8072", 6, 17, &[] as &[(Oid, Value)])
+        .unwrap();
+
+    let events = receiver.join().unwrap();
+
+    assert_eq!(events[0].version, Version::V2c);
+    assert!(!events[0].inform);
+    let Notification::V2 {
+        trap_oid, bindings, ..
+    } = &events[0].notification
+    else {
+        panic!("expected a v2 notification");
+    };
+    assert_eq!(*trap_oid, oid("1.3.6.1.6.3.1.1.5.3"));
+    assert_eq!(
+        *bindings,
+        vec![(oid("1.3.6.1.2.1.2.2.1.1.2"), Value::Integer(2))]
+    );
+
+    assert!(events[1].inform);
+
+    assert_eq!(events[2].community, b"traps");
+    assert!(matches!(
+        &events[2].notification,
+        Notification::V1 {
+            generic_trap: 6,
+            specific_trap: 17,
+            ..
+        }
+    ));
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_trap_listener_is_a_stream() {
+    use futures_util::StreamExt;
+
+    let mut listener = super::AsyncTrapListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let sess = super::AsyncSession::new(1, addr, b"public", 1000)
+        .await
+        .unwrap();
+    sess.send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+        .await
+        .unwrap();
+
+    let event = listener.next().await.unwrap().unwrap();
+    assert!(matches!(
+        event.notification,
+        super::Notification::V2 { trap_oid, .. } if trap_oid == oid("1.3.6.1.6.3.1.1.5.1")
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn results_serialize_with_dotted_oids() {
+    use std::collections::BTreeMap;
+
+    use super::{Notification, TrapEvent, Version};
+
+    let event = TrapEvent {
+        source: "192.0.2.1:162".parse().unwrap(),
+        version: Version::V2c,
+        community: b"public".to_vec(),
+        engine_id: None,
+        inform: false,
+        notification: Notification::V2 {
+            uptime: 4200,
+            trap_oid: oid("1.3.6.1.6.3.1.1.5.3"),
+            bindings: vec![(oid("1.3.6.1.2.1.2.2.1.1.7"), Value::Integer(7))],
+        },
+        received: std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000),
+        raw: vec![0x30, 0x00],
+        trap_name: Some("IF-MIB::linkDown".to_string()),
+        bindings: Vec::new(),
+        annotations: BTreeMap::from([("site".to_string(), "lab".to_string())]),
+        suppressed: 2,
+    };
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        json["notification"]["V2"]["trap_oid"],
+        "1.3.6.1.6.3.1.1.5.3"
+    );
+    assert_eq!(serde_json::from_value::<TrapEvent>(json).unwrap(), event);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Dump {
+        #[serde(with = "super::oid_keys")]
+        walk: BTreeMap<Vec<u32>, Value>,
+    }
+
+    let walk = BTreeMap::from([
+        (
+            vec![1, 3, 6, 1, 2, 1, 4, 20, 1, 1, 10, 0, 0, 1],
+            Value::IpAddress([10, 0, 0, 1].into()),
+        ),
+        (vec![1, 3, 6, 1, 2, 1, 1, 3, 0], Value::TimeTicks(360000)),
+    ]);
+    let json = serde_json::to_string(&Dump { walk: walk.clone() }).unwrap();
+    assert_eq!(
+        json,
+        r#"{"walk":{"1.3.6.1.2.1.1.3.0":{"TimeTicks":360000},"1.3.6.1.2.1.4.20.1.1.10.0.0.1":{"IpAddress":"10.0.0.1"}}}"#
+    );
+    assert_eq!(serde_json::from_str::<Dump>(&json).unwrap().walk, walk);
+}
+
+/// An in-memory v2c agent serving a fixed MIB view to GET, GETNEXT and GETBULK.
+struct ViewAgent {
+    view: std::collections::BTreeMap<Oid, Value>,
+    response: std::cell::RefCell<Option<Vec<u8>>>,
+}
+
+impl ViewAgent {
+    fn new(view: &[(&str, Value)]) -> Self {
+        ViewAgent {
+            view: view
+                .iter()
+                .map(|(name, value)| (oid(name), value.clone()))
+                .collect(),
+            response: Default::default(),
+        }
+    }
+
+    fn next(&self, name: &Oid) -> (Oid, Value) {
+        use std::ops::Bound;
+
+        match self
+            .view
+            .range((Bound::Excluded(name), Bound::Unbounded))
+            .next()
+        {
+            Some((name, value)) => (name.clone(), value.clone()),
+            None => (name.clone(), Value::EndOfMibView),
+        }
+    }
+}
+
+impl super::Transport for ViewAgent {
+    fn send(&self, data: &[u8]) -> std::io::Result<()> {
+        use rasn_snmp::v2c;
+
+        let request: v2c::Message<v2::Pdus> = rasn::ber::decode(data).unwrap();
+        let names = |vars: &[v2::VarBind]| -> Vec<Oid> {
+            vars.iter()
+                .map(|var| Oid::from(var.name.to_vec()))
+                .collect()
+        };
+
+        let (request_id, vars) = match &request.data {
+            v2::Pdus::GetRequest(get) => {
+                let vars = names(&get.0.variable_bindings).into_iter().map(|name| {
+                    let value = self.view.get(&name).cloned();
+                    (name, value.unwrap_or(Value::NoSuchObject))
+                });
+                (get.0.request_id, vars.collect())
+            }
+            v2::Pdus::GetNextRequest(next) => {
+                let names = names(&next.0.variable_bindings);
+                let vars = names.iter().map(|name| self.next(name));
+                (next.0.request_id, vars.collect())
+            }
+            v2::Pdus::GetBulkRequest(bulk) => {
+                let mut names = names(&bulk.0.variable_bindings);
+                let mut vars = Vec::new();
+                for _ in 0..bulk.0.max_repetitions {
+                    for name in &mut names {
+                        let (next, value) = self.next(name);
+                        *name = next.clone();
+                        vars.push((next, value));
+                    }
+                }
+                (bulk.0.request_id, vars)
+            }
+            _ => panic!("expected a GET, GETNEXT or GETBULK"),
+        };
+
+        let response = v2::Pdu {
+            request_id,
+            error_status: 0,
+            error_index: 0,
+            variable_bindings: vars
+                .into_iter()
+                .map(|(name, value)| v2::VarBind {
+                    name: name.to_asn(),
+                    value: value.into(),
+                })
+                .collect(),
+        };
+        *self.response.borrow_mut() = Some(
+            rasn::ber::encode(&v2c::Message {
+                version: request.version,
+                community: request.community,
+                data: v2::Pdus::Response(v2::Response(response)),
+            })
+            .unwrap(),
+        );
+
+        Ok(())
+    }
+
+    fn recv(&self, buf: &mut [u8], _timeout: std::time::Duration) -> std::io::Result<usize> {
+        let message = self.response.borrow_mut().take();
+        let message = message.ok_or(std::io::ErrorKind::TimedOut)?;
+
+        buf[..message.len()].copy_from_slice(&message);
+        Ok(message.len())
+    }
+
+    fn max_msg_size(&self) -> usize {
+        65507
+    }
+}
+
+#[test]
+fn system_info_reads_the_system_group() {
+    let agent = ViewAgent::new(&[
+        (
+            "1.3.6.1.2.1.1.1.0",
+            Value::OctetString(b"Linux router".to_vec()),
+        ),
+        (
+            "1.3.6.1.2.1.1.2.0",
+            Value::Oid(vec![1, 3, 6, 1, 4, 1, 8072, 3, 2, 10]),
+        ),
+        ("1.3.6.1.2.1.1.3.0", Value::TimeTicks(123456)),
+        ("1.3.6.1.2.1.1.5.0", Value::OctetString(b"core-1".to_vec())),
+        ("1.3.6.1.2.1.1.7.0", Value::Integer(72)),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let info = sess.system_info().unwrap();
+    assert_eq!(info.descr, "Linux router");
+    assert_eq!(info.object_id, oid("1.3.6.1.4.1.8072.3.2.10"));
+    assert_eq!(info.uptime, std::time::Duration::from_millis(1234560));
+    assert_eq!(info.name, "core-1");
+    assert_eq!(info.contact, "");
+    assert_eq!(info.services, 72);
+}
+
+#[test]
+fn interfaces_join_if_table_and_if_x_table() {
+    use super::IfStatus;
+
+    let agent = ViewAgent::new(&[
+        ("1.3.6.1.2.1.2.2.1.2.1", Value::OctetString(b"lo".to_vec())),
+        (
+            "1.3.6.1.2.1.2.2.1.2.2",
+            Value::OctetString(b"eth0".to_vec()),
+        ),
+        ("1.3.6.1.2.1.2.2.1.5.1", Value::Gauge32(10_000_000)),
+        ("1.3.6.1.2.1.2.2.1.5.2", Value::Gauge32(u32::MAX)),
+        (
+            "1.3.6.1.2.1.2.2.1.6.2",
+            Value::OctetString(vec![0, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]),
+        ),
+        ("1.3.6.1.2.1.2.2.1.8.1", Value::Integer(1)),
+        ("1.3.6.1.2.1.2.2.1.8.2", Value::Integer(7)),
+        ("1.3.6.1.2.1.2.2.1.10.2", Value::Counter32(1000)),
+        ("1.3.6.1.2.1.4.3.0", Value::Counter32(5)),
+        (
+            "1.3.6.1.2.1.31.1.1.1.1.2",
+            Value::OctetString(b"Ethernet0".to_vec()),
+        ),
+        ("1.3.6.1.2.1.31.1.1.1.6.2", Value::Counter64(1 << 40)),
+        ("1.3.6.1.2.1.31.1.1.1.15.2", Value::Gauge32(10_000)),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let interfaces = sess.interfaces().unwrap();
+    assert_eq!(interfaces.len(), 2);
+
+    let (lo, eth0) = (&interfaces[0], &interfaces[1]);
+    assert_eq!(
+        (lo.index, lo.descr.as_deref(), lo.name.as_deref()),
+        (1, Some("lo"), None)
+    );
+    assert_eq!(lo.speed, Some(10_000_000));
+    assert_eq!(lo.oper_status, Some(IfStatus::Up));
+
+    assert_eq!(eth0.name.as_deref(), Some("Ethernet0"));
+    assert_eq!(eth0.speed, Some(10_000_000_000));
+    assert_eq!(eth0.oper_status, Some(IfStatus::LowerLayerDown));
+    assert_eq!(
+        eth0.phys_address.as_deref(),
+        Some(&[0, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e][..])
+    );
+    assert_eq!(
+        (eth0.in_octets, eth0.hc_in_octets),
+        (Some(1000), Some(1 << 40))
+    );
+    assert_eq!(eth0.hc_out_octets, None);
+}
+
+#[test]
+fn fdb_table_decodes_macs_and_maps_ports() {
+    use super::FdbStatus;
+
+    let agent = ViewAgent::new(&[
+        ("1.3.6.1.2.1.17.1.4.1.2.1", Value::Integer(10101)),
+        ("1.3.6.1.2.1.17.1.4.1.2.2", Value::Integer(10102)),
+        (
+            "1.3.6.1.2.1.17.7.1.2.2.1.2.1.0.26.43.60.77.94",
+            Value::Integer(2),
+        ),
+        (
+            "1.3.6.1.2.1.17.7.1.2.2.1.2.20.170.187.204.0.0.1",
+            Value::Integer(0),
+        ),
+        (
+            "1.3.6.1.2.1.17.7.1.2.2.1.3.1.0.26.43.60.77.94",
+            Value::Integer(3),
+        ),
+        (
+            "1.3.6.1.2.1.17.7.1.2.2.1.3.20.170.187.204.0.0.1",
+            Value::Integer(4),
+        ),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let fdb = sess.fdb_table().unwrap();
+    assert_eq!(fdb.len(), 2);
+    assert_eq!(
+        (
+            fdb[0].vlan,
+            fdb[0].mac,
+            fdb[0].port,
+            fdb[0].if_index,
+            fdb[0].status
+        ),
+        (
+            Some(1),
+            super::MacAddr([0, 26, 43, 60, 77, 94]),
+            2,
+            Some(10102),
+            Some(FdbStatus::Learned)
+        )
+    );
+    assert_eq!(
+        (fdb[1].vlan, fdb[1].port, fdb[1].if_index, fdb[1].status),
+        (Some(20), 0, None, Some(FdbStatus::SelfAddress))
+    );
+
+    let agent = ViewAgent::new(&[
+        ("1.3.6.1.2.1.17.4.3.1.2.0.26.43.60.77.94", Value::Integer(1)),
+        ("1.3.6.1.2.1.17.4.3.1.3.0.26.43.60.77.94", Value::Integer(3)),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let fdb = sess.fdb_table().unwrap();
+    assert_eq!((fdb[0].vlan, fdb[0].mac[5], fdb[0].port), (None, 94, 1));
+}
+
+#[test]
+fn neighbor_table_decodes_address_indices() {
+    use super::NeighborKind;
+
+    let mac = Value::OctetString(vec![0, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+    let agent = ViewAgent::new(&[
+        ("1.3.6.1.2.1.4.35.1.4.3.1.4.10.0.0.1", mac.clone()),
+        (
+            "1.3.6.1.2.1.4.35.1.4.3.2.16.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.1",
+            mac.clone(),
+        ),
+        (
+            "1.3.6.1.2.1.4.35.1.4.3.1.4.10.0.0.9",
+            Value::OctetString(Vec::new()),
+        ),
+        ("1.3.6.1.2.1.4.35.1.6.3.1.4.10.0.0.1", Value::Integer(3)),
+        (
+            "1.3.6.1.2.1.4.35.1.6.3.2.16.254.128.0.0.0.0.0.0.0.0.0.0.0.0.0.1",
+            Value::Integer(5),
+        ),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let neighbors = sess.neighbor_table().unwrap();
+    assert_eq!(neighbors.len(), 2);
+    assert_eq!(neighbors[0].if_index, 3);
+    assert_eq!(
+        neighbors[0].ip,
+        "10.0.0.1".parse::<std::net::IpAddr>().unwrap()
+    );
+    assert_eq!(neighbors[0].mac, [0, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+    assert_eq!(neighbors[0].kind, Some(NeighborKind::Dynamic));
+    assert_eq!(
+        neighbors[1].ip,
+        "fe80::1".parse::<std::net::IpAddr>().unwrap()
+    );
+    assert_eq!(neighbors[1].kind, Some(NeighborKind::Local));
+
+    let agent = ViewAgent::new(&[
+        ("1.3.6.1.2.1.4.22.1.2.2.192.168.1.7", mac),
+        ("1.3.6.1.2.1.4.22.1.4.2.192.168.1.7", Value::Integer(4)),
+    ]);
+    let sess = SyncSession::builder("unused").build_with(agent).unwrap();
+
+    let neighbors = sess.neighbor_table().unwrap();
+    assert_eq!(neighbors[0].if_index, 2);
+    assert_eq!(
+        neighbors[0].ip,
+        "192.168.1.7".parse::<std::net::IpAddr>().unwrap()
+    );
+    assert_eq!(neighbors[0].kind, Some(NeighborKind::Static));
+}
+
+#[test]
+fn table_indices_round_trip() {
+    use super::{IndexDecoder, IndexEncoder};
+
+    // vacmAccessTable: vacmGroupName, vacmAccessContextPrefix, vacmAccessSecurityModel and
+    // vacmAccessSecurityLevel.
+    let index = IndexEncoder::new()
+        .string(b"admins")
+        .string(b"")
+        .integer(3)
+        .integer(3)
+        .finish();
+    assert_eq!(index, [6, 97, 100, 109, 105, 110, 115, 0, 3, 3]);
+
+    let mut decoder = IndexDecoder::new(&index);
+    assert_eq!(decoder.string().unwrap(), b"admins");
+    assert_eq!(decoder.string().unwrap(), b"");
+    assert_eq!(
+        (decoder.integer().unwrap(), decoder.integer().unwrap()),
+        (3, 3)
+    );
+    decoder.finish().unwrap();
+
+    let index = IndexEncoder::new()
+        .ip_address([10, 0, 0, 1].into())
+        .oid(&oid("1.3.6"))
+        .implied_string(b"ab")
+        .finish();
+    let mut decoder = IndexDecoder::new(&index);
+    assert_eq!(
+        decoder.ip_address().unwrap(),
+        std::net::Ipv4Addr::new(10, 0, 0, 1)
+    );
+    assert_eq!(decoder.oid().unwrap(), oid("1.3.6"));
+    assert_eq!(decoder.implied_string().unwrap(), b"ab");
+    decoder.finish().unwrap();
+
+    let mut decoder = IndexDecoder::new(&[5, 1, 2]);
+    assert!(matches!(decoder.string(), Err(SnmpError::InvalidIndex(_))));
+    let mut decoder = IndexDecoder::new(&[1, 256]);
+    assert!(matches!(decoder.string(), Err(SnmpError::InvalidIndex(_))));
+    let mut decoder = IndexDecoder::new(&[1, 2]);
+    decoder.integer().unwrap();
+    assert!(matches!(decoder.finish(), Err(SnmpError::InvalidIndex(_))));
+}
+
+#[test]
+fn exceptions_are_values_unless_asked_to_fail() {
+    let view = [("1.3.6.1.2.1.1.4.0", Value::OctetString(Vec::new()))];
+
+    let sess = SyncSession::builder("unused")
+        .build_with(ViewAgent::new(&view))
+        .unwrap();
+    let vars = sess
+        .get_many(&["1.3.6.1.2.1.1.4.0", "1.3.6.1.2.1.1.99.0"])
+        .unwrap();
+    assert_eq!(vars[0].1, Value::OctetString(Vec::new()));
+    assert_eq!(vars[1].1, Value::NoSuchObject);
+
+    let sess = SyncSession::builder("unused")
+        .exceptions_as_errors(true)
+        .build_with(ViewAgent::new(&view))
+        .unwrap();
+    assert!(sess.get("1.3.6.1.2.1.1.4.0").is_ok());
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.99.0"),
+        Err(SnmpError::NoSuchObject(missing)) if missing == oid("1.3.6.1.2.1.1.99.0")
+    ));
+}
+
+#[test]
+fn bulk_table_fetch_matches_getnext_fetch_on_sparse_tables() {
+    let mut view = Vec::new();
+    for row in 1..=7u32 {
+        view.push((
+            format!("1.3.6.1.2.1.2.2.1.2.{}", row),
+            Value::Integer(row.into()),
+        ));
+        // Column 5 only has every other row, and column 8 ends after the second.
+        if row % 2 == 1 {
+            view.push((format!("1.3.6.1.2.1.2.2.1.5.{}", row), Value::Gauge32(row)));
+        }
+        if row <= 2 {
+            view.push((format!("1.3.6.1.2.1.2.2.1.8.{}", row), Value::Integer(1)));
+        }
+    }
+    view.push(("1.3.6.1.2.1.2.2.1.9.1".to_string(), Value::TimeTicks(0)));
+    view.push(("1.3.6.1.2.1.4.1.0".to_string(), Value::Integer(1)));
+    let view: Vec<(&str, Value)> = view.iter().map(|(n, v)| (n.as_str(), v.clone())).collect();
+
+    let sess = SyncSession::builder("unused")
+        .build_with(ViewAgent::new(&view))
+        .unwrap();
+
+    let expected = sess.get_table("1.3.6.1.2.1.2.2", &[2, 5, 8]).unwrap();
+    assert_eq!(expected.len(), 7);
+    assert_eq!(expected[&vec![2]].len(), 2);
+
+    for max_repetitions in [1, 3, 10] {
+        let table = sess
+            .get_table_bulk("1.3.6.1.2.1.2.2", &[2, 5, 8], max_repetitions)
+            .unwrap();
+        assert_eq!(table, expected);
+    }
+
+    let whole = sess.get_table_bulk("1.3.6.1.2.1.2.2", &[], 4).unwrap();
+    assert_eq!(whole, sess.get_table("1.3.6.1.2.1.2.2", &[]).unwrap());
+}
+
+#[test]
+fn agent_answers_from_registered_handlers() {
+    use super::{Agent, ErrorStatus};
+    use std::collections::BTreeMap;
+    use std::sync::RwLock;
+
+    let system = BTreeMap::from([
+        (
+            oid("1.3.6.1.2.1.1.1.0"),
+            Value::OctetString(b"test agent".to_vec()),
+        ),
+        (
+            oid("1.3.6.1.2.1.1.5.0"),
+            Value::OctetString(b"router".to_vec()),
+        ),
+    ]);
+    let interfaces = RwLock::new(BTreeMap::from([
+        (oid("1.3.6.1.2.1.2.2.1.7.1"), Value::Integer(1)),
+        (oid("1.3.6.1.2.1.2.2.1.7.2"), Value::Integer(2)),
+    ]));
+    let if_x_table = BTreeMap::from([(oid("1.3.6.1.2.1.31.1.1.1.6.1"), Value::Counter64(7))]);
+
+    let mut agent = Agent::new().write_community("private");
+    agent.register("1.3.6.1.2.1.31", if_x_table).unwrap();
+    agent.register("1.3.6.1.2.1.2", interfaces).unwrap();
+    agent.register("1.3.6.1.2.1.1", system).unwrap();
+    assert!(agent.register("1.3.6.1.2.1.1.9", BTreeMap::new()).is_err());
+    let agent = agent.spawn("127.0.0.1:0").unwrap();
+    let addr = agent.local_addr().unwrap();
+
+    let sess = SyncSession::new(1, addr, b"public", 1000).unwrap();
+    let vars = sess
+        .get_many(&["1.3.6.1.2.1.1.5.0", "1.3.6.1.2.1.1.2.0"])
+        .unwrap();
+    assert_eq!(vars[0].1, Value::OctetString(b"router".to_vec()));
+    assert_eq!(vars[1].1, Value::NoSuchObject);
+
+    // Walks cross from one handler's subtree into the next.
+    let walked = sess.walk("1.3.6.1.2.1").unwrap();
+    assert_eq!(walked.len(), 5);
+    assert_eq!(sess.bulk_walk("1.3.6.1.2.1", 2).unwrap(), walked);
+
+    assert!(matches!(
+        sess.set(&[("1.3.6.1.2.1.2.2.1.7.2", Value::Integer(1))]),
+        Err(SnmpError::AgentError {
+            status: ErrorStatus::NoAccess,
+            index: 1,
+            ..
+        })
+    ));
+
+    let writer = SyncSession::new(1, addr, b"private", 1000).unwrap();
+    writer
+        .set(&[("1.3.6.1.2.1.2.2.1.7.2", Value::Integer(1))])
+        .unwrap();
+    assert_eq!(
+        sess.get("1.3.6.1.2.1.2.2.1.7.2").unwrap()[0].1,
+        Value::Integer(1)
+    );
+    assert!(matches!(
+        writer.set(&[
+            ("1.3.6.1.2.1.2.2.1.7.1", Value::Integer(2)),
+            ("1.3.6.1.2.1.2.2.1.7.3", Value::Integer(2)),
+        ]),
+        Err(SnmpError::AgentError {
+            status: ErrorStatus::NoCreation,
+            index: 2,
+            ..
+        })
+    ));
+    assert!(matches!(
+        writer.set(&[("1.3.6.1.2.1.1.5.0", Value::OctetString(b"x".to_vec()))]),
+        Err(SnmpError::AgentError {
+            status: ErrorStatus::NotWritable,
+            index: 1,
+            ..
+        })
+    ));
+
+    // SNMPv1 walks skip the Counter64 and see the end of the MIB as noSuchName.
+    let v1 = SyncSession::new(0, addr, b"public", 1000).unwrap();
+    assert_eq!(v1.walk("1.3.6.1.2.1").unwrap().len(), 4);
+    assert!(matches!(
+        v1.get("1.3.6.1.2.1.1.2.0"),
+        Err(SnmpError::AgentError {
+            status: ErrorStatus::NoSuchName,
+            index: 1,
+            ..
+        })
+    ));
+
+    let stranger = SyncSession::new(1, addr, b"guess", 200).unwrap();
+    assert!(matches!(
+        stranger.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+}
+
+#[test]
+fn mock_agent_replays_a_walk_with_injected_faults() {
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let mut instances = vec![(oid("1.3.6.1.2.1.1.5.0"), Value::OctetString(b"r1".to_vec()))];
+    for row in 1..=12u32 {
+        instances.push((
+            oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", row)),
+            Value::Integer(row.into()),
+        ));
+    }
+    let agent = MockAgent::new(instances).unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 300).unwrap();
+
+    let walk = sess.walk("1.3.6.1.2").unwrap();
+    assert_eq!(walk.len(), 13);
+    assert_eq!(sess.bulk_walk("1.3.6.1.2", 5).unwrap(), walk);
+
+    // A walk of the mock agent replays as another one.
+    let replay = MockAgent::from_walk("1.3.6.1.2", walk.clone()).unwrap();
+    let replayed = SyncSession::new(1, replay.local_addr().unwrap(), b"public", 300).unwrap();
+    assert_eq!(replayed.walk("1.3.6.1.2").unwrap(), walk);
+
+    // One lost request is made up for by the retransmission.
+    agent.drop_next(1);
+    let received = agent.received();
+    assert!(sess.get("1.3.6.1.2.1.1.5.0").is_ok());
+    assert_eq!(agent.received(), received + 2);
+
+    // An answer slower than the request and its retransmission together times out.
+    agent.set_delay(Duration::from_millis(700));
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+}
+
+#[test]
+fn dumps_load_from_snmprec_and_snmpwalk_output() {
+    use super::dump::{parse_snmprec, parse_snmpwalk};
+
+    let snmprec = "\
+# captured from r1
+1.3.6.1.2.1.1.1.0|4|Cisco IOS Software
+1.3.6.1.2.1.1.2.0|6|1.3.6.1.4.1.9.1.1
+1.3.6.1.2.1.1.3.0|67|8640000
+1.3.6.1.2.1.2.2.1.6.1|4x|001a2b3c4d5e
+1.3.6.1.2.1.2.2.1.7.1|2|1
+1.3.6.1.2.1.4.20.1.1.10.0.0.1|64|10.0.0.1
+1.3.6.1.2.1.31.1.1.1.6.1|70|12345678901
+";
+    let snmpwalk = r#"
+.1.3.6.1.2.1.1.1.0 = STRING: "Cisco IOS Software"
+.1.3.6.1.2.1.1.2.0 = OID: .1.3.6.1.4.1.9.1.1
+.1.3.6.1.2.1.1.3.0 = Timeticks: (8640000) 1 day, 0:00:00.00
+.1.3.6.1.2.1.2.2.1.6.1 = Hex-STRING: 00 1A 2B
+3C 4D 5E
+.1.3.6.1.2.1.2.2.1.7.1 = INTEGER: up(1)
+.1.3.6.1.2.1.4.20.1.1.10.0.0.1 = IpAddress: 10.0.0.1
+.1.3.6.1.2.1.31.1.1.1.6.1 = Counter64: 12345678901
+"#;
+    let captured = parse_snmprec(snmprec).unwrap();
+    assert_eq!(captured.len(), 7);
+    assert_eq!(captured, parse_snmpwalk(snmpwalk).unwrap());
+    assert_eq!(
+        captured[&oid("1.3.6.1.2.1.2.2.1.6.1")],
+        Value::OctetString(vec![0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e])
+    );
+
+    let strings = parse_snmpwalk(
+        ".1.3.6.1.2.1.1.4.0 = \"\"\n.1.3.6.1.2.1.1.6.0 = STRING: \"rack \\\"A\\\"\nrow 2\"\n",
+    )
+    .unwrap();
+    assert_eq!(
+        strings[&oid("1.3.6.1.2.1.1.4.0")],
+        Value::OctetString(Vec::new())
+    );
+    assert_eq!(
+        strings[&oid("1.3.6.1.2.1.1.6.0")],
+        Value::OctetString(b"rack \"A\"\nrow 2".to_vec())
+    );
+
+    let err = parse_snmprec("1.3.6.1.2.1.1.1.0|4|ok\n1.3.6.1.2.1.1.3.0|67|soon\n").unwrap_err();
+    assert_eq!(err.to_string(), "snmprec:2: invalid number");
+}
+
+#[test]
+fn agentx_subagent_serves_its_subtrees_through_the_master() {
+    use super::agentx::{self, Header, Subagent, Writer};
+    use super::Agent;
+    use std::collections::BTreeMap;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::RwLock;
+    use std::time::Duration;
+
+    fn recv(stream: &mut TcpStream) -> (Header, Vec<u8>) {
+        let mut buf = Vec::new();
+        loop {
+            if let Some((header, payload, _)) = Header::decode(&buf).unwrap() {
+                return (header, payload.to_vec());
+            }
+            let mut chunk = [0; 1024];
+            let len = stream.read(&mut chunk).unwrap();
+            assert!(len > 0);
+            buf.extend_from_slice(&chunk[..len]);
+        }
+    }
+
+    fn respond(stream: &mut TcpStream, request: &Header, session_id: u32) {
+        let mut payload = Writer::default();
+        payload.u32(0);
+        payload.u16(0);
+        payload.u16(0);
+        let header = Header {
+            kind: agentx::RESPONSE,
+            session_id,
+            ..*request
+        };
+        stream.write_all(&header.encode(&payload.0)).unwrap();
+    }
+
+    /// Sends a request and returns the Response's error, index and bindings.
+    fn call(
+        stream: &mut TcpStream,
+        kind: u8,
+        packet_id: u32,
+        payload: &Writer,
+    ) -> (u16, u16, Vec<(Oid, Value)>) {
+        let header = Header {
+            kind,
+            flags: 0,
+            session_id: 42,
+            transaction_id: packet_id,
+            packet_id,
+        };
+        stream.write_all(&header.encode(&payload.0)).unwrap();
+
+        let (response, bytes) = recv(stream);
+        assert_eq!(response.kind, agentx::RESPONSE);
+        assert_eq!(response.packet_id, packet_id);
+        let mut fields = response.reader(&bytes);
+        fields.u32().unwrap();
+        let (error, index) = (fields.u16().unwrap(), fields.u16().unwrap());
+        let mut vars = Vec::new();
+        while !fields.is_empty() {
+            vars.push(fields.var_bind().unwrap());
+        }
+        (error, index, vars)
+    }
+
+    fn ranges(starts: &[&str]) -> Writer {
+        let mut payload = Writer::default();
+        for start in starts {
+            payload.oid(&oid(start), false);
+            payload.oid(&[], false);
+        }
+        payload
+    }
+
+    let master = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = master.local_addr().unwrap();
+    let subagent = std::thread::spawn(move || {
+        let mut agent = Agent::new();
+        let instances = BTreeMap::from([
+            (oid("1.3.6.1.4.1.99999.1.0"), Value::Integer(5)),
+            (
+                oid("1.3.6.1.4.1.99999.2.0"),
+                Value::OctetString(b"x".to_vec()),
+            ),
+        ]);
+        agent
+            .register("1.3.6.1.4.1.99999", RwLock::new(instances))
+            .unwrap();
+
+        let mut subagent = Subagent::connect_tcp(addr, agent, "test")
+            .unwrap()
+            .ping_interval(Duration::from_millis(200));
+        assert_eq!(subagent.session_id(), 42);
+        subagent.run()
+    });
+    let (mut stream, _) = master.accept().unwrap();
+
+    let (open, _) = recv(&mut stream);
+    assert_eq!(open.kind, agentx::OPEN);
+    respond(&mut stream, &open, 42);
+    let (register, payload) = recv(&mut stream);
+    assert_eq!((register.kind, register.session_id), (agentx::REGISTER, 42));
+    let (subtree, _) = register.reader(&payload[4..]).oid().unwrap();
+    assert_eq!(subtree, oid("1.3.6.1.4.1.99999"));
+    respond(&mut stream, &register, 42);
+
+    let (_, _, vars) = call(
+        &mut stream,
+        agentx::GET,
+        1,
+        &ranges(&["1.3.6.1.4.1.99999.1.0", "1.3.6.1.4.1.99999.9.0"]),
+    );
+    assert_eq!(vars[0].1, Value::Integer(5));
+    assert_eq!(vars[1].1, Value::NoSuchObject);
+
+    let (_, _, vars) = call(
+        &mut stream,
+        agentx::GET_NEXT,
+        2,
+        &ranges(&["1.3.6.1.4.1.99999"]),
+    );
+    assert_eq!(vars, [(oid("1.3.6.1.4.1.99999.1.0"), Value::Integer(5))]);
+
+    let mut bulk = Writer::default();
+    bulk.u16(0);
+    bulk.u16(3);
+    bulk.0.extend(ranges(&["1.3.6.1.4.1.99999"]).0);
+    let (_, _, vars) = call(&mut stream, agentx::GET_BULK, 3, &bulk);
+    assert_eq!(vars.len(), 3);
+    assert_eq!(vars[1].1, Value::OctetString(b"x".to_vec()));
+    assert_eq!(vars[2].1, Value::EndOfMibView);
+
+    let mut test = Writer::default();
+    test.var_bind(&oid("1.3.6.1.4.1.99999.1.0"), &Value::Integer(7))
+        .unwrap();
+    assert_eq!(call(&mut stream, agentx::TEST_SET, 4, &test).0, 0);
+    assert_eq!(
+        call(&mut stream, agentx::COMMIT_SET, 4, &Writer::default()).0,
+        0
+    );
+    let (_, _, vars) = call(
+        &mut stream,
+        agentx::GET,
+        5,
+        &ranges(&["1.3.6.1.4.1.99999.1.0"]),
+    );
+    assert_eq!(vars[0].1, Value::Integer(7));
+
+    let mut test = Writer::default();
+    test.var_bind(
+        &oid("1.3.6.1.4.1.99999.2.0"),
+        &Value::OctetString(b"y".to_vec()),
+    )
+    .unwrap();
+    test.var_bind(&oid("1.3.6.1.4.1.99999.1.0"), &Value::Gauge32(1))
+        .unwrap();
+    let (error, index, _) = call(&mut stream, agentx::TEST_SET, 6, &test);
+    assert_eq!((error, index), (7, 2));
+
+    // INTEGERs beyond 32 bits are refused rather than wrapped.
+    let mut writer = Writer::default();
+    assert!(writer
+        .var_bind(&oid("1.3.6.1.4.1.99999.1.0"), &Value::Integer(1 << 40))
+        .is_err());
+    assert!(writer.0.is_empty());
+
+    // Idle sessions are kept alive with pings.
+    let (ping, _) = recv(&mut stream);
+    assert_eq!((ping.kind, ping.session_id), (agentx::PING, 42));
+    respond(&mut stream, &ping, 42);
+
+    let close = Header {
+        kind: agentx::CLOSE,
+        flags: 0,
+        session_id: 42,
+        transaction_id: 0,
+        packet_id: 7,
+    };
+    let mut reason = Writer::default();
+    reason.u32(0x0100_0000);
+    stream.write_all(&close.encode(&reason.0)).unwrap();
+    subagent.join().unwrap().unwrap();
+}
+
+#[cfg(feature = "prometheus")]
+#[test]
+fn prometheus_metrics_render_tables_and_walks() {
+    use super::prometheus::{self, Metric, Metrics};
+    use super::testing::MockAgent;
+    use std::io::{Read, Write};
+
+    let mut instances = Vec::new();
+    for row in [1u32, 2] {
+        instances.push((
+            oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", row)),
+            Value::OctetString(format!("eth{}", row).into_bytes()),
+        ));
+        instances.push((
+            oid(&format!("1.3.6.1.2.1.2.2.1.10.{}", row)),
+            Value::Counter32(row * 1000),
+        ));
+    }
+    instances.push((oid("1.3.6.1.2.1.4.20.1.2.10.0.0.1"), Value::Integer(1)));
+    let agent = MockAgent::new(instances).unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+
+    let mut mib = super::Mib::new();
+    mib.load_str(IF_MIB).unwrap();
+    mib.load_str(
+        "ACME-MIB DEFINITIONS ::= BEGIN
+         IMPORTS ifEntry FROM IF-MIB;
+         ifInOctets OBJECT IDENTIFIER ::= { ifEntry 10 }
+         END",
+    )
+    .unwrap();
+
+    let mut metrics = Metrics::with_mib(&mib);
+    let if_table = Metric::new("1.3.6.1.2.1.2.2")
+        .unwrap()
+        .name("snmp_{name}")
+        .labels(&["ifIndex"]);
+    metrics.add_table(&if_table, &sess.get_table("1.3.6.1.2.1.2.2", &[]).unwrap());
+    let addresses = Metric::new("1.3.6.1.2.1.4.20.1.2")
+        .unwrap()
+        .labels(&["address"])
+        .help("Interface of an \"address\"");
+    metrics.add_walk(&addresses, &sess.walk("1.3.6.1.2.1.4.20.1.2").unwrap());
+
+    let rendered = metrics.render();
+    assert_eq!(
+        rendered,
+        "# HELP oid_1_3_6_1_2_1_4_20_1_2 Interface of an \"address\"
+# TYPE oid_1_3_6_1_2_1_4_20_1_2 gauge
+oid_1_3_6_1_2_1_4_20_1_2{address=\"10.0.0.1\"} 1
+# HELP snmp_ifInOctets ACME-MIB::ifInOctets
+# TYPE snmp_ifInOctets counter
+snmp_ifInOctets{ifIndex=\"1\"} 1000
+snmp_ifInOctets{ifIndex=\"2\"} 2000
+"
+    );
+
+    let server = prometheus::serve("127.0.0.1:0", move |path| {
+        (path == "/metrics").then(|| rendered.clone())
+    })
+    .unwrap();
+    let request = |method: &str, path: &str| {
+        let mut stream = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            method, path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let scrape = |path: &str| request("GET", path);
+
+    let response = scrape("/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("snmp_ifInOctets{ifIndex=\"2\"} 2000\n"));
+    assert!(scrape("/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(request("POST", "/metrics")
+        .starts_with("HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\n"));
+
+    // A family has one type: values of another kind are left out.
+    let mut metrics = Metrics::new();
+    let mixed = Metric::new("1.3.6.1.4.1.99999.1")
+        .unwrap()
+        .name("mixed")
+        .labels(&["index"]);
+    let walk = std::collections::BTreeMap::from([
+        (vec![1], Value::Counter32(5)),
+        (vec![2], Value::Integer(3)),
+    ]);
+    metrics.add_walk(&mixed, &walk);
+    assert!(metrics
+        .render()
+        .ends_with("# TYPE mixed counter\nmixed{index=\"1\"} 5\n"));
+}
+
+#[test]
+fn session_stats_count_retries_timeouts_and_round_trips() {
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 300).unwrap();
+    assert_eq!(sess.stats(), super::SessionStats::default());
+
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    let stats = sess.stats();
+    assert_eq!((stats.requests, stats.retries, stats.timeouts), (2, 0, 0));
+    let (min, avg, max) = (
+        stats.rtt_min.unwrap(),
+        stats.rtt_avg.unwrap(),
+        stats.rtt_max.unwrap(),
+    );
+    assert!(min <= avg && avg <= max && max < Duration::from_millis(300));
+
+    // The answer to a retransmission is not a round-trip time sample.
+    agent.drop_next(1);
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    agent.drop_next(2);
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+    let later = sess.stats();
+    assert_eq!((later.requests, later.retries, later.timeouts), (6, 2, 1));
+    assert_eq!(later.decode_errors, 0);
+    assert_eq!(
+        (later.rtt_min, later.rtt_max),
+        (stats.rtt_min, stats.rtt_max)
+    );
+
+    // Garbage in the place of a response is a decode error.
+    let fake = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sess = SyncSession::new(1, fake.local_addr().unwrap(), b"public", 300).unwrap();
+    let responder = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (_, source) = fake.recv_from(&mut buf).unwrap();
+        fake.send_to(&[0x30, 0x03, 0x02, 0x01], source).unwrap();
+    });
+    assert!(sess.get("1.3.6.1.2.1.1.5.0").is_err());
+    responder.join().unwrap();
+    assert_eq!(sess.stats().decode_errors, 1);
+}
+
+#[test]
+fn buffer_pool_reuses_allocations() {
+    use super::buffers::BufferPool;
+
+    let pool = BufferPool::default();
+    let recv = pool.get().zeroed(65507);
+    assert!(recv.iter().all(|byte| *byte == 0));
+    let address = recv.as_ptr();
+    drop(recv);
+
+    let mut reused = pool.get();
+    assert!(reused.is_empty() && reused.capacity() >= 65507);
+    assert_eq!(reused.as_ptr(), address);
+    reused.extend_from_slice(b"leftovers");
+    drop(reused);
+    assert!(pool.get().zeroed(4).iter().all(|byte| *byte == 0));
+
+    // Messages encoded into a reused buffer are the same as freshly encoded ones.
+    let security = super::security::Security::community(1, b"public");
+    let data = super::pdu::get(&[oid("1.3.6.1.2.1.1.5.0")]);
+    let mut message = pool.get();
+    message.extend_from_slice(&[0xff; 100]);
+    security
+        .encode(data.clone(), &Default::default(), 4096, &mut message)
+        .unwrap();
+    let mut fresh = Vec::new();
+    security
+        .encode(data, &Default::default(), 4096, &mut fresh)
+        .unwrap();
+    assert_eq!(*message, fresh);
+}
+
+#[test]
+fn bulk_walk_visit_borrows_from_the_receive_buffer() {
+    use super::testing::MockAgent;
+    use super::{RequestOptions, ValueRef};
+    use std::collections::BTreeMap;
+    use std::ops::ControlFlow;
+
+    let mut instances = vec![
+        (
+            oid("1.3.6.1.2.1.1.2.0"),
+            Value::Oid(vec![1, 3, 6, 1, 4, 1, 9, 1, 1]),
+        ),
+        (oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(12345)),
+        (oid("1.3.6.1.2.1.1.5.0"), Value::OctetString(b"r1".to_vec())),
+        (oid("1.3.6.1.2.1.2.1.0"), Value::Integer(-7)),
+        (
+            oid("1.3.6.1.2.1.4.20.1.1.10.0.0.1"),
+            Value::IpAddress([10, 0, 0, 1].into()),
+        ),
+        (oid("1.3.6.1.2.1.31.1.1.1.6.1"), Value::Counter64(u64::MAX)),
+    ];
+    for row in 1..=40u32 {
+        instances.push((
+            oid(&format!("1.3.6.1.2.1.2.2.1.10.{}", row)),
+            Value::Counter32(row * 1000),
+        ));
+    }
+    let agent = MockAgent::new(instances).unwrap();
+
+    for version in [0, 1] {
+        let sess = SyncSession::new(version, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+        let opts = RequestOptions::new().max_repetitions(7);
+
+        let mut visited = BTreeMap::new();
+        sess.bulk_walk_visit_with("1.3.6.1.2.1", &opts, |name, value| {
+            visited.insert(name[6..].to_vec(), Value::from(value));
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let expected = sess.bulk_walk_with("1.3.6.1.2.1", &opts).unwrap();
+        assert_eq!(visited.len(), if version == 0 { 45 } else { 46 });
+        assert_eq!(visited, expected);
+
+        // Breaking stops the walk before the next request.
+        let received = agent.received();
+        let mut names = Vec::new();
+        sess.bulk_walk_visit_with("1.3.6.1.2.1.2.2", &opts, |name, value| {
+            assert!(matches!(value, ValueRef::Counter32(_)));
+            names.push(name.to_vec());
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert_eq!(names, [oid("1.3.6.1.2.1.2.2.1.10.1").as_slice()]);
+        assert_eq!(agent.received(), received + 1);
+    }
+}
+
+#[test]
+fn version_fallback_settles_on_the_version_the_agent_answers() {
+    use super::{Agent, Version};
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    // An agent that ignores SNMPv2c, like many old devices.
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let mut agent = Agent::new();
+    let instances = BTreeMap::from([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]);
+    agent.register("1.3.6.1.2.1", instances).unwrap();
+    std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        while let Ok((len, source)) = socket.recv_from(&mut buf) {
+            if super::ber::message_version(&buf[..len]) == Some(0) {
+                let response = agent.respond(&buf[..len]).unwrap();
+                socket.send_to(&response, source).unwrap();
+            }
+        }
+    });
+
+    let builder = SyncSession::builder(addr.to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0);
+    let plain = builder.clone().build().unwrap();
+    assert!(matches!(
+        plain.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+    assert_eq!(plain.version(), Version::V2c);
+
+    let sess = builder.version_fallback(true).build().unwrap();
+    assert_eq!(
+        sess.get("1.3.6.1.2.1.1.5.0").unwrap()[0].1,
+        Value::Integer(1)
+    );
+    assert_eq!(sess.version(), Version::V1);
+    assert_eq!(sess.stats().timeouts, 1);
+    assert_eq!(
+        sess.get("1.3.6.1.2.1.1.5.0").unwrap()[0].1,
+        Value::Integer(1)
+    );
+    assert_eq!(sess.stats().timeouts, 1);
+}
+
+#[test]
+fn community_fallback_keeps_the_first_answered_community() {
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let builder = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0);
+
+    let sess = builder
+        .clone()
+        .communities(["nope", "also-nope", "public"])
+        .build()
+        .unwrap();
+    assert_eq!(sess.community(), Some(&b"nope"[..]));
+    assert_eq!(
+        sess.get("1.3.6.1.2.1.1.5.0").unwrap()[0].1,
+        Value::Integer(1)
+    );
+    assert_eq!(sess.community(), Some(&b"public"[..]));
+    assert_eq!(sess.stats().timeouts, 2);
+
+    // Later requests go straight to the community that worked.
+    let received = agent.received();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(agent.received(), received + 1);
+
+    // When none is answered, the session keeps the community it had.
+    let sess = builder
+        .clone()
+        .communities(["nope", "also-nope"])
+        .build()
+        .unwrap();
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Timeout)
+    ));
+    assert_eq!(sess.community(), Some(&b"nope"[..]));
+
+    let empty: [&str; 0] = [];
+    assert!(builder.communities(empty).build().is_err());
+}
+
+#[test]
+fn scan_finds_agents_with_the_credentials_they_answer() {
+    use super::discover::{Credentials, Scan};
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let agent = MockAgent::new([
+        (
+            oid("1.3.6.1.2.1.1.1.0"),
+            Value::OctetString(b"mock".to_vec()),
+        ),
+        (
+            oid("1.3.6.1.2.1.1.2.0"),
+            Value::Oid(vec![1, 3, 6, 1, 4, 1, 9]),
+        ),
+        (oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(100)),
+        (oid("1.3.6.1.2.1.1.4.0"), Value::OctetString(Vec::new())),
+        (
+            oid("1.3.6.1.2.1.1.5.0"),
+            Value::OctetString(b"sw1".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.1.6.0"), Value::OctetString(Vec::new())),
+        (oid("1.3.6.1.2.1.1.7.0"), Value::Integer(6)),
+    ])
+    .unwrap();
+
+    // 127.0.0.1 and 127.0.0.2, without the network and broadcast addresses.
+    let scan = Scan::new(["127.0.0.0/30"])
+        .unwrap()
+        .port(agent.local_addr().unwrap().port())
+        .credentials(vec![
+            Credentials::V2c(b"nope".to_vec()),
+            Credentials::V1(b"public".to_vec()),
+        ])
+        .timeout(Duration::from_millis(100))
+        .rate(100);
+    assert_eq!(scan.len(), 2);
+
+    let found = scan.run().unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].addr, agent.local_addr().unwrap());
+    assert!(matches!(&found[0].credentials, Credentials::V1(community) if community == b"public"));
+    assert_eq!(found[0].system.name, "sw1");
+    assert_eq!(found[0].system.object_id, oid("1.3.6.1.4.1.9"));
+
+    assert!(Scan::new(["10.0.0.0/33"]).is_err());
+    assert!(Scan::new(["2001:db8::/32"]).is_err());
+    assert_eq!(Scan::new(["10.0.0.0/24", "10.0.1.7"]).unwrap().len(), 255);
+}
+
+#[test]
+fn broadcast_get_collects_each_responder_once() {
+    use super::discover::{self, Credentials};
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let responders = discover::broadcast_get(
+        agent.local_addr().unwrap(),
+        &Credentials::V2c(b"public".to_vec()),
+        &[oid("1.3.6.1.2.1.1.5.0")],
+        Duration::from_millis(200),
+    )
+    .unwrap();
+    assert_eq!(
+        responders,
+        vec![(
+            agent.local_addr().unwrap(),
+            vec![(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]
+        )]
+    );
+
+    let responders = discover::broadcast_get(
+        agent.local_addr().unwrap(),
+        &Credentials::V2c(b"nope".to_vec()),
+        &[oid("1.3.6.1.2.1.1.5.0")],
+        Duration::from_millis(100),
+    )
+    .unwrap();
+    assert!(responders.is_empty());
+}
+
+#[test]
+fn rate_limiter_paces_requests_per_target_and_globally() {
+    use super::testing::MockAgent;
+    use super::RateLimiter;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let builder = SyncSession::builder(agent.local_addr().unwrap().to_string());
+
+    let sess = builder
+        .clone()
+        .rate_limit(RateLimiter::new().per_second(20))
+        .build()
+        .unwrap();
+    let started = Instant::now();
+    for _ in 0..5 {
+        sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // Notifications take their turn like requests.
+    let started = Instant::now();
+    for _ in 0..5 {
+        sess.send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+            .unwrap();
+    }
+    assert!(started.elapsed() >= Duration::from_millis(200));
+
+    // Two targets with no pacing of their own, within a global limit of one request in
+    // flight: their requests cannot overlap.
+    agent.set_delay(Duration::from_millis(50));
+    let global = RateLimiter::new().max_outstanding(1);
+    let started = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..2 {
+            let sess = builder
+                .clone()
+                .rate_limit(RateLimiter::new().within(&global))
+                .build()
+                .unwrap();
+            scope.spawn(move || {
+                for _ in 0..2 {
+                    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+                }
+            });
+        }
+    });
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn walks_report_progress_and_stop_when_cancelled() {
+    use super::testing::MockAgent;
+    use super::{CancelToken, RequestOptions};
+    use std::sync::{Arc, Mutex};
+
+    let agent = MockAgent::new((1..=10).map(|i| {
+        (
+            oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", i)),
+            Value::Integer(i),
+        )
+    }))
+    .unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let opts = RequestOptions::new().max_repetitions(4).progress({
+        let reports = reports.clone();
+        move |count, last: &Oid| reports.lock().unwrap().push((count, last.to_string()))
+    });
+    assert_eq!(
+        sess.bulk_walk_with("1.3.6.1.2.1.2", &opts).unwrap().len(),
+        10
+    );
+    assert_eq!(
+        reports.lock().unwrap()[..2],
+        [
+            (4, "1.3.6.1.2.1.2.2.1.2.4".to_string()),
+            (8, "1.3.6.1.2.1.2.2.1.2.8".to_string())
+        ]
+    );
+
+    // Cancelling from the progress callback returns what was walked up to then.
+    let token = CancelToken::new();
+    let opts = RequestOptions::new().cancel(token.clone()).progress({
+        let token = token.clone();
+        move |count, _: &Oid| {
+            if count == 3 {
+                token.cancel();
+            }
+        }
+    });
+    let walked = sess.walk_with("1.3.6.1.2.1.2", &opts).unwrap();
+    assert_eq!(walked.keys().last(), Some(&vec![2, 1, 2, 3]));
+    assert!(token.is_cancelled());
+
+    let received = agent.received();
+    let table = sess.get_table_with("1.3.6.1.2.1.2.2", &[2], &opts).unwrap();
+    assert!(table.is_empty());
+    assert_eq!(agent.received(), received);
+}
+
+#[test]
+fn failed_walks_keep_what_they_collected() {
+    use super::testing::MockAgent;
+    use super::RequestOptions;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let agent = Arc::new(
+        MockAgent::new((1..=10).map(|i| {
+            (
+                oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", i)),
+                Value::Integer(i),
+            )
+        }))
+        .unwrap(),
+    );
+    let sess = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .build()
+        .unwrap();
+
+    // The agent goes quiet once four instances are in.
+    let opts = RequestOptions::new().max_repetitions(2).progress({
+        let agent = agent.clone();
+        move |count, _: &Oid| {
+            if count == 4 {
+                agent.drop_next(usize::MAX);
+            }
+        }
+    });
+    let partial = sess.bulk_walk_partial("1.3.6.1.2.1.2", &opts).unwrap_err();
+    assert!(matches!(partial.error, SnmpError::Timeout));
+    assert_eq!(partial.collected.len(), 4);
+    assert_eq!(partial.last, Some(oid("1.3.6.1.2.1.2.2.1.2.4")));
+
+    let partial = sess.walk_partial("1.3.6.1.2.1.2", &opts).unwrap_err();
+    assert!(partial.collected.is_empty());
+    assert_eq!(partial.last, None);
+    assert!(matches!(
+        sess.walk_with("1.3.6.1.2.1.2", &opts),
+        Err(SnmpError::Timeout)
+    ));
+}
+
+#[test]
+fn walk_cursor_spreads_a_walk_over_several_calls() {
+    use super::testing::MockAgent;
+    use super::{RequestOptions, WalkCursor};
+
+    let agent = MockAgent::new((1..=10).map(|i| {
+        (
+            oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", i)),
+            Value::Integer(i),
+        )
+    }))
+    .unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+    let opts = RequestOptions::new().max_repetitions(3);
+
+    let mut cursor = WalkCursor::new("1.3.6.1.2.1.2").unwrap();
+    let first = sess.walk_resume(&mut cursor, 2, &opts).unwrap();
+    assert_eq!(first.len(), 6);
+    assert_eq!(cursor.after(), Some(&oid("1.3.6.1.2.1.2.2.1.2.6")));
+    assert!(!cursor.is_done());
+
+    let mut walked = first;
+    while !cursor.is_done() {
+        walked.extend(sess.walk_resume(&mut cursor, 1, &opts).unwrap());
+    }
+    assert_eq!(walked, sess.walk("1.3.6.1.2.1.2").unwrap());
+    assert!(sess.walk_resume(&mut cursor, 1, &opts).unwrap().is_empty());
+
+    let mut cursor =
+        WalkCursor::resume("1.3.6.1.2.1.2", Some(oid("1.3.6.1.2.1.2.2.1.2.8"))).unwrap();
+    let rest = sess.walk_resume(&mut cursor, 10, &opts).unwrap();
+    assert_eq!(rest.keys().next(), Some(&vec![2, 1, 2, 9]));
+    assert!(cursor.is_done());
+}
+
+#[test]
+fn socket_options_apply_tos_ttl_and_receive_buffer() {
+    use super::testing::MockAgent;
+    use super::transport::SocketOptions;
+    use socket2::SockRef;
+
+    let options = SocketOptions {
+        tos: Some(16 << 2),
+        ttl: Some(7),
+        recv_buffer: Some(1 << 16),
+        ..SocketOptions::default()
+    };
+    let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    options.apply(SockRef::from(&socket), false).unwrap();
+
+    let socket = SockRef::from(&socket);
+    assert_eq!(socket.tos_v4().unwrap(), 16 << 2);
+    assert_eq!(socket.ttl_v4().unwrap(), 7);
+    assert!(socket.recv_buffer_size().unwrap() >= 1 << 16);
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let sess = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .dscp(16)
+        .ttl(7)
+        .socket_recv_buffer(1 << 16)
+        .build()
+        .unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+}
+
+#[test]
+fn sessions_bind_the_local_address_and_source_port_given() {
+    use super::testing::MockAgent;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let free = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = free.local_addr().unwrap().port();
+    drop(free);
+
+    let sess = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .local_ip(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .source_port(port)
+        .build()
+        .unwrap();
+    assert_eq!(
+        super::Transport::local_addr(&sess.transport).unwrap(),
+        (Ipv4Addr::LOCALHOST, port).into()
+    );
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+
+    // The port is taken now.
+    assert!(
+        SyncSession::builder(agent.local_addr().unwrap().to_string())
+            .source_port(port)
+            .build()
+            .is_err()
+    );
+}
+
+#[test]
+fn udp_sessions_fail_over_between_resolved_addresses() {
+    use super::testing::MockAgent;
+    use super::transport;
+    use super::UdpTransport;
+    use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+    use std::time::Duration;
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addrs = [silent.local_addr().unwrap(), agent.local_addr().unwrap()];
+
+    let sess = SyncSession::builder("ignored")
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .build_with(UdpTransport::connect(&addrs[..]).unwrap())
+        .unwrap();
+    assert_eq!(
+        sess.get("1.3.6.1.2.1.1.5.0").unwrap()[0].1,
+        Value::Integer(1)
+    );
+    assert_eq!(sess.stats().timeouts, 1);
+
+    // The session stays with the address that answered.
+    let received = agent.received();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(agent.received(), received + 1);
+    assert_eq!(sess.stats().timeouts, 1);
+
+    let scoped = |port, scope_id| {
+        Some(SocketAddr::from(SocketAddrV6::new(
+            "fe80::1".parse::<Ipv6Addr>().unwrap(),
+            port,
+            0,
+            scope_id,
+        )))
+    };
+    assert_eq!(
+        transport::literal("fe80::1%3", 161).unwrap(),
+        scoped(161, 3)
+    );
+    assert_eq!(
+        transport::literal("[fe80::1%3]:1161", 161).unwrap(),
+        scoped(1161, 3)
+    );
+    assert_eq!(
+        transport::literal("[::1]", 161).unwrap(),
+        Some("[::1]:161".parse().unwrap())
+    );
+    assert_eq!(transport::literal("router1", 161).unwrap(), None);
+    #[cfg(target_os = "linux")]
+    assert!(matches!(
+        transport::literal("fe80::1%lo", 161).unwrap(),
+        Some(SocketAddr::V6(addr)) if addr.scope_id() > 0
+    ));
+}
+
+#[test]
+fn sessions_fail_over_to_fallback_addresses_and_report_the_active_one() {
+    use super::testing::MockAgent;
+    use std::time::Duration;
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let silent_addr = silent.local_addr().unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    let sess = SyncSession::builder(silent_addr.to_string())
+        .fallback(agent_addr.to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .build()
+        .unwrap();
+    assert_eq!(sess.peer_addr().unwrap(), silent_addr);
+
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(sess.peer_addr().unwrap(), agent_addr);
+}
+
+#[test]
+fn sessions_look_the_agent_up_again_periodically_or_on_failure() {
+    use super::testing::MockAgent;
+    use super::{Transport, UdpTransport};
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::RwLock;
+    use std::time::Duration;
+
+    /// An agent whose name resolves to `moved_to` from the first lookup on.
+    struct Moving {
+        inner: RwLock<UdpTransport>,
+        moved_to: SocketAddr,
+        lookups: AtomicUsize,
+    }
+
+    impl Transport for Moving {
+        fn send(&self, data: &[u8]) -> io::Result<()> {
+            self.inner.read().unwrap().send(data)
+        }
+
+        fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+            self.inner.read().unwrap().recv(buf, timeout)
+        }
+
+        fn max_msg_size(&self) -> usize {
+            1500
+        }
+
+        fn peer_addr(&self) -> io::Result<SocketAddr> {
+            self.inner.read().unwrap().peer_addr()
+        }
+
+        fn resolve(&self) -> io::Result<bool> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            *self.inner.write().unwrap() = UdpTransport::connect(self.moved_to)?;
+            Ok(true)
+        }
+    }
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.5.0"), Value::Integer(1))]).unwrap();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let moving = || Moving {
+        inner: RwLock::new(UdpTransport::connect(silent.local_addr().unwrap()).unwrap()),
+        moved_to: agent.local_addr().unwrap(),
+        lookups: AtomicUsize::new(0),
+    };
+    let builder = SyncSession::builder("agent.example")
+        .timeout(Duration::from_millis(100))
+        .retries(0);
+
+    let sess = builder
+        .clone()
+        .resolve_on_failure(true)
+        .build_with(moving())
+        .unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(sess.peer_addr().unwrap(), agent.local_addr().unwrap());
+    assert_eq!(sess.stats().timeouts, 1);
+
+    let sess = builder
+        .resolve_every(Duration::ZERO)
+        .build_with(moving())
+        .unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(sess.stats().timeouts, 0);
+    assert_eq!(sess.transport.lookups.load(Ordering::Relaxed), 2);
+
+    // Without either, the session stays where it was opened.
+    let sess = SyncSession::builder("agent.example")
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .build_with(moving())
+        .unwrap();
+    assert!(sess.get("1.3.6.1.2.1.1.5.0").is_err());
+    assert_eq!(sess.transport.lookups.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn typed_getters_convert_or_report_the_type_they_got() {
+    use super::testing::MockAgent;
+    use super::SnmpError;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    let agent = MockAgent::new([
+        (
+            oid("1.3.6.1.2.1.1.2.0"),
+            Value::Oid(vec![1, 3, 6, 1, 4, 1, 9]),
+        ),
+        (
+            oid("1.3.6.1.2.1.1.5.0"),
+            Value::OctetString(b"core-1\0".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.2.2.1.5.1"), Value::Gauge32(1_000_000_000)),
+        (
+            oid("1.3.6.1.2.1.4.20.1.1.1"),
+            Value::IpAddress([192, 0, 2, 1].into()),
+        ),
+        (oid("1.3.6.1.2.1.31.1.1.1.6.1"), Value::Counter64(1 << 40)),
+    ])
+    .unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+
+    assert_eq!(sess.get_string("1.3.6.1.2.1.1.5.0").unwrap(), "core-1");
+    assert_eq!(
+        sess.get_oid("1.3.6.1.2.1.1.2.0").unwrap(),
+        oid("1.3.6.1.4.1.9")
+    );
+    assert_eq!(
+        sess.get_u32("1.3.6.1.2.1.2.2.1.5.1").unwrap(),
+        1_000_000_000
+    );
+    assert_eq!(
+        sess.get_u64("1.3.6.1.2.1.2.2.1.5.1").unwrap(),
+        1_000_000_000
+    );
+    assert_eq!(sess.get_u64("1.3.6.1.2.1.31.1.1.1.6.1").unwrap(), 1 << 40);
+    assert_eq!(
+        sess.get_ip("1.3.6.1.2.1.4.20.1.1.1").unwrap(),
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))
+    );
+
+    let err = sess.get_u32("1.3.6.1.2.1.31.1.1.1.6.1").unwrap_err();
+    assert!(matches!(
+        &err,
+        SnmpError::WrongType {
+            got: Value::Counter64(_),
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "expected a 32-bit unsigned integer, got Counter64"
+    );
+    assert!(matches!(
+        sess.get_u64("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::WrongType { .. })
+    ));
+    assert!(matches!(
+        sess.get_string("1.3.6.1.2.1.1.6.0"),
+        Err(SnmpError::NoSuchObject(_) | SnmpError::NoSuchInstance(_))
+    ));
+}
+
+#[test]
+fn values_convert_into_native_types() {
+    use super::SnmpError;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    let speed: u64 = Value::Counter64(10_000_000_000).try_into().unwrap();
+    assert_eq!(speed, 10_000_000_000);
+    assert_eq!(u32::try_from(Value::Integer(7)).unwrap(), 7);
+    assert!(u32::try_from(Value::Integer(-1)).is_err());
+    assert_eq!(i64::try_from(Value::Gauge32(5)).unwrap(), 5);
+    assert_eq!(
+        String::try_from(Value::OctetString(b"eth0".to_vec())).unwrap(),
+        "eth0"
+    );
+    assert_eq!(
+        Vec::<u8>::try_from(Value::Opaque(vec![0x9f, 0x78])).unwrap(),
+        [0x9f, 0x78]
+    );
+    assert_eq!(
+        IpAddr::try_from(Value::OctetString(vec![
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1
+        ]))
+        .unwrap(),
+        "2001:db8::1".parse::<IpAddr>().unwrap()
+    );
+    assert_eq!(
+        Duration::try_from(Value::TimeTicks(12345)).unwrap(),
+        Duration::from_millis(123450)
+    );
+    assert_eq!(
+        super::Oid::try_from(Value::Oid(vec![1, 3, 6])).unwrap(),
+        oid("1.3.6")
+    );
+
+    let err = Duration::try_from(Value::Integer(1)).unwrap_err();
+    assert!(matches!(
+        err,
+        SnmpError::WrongType {
+            expected: "TimeTicks",
+            got: Value::Integer(1)
+        }
+    ));
+}
+
+#[test]
+fn ticks_format_like_net_snmp_and_estimate_the_boot_time() {
+    use super::Ticks;
+    use std::time::{Duration, SystemTime};
+
+    assert_eq!(Ticks(12345678).to_string(), "(12345678) 1 day, 10:17:36.78");
+    assert_eq!(Ticks(4200).to_string(), "(4200) 0:00:42.00");
+    assert_eq!(
+        Ticks(8640000 * 3 + 1).to_string(),
+        "(25920001) 3 days, 0:00:00.01"
+    );
+    assert_eq!(Ticks(150).duration(), Duration::from_millis(1500));
+    assert_eq!(Ticks::try_from(Value::TimeTicks(7)).unwrap(), Ticks(7));
+
+    let read_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    assert_eq!(
+        Ticks(100 * 3600).boot_time(read_at),
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - 3600)
+    );
+}
+
+#[test]
+fn mac_addresses_parse_display_and_format_known_columns() {
+    use super::{Formatter, MacAddr, Mib};
+
+    let mac = MacAddr([0x00, 0x1a, 0x2b, 0x3c, 0x4d, 0x5e]);
+    assert_eq!(mac.to_string(), "00:1a:2b:3c:4d:5e");
+    assert_eq!(mac.dashed(), "00-1A-2B-3C-4D-5E");
+    assert_eq!(mac.dotted(), "001a.2b3c.4d5e");
+    for text in [
+        "00:1a:2b:3c:4d:5e",
+        "00-1A-2B-3C-4D-5E",
+        "001a.2b3c.4d5e",
+        "001A2B3C4D5E",
+        "0:1a:2b:3c:4d:5e",
+    ] {
+        assert_eq!(text.parse::<MacAddr>().unwrap(), mac, "{}", text);
+    }
+    for text in [
+        "00:1a:2b:3c:4d",
+        "00:1a-2b:3c:4d:5e",
+        "001a.2b3c.4d5",
+        "00:1a:2b:3c:4d:zz",
+        "é0:1a:2b:3c:4d:5",
+    ] {
+        assert!(text.parse::<MacAddr>().is_err(), "{}", text);
+    }
+    assert_eq!(
+        MacAddr::try_from(Value::OctetString(mac.to_vec())).unwrap(),
+        mac
+    );
+    assert!(MacAddr::try_from(Value::OctetString(vec![1, 2, 3])).is_err());
+
+    let mib = Mib::new();
+    let formatter = Formatter::new(&mib);
+    let value = Value::OctetString(mac.to_vec());
+    assert_eq!(
+        formatter.format(&oid("1.3.6.1.2.1.2.2.1.6.3"), &value),
+        "00:1a:2b:3c:4d:5e"
+    );
+    assert_eq!(
+        formatter.format(&oid("1.0.8802.1.1.2.1.4.1.1.5.0.7.1"), &value),
+        "00:1a:2b:3c:4d:5e"
+    );
+    assert_eq!(
+        formatter.format(&oid("1.3.6.1.2.1.1.5.0"), &value),
+        "00 1A 2B 3C 4D 5E"
+    );
+}
+
+#[test]
+fn opaque_wrapped_floats_and_64_bit_integers_decode_into_their_variants() {
+    use super::dump::parse_snmpwalk;
+    use super::testing::MockAgent;
+    use super::ValueRef;
+    use std::ops::ControlFlow;
+
+    let float = Value::Opaque(vec![0x9f, 0x78, 0x04, 0x3f, 0xc0, 0x00, 0x00]);
+    assert_eq!(
+        Value::from(v2::VarBindValue::from(float)),
+        Value::Float(1.5)
+    );
+    for value in [
+        Value::Float(-0.25),
+        Value::Double(230.1),
+        Value::Integer64(-129),
+        Value::Integer64(i64::MIN),
+        Value::Unsigned64(u64::MAX),
+        Value::Unsigned64(0),
+        Value::Opaque(vec![0x9f, 0x7f, 0x01, 0x00]),
+    ] {
+        assert_eq!(Value::from(v2::VarBindValue::from(value.clone())), value);
+    }
+    assert_eq!(f64::try_from(Value::Float(1.5)).unwrap(), 1.5);
+    assert_eq!(Value::Double(230.1).to_string(), "230.1");
+
+    let walk = parse_snmpwalk(
+        ".1.3.6.1.4.1.2021.10.1.6.1 = Opaque: Float: 0.080000\n\
+         .1.3.6.1.4.1.2021.10.1.6.2 = Opaque: UInt64: 18446744073709551615\n",
+    )
+    .unwrap();
+    assert_eq!(walk[&oid("1.3.6.1.4.1.2021.10.1.6.1")], Value::Float(0.08));
+    assert_eq!(
+        walk[&oid("1.3.6.1.4.1.2021.10.1.6.2")],
+        Value::Unsigned64(u64::MAX)
+    );
+
+    let agent = MockAgent::new([(oid("1.3.6.1.4.1.2021.10.1.6.1"), Value::Float(0.08))]).unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+    let mut visited = Vec::new();
+    sess.bulk_walk_visit("1.3.6.1.4.1.2021.10.1.6", |_, value| {
+        visited.push(value == ValueRef::Float(0.08));
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(visited, [true]);
+}
+
+#[test]
+fn date_and_time_decodes_both_lengths_and_converts_to_system_time() {
+    use super::DateAndTime;
+    use std::time::{Duration, SystemTime};
+
+    // 2024-03-01 12:34:56.7 +02:00, as hrSystemDate sends it.
+    let octets = [0x07, 0xe8, 3, 1, 12, 34, 56, 7, b'+', 2, 0];
+    let date = DateAndTime::try_from(Value::OctetString(octets.to_vec())).unwrap();
+    assert_eq!(date.to_string(), "2024-03-01T12:34:56.7+02:00");
+    assert_eq!(date.to_octets(), octets);
+    assert_eq!(
+        date.to_system_time(),
+        SystemTime::UNIX_EPOCH + Duration::from_millis(1_709_289_296_700)
+    );
+
+    let local = DateAndTime::from_octets(&octets[..8]).unwrap();
+    assert_eq!(local.utc_offset, None);
+    assert_eq!(local.to_string(), "2024-03-01T12:34:56.7");
+
+    let west = DateAndTime::from_octets(&[0x07, 0xd0, 2, 29, 0, 0, 0, 0, b'-', 5, 30]).unwrap();
+    assert_eq!(west.utc_offset, Some(-330));
+    assert_eq!(west.to_string(), "2000-02-29T00:00:00.0-05:30");
+
+    let now = SystemTime::UNIX_EPOCH + Duration::from_millis(1_709_282_096_700);
+    let utc = DateAndTime::from_system_time(now);
+    assert_eq!(utc.to_string(), "2024-03-01T08:34:56.7+00:00");
+    assert_eq!(utc.to_system_time(), now);
+
+    assert!(DateAndTime::from_octets(&[0x07, 0xe8, 13, 1, 0, 0, 0, 0]).is_none());
+    assert!(DateAndTime::from_octets(&[0x07, 0xe8, 3, 1, 0, 0, 0, 0, b'*', 0, 0]).is_none());
+    assert!(DateAndTime::try_from(Value::OctetString(vec![1, 2, 3])).is_err());
+}
+
+#[test]
+fn bits_encode_from_names_and_decode_to_positions_and_names() {
+    use super::testing::MockAgent;
+    use super::Bits;
+
+    let mut mib = super::Mib::new();
+    mib.load_str(
+        r#"ACME-MIB DEFINITIONS ::= BEGIN
+        IMPORTS enterprises, OBJECT-TYPE FROM SNMPv2-SMI TEXTUAL-CONVENTION FROM SNMPv2-TC;
+
+        AcmeAlarms ::= TEXTUAL-CONVENTION
+            STATUS current
+            DESCRIPTION ""
+            SYNTAX BITS { fan(0), psu(1), overheat(9) }
+
+        acme OBJECT IDENTIFIER ::= { enterprises 99999 }
+        acmeAlarms OBJECT-TYPE SYNTAX AcmeAlarms MAX-ACCESS read-write STATUS current
+            DESCRIPTION "" ::= { acme 1 }
+        acmeName OBJECT-TYPE SYNTAX OCTET STRING MAX-ACCESS read-write STATUS current
+            DESCRIPTION "" ::= { acme 2 }
+        END"#,
+    )
+    .unwrap();
+    let alarms = mib.lookup("acmeAlarms.0").unwrap();
+
+    let bits = Bits::from_names(&mib, &alarms, ["fan", "overheat"]).unwrap();
+    assert_eq!(bits, Bits(vec![0x80, 0x40]));
+    assert_eq!(bits, Bits::from_positions([9, 0]));
+    assert!(matches!(
+        Bits::from_names(&mib, &alarms, ["smoke"]),
+        Err(SnmpError::UnknownName(name)) if name == "smoke"
+    ));
+    assert!(Bits::from_names(&mib, &mib.lookup("acmeName.0").unwrap(), ["fan"]).is_err());
+
+    let agent = MockAgent::new([(alarms.clone(), Value::OctetString(vec![]))]).unwrap();
+    let addr = agent.local_addr().unwrap();
+    let writer = SyncSession::new(1, addr, b"private", 1000).unwrap();
+    writer.set(&[(alarms.clone(), bits.into())]).unwrap();
+
+    let session = SyncSession::new(1, addr, b"public", 1000).unwrap();
+    let (_, value) = session.get(&alarms).unwrap().remove(0);
+    let mut bits = Bits::try_from(value).unwrap();
+    assert_eq!(bits.positions(), [0, 9]);
+    assert_eq!(
+        bits.names(&mib, &alarms),
+        [(0, Some("fan")), (9, Some("overheat"))]
+    );
+
+    bits.clear(0);
+    bits.set(12);
+    assert!(!bits.is_set(0) && bits.is_set(9) && bits.is_set(12) && !bits.is_set(40));
+    assert_eq!(
+        bits.names(&mib, &alarms),
+        [(9, Some("overheat")), (12, None)]
+    );
+}
+
+#[test]
+fn rows_are_created_with_create_and_go_or_in_steps_and_destroyed() {
+    use std::collections::BTreeMap;
+    use std::sync::RwLock;
+
+    use super::{Agent, ErrorStatus, Handler, RowStatus};
+
+    const ENTRY: [u32; 9] = [1, 3, 6, 1, 4, 1, 99999, 1, 1];
+
+    /// A table of a name column 2 and a RowStatus column 3, which needs the name set
+    /// before a row can be active.
+    struct Rows {
+        create_and_go: bool,
+        instances: RwLock<BTreeMap<Oid, Value>>,
+    }
+
+    impl Handler for Rows {
+        fn get(&self, oid: &Oid) -> Value {
+            Handler::get(&*self.instances.read().unwrap(), oid)
+        }
+
+        fn get_next(&self, oid: &Oid) -> Option<(Oid, Value)> {
+            self.instances.read().unwrap().get_next(oid)
+        }
+
+        fn set(&self, oid: &Oid, value: &Value) -> Result<(), ErrorStatus> {
+            let mut instances = self.instances.write().unwrap();
+            let (column, index) = oid[ENTRY.len()..].split_first().unwrap();
+            let instance = |column: u32| Oid::from([&ENTRY[..], &[column], index].concat());
+            if *column != 3 {
+                instances.insert(oid.clone(), value.clone());
+                return Ok(());
+            }
+
+            let named = instances.contains_key(&instance(2));
+            let status = match RowStatus::try_from(value.clone()) {
+                Ok(RowStatus::CreateAndGo) if self.create_and_go && named => RowStatus::Active,
+                Ok(RowStatus::CreateAndGo) => return Err(ErrorStatus::WrongValue),
+                Ok(RowStatus::CreateAndWait) if named => RowStatus::NotInService,
+                Ok(RowStatus::CreateAndWait) => RowStatus::NotReady,
+                Ok(RowStatus::Active) if named => RowStatus::Active,
+                Ok(RowStatus::Active) => return Err(ErrorStatus::InconsistentValue),
+                Ok(RowStatus::Destroy) => {
+                    instances.retain(|name, _| !name.ends_with(index));
+                    return Ok(());
+                }
+                _ => return Err(ErrorStatus::WrongValue),
+            };
+            instances.insert(oid.clone(), status.into());
+            Ok(())
+        }
+    }
+
+    let name = |index: u32| Oid::from([&ENTRY[..], &[2, index]].concat());
+    let status = |index: u32| Oid::from([&ENTRY[..], &[3, index]].concat());
+
+    for create_and_go in [true, false] {
+        let mut agent = Agent::new().write_community(b"private");
+        let rows = Rows {
+            create_and_go,
+            instances: RwLock::default(),
+        };
+        agent.register("1.3.6.1.4.1.99999", rows).unwrap();
+        let agent = agent.spawn("127.0.0.1:0").unwrap();
+        let session = SyncSession::new(1, agent.local_addr().unwrap(), b"private", 1000).unwrap();
+
+        let columns = [(2, Value::OctetString(b"uplink".to_vec()))];
+        session.create_row(ENTRY, 3, &[7], &columns).unwrap();
+        let vars = session.get_many(&[name(7), status(7)]).unwrap();
+        assert_eq!(vars[0].1, Value::OctetString(b"uplink".to_vec()));
+        assert_eq!(
+            RowStatus::try_from(vars[1].1.clone()).unwrap(),
+            RowStatus::Active
+        );
+
+        // Without its name the row cannot be made active, and is not left behind.
+        let err = session.create_row(ENTRY, 3, &[8], &[]).unwrap_err();
+        assert!(matches!(
+            err,
+            SnmpError::AgentError {
+                status: ErrorStatus::InconsistentValue | ErrorStatus::WrongValue,
+                ..
+            }
+        ));
+        assert_eq!(session.get(status(8)).unwrap()[0].1, Value::NoSuchObject);
+
+        session.destroy_row(ENTRY, 3, &[7]).unwrap();
+        assert_eq!(session.get(name(7)).unwrap()[0].1, Value::NoSuchObject);
+    }
+}
+
+#[test]
+fn failed_sets_name_the_binding_and_verified_sets_read_back() {
+    use std::collections::BTreeMap;
+    use std::sync::RwLock;
+
+    use super::{Agent, ErrorStatus, Handler, RequestOptions};
+
+    /// Accepts any INTEGER, but keeps it to at most 100.
+    struct Clamped(RwLock<BTreeMap<Oid, Value>>);
+
+    impl Handler for Clamped {
+        fn get(&self, oid: &Oid) -> Value {
+            Handler::get(&self.0, oid)
+        }
+
+        fn get_next(&self, oid: &Oid) -> Option<(Oid, Value)> {
+            self.0.get_next(oid)
+        }
+
+        fn set(&self, oid: &Oid, value: &Value) -> Result<(), ErrorStatus> {
+            match value {
+                Value::Integer(n) => Handler::set(&self.0, oid, &Value::Integer((*n).min(100))),
+                _ => Err(ErrorStatus::WrongType),
+            }
+        }
+    }
+
+    let instances = (1..=3).map(|n| {
+        (
+            oid(&format!("1.3.6.1.4.1.99999.{}.0", n)),
+            Value::Integer(0),
+        )
+    });
+    let mut agent = Agent::new().write_community(b"private");
+    agent
+        .register(
+            "1.3.6.1.4.1.99999",
+            Clamped(RwLock::new(instances.collect())),
+        )
+        .unwrap();
+    let agent = agent.spawn("127.0.0.1:0").unwrap();
+    let session = SyncSession::new(1, agent.local_addr().unwrap(), b"private", 1000).unwrap();
+
+    let set = |values: [Value; 3]| -> Vec<(Oid, Value)> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| (oid(&format!("1.3.6.1.4.1.99999.{}.0", i + 1)), value))
+            .collect()
+    };
+
+    let bindings = set([
+        Value::Integer(1),
+        Value::OctetString(b"two".to_vec()),
+        Value::Integer(3),
+    ]);
+    let err = session.set(&bindings).unwrap_err();
+    assert_eq!(err.binding_index(), Some(1));
+    assert!(matches!(
+        &err,
+        SnmpError::AgentError { status: ErrorStatus::WrongType, bindings: sent, .. } if sent[1].0 == bindings[1].0
+    ));
+    assert_eq!(
+        err.to_string(),
+        "agent returned wrongType for 1.3.6.1.4.1.99999.2.0"
+    );
+
+    let verify = RequestOptions::new().verify(true);
+    let bindings = set([Value::Integer(10), Value::Integer(20), Value::Integer(30)]);
+    session.set_with(&bindings, &verify).unwrap();
+
+    let bindings = set([Value::Integer(50), Value::Integer(500), Value::Integer(5)]);
+    assert!(session.set(&bindings).is_ok());
+    let err = session.set_with(&bindings, &verify).unwrap_err();
+    assert_eq!(err.binding_index(), Some(1));
+    let SnmpError::Unverified { bindings: read, .. } = err else {
+        panic!("expected Unverified, got {:?}", err);
+    };
+    let read: Vec<Value> = read.into_iter().map(|(_, value)| value).collect();
+    assert_eq!(
+        read,
+        [Value::Integer(50), Value::Integer(100), Value::Integer(5)]
+    );
+}
+
+#[test]
+fn next_iter_crosses_subtrees_until_the_end_of_the_mib_view() {
+    use super::testing::MockAgent;
+
+    let agent = MockAgent::new([
+        (
+            oid("1.3.6.1.2.1.1.5.0"),
+            Value::OctetString(b"sw1".to_vec()),
+        ),
+        (oid("1.3.6.1.2.1.2.2.1.1.1"), Value::Integer(1)),
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.1"),
+            Value::OctetString(b"eth0".to_vec()),
+        ),
+    ])
+    .unwrap();
+    let addr = agent.local_addr().unwrap();
+
+    for version in [0, 1] {
+        let session = SyncSession::new(version, addr, b"public", 1000).unwrap();
+
+        let names: Vec<Oid> = session
+            .next_iter("1.3.6.1.2.1.1")
+            .map(|var| var.unwrap().0)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                oid("1.3.6.1.2.1.1.5.0"),
+                oid("1.3.6.1.2.1.2.2.1.1.1"),
+                oid("1.3.6.1.2.1.2.2.1.2.1")
+            ]
+        );
+
+        // Jumping from the last instance of one column to the next.
+        let (name, value) = session
+            .next_iter("1.3.6.1.2.1.2.2.1.1.1")
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, oid("1.3.6.1.2.1.2.2.1.2.1"));
+        assert_eq!(value, Value::OctetString(b"eth0".to_vec()));
+
+        assert!(session.next_iter("1.3.6.1.2.1.3").next().is_none());
+        assert!(session.next_iter("1.3.x").next().unwrap().is_err());
+    }
+}
+
+#[test]
+fn filtered_walks_keep_only_the_instances_asked_for() {
+    use super::testing::MockAgent;
+
+    let status = |index: u32, status: i64| {
+        (
+            oid(&format!("1.3.6.1.2.1.2.2.1.8.{}", index)),
+            Value::Integer(status),
+        )
+    };
+    let agent = MockAgent::new([status(1, 1), status(2, 2), status(3, 1), status(4, 7)]).unwrap();
+    let addr = agent.local_addr().unwrap();
+
+    for version in [0, 1] {
+        let session = SyncSession::new(version, addr, b"public", 1000).unwrap();
+
+        let up = session
+            .walk_filtered("1.3.6.1.2.1.2.2.1.8", |_, value| {
+                *value == Value::Integer(1)
+            })
+            .unwrap();
+        assert_eq!(up.keys().collect::<Vec<_>>(), [&[1], &[3]]);
+
+        let down = session
+            .walk_filter_map("1.3.6.1.2.1.2.2.1.8", |suffix, value| {
+                (value == Value::Integer(2)).then(|| suffix[0])
+            })
+            .unwrap();
+        assert_eq!(down.into_values().collect::<Vec<_>>(), [2]);
+    }
+}
+
+#[test]
+fn column_walks_fetch_columns_at_once_and_join_them_by_index() {
+    use super::testing::MockAgent;
+    use super::{ColumnWalk, Dispatcher, SessionBuilder};
+
+    let mut instances = Vec::new();
+    for index in 1..=5u32 {
+        let column = |column: u32| oid(&format!("1.3.6.1.2.1.2.2.1.{}.{}", column, index));
+        instances.push((column(1), Value::Integer(index.into())));
+        instances.push((
+            column(2),
+            Value::OctetString(format!("eth{}", index).into_bytes()),
+        ));
+        if index != 3 {
+            instances.push((column(8), Value::Integer(1)));
+        }
+    }
+    let agent = MockAgent::new(instances).unwrap();
+    let addr = agent.local_addr().unwrap();
+
+    let session = SyncSession::new(1, addr, b"public", 1000).unwrap();
+    let expected = session.get_table("1.3.6.1.2.1.2.2", &[1, 2, 8]).unwrap();
+    assert_eq!(expected.len(), 5);
+    assert!(!expected[&vec![3]].contains_key(&8));
+
+    let builder = SessionBuilder::new(addr.to_string());
+    let walk = ColumnWalk::new(builder.clone(), "1.3.6.1.2.1.2.2", &[1, 2, 8])
+        .unwrap()
+        .max_repetitions(2);
+    assert_eq!(walk.run().unwrap(), expected);
+    assert_eq!(walk.clone().concurrency(1).run().unwrap(), expected);
+
+    let dispatcher = Dispatcher::bind("127.0.0.1:0").unwrap();
+    assert_eq!(walk.run_dispatched(&dispatcher).unwrap(), expected);
+
+    let v1 = ColumnWalk::new(builder.v1(b"public"), "1.3.6.1.2.1.2.2", &[1, 2, 8]).unwrap();
+    assert_eq!(v1.run().unwrap(), expected);
+
+    let whole = ColumnWalk::new(
+        SessionBuilder::new(addr.to_string()),
+        "1.3.6.1.2.1.2.2",
+        &[],
+    )
+    .unwrap();
+    assert_eq!(whole.run().unwrap(), expected);
+
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let unanswered = SessionBuilder::new(silent.local_addr().unwrap().to_string())
+        .timeout(std::time::Duration::from_millis(50))
+        .retries(0);
+    let walk = ColumnWalk::new(unanswered, "1.3.6.1.2.1.2.2", &[1, 2, 8]).unwrap();
+    assert!(matches!(walk.run(), Err(SnmpError::Timeout)));
+}
+
+#[test]
+fn walk_many_walks_every_target_and_reports_each_outcome() {
+    use std::time::Duration;
+
+    use super::testing::MockAgent;
+    use super::SessionBuilder;
+
+    let agents: Vec<MockAgent> = (1..=3)
+        .map(|n| {
+            let instances = (1..=n).map(|index| {
+                (
+                    oid(&format!("1.3.6.1.2.1.2.2.1.2.{}", index)),
+                    Value::OctetString(format!("eth{}", index).into_bytes()),
+                )
+            });
+            MockAgent::new(instances.collect::<Vec<_>>()).unwrap()
+        })
+        .collect();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    let mut targets: Vec<SessionBuilder> = agents
+        .iter()
+        .map(|agent| SessionBuilder::new(agent.local_addr().unwrap().to_string()))
+        .collect();
+    targets.insert(
+        1,
+        SessionBuilder::new(silent.local_addr().unwrap().to_string())
+            .timeout(Duration::from_millis(50))
+            .retries(0),
+    );
+    targets.push(SessionBuilder::new(agents[0].local_addr().unwrap().to_string()).v1(b"public"));
+
+    let check = |results: Vec<super::WalkResult>| {
+        let rows: Vec<Option<usize>> = results
+            .iter()
+            .map(|result| result.as_ref().ok().map(|walked| walked.len()))
+            .collect();
+        assert_eq!(rows, [Some(1), None, Some(2), Some(3), Some(1)]);
+        assert!(matches!(results[1], Err(SnmpError::Timeout)));
+        assert_eq!(
+            results[3].as_ref().unwrap()[&vec![3]],
+            Value::OctetString(b"eth3".to_vec())
+        );
+    };
+
+    for concurrency in [1, 2, 16] {
+        check(super::walk_many(&targets, "1.3.6.1.2.1.2.2.1.2", concurrency).unwrap());
+    }
+    assert!(super::walk_many(&targets, "1.3.x", 2).is_err());
+
+    #[cfg(feature = "tokio")]
+    {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let results = runtime
+            .block_on(super::walk_many_async(&targets, "1.3.6.1.2.1.2.2.1.2", 3))
+            .unwrap();
+        check(results);
+    }
+}
+
+#[test]
+fn caches_answer_fresh_gets_and_walks_without_asking_the_agent() {
+    use std::time::Duration;
+
+    use super::testing::MockAgent;
+    use super::Cache;
+
+    let descr = oid("1.3.6.1.2.1.1.1.0");
+    let uptime = oid("1.3.6.1.2.1.1.3.0");
+    let alias = |index: u32| oid(&format!("1.3.6.1.2.1.31.1.1.1.18.{}", index));
+    let agent = MockAgent::new([
+        (descr.clone(), Value::OctetString(b"switch".to_vec())),
+        (uptime.clone(), Value::TimeTicks(100)),
+        (alias(1), Value::OctetString(b"uplink".to_vec())),
+        (alias(2), Value::OctetString(b"server".to_vec())),
+    ])
+    .unwrap();
+    let session = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+    let cache = Cache::new(session, Duration::from_secs(60))
+        .ttl_for("1.3.6.1.2.1.1.3", Duration::ZERO)
+        .unwrap();
+
+    let vars = cache.get_many(&[descr.clone(), uptime.clone()]).unwrap();
+    assert_eq!(
+        vars[0],
+        (descr.clone(), Value::OctetString(b"switch".to_vec()))
+    );
+    assert_eq!(agent.received(), 1);
+
+    // sysDescr comes from the cache, sysUpTime is never cached.
+    let vars = cache.get_many(&[uptime.clone(), descr.clone()]).unwrap();
+    assert_eq!(vars[0], (uptime.clone(), Value::TimeTicks(100)));
+    assert_eq!(vars[1].0, descr);
+    assert_eq!(agent.received(), 2);
+    cache.get(&descr).unwrap();
+    assert_eq!(agent.received(), 2);
+
+    let walk = cache.walk("1.3.6.1.2.1.31.1.1.1.18").unwrap();
+    assert_eq!(walk.len(), 2);
+    let requests = agent.received();
+    assert_eq!(cache.walk("1.3.6.1.2.1.31.1.1.1.18").unwrap(), walk);
+    assert_eq!(agent.received(), requests);
+
+    cache.invalidate(alias(1)).unwrap();
+    cache.walk("1.3.6.1.2.1.31.1.1.1.18").unwrap();
+    assert!(agent.received() > requests);
+    cache.get(&descr).unwrap();
+    let requests = agent.received();
+
+    cache.clear();
+    cache.get(&descr).unwrap();
+    assert_eq!(agent.received(), requests + 1);
+
+    // With room for one entry, the other is evicted.
+    let small = Cache::new(
+        SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap(),
+        Duration::from_secs(60),
+    )
+    .max_entries(1);
+    small.get(&descr).unwrap();
+    small.get(alias(1)).unwrap();
+    let requests = agent.received();
+    small.get(alias(1)).unwrap();
+    small.get(&descr).unwrap();
+    assert_eq!(agent.received(), requests + 1);
+
+    let short = Cache::new(
+        SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap(),
+        Duration::from_millis(50),
+    );
+    short.get(&descr).unwrap();
+    std::thread::sleep(Duration::from_millis(80));
+    let requests = agent.received();
+    short.get(&descr).unwrap();
+    assert_eq!(agent.received(), requests + 1);
+}
+
+#[test]
+fn schedulers_collect_on_interval_and_back_off_from_agents_that_are_down() {
+    use std::time::{Duration, Instant};
+
+    use super::testing::MockAgent;
+    use super::{Collected, Job, Scheduler, SessionBuilder};
+
+    let agent = MockAgent::new([
+        (oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(42)),
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.1"),
+            Value::OctetString(b"eth0".to_vec()),
+        ),
+        (
+            oid("1.3.6.1.2.1.2.2.1.2.2"),
+            Value::OctetString(b"eth1".to_vec()),
+        ),
+    ])
+    .unwrap();
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let builder = SessionBuilder::new(agent.local_addr().unwrap().to_string());
+    let interval = Duration::from_millis(50);
+
+    let jobs = vec![
+        Job::get(builder.clone(), &["1.3.6.1.2.1.1.3.0"], interval)
+            .unwrap()
+            .jitter(Duration::from_millis(10)),
+        Job::table(builder, "1.3.6.1.2.1.2.2", &[2], interval).unwrap(),
+        Job::get(
+            SessionBuilder::new(silent.local_addr().unwrap().to_string())
+                .timeout(Duration::from_millis(10))
+                .retries(0),
+            &["1.3.6.1.2.1.1.3.0"],
+            interval,
+        )
+        .unwrap()
+        .max_backoff(Duration::from_millis(400)),
+    ];
+    let scheduler = Scheduler::start(jobs, 2);
+
+    let deadline = Instant::now() + Duration::from_millis(700);
+    let mut samples = [Vec::new(), Vec::new(), Vec::new()];
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match scheduler.samples().recv_timeout(remaining) {
+            Ok(sample) => samples[sample.job].push(sample),
+            Err(_) => break,
+        }
+    }
+    drop(scheduler);
+
+    assert!(samples[0].len() >= 8, "{} samples", samples[0].len());
+    for sample in &samples[0] {
+        let Ok(Collected::Bindings(vars)) = &sample.result else {
+            panic!("unexpected {:?}", sample.result);
+        };
+        assert_eq!(vars[0].1, Value::TimeTicks(42));
+        assert!(sample.time <= std::time::SystemTime::now());
+    }
+    assert!(samples[1].iter().all(|sample| matches!(
+        &sample.result,
+        Ok(Collected::Table { rows, .. }) if rows.len() == 2
+    )));
+
+    // Down from the start: tried at 0, then after 100, 200 and 400ms.
+    let down = &samples[2];
+    assert!((2..=5).contains(&down.len()), "{} samples", down.len());
+    assert!(down
+        .iter()
+        .all(|sample| matches!(sample.result, Err(SnmpError::Timeout))));
+    assert!(down[1..].iter().all(|sample| sample.missed >= 1));
+    assert!(down
+        .windows(2)
+        .all(|pair| pair[1].started - pair[0].started >= interval * 2));
+}
+
+#[test]
+fn evaluator_raises_and_clears_with_hysteresis() {
+    use super::{Collected, Evaluator, Measure, Rule, Sample};
+    use std::time::{Duration, Instant, SystemTime};
+
+    let start = Instant::now();
+    let sample = |secs: u64, errors: u32, used: i64| Sample {
+        job: 0,
+        time: SystemTime::now(),
+        started: start + Duration::from_secs(secs),
+        missed: 0,
+        peer: None,
+        result: Ok(Collected::Bindings(vec![
+            (oid("1.3.6.1.2.1.2.2.1.14.1"), Value::Counter32(errors)),
+            (oid("1.3.6.1.2.1.25.2.3.1.5.1"), Value::Integer(100)),
+            (oid("1.3.6.1.2.1.25.2.3.1.6.1"), Value::Integer(used)),
+        ])),
+    };
+
+    let mut evaluator = Evaluator::new()
+        .rule(Rule::above(
+            "errors",
+            Measure::rate("1.3.6.1.2.1.2.2.1.14").unwrap(),
+            100.0 / 60.0,
+        ))
+        .rule(
+            Rule::above(
+                "storage",
+                Measure::ratio("1.3.6.1.2.1.25.2.3.1.6", "1.3.6.1.2.1.25.2.3.1.5").unwrap(),
+                0.9,
+            )
+            .clear_at(0.8),
+        )
+        // A second threshold on the same counter sees every sample too.
+        .rule(Rule::above(
+            "errors critical",
+            Measure::rate("1.3.6.1.2.1.2.2.1.14").unwrap(),
+            3.0,
+        ));
+
+    let summary = |alarm: &super::Alarm| (alarm.rule, alarm.index.clone(), alarm.raised);
+    // No rate from a first sample.
+    assert!(evaluator.evaluate(&sample(0, 0, 50)).is_empty());
+
+    let alarms = evaluator.evaluate(&sample(60, 200, 95));
+    assert_eq!(
+        alarms.iter().map(summary).collect::<Vec<_>>(),
+        [(0, vec![1], true), (1, vec![1], true), (2, vec![1], true)]
+    );
+    assert!((alarms[0].measured - 200.0 / 60.0).abs() < 1e-9);
+    assert_eq!(alarms[2].measured, alarms[0].measured);
+
+    // Still raised: no events, and 85% is within the hysteresis band.
+    assert!(evaluator.evaluate(&sample(120, 400, 85)).is_empty());
+    assert_eq!(evaluator.raised().count(), 3);
+
+    let alarms = evaluator.evaluate(&sample(180, 410, 80));
+    assert_eq!(
+        alarms.iter().map(summary).collect::<Vec<_>>(),
+        [
+            (0, vec![1], false),
+            (1, vec![1], false),
+            (2, vec![1], false)
+        ]
+    );
+    assert_eq!(evaluator.raised().count(), 0);
+
+    let failed = Sample {
+        result: Err(SnmpError::Timeout),
+        ..sample(240, 0, 0)
+    };
+    assert!(evaluator.evaluate(&failed).is_empty());
+}
+
+#[test]
+fn engine_ids_parse_describe_and_generate() {
+    use super::{EngineId, EngineIdFormat, MacAddr};
+
+    let id: EngineId = "0x80:00:1f:88:03:52:54:00:12:34:56".parse().unwrap();
+    assert_eq!(id.to_string(), "80001f8803525400123456");
+    assert_eq!(id.enterprise(), 8072);
+    assert_eq!(
+        id.format(),
+        EngineIdFormat::Mac(MacAddr([0x52, 0x54, 0, 0x12, 0x34, 0x56]))
+    );
+    assert_eq!(format!("{id:#}"), "enterprise 8072, MAC 52:54:00:12:34:56");
+    assert_eq!(
+        id,
+        EngineId::mac(8072, MacAddr([0x52, 0x54, 0, 0x12, 0x34, 0x56]))
+    );
+
+    let ip = EngineId::ipv4(9, "192.0.2.1".parse().unwrap());
+    assert_eq!(format!("{ip:#}"), "enterprise 9, IPv4 192.0.2.1");
+    assert_eq!(
+        EngineId::text(9, "router").unwrap().format(),
+        EngineIdFormat::Text("router")
+    );
+    assert_eq!(EngineId::local().to_string(), "8000000006");
+    assert_eq!(EngineId::local().format(), EngineIdFormat::Local);
+    let legacy = EngineId::new([0, 0, 0, 9, 1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    assert_eq!(
+        legacy.format(),
+        EngineIdFormat::Legacy(&[1, 2, 3, 4, 5, 6, 7, 8])
+    );
+
+    assert!(EngineId::text(9, &"x".repeat(28)).is_err());
+    assert!("8000".parse::<EngineId>().is_err());
+    assert!("80001f880g".parse::<EngineId>().is_err());
+
+    let generated = EngineId::generate(8072);
+    assert_eq!(generated.enterprise(), 8072);
+    assert!(matches!(generated.format(), EngineIdFormat::Octets(data) if data.len() == 12));
+    assert_ne!(generated, EngineId::generate(8072));
+
+    let usm = discovered_usm(UsmUser::new(b"public"));
+    assert_eq!(
+        usm.engine_id().unwrap().as_bytes(),
+        [0x80, 0, 0x1f, 0x88, 4, 1, 2, 3]
+    );
+}
+
+#[test]
+fn usm_user_tables_share_localized_keys() {
+    use super::{SessionBuilder, UsmUserTable};
+
+    let user = UsmUser::new(b"admin")
+        .auth(AuthProtocol::Sha256, b"authpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword");
+    let users = UsmUserTable::new();
+    users.add(UsmUser::new(b"public"));
+    users.add(user.clone());
+    assert_eq!(users.names(), [b"admin".to_vec(), b"public".to_vec()]);
+    assert_eq!(users.get(b"admin"), Some(user.clone()));
+
+    let pdus = usm_report(&[1, 3, 6, 1]);
+    let mut plain = discovered_usm(user.clone());
+    for _ in 0..2 {
+        let mut cached = discover(Usm::new(user.clone(), 4096).with_users(users.clone()), 0);
+        assert_eq!(
+            plain.decode(&cached.encode(pdus.clone()).unwrap()).unwrap(),
+            pdus
+        );
+        assert_eq!(
+            cached.decode(&plain.encode(pdus.clone()).unwrap()).unwrap(),
+            pdus
+        );
+    }
+
+    // A user replaced since is keyed from its own passphrases, not the cached ones.
+    users.add(user.clone().auth(AuthProtocol::Sha256, b"another password"));
+    let mut stale = discover(Usm::new(user, 4096).with_users(users.clone()), 0);
+    assert_eq!(
+        plain.decode(&stale.encode(pdus.clone()).unwrap()).unwrap(),
+        pdus
+    );
+
+    assert!(users.remove(b"public").is_some());
+    let missing = SessionBuilder::new("127.0.0.1:161")
+        .v3_user(&users, b"public")
+        .build();
+    assert!(missing.is_err());
+}
+
+#[cfg(feature = "ring")]
+#[test]
+fn ring_provider_matches_rust_crypto() {
+    use super::{CryptoProvider, Ring, RustCrypto};
+
+    for auth in [
+        AuthProtocol::Md5,
+        AuthProtocol::Sha1,
+        AuthProtocol::Sha224,
+        AuthProtocol::Sha256,
+        AuthProtocol::Sha384,
+        AuthProtocol::Sha512,
+    ] {
+        let key = RustCrypto.password_to_key(auth, b"maplesyrup");
+        assert_eq!(Ring.password_to_key(auth, b"maplesyrup"), key);
+        assert_eq!(
+            Ring.digest(auth, &[&key, b"engine", &key]),
+            RustCrypto.digest(auth, &[&key, b"engine", &key])
+        );
+        assert_eq!(
+            Ring.hmac(auth, &key, b"message"),
+            RustCrypto.hmac(auth, &key, b"message")
+        );
+    }
+
+    let mut data = *b"sixteen octets!!";
+    Ring.aes_cfb_encrypt(&[7; 16], &[1; 16], &mut data);
+    RustCrypto.aes_cfb_decrypt(&[7; 16], &[1; 16], &mut data);
+    assert_eq!(&data, b"sixteen octets!!");
+}
+
+#[test]
+fn v3_requests_carry_the_context_given() {
+    use super::options::Context;
+    use super::testing::MockAgent;
+    use super::{EngineId, RequestOptions};
+
+    let scoped = |message: &[u8]| {
+        let message: v3::Message = rasn::ber::decode(message).unwrap();
+        match message.scoped_data {
+            v3::ScopedPduData::CleartextPdu(scoped) => (scoped.engine_id, scoped.name),
+            other => panic!("unexpected {other:?}"),
+        }
+    };
+    let mut usm = discovered_usm(UsmUser::new(b"public"));
+    let pdus = usm_report(&[1, 3, 6, 1]);
+
+    let (engine_id, name) = scoped(&usm.encode(pdus.clone()).unwrap());
+    assert_eq!(engine_id.as_ref(), [0x80, 0, 0x1f, 0x88, 4, 1, 2, 3]);
+    assert!(name.is_empty());
+
+    let proxied = EngineId::text(9, "behind").unwrap();
+    let context = Context {
+        engine_id: None,
+        name: Some(b"vlan-10".to_vec()),
+    }
+    .or(&Context {
+        engine_id: Some(proxied.clone()),
+        name: Some(b"ignored".to_vec()),
+    });
+    let (engine_id, name) = scoped(&usm.encode_into(pdus, &context, Vec::new()).unwrap());
+    assert_eq!(engine_id.as_ref(), proxied.as_bytes());
+    assert_eq!(name.as_ref(), b"vlan-10");
+
+    let agent = MockAgent::new([(oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(1))]).unwrap();
+    let session = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+    let opts = RequestOptions::new().context("vlan-10");
+    assert!(matches!(
+        session.get_with("1.3.6.1.2.1.1.3.0", &opts),
+        Err(SnmpError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn usm_reports_decode_into_their_errors() {
+    use super::security::Security;
+    use super::usm;
+    use std::sync::Mutex;
+
+    let mut agent = discovered_usm(UsmUser::new(b"public"));
+    let security = Security::Usm(Mutex::new(discovered_usm(UsmUser::new(b"public"))));
+    let decode = |counter: &[u32], agent: &mut Usm| {
+        let report = agent.encode(usm_report(counter)).unwrap();
+        // The report answers a message with its own msgID.
+        security.decode(&report, &report, 0, Decoding::default())
+    };
+
+    for (counter, expected) in [
+        (
+            usm::USM_STATS_UNKNOWN_USER_NAMES,
+            "agent does not know the user",
+        ),
+        (
+            usm::USM_STATS_WRONG_DIGESTS,
+            "agent rejected the message digest",
+        ),
+        (
+            usm::USM_STATS_DECRYPTION_ERRORS,
+            "agent could not decrypt the message",
+        ),
+        (
+            usm::USM_STATS_UNSUPPORTED_SEC_LEVELS,
+            "agent does not support the security level for the user",
+        ),
+    ] {
+        let err = decode(counter, &mut agent).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+        assert!(!security.resync());
+    }
+
+    // Recoverable: the session resends after rediscovering the engine.
+    assert!(matches!(
+        decode(usm::USM_STATS_UNKNOWN_ENGINE_IDS, &mut agent),
+        Err(SnmpError::UnknownEngineId)
+    ));
+    assert!(security.resync());
+
+    assert!(matches!(
+        decode(&[1, 3, 6, 1, 4, 1, 99, 1, 0], &mut agent),
+        Err(SnmpError::Report(oid)) if oid == super::Oid::from(vec![1, 3, 6, 1, 4, 1, 99, 1, 0])
+    ));
+
+    // A report on an earlier message does not fail the request waiting now.
+    let stale = agent
+        .encode(usm_report(usm::USM_STATS_WRONG_DIGESTS))
+        .unwrap();
+    let request = agent
+        .encode(usm_report(usm::USM_STATS_WRONG_DIGESTS))
+        .unwrap();
+    assert!(matches!(
+        security.decode(&stale, &request, 0, Decoding::default()),
+        Ok(None)
+    ));
+}
+
+#[test]
+fn v3_traps_are_sent_from_the_local_engine() {
+    use super::{EngineId, SessionBuilder};
+
+    let user = UsmUser::new(b"admin")
+        .auth(AuthProtocol::Sha1, b"authpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword");
+    let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver
+        .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+        .unwrap();
+
+    let engine_id = EngineId::new([0x80, 0, 0x1f, 0x88, 4, 1, 2, 3]).unwrap();
+    let session = SessionBuilder::new(receiver.local_addr().unwrap().to_string())
+        .v3(user.clone())
+        .local_engine(engine_id, 7)
+        .build()
+        .unwrap();
+    session
+        .send_trap(
+            "1.3.6.1.6.3.1.1.5.1",
+            &[("1.3.6.1.2.1.1.5.0", Value::OctetString(b"host".to_vec()))],
+        )
+        .unwrap();
+
+    let mut buf = [0; 2048];
+    let len = receiver.recv(&mut buf).unwrap();
+    let message: v3::Message = rasn::ber::decode(&buf[..len]).unwrap();
+    assert_eq!(message.global_data.flags.as_ref(), [0x03]);
+    let params: v3::USMSecurityParameters =
+        rasn::ber::decode(&message.security_parameters).unwrap();
+    assert_eq!(params.authoritative_engine_boots, 7.into());
+
+    // The receiver knows the user under the sender's engine ID.
+    let mut usm = discovered_usm(user.clone());
+    let v2::Pdus::Trap(trap) = usm.decode(&buf[..len]).unwrap() else {
+        panic!("not a trap");
+    };
+    assert_eq!(trap.0.variable_bindings.len(), 3);
+
+    let without = SessionBuilder::new(receiver.local_addr().unwrap().to_string())
+        .v3(user)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        without.send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)]),
+        Err(SnmpError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn trap_listener_authenticates_notifications() {
+    use super::{EngineId, SessionBuilder, TrapListener, TrapStats, UsmUserTable, Version};
+
+    let user = UsmUser::new(b"traps")
+        .auth(AuthProtocol::Sha256, b"authpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword");
+    let users = UsmUserTable::new();
+    users.add(user.clone());
+
+    let listener_id = EngineId::text(8072, "listener").unwrap();
+    let mut listener = TrapListener::bind("127.0.0.1:0")
+        .unwrap()
+        .users(&users)
+        .local_engine(listener_id.clone(), 1)
+        .source_communities("127.0.0.1".parse().unwrap(), ["private"]);
+    let addr = listener.local_addr().unwrap().to_string();
+
+    let receiver = std::thread::spawn(move || {
+        let events: Vec<_> = listener.by_ref().take(3).map(Result::unwrap).collect();
+        (events, listener.stats())
+    });
+
+    let sender_id = EngineId::text(8072, "sender").unwrap();
+    let v3 = |user: UsmUser| {
+        SessionBuilder::new(addr.clone())
+            .v3(user)
+            .local_engine(sender_id.clone(), 3)
+            .build()
+            .unwrap()
+    };
+    let trap = |session: &super::SyncSession| {
+        session
+            .send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+            .unwrap()
+    };
+
+    trap(&SyncSession::new(1, addr.as_str(), b"public", 1000).unwrap());
+    trap(&v3(UsmUser::new(b"traps")
+        .auth(AuthProtocol::Sha256, b"wrongpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword")));
+    trap(&v3(UsmUser::new(b"traps")));
+    trap(&SyncSession::new(1, addr.as_str(), b"private", 1000).unwrap());
+    trap(&v3(user.clone()));
+    v3(user)
+        .send_inform("1.3.6.1.6.3.1.1.5.4", &[] as &[(Oid, Value)])
+        .unwrap();
+
+    let (events, stats) = receiver.join().unwrap();
+    assert_eq!(events[0].community, b"private");
+    assert_eq!(events[1].version, Version::V3);
+    assert_eq!(events[1].community, b"traps");
+    assert_eq!(events[1].engine_id, Some(sender_id));
+    assert!(events[2].inform);
+    assert_eq!(events[2].engine_id, Some(listener_id));
+    assert_eq!(
+        stats,
+        TrapStats {
+            accepted: 3,
+            unauthenticated: 3,
+            ..TrapStats::default()
+        }
+    );
+}
+
+#[test]
+fn trap_forwarder_relays_and_translates_v1_traps() {
+    use super::{Notification, TrapForwarder, TrapListener, Version};
+
+    let listener = TrapListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener
+        .set_read_timeout(Some(std::time::Duration::from_millis(500)))
+        .unwrap();
+
+    let mut v2c = TrapListener::bind("127.0.0.1:0").unwrap();
+    let mut v1 = TrapListener::bind("127.0.0.1:0").unwrap();
+    let v2c_session = SyncSession::new(1, v2c.local_addr().unwrap(), b"collector", 1000).unwrap();
+    let v1_session = SyncSession::new(0, v1.local_addr().unwrap(), b"legacy", 1000).unwrap();
+
+    let mut forwarder = TrapForwarder::new(listener)
+        .destination(v2c_session)
+        .destination(v1_session)
+        .translate(true);
+    let forwarding = std::thread::spawn(move || forwarder.run());
+
+    let sender = SyncSession::new(0, addr, b"site", 1000).unwrap();
+    sender
+        .send_v1_trap("1.3.6.1.4.1.8072", 6, 17, &[] as &[(Oid, Value)])
+        .unwrap();
+
+    let translated = v2c.recv().unwrap();
+    assert_eq!(translated.community, b"collector");
+    let Notification::V2 {
+        trap_oid, bindings, ..
+    } = translated.notification
+    else {
+        panic!("expected a translated notification");
+    };
+    assert_eq!(trap_oid, oid("1.3.6.1.4.1.8072.0.17"));
+    assert_eq!(
+        bindings[1],
+        (
+            oid("1.3.6.1.6.3.18.1.4.0"),
+            Value::OctetString(b"site".to_vec())
+        )
+    );
+    assert_eq!(
+        bindings[2],
+        (
+            oid("1.3.6.1.6.3.1.1.4.3.0"),
+            Value::Oid(vec![1, 3, 6, 1, 4, 1, 8072])
+        )
+    );
+
+    let relayed = v1.recv().unwrap();
+    assert_eq!(relayed.version, Version::V1);
+    assert_eq!(relayed.community, b"legacy");
+    assert!(matches!(
+        relayed.notification,
+        Notification::V1 {
+            specific_trap: 17,
+            ..
+        }
+    ));
+
+    assert!(forwarding.join().unwrap().is_err());
+}
+
+#[test]
+fn traps_translate_between_v1_and_v2() {
+    use super::translate::{v1_to_v2, v2_to_v1};
+    use super::Notification;
+    use std::net::Ipv4Addr;
+
+    let link_down = Notification::V1 {
+        enterprise: oid("1.3.6.1.4.1.9.1.1"),
+        agent_addr: Ipv4Addr::new(192, 0, 2, 7),
+        generic_trap: 2,
+        specific_trap: 0,
+        timestamp: 4200,
+        bindings: vec![(oid("1.3.6.1.2.1.2.2.1.1.3"), Value::Integer(3))],
+    };
+    let v2 = v1_to_v2(&link_down, b"public");
+    let Notification::V2 {
+        uptime,
+        trap_oid,
+        bindings,
+    } = &v2
+    else {
+        panic!("expected a v2 notification");
+    };
+    assert_eq!((*uptime, trap_oid), (4200, &oid("1.3.6.1.6.3.1.1.5.3")));
+    assert_eq!(bindings.len(), 4);
+    // The appended bindings survive the way back, and give back the agent fields.
+    let Notification::V1 {
+        enterprise,
+        agent_addr,
+        generic_trap,
+        bindings: v1_bindings,
+        ..
+    } = v2_to_v1(&v2, Ipv4Addr::UNSPECIFIED)
+    else {
+        panic!("expected a v1 trap");
+    };
+    assert_eq!(enterprise, oid("1.3.6.1.4.1.9.1.1"));
+    assert_eq!(agent_addr, Ipv4Addr::new(192, 0, 2, 7));
+    assert_eq!(generic_trap, 2);
+    assert_eq!(v1_bindings, *bindings);
+
+    let specific = Notification::V2 {
+        uptime: 100,
+        trap_oid: oid("1.3.6.1.4.1.9.9.41.2.0.1"),
+        bindings: vec![
+            (oid("1.3.6.1.2.1.31.1.1.1.6.1"), Value::Counter64(1 << 40)),
+            (
+                oid("1.3.6.1.2.1.1.5.0"),
+                Value::OctetString(b"sw1".to_vec()),
+            ),
+        ],
+    };
+    assert_eq!(
+        v2_to_v1(&specific, Ipv4Addr::new(198, 51, 100, 1)),
+        Notification::V1 {
+            enterprise: oid("1.3.6.1.4.1.9.9.41.2"),
+            agent_addr: Ipv4Addr::new(198, 51, 100, 1),
+            generic_trap: 6,
+            specific_trap: 1,
+            timestamp: 100,
+            bindings: vec![(
+                oid("1.3.6.1.2.1.1.5.0"),
+                Value::OctetString(b"sw1".to_vec())
+            )],
+        }
+    );
+}
+
+#[test]
+fn trap_events_are_resolved_and_annotated() {
+    use super::{Mib, TrapListener};
+
+    let mut mib = Mib::new();
+    mib.load_str(IF_MIB).unwrap();
+    mib.load_str(
+        "TEST-TRAPS-MIB DEFINITIONS ::= BEGIN
+         snmpTraps OBJECT IDENTIFIER ::= { iso(1) org(3) dod(6) internet(1) snmpV2(6) 3 1 1 5 }
+         linkDown OBJECT IDENTIFIER ::= { snmpTraps 3 }
+         END",
+    )
+    .unwrap();
+
+    let mut listener = TrapListener::bind("127.0.0.1:0")
+        .unwrap()
+        .mib(mib)
+        .hook(|event| {
+            let site = if event.source.ip().is_loopback() {
+                "lab"
+            } else {
+                "field"
+            };
+            event
+                .annotations
+                .insert("site".to_string(), site.to_string());
+        });
+
+    let sender = SyncSession::new(0, listener.local_addr().unwrap(), b"public", 1000).unwrap();
+    sender
+        .send_v1_trap(
+            "1.3.6.1.4.1.8072",
+            2,
+            0,
+            &[(
+                "1.3.6.1.2.1.2.2.1.2.3",
+                Value::OctetString(b"eth0".to_vec()),
+            )],
+        )
+        .unwrap();
+
+    let event = listener.recv().unwrap();
+    assert_eq!(event.trap_name.as_deref(), Some("TEST-TRAPS-MIB::linkDown"));
+    assert_eq!(event.bindings[0].name.as_deref(), Some("IF-MIB::ifDescr.3"));
+    assert_eq!(event.bindings[0].display, "eth0");
+    assert_eq!(event.annotations["site"], "lab");
+    assert!(event.raw.starts_with(&[0x30]));
+    assert!(event.received <= std::time::SystemTime::now());
+}
+
+#[test]
+fn trap_listener_drops_duplicates_and_floods() {
+    use super::TrapListener;
+    use std::time::Duration;
+
+    let mut listener = TrapListener::bind("127.0.0.1:0")
+        .unwrap()
+        .dedup(Duration::from_millis(300));
+    listener
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .unwrap();
+    let sender = SyncSession::new(1, listener.local_addr().unwrap(), b"public", 1000).unwrap();
+    let link_down = |index: i64| {
+        sender
+            .send_trap(
+                "1.3.6.1.6.3.1.1.5.3",
+                &[("1.3.6.1.2.1.2.2.1.1.1", Value::Integer(index))],
+            )
+            .unwrap()
+    };
+
+    for _ in 0..3 {
+        link_down(1);
+    }
+    link_down(2);
+    let mut events: Vec<_> = listener.by_ref().take(2).map(Result::unwrap).collect();
+    // Copies are timed as they are read, so the window has to pass after the first.
+    std::thread::sleep(Duration::from_millis(400));
+    link_down(1);
+    events.push(listener.recv().unwrap());
+
+    let suppressed: Vec<_> = events.iter().map(|event| event.suppressed).collect();
+    assert_eq!(suppressed, [0, 0, 2]);
+    assert_eq!(events[1].bindings[0].value, Value::Integer(2));
+    assert_eq!(listener.stats().duplicates, 2);
+
+    let mut limited = TrapListener::bind("127.0.0.1:0")
+        .unwrap()
+        .source_rate_limit(1, 2);
+    limited
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let sender = SyncSession::new(1, limited.local_addr().unwrap(), b"public", 1000).unwrap();
+    for index in 0..4 {
+        sender
+            .send_trap(
+                "1.3.6.1.6.3.1.1.5.3",
+                &[("1.3.6.1.2.1.2.2.1.1.1", Value::Integer(index))],
+            )
+            .unwrap();
+    }
+
+    assert!(limited.recv().is_ok());
+    assert!(limited.recv().is_ok());
+    assert!(limited.recv().is_err());
+    assert_eq!(limited.stats().rate_limited, 2);
+}
+
+#[test]
+fn trap_listener_delivers_to_sinks() {
+    use super::TrapListener;
+    use std::sync::{mpsc, Arc, Mutex};
+
+    let (sender, events) = mpsc::channel();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let failing = |_: &super::TrapEvent| Err(std::io::Error::other("down"));
+
+    let mut listener = TrapListener::bind("127.0.0.1:0")
+        .unwrap()
+        .sink(sender)
+        .sink({
+            let seen = seen.clone();
+            move |event: &super::TrapEvent| {
+                seen.lock().unwrap().push(event.notification.trap_oid());
+                Ok(())
+            }
+        })
+        .sink(failing);
+
+    let session = SyncSession::new(1, listener.local_addr().unwrap(), b"public", 1000).unwrap();
+    session
+        .send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+        .unwrap();
+    let event = listener.recv().unwrap();
+
+    assert_eq!(events.try_recv().unwrap(), event);
+    assert_eq!(*seen.lock().unwrap(), [oid("1.3.6.1.6.3.1.1.5.1")]);
+    assert_eq!(listener.stats().sink_errors, 1);
+}
+
+#[cfg(feature = "webhook")]
+#[test]
+fn webhook_posts_events_as_json() {
+    use super::{TrapListener, TrapSink, Webhook};
+    use std::io::{Read, Write};
+
+    let server = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/traps", server.local_addr().unwrap());
+    let receiver = std::thread::spawn(move || {
+        let (mut stream, _) = server.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        while !request.ends_with(b"}") {
+            let len = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..len]);
+        }
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+        String::from_utf8(request).unwrap()
+    });
+
+    let mut listener = TrapListener::bind("127.0.0.1:0").unwrap();
+    let session = SyncSession::new(1, listener.local_addr().unwrap(), b"public", 1000).unwrap();
+    session
+        .send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+        .unwrap();
+    let event = listener.recv().unwrap();
+
+    let mut webhook = Webhook::new(&url).unwrap().header("X-Token", "secret");
+    webhook.deliver(&event).unwrap();
+
+    let request = receiver.join().unwrap();
+    assert!(request.starts_with("POST /traps HTTP/1.1\r\n"));
+    assert!(request.contains("X-Token: secret\r\n"));
+    let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(
+        json["notification"]["V2"]["trap_oid"],
+        "1.3.6.1.6.3.1.1.5.1"
+    );
+
+    assert!(Webhook::new("https://alerts.example.net/").is_err());
+}
+
+#[test]
+fn raw_pdus_and_messages_reuse_the_session() {
+    use super::rasn_snmp::v2c;
+    use super::testing::MockAgent;
+
+    let agent = MockAgent::new([(
+        oid("1.3.6.1.2.1.1.5.0"),
+        Value::OctetString(b"sw1".to_vec()),
+    )])
+    .unwrap();
+    let sess = SyncSession::new(1, agent.local_addr().unwrap(), b"public", 1000).unwrap();
+
+    let request = v2::Pdus::GetNextRequest(v2::GetNextRequest(v2::Pdu {
+        request_id: 0,
+        error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+        error_index: 0,
+        variable_bindings: vec![v2::VarBind {
+            name: ObjectIdentifier::new(vec![1, 3, 6, 1, 2, 1, 1]).unwrap(),
+            value: v2::VarBindValue::Unspecified,
+        }],
+    }));
+    let v2::Pdus::Response(response) = sess.send_pdu(request.clone()).unwrap() else {
+        panic!("expected a response");
+    };
+    assert_ne!(response.0.request_id, 0);
+    assert_eq!(
+        Oid::from_asn(&response.0.variable_bindings[0].name),
+        oid("1.3.6.1.2.1.1.5.0")
+    );
+
+    let message = v2c::Message {
+        version: 1.into(),
+        community: b"public".to_vec().into(),
+        data: request,
+    };
+    let raw = sess
+        .send_raw(&rasn::ber::encode(&message).unwrap())
+        .unwrap();
+    let answer: v2c::Message<v2::Pdus> = rasn::ber::decode(&raw).unwrap();
+    assert!(matches!(answer.data, v2::Pdus::Response(_)));
+}
+
+#[test]
+fn send_and_receive_hooks_see_and_rewrite_messages() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::testing::MockAgent;
+
+    let agent = MockAgent::new([(
+        oid("1.3.6.1.2.1.1.5.0"),
+        Value::OctetString(b"sw1".to_vec()),
+    )])
+    .unwrap();
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let sess = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .timeout(Duration::from_millis(200))
+        .retries(1)
+        .on_send({
+            let sent = sent.clone();
+            move |message, pdu| {
+                let mut sent = sent.lock().unwrap();
+                sent.push(pdu.cloned());
+                // The agent ignores the first copy, sent with another community.
+                if sent.len() == 1 {
+                    let at = message.windows(6).position(|w| w == b"public").unwrap();
+                    message[at..at + 6].copy_from_slice(b"secret");
+                }
+            }
+        })
+        .on_receive({
+            let received = received.clone();
+            move |message, pdu| received.lock().unwrap().push((message.len(), pdu.cloned()))
+        })
+        .build()
+        .unwrap();
+
+    let vars = sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    assert_eq!(vars[0].1, Value::OctetString(b"sw1".to_vec()));
+    assert_eq!(sess.stats().retries, 1);
+
+    let hooked = sent.clone();
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.len(), 2);
+    let Some(v2::Pdus::GetRequest(request)) = &sent[1] else {
+        panic!("expected the GET");
+    };
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    let (len, Some(v2::Pdus::Response(response))) = &received[0] else {
+        panic!("expected the response");
+    };
+    assert!(*len > 0);
+    assert_eq!(response.0.request_id, request.0.request_id);
+    drop(sent);
+
+    // Notifications go through the hook too.
+    sess.send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+        .unwrap();
+    assert!(matches!(hooked.lock().unwrap()[2], Some(v2::Pdus::Trap(_))));
+}
+
+#[cfg(feature = "pcap")]
+#[test]
+fn capture_writes_datagrams_with_ip_and_udp_headers() {
+    use super::testing::MockAgent;
+    use super::PcapWriter;
+
+    // The one's complement sum of a header with its checksum in place is all ones.
+    fn sums_to_ones(parts: &[&[u8]]) -> bool {
+        let mut sum: u32 = 0;
+        for word in parts.concat().chunks(2) {
+            sum += u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0));
+        }
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        sum == 0xffff
+    }
+
+    let agent = MockAgent::new([(
+        oid("1.3.6.1.2.1.1.5.0"),
+        Value::OctetString(b"sw1".to_vec()),
+    )])
+    .unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+    let path = std::env::temp_dir().join(format!("yar-snmp-{}.pcap", std::process::id()));
+
+    let sess = SyncSession::builder(agent_addr.to_string())
+        .capture(PcapWriter::create(&path).unwrap())
+        .build()
+        .unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    sess.send_trap("1.3.6.1.6.3.1.1.5.1", &[] as &[(Oid, Value)])
+        .unwrap();
+    drop(sess);
+
+    let capture = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(capture[..4], 0xa1b2_c3d4u32.to_le_bytes());
+    assert_eq!(capture[20..24], 101u32.to_le_bytes());
+
+    let mut records = &capture[24..];
+    let mut ports = Vec::new();
+    while !records.is_empty() {
+        let len = u32::from_le_bytes(records[8..12].try_into().unwrap()) as usize;
+        let packet = &records[16..16 + len];
+        records = &records[16 + len..];
+
+        let (ip, udp) = packet.split_at(20);
+        assert_eq!(ip[0], 0x45);
+        assert_eq!(ip[9], 17);
+        assert_eq!(usize::from(u16::from_be_bytes([ip[2], ip[3]])), len);
+        assert!(sums_to_ones(&[ip]));
+        assert_eq!(ip[12..16], [127, 0, 0, 1]);
+        let pseudo = [&ip[12..20], &[0, 17], &(udp.len() as u16).to_be_bytes()].concat();
+        assert!(sums_to_ones(&[&pseudo, udp]));
+
+        ports.push((
+            u16::from_be_bytes([udp[0], udp[1]]),
+            u16::from_be_bytes([udp[2], udp[3]]),
+        ));
+        assert_eq!(udp[8], 0x30);
+    }
+    let [(local_port, to), response, trap] = ports[..] else {
+        panic!("expected a request, its response and the trap");
+    };
+    assert_eq!(to, agent_addr.port());
+    assert_eq!(response, (agent_addr.port(), local_port));
+    assert_eq!(trap, (local_port, agent_addr.port()));
+}
+
+#[test]
+fn debug_dump_renders_messages_and_marks_where_decoding_broke() {
+    use super::debug;
+    use super::rasn_snmp::v2c;
+
+    let message = v2c::Message {
+        version: 1.into(),
+        community: b"public".to_vec().into(),
+        data: v2::Pdus::Response(v2::Response(v2::Pdu {
+            request_id: 42,
+            error_status: 2,
+            error_index: 1,
+            variable_bindings: super::pdu::var_binds(&[
+                (
+                    oid("1.3.6.1.2.1.1.5.0"),
+                    Value::OctetString(b"sw1".to_vec()),
+                ),
+                (oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(12345)),
+                (oid("1.3.6.1.2.1.1.9.0"), Value::NoSuchObject),
+            ]),
+        })),
+    };
+    let bytes = rasn::ber::encode(&message).unwrap();
+    let dump = debug::dump(&bytes);
+    assert_eq!(
+        dump,
+        "SNMPv2c message\n\
+         \x20 community: public\n\
+         \x20 Response-PDU\n\
+         \x20   request-id: 42\n\
+         \x20   error-status: noSuchName(2)\n\
+         \x20   error-index: 1\n\
+         \x20   variable-bindings:\n\
+         \x20     1.3.6.1.2.1.1.5.0 = OCTET STRING: sw1\n\
+         \x20     1.3.6.1.2.1.1.3.0 = TimeTicks: (12345) 0:02:03.45\n\
+         \x20     1.3.6.1.2.1.1.9.0 = noSuchObject\n"
+    );
+
+    // The first binding claims more octets than its list holds.
+    let mut corrupt = bytes.clone();
+    let at = corrupt.windows(2).position(|w| w == [0x30, 0x0f]).unwrap();
+    corrupt[at + 1] = 0x7f;
+    let dump = debug::dump(&corrupt);
+    assert!(dump.starts_with(&format!("failed to decode response at offset {at}\n0000 ")));
+    assert!(dump.contains(&format!("^^ offset {at:#x}")));
+}
+
+/// Truncations, single-octet corruptions and random edits of `corpus`, followed by a few
+/// hand-made hostile messages.
+fn mutations(corpus: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut random = move |bound: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % bound as u64) as usize
+    };
+
+    let mut inputs = Vec::new();
+    for message in corpus {
+        for len in 0..message.len() {
+            inputs.push(message[..len].to_vec());
+        }
+        for at in 0..message.len() {
+            for octet in [0x00, 0x01, 0x7f, 0x80, 0x81, 0x84, 0xff] {
+                let mut corrupt = message.clone();
+                corrupt[at] = octet;
+                inputs.push(corrupt);
+            }
+        }
+        for _ in 0..500 {
+            let mut edited = message.clone();
+            for _ in 0..1 + random(4) {
+                let at = random(edited.len() + 1);
+                match random(4) {
+                    0 if at < edited.len() => edited[at] = random(256) as u8,
+                    1 => edited.insert(at, random(256) as u8),
+                    2 if at < edited.len() => drop(edited.remove(at)),
+                    _ => {
+                        let end = (at + random(16)).min(edited.len());
+                        let copy = edited[at..end].to_vec();
+                        edited.splice(at..at, copy);
+                    }
+                }
+            }
+            inputs.push(edited);
+        }
+    }
+
+    let mut nested = Vec::new();
+    for _ in 0..10_000 {
+        nested.extend_from_slice(&[0x30, 0x80]);
+    }
+    inputs.extend([
+        nested,
+        // Lengths of four and eight octets, far beyond the message.
+        vec![0x30, 0x84, 0xff, 0xff, 0xff, 0xff, 0x02, 0x01, 0x01],
+        vec![0x30, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        // A version of a hundred octets.
+        [&[0x30, 0x66, 0x02, 0x64][..], &[0x7f; 100]].concat(),
+        // v2c GET of an OID whose arc overflows, then of an OID of one arc.
+        vec![
+            0x30, 0x2a, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0,
+            0x1d, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x12, 0x30, 0x10,
+            0x06, 0x0c, 0x2b, 0x06, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+            0x05, 0x00,
+        ],
+        vec![
+            0x30, 0x1e, 0x02, 0x01, 0x01, 0x04, 0x06, b'p', b'u', b'b', b'l', b'i', b'c', 0xa0,
+            0x11, 0x02, 0x01, 0x01, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x30, 0x06, 0x30, 0x04,
+            0x06, 0x00, 0x05, 0x00,
+        ],
+    ]);
+    inputs
+}
+
+#[test]
+fn malformed_messages_never_panic() {
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::ops::ControlFlow;
+
+    use super::options::Context;
+    use super::security::Security;
+    use super::trap::Receiver;
+    use super::visit::VisitWalk;
+    use super::{agentx, ber, debug, pdu, Agent, UsmUserTable};
+
+    let mut agent = Agent::new();
+    agent
+        .register(
+            oid("1.3.6.1.2.1.1"),
+            std::collections::BTreeMap::from([
+                (
+                    oid("1.3.6.1.2.1.1.1.0"),
+                    Value::OctetString(b"switch".to_vec()),
+                ),
+                (oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(100)),
+                (oid("1.3.6.1.2.1.1.7.0"), Value::Counter64(1 << 40)),
+            ]),
+        )
+        .unwrap();
+    let oids = [oid("1.3.6.1.2.1.1.1.0"), oid("1.3.6.1.2.1.1.3.0")];
+    let bindings = [(oid("1.3.6.1.2.1.1.3.0"), Value::TimeTicks(100))];
+    let encode = |security: &Security, data: v2::Pdus| {
+        let mut message = Vec::new();
+        security
+            .encode(data, &Context::default(), 65507, &mut message)
+            .unwrap();
+        message
+    };
+
+    let v1 = Security::community(0, b"public");
+    let v2c = Security::community(1, b"public");
+    let mut corpus = vec![
+        encode(&v1, pdu::get(&oids)),
+        encode(&v2c, pdu::getbulk(&oids, 1, 10)),
+        encode(
+            &v2c,
+            pdu::inform(100, &oid("1.3.6.1.6.3.1.1.5.1"), &bindings),
+        ),
+        v1.encode_v1_trap(
+            super::v1::trap(
+                &oid("1.3.6.1.4.1.8072"),
+                Ipv4Addr::LOCALHOST,
+                6,
+                1,
+                100,
+                &bindings,
+            )
+            .unwrap(),
+        )
+        .unwrap(),
+    ];
+    let responses: Vec<Vec<u8>> = corpus[..2]
+        .iter()
+        .filter_map(|m| agent.respond(m))
+        .collect();
+    assert_eq!(responses.len(), 2);
+    corpus.extend(responses);
+
+    let secure = UsmUser::new(b"admin")
+        .auth(AuthProtocol::Sha1, b"authpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword");
+    let open = UsmUser::new(b"traps");
+    corpus.push(
+        discovered_usm(secure.clone())
+            .encode(pdu::get(&oids))
+            .unwrap(),
+    );
+    let sender = super::EngineId::text(8072, "sender").unwrap();
+    let mut sender = Usm::new(open.clone(), 4096).with_local_engine(sender, 1);
+    corpus.push(sender.encode(pdu::trap(100, &oids[0], &bindings)).unwrap());
+
+    let users = UsmUserTable::new();
+    users.add(secure.clone());
+    users.add(open);
+    let mut receiver = Receiver::default();
+    receiver.communities(["public"]);
+    receiver.users(&users);
+    let source = SocketAddr::from((Ipv4Addr::LOCALHOST, 162));
+    let mut usm = discovered_usm(secure);
+
+    let inputs = mutations(&corpus);
+    for input in &inputs {
+        let input = &input[..];
+        ber::message_len(input);
+        ber::message_id(input);
+        ber::invalid_offset(input);
+        debug::dump(input);
+        agent.respond(input);
+        receiver.receive(source, input);
+        for decoding in [Decoding::Lenient, Decoding::Strict] {
+            let _ = usm.decode_with(input, decoding);
+        }
+        for id in [0, 1] {
+            for decoding in [Decoding::Lenient, Decoding::Strict] {
+                let _ = v1.decode(input, input, id, decoding);
+                let _ = v2c.decode(input, input, id, decoding);
+            }
+            let mut walk = VisitWalk::new(oid("1.3.6.1.2.1.1"), 10);
+            walk.accept(input, b"public", id, &mut |_, _| ControlFlow::Continue(()));
+        }
+        if let Ok(Some((header, payload, _))) = agentx::Header::decode(input) {
+            let mut reader = header.reader(payload);
+            while reader.var_bind().is_ok() {}
+        }
+    }
+
+    // Cut short, a message is a decoding error and nothing else.
+    let response = &corpus[corpus.len() - 3];
+    for len in 0..response.len() {
+        assert!(matches!(
+            v2c.decode(&response[..len], &[], 0, Decoding::default()),
+            Err(SnmpError::Decode { .. })
+        ));
+    }
+}
+
+#[test]
+fn lenient_decoding_repairs_what_strict_decoding_rejects() {
+    use rasn_snmp::v2c;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let tlv = |tag: u8, contents: &[u8]| [&[tag, contents.len() as u8][..], contents].concat();
+        let mut buf = [0; 1500];
+
+        // SNMPv1 GETs decode as v2c messages too, the version aside.
+        while let Ok((len, peer)) = agent.recv_from(&mut buf) {
+            let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+            let v2::Pdus::GetRequest(get) = request.data else {
+                panic!("expected a GetRequest");
+            };
+            let name = get.0.variable_bindings[0].name.clone();
+
+            // 200 without its leading zero octet, a Counter64 and an unknown error-status.
+            let (status, value) = match name.last() {
+                Some(1) => (0, tlv(0x41, &[0xc8])),
+                Some(2) => (0, tlv(0x46, &[5])),
+                _ => (42, tlv(0x05, &[])),
+            };
+            let binding = tlv(0x30, &[rasn::ber::encode(&name).unwrap(), value].concat());
+            let pdu = [
+                rasn::ber::encode(&get.0.request_id).unwrap(),
+                tlv(0x02, &[status]),
+                tlv(0x02, &[0]),
+                tlv(0x30, &binding),
+            ];
+            let message = [
+                rasn::ber::encode(&request.version).unwrap(),
+                rasn::ber::encode(&request.community).unwrap(),
+                tlv(0xa2, &pdu.concat()),
+            ];
+            agent.send_to(&tlv(0x30, &message.concat()), peer).unwrap();
+        }
+    });
+
+    let session = |v1: bool, decoding| {
+        let builder = SyncSession::builder(agent_addr.to_string());
+        let builder = if v1 {
+            builder.v1("public")
+        } else {
+            builder.v2c("public")
+        };
+        // Rejected responses are only reported once the timeout runs out.
+        builder
+            .decoding(decoding)
+            .timeout(std::time::Duration::from_millis(100))
+            .retries(0)
+            .build()
+            .unwrap()
+    };
+
+    for v1 in [true, false] {
+        let lenient = session(v1, Decoding::Lenient);
+        let strict = session(v1, Decoding::Strict);
+
+        assert_eq!(
+            lenient.get("1.3.6.1.4.1.1").unwrap()[0].1,
+            Value::Counter32(200)
+        );
+        assert!(matches!(
+            strict.get("1.3.6.1.4.1.1"),
+            Err(SnmpError::InvalidMessage(_))
+        ));
+
+        assert!(matches!(
+            lenient.get("1.3.6.1.4.1.3"),
+            Err(SnmpError::AgentError {
+                status: crate::ErrorStatus::GenErr,
+                ..
+            })
+        ));
+        assert!(matches!(
+            strict.get("1.3.6.1.4.1.3"),
+            Err(SnmpError::InvalidMessage(_))
+        ));
+    }
+
+    // Counter64 is not an SNMPv1 type.
+    let lenient = session(true, Decoding::Lenient);
+    assert_eq!(
+        lenient.get("1.3.6.1.4.1.2").unwrap()[0].1,
+        Value::Counter64(5)
+    );
+    assert!(matches!(
+        session(true, Decoding::Strict).get("1.3.6.1.4.1.2"),
+        Err(SnmpError::InvalidMessage(_))
+    ));
+}
+
+#[test]
+fn trap_receiver_rejects_replayed_v3_traps() {
+    use super::trap::Receiver;
+    use super::{EngineId, TrapStats, UsmUserTable};
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    let user = UsmUser::new(b"traps")
+        .auth(AuthProtocol::Sha256, b"authpassword")
+        .privacy(PrivProtocol::Aes128, b"privpassword");
+    let users = UsmUserTable::new();
+    users.add(user.clone());
+    let mut receiver = Receiver::default();
+    receiver.users(&users);
+    let source = SocketAddr::from((Ipv4Addr::LOCALHOST, 162));
+
+    let sender = EngineId::text(8072, "sender").unwrap();
+    let mut usm = Usm::new(user.clone(), 4096).with_local_engine(sender.clone(), 2);
+    let trap = super::pdu::trap(100, &oid("1.3.6.1.6.3.1.1.5.1"), &[]);
+    usm.set_local_time(1000);
+    let first = usm.encode(trap.clone()).unwrap();
+    let accepted =
+        |receiver: &mut Receiver, message: &[u8]| receiver.receive(source, message).0.is_some();
+
+    assert!(accepted(&mut receiver, &first));
+    // Within the window of the latest clock, a copy may still be a late original.
+    assert!(accepted(&mut receiver, &first));
+
+    usm.set_local_time(1000 + 151);
+    assert!(accepted(&mut receiver, &usm.encode(trap.clone()).unwrap()));
+    assert!(!accepted(&mut receiver, &first));
+
+    // A reboot leaves every message from before it behind.
+    let mut rebooted = Usm::new(user, 4096).with_local_engine(sender, 3);
+    let after = rebooted.encode(trap).unwrap();
+    assert!(accepted(&mut receiver, &after));
+    assert!(!accepted(&mut receiver, &first));
+    assert!(accepted(&mut receiver, &after));
+
+    assert_eq!(
+        receiver.stats(),
+        TrapStats {
+            accepted: 5,
+            unauthenticated: 2,
+            ..TrapStats::default()
+        }
+    );
+}
+
+#[test]
+fn malformed_datagrams_do_not_end_requests() {
+    use rasn_snmp::v2c;
+
+    let agent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let agent_addr = agent.local_addr().unwrap();
+
+    let responder = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (len, peer) = agent.recv_from(&mut buf).unwrap();
+        let request: v2c::Message<v2::Pdus> = rasn::ber::decode(&buf[..len]).unwrap();
+        let v2::Pdus::GetRequest(get) = request.data else {
+            panic!("expected a GetRequest");
+        };
+
+        agent.send_to(&[0x30, 0x03, 0x02, 0x01], peer).unwrap();
+        let response = v2c::Message {
+            version: request.version,
+            community: request.community,
+            data: v2::Pdus::Response(v2::Response(get.0)),
+        };
+        agent
+            .send_to(&rasn::ber::encode(&response).unwrap(), peer)
+            .unwrap();
+    });
+
+    let sess = SyncSession::new(1, agent_addr, b"public", 1000).unwrap();
+    sess.get("1.3.6.1.2.1.1.5.0").unwrap();
+    responder.join().unwrap();
+    assert_eq!(sess.stats().decode_errors, 1);
+
+    // With nothing else arriving, the request fails with why the datagram was dropped.
+    let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let sess = SyncSession::builder(silent.local_addr().unwrap().to_string())
+        .timeout(std::time::Duration::from_millis(100))
+        .retries(0)
+        .build()
+        .unwrap();
+    let responder = std::thread::spawn(move || {
+        let mut buf = [0; 1500];
+        let (_, peer) = silent.recv_from(&mut buf).unwrap();
+        silent.send_to(&[0x30, 0x03, 0x02, 0x01], peer).unwrap();
+    });
+    assert!(matches!(
+        sess.get("1.3.6.1.2.1.1.5.0"),
+        Err(SnmpError::Decode { .. })
+    ));
+    responder.join().unwrap();
+}
+
+#[test]
+fn visiting_walks_recover_like_other_requests() {
+    use super::testing::MockAgent;
+    use std::ops::ControlFlow;
+    use std::time::Duration;
+
+    let agent = MockAgent::new(
+        (1..=5).map(|i| (oid(&format!("1.3.6.1.2.1.1.{}.0", i)), Value::Integer(i))),
+    )
+    .unwrap();
+    let sess = SyncSession::builder(agent.local_addr().unwrap().to_string())
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .communities(["nope", "public"])
+        .build()
+        .unwrap();
+
+    let mut visited = Vec::new();
+    sess.bulk_walk_visit("1.3.6.1.2.1.1", |name, _| {
+        visited.push(name.to_vec());
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(visited.len(), 5);
+    assert_eq!(sess.community(), Some(&b"public"[..]));
+}
+
+#[test]
+fn trap_dedup_forgets_unreported_duplicates() {
+    use super::trap::Receiver;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    let mut receiver = Receiver::default();
+    receiver.communities(["public"]);
+    receiver.dedup(Duration::from_millis(50));
+    let source = SocketAddr::from((Ipv4Addr::LOCALHOST, 162));
+    let trap = |trap_oid| {
+        rasn::ber::encode(&rasn_snmp::v2c::Message {
+            version: 1.into(),
+            community: b"public".to_vec().into(),
+            data: super::pdu::trap(100, &oid(trap_oid), &[]),
+        })
+        .unwrap()
+    };
+    let (cold_start, warm_start) = (trap("1.3.6.1.6.3.1.1.5.1"), trap("1.3.6.1.6.3.1.1.5.2"));
+
+    assert!(receiver.receive(source, &cold_start).0.is_some());
+    assert!(receiver.receive(source, &cold_start).0.is_none());
 
-    // for var in walk {
-    //     println!("{:?} => {:?}", var.0, var.1)
-    // }
+    // Two windows on, the next notification prunes the copy count no one reported.
+    std::thread::sleep(Duration::from_millis(120));
+    assert!(receiver.receive(source, &warm_start).0.is_some());
+    let event = receiver.receive(source, &cold_start).0.unwrap();
+    assert_eq!(event.suppressed, 0);
 }
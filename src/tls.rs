@@ -0,0 +1,235 @@
+//! SNMP over TLS (RFC 6353): a [`Transport`] that authenticates both ends with certificates,
+//! used together with the Transport Security Model instead of USM.
+//!
+//! Only the TLS over TCP half of the (D)TLS Transport Model is implemented; there is no
+//! DTLS over UDP, so agents that only listen on 10161/udp cannot be reached with it.
+
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore,
+    SignatureScheme, StreamOwned,
+};
+use sha1::{Digest, Sha1};
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::transport::{read_message, TCP_MAX_MESSAGE_SIZE};
+use crate::Transport;
+
+/// How the agent's certificate is checked.
+#[derive(Debug, Clone)]
+enum ServerIdentity {
+    /// Chains to one of the roots and names the host connected to.
+    Roots(RootCertStore),
+    /// An SnmpTLSFingerprint (RFC 6353 2.): a TLS HashAlgorithm octet followed by the
+    /// digest of the certificate, as in snmpTlstmAddrServerFingerprint.
+    Fingerprint(Vec<u8>),
+}
+
+/// The certificates of a TLS session: the client's own chain and key, which the agent maps
+/// to a securityName, and how the agent's certificate is verified.
+#[derive(Debug)]
+pub struct TlsConfig {
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    server: ServerIdentity,
+}
+
+impl Clone for TlsConfig {
+    fn clone(&self) -> Self {
+        TlsConfig {
+            chain: self.chain.clone(),
+            key: self.key.clone_key(),
+            server: self.server.clone(),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// `chain` starts with the client certificate; both are DER-encoded.
+    pub fn new(chain: Vec<CertificateDer<'static>>, key: PrivateKeyDer<'static>) -> Self {
+        TlsConfig {
+            chain,
+            key,
+            server: ServerIdentity::Roots(RootCertStore::empty()),
+        }
+    }
+
+    /// Trusts agents whose certificate chains to `root` and matches the host name.
+    pub fn trust_root(mut self, root: CertificateDer<'static>) -> io::Result<Self> {
+        let mut roots = match self.server {
+            ServerIdentity::Roots(roots) => roots,
+            ServerIdentity::Fingerprint(_) => RootCertStore::empty(),
+        };
+        roots.add(root).map_err(io::Error::other)?;
+
+        self.server = ServerIdentity::Roots(roots);
+        Ok(self)
+    }
+
+    /// Trusts exactly the agent certificate with this SnmpTLSFingerprint, e.g.
+    /// `[4, <SHA-256 digest>...]`, regardless of who issued it.
+    pub fn server_fingerprint(mut self, fingerprint: &[u8]) -> io::Result<Self> {
+        match fingerprint.split_first() {
+            Some((algorithm, digest))
+                if fingerprint_digest(*algorithm, b"").len() == digest.len() =>
+            {
+                self.server = ServerIdentity::Fingerprint(fingerprint.to_vec());
+                Ok(self)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "unsupported SnmpTLSFingerprint",
+            )),
+        }
+    }
+
+    fn client_config(&self) -> io::Result<ClientConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+
+        let builder = match &self.server {
+            ServerIdentity::Roots(roots) => builder.with_root_certificates(roots.clone()),
+            ServerIdentity::Fingerprint(fingerprint) => builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(FingerprintVerifier {
+                    fingerprint: fingerprint.clone(),
+                    provider,
+                })),
+        };
+
+        builder
+            .with_client_auth_cert(self.chain.clone(), self.key.clone_key())
+            .map_err(io::Error::other)
+    }
+}
+
+/// Digests `cert` with the TLS HashAlgorithm `algorithm` (RFC 5246 7.4.1.4.1); empty for
+/// algorithms that are not supported.
+fn fingerprint_digest(algorithm: u8, cert: &[u8]) -> Vec<u8> {
+    match algorithm {
+        2 => Sha1::digest(cert).to_vec(),
+        3 => Sha224::digest(cert).to_vec(),
+        4 => Sha256::digest(cert).to_vec(),
+        5 => Sha384::digest(cert).to_vec(),
+        6 => Sha512::digest(cert).to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Debug)]
+struct FingerprintVerifier {
+    fingerprint: Vec<u8>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let (algorithm, digest) = self
+            .fingerprint
+            .split_first()
+            .expect("fingerprints are validated on configuration");
+
+        if fingerprint_digest(*algorithm, end_entity) == digest {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(CertificateError::ApplicationVerificationFailure.into())
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = &self.provider.signature_verification_algorithms;
+        verify_tls12_signature(message, cert, dss, algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        let algorithms = &self.provider.signature_verification_algorithms;
+        verify_tls13_signature(message, cert, dss, algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// SNMP over TLS on TCP port 10161 by convention; DTLS over UDP is not supported. The
+/// handshake completes on the first message; verification failures surface as I/O errors
+/// then.
+#[derive(Debug)]
+pub struct TlsTransport {
+    stream: Mutex<StreamOwned<ClientConnection, TcpStream>>,
+    local_addr: SocketAddr,
+}
+
+impl TlsTransport {
+    /// `server_name` is the name the agent's certificate is checked against when it is
+    /// verified with roots.
+    pub fn connect<A: ToSocketAddrs>(
+        dest_addr: A,
+        server_name: &str,
+        config: &TlsConfig,
+    ) -> io::Result<Self> {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        let connection = ClientConnection::new(Arc::new(config.client_config()?), server_name)
+            .map_err(io::Error::other)?;
+        let socket = TcpStream::connect(dest_addr)?;
+
+        Ok(TlsTransport {
+            local_addr: socket.local_addr()?,
+            stream: Mutex::new(StreamOwned::new(connection, socket)),
+        })
+    }
+}
+
+impl Transport for TlsTransport {
+    fn send(&self, data: &[u8]) -> io::Result<()> {
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+
+        io::Write::write_all(&mut *stream, data)?;
+        io::Write::flush(&mut *stream)
+    }
+
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let mut stream = self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+
+        stream.sock.set_read_timeout(Some(timeout))?;
+        read_message(&mut *stream, buf)
+    }
+
+    fn max_msg_size(&self) -> usize {
+        TCP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+}
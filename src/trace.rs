@@ -0,0 +1,12 @@
+//! Diagnostics through `tracing` when the `tracing` feature is enabled; without it the
+//! events compile to nothing.
+
+/// `event!(debug, field = value, "message")` forwards to `tracing::debug!` and friends.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+pub(crate) use event;
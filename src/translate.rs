@@ -0,0 +1,128 @@
+//! Translation between SNMPv1 traps and SNMPv2 notifications (RFC 3584 3.1 and 3.2), for
+//! receivers and forwarders that bridge the two.
+
+use std::net::Ipv4Addr;
+
+use crate::{Notification, Oid, Value};
+
+/// snmpTraps, the OIDs of the generic traps of SNMPv2-MIB.
+const SNMP_TRAPS: [u32; 9] = [1, 3, 6, 1, 6, 3, 1, 1, 5];
+/// snmpTrapAddress.0 and snmpTrapCommunity.0 of SNMP-COMMUNITY-MIB.
+const SNMP_TRAP_ADDRESS: [u32; 10] = [1, 3, 6, 1, 6, 3, 18, 1, 3, 0];
+const SNMP_TRAP_COMMUNITY: [u32; 10] = [1, 3, 6, 1, 6, 3, 18, 1, 4, 0];
+const SNMP_TRAP_ENTERPRISE: [u32; 11] = [1, 3, 6, 1, 6, 3, 1, 1, 4, 3, 0];
+
+/// The SNMPv2 form of an SNMPv1 trap received with `community` (RFC 3584 3.1): the
+/// generic trap's snmpTraps OID or `enterprise.0.specific` as snmpTrapOID.0, the
+/// timestamp as sysUpTime.0, and the agent address, community and enterprise appended as
+/// snmpTrapAddress.0, snmpTrapCommunity.0 and snmpTrapEnterprise.0 unless already bound.
+/// v2 notifications are returned as they are.
+pub fn v1_to_v2(notification: &Notification, community: &[u8]) -> Notification {
+    let Notification::V1 {
+        enterprise,
+        agent_addr,
+        generic_trap,
+        specific_trap,
+        timestamp,
+        bindings,
+    } = notification
+    else {
+        return notification.clone();
+    };
+
+    let mut bindings = bindings.clone();
+    let appended = [
+        (&SNMP_TRAP_ADDRESS[..], Value::IpAddress(*agent_addr)),
+        (
+            &SNMP_TRAP_COMMUNITY[..],
+            Value::OctetString(community.to_vec()),
+        ),
+        (
+            &SNMP_TRAP_ENTERPRISE[..],
+            Value::Oid(enterprise.as_slice().to_vec()),
+        ),
+    ];
+    for (name, value) in appended {
+        if !bindings.iter().any(|(bound, _)| bound.as_slice() == name) {
+            bindings.push((Oid::from(name), value));
+        }
+    }
+
+    Notification::V2 {
+        uptime: *timestamp,
+        trap_oid: v1_trap_oid(enterprise, *generic_trap, *specific_trap),
+        bindings,
+    }
+}
+
+/// The snmpTrapOID.0 of an SNMPv1 trap: the generic trap's snmpTraps OID, or
+/// `enterprise.0.specific`.
+pub(crate) fn v1_trap_oid(enterprise: &Oid, generic_trap: u32, specific_trap: u32) -> Oid {
+    match generic_trap {
+        0..=5 => [&SNMP_TRAPS[..], &[generic_trap + 1]].concat().into(),
+        _ => [enterprise.as_slice(), &[0, specific_trap]].concat().into(),
+    }
+}
+
+/// The SNMPv1 form of an SNMPv2 notification (RFC 3584 3.2): a generic trap for the
+/// snmpTraps OIDs, with snmpTrapEnterprise.0 or snmpTraps as its enterprise, and an
+/// enterprise-specific one otherwise, its enterprise being snmpTrapOID.0 without the last
+/// sub-identifier and a `0` before it. The agent address is snmpTrapAddress.0, or
+/// `agent_addr` when it is not bound. Bindings SNMPv1 cannot carry, Counter64 values and
+/// exceptions, are left out. v1 traps are returned as they are.
+pub fn v2_to_v1(notification: &Notification, agent_addr: Ipv4Addr) -> Notification {
+    let Notification::V2 {
+        uptime,
+        trap_oid,
+        bindings,
+    } = notification
+    else {
+        return notification.clone();
+    };
+
+    let bound = |name: &[u32]| {
+        bindings
+            .iter()
+            .find(|(bound, _)| bound.as_slice() == name)
+            .map(|(_, value)| value)
+    };
+
+    let (enterprise, generic_trap, specific_trap) = match trap_oid.suffix(&SNMP_TRAPS[..].into()) {
+        Some(&[generic @ 1..=6]) => {
+            let enterprise = match bound(&SNMP_TRAP_ENTERPRISE) {
+                Some(Value::Oid(enterprise)) => enterprise.clone(),
+                _ => SNMP_TRAPS.to_vec(),
+            };
+            (enterprise, generic - 1, 0)
+        }
+        _ => {
+            let oid = trap_oid.as_slice();
+            let (specific, mut enterprise) = match oid.split_last() {
+                Some((specific, enterprise)) => (*specific, enterprise),
+                None => (0, oid),
+            };
+            if let Some((0, rest)) = enterprise.split_last() {
+                enterprise = rest;
+            }
+            (enterprise.to_vec(), 6, specific)
+        }
+    };
+
+    let agent_addr = match bound(&SNMP_TRAP_ADDRESS) {
+        Some(Value::IpAddress(addr)) => *addr,
+        _ => agent_addr,
+    };
+
+    Notification::V1 {
+        enterprise: enterprise.into(),
+        agent_addr,
+        generic_trap,
+        specific_trap,
+        timestamp: *uptime,
+        bindings: bindings
+            .iter()
+            .filter(|(_, value)| !matches!(value, Value::Counter64(_)) && !value.is_exception())
+            .cloned()
+            .collect(),
+    }
+}
@@ -0,0 +1,556 @@
+//! Transports carrying encoded messages between a session and an agent.
+
+use std::io::{self, Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream, ToSocketAddrs, UdpSocket,
+};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
+
+use socket2::SockRef;
+
+use crate::ber::header;
+
+/// The largest payload of an IPv4 UDP datagram.
+pub(crate) const UDP_MAX_MESSAGE_SIZE: usize = 65507;
+
+/// The largest message reassembled from a TCP stream.
+pub(crate) const TCP_MAX_MESSAGE_SIZE: usize = 65535;
+
+/// Moves whole SNMP messages to and from one agent. Implement it to run sessions over
+/// tunnels or against in-memory agents in tests.
+pub trait Transport {
+    fn send(&self, data: &[u8]) -> io::Result<()>;
+
+    /// Receives one message into `buf` and returns its length. When `timeout` passes first
+    /// this fails with [`io::ErrorKind::TimedOut`] or [`io::ErrorKind::WouldBlock`], which
+    /// makes the session retransmit.
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize>;
+
+    /// The largest message the transport can carry in either direction.
+    fn max_msg_size(&self) -> usize;
+
+    /// The local address, which SNMPv1 traps report as their agent address.
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// The address of the agent requests currently go to.
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+
+    /// How many other addresses of the agent [`Transport::fail_over`] can switch to.
+    fn alternatives(&self) -> usize {
+        0
+    }
+
+    /// Switches to the next address of the agent, after the current one left a request
+    /// unanswered.
+    fn fail_over(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Looks the agent's host names up again, if it was given by name, and reconnects when
+    /// its addresses changed. Returns whether they did.
+    fn resolve(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Parses `host` when it is an address rather than a name: an IP address, an IPv6 address
+/// with a zone such as `fe80::1%eth0`, or either as a socket address like
+/// `[fe80::1%eth0]:1161`, whose port overrides `port`.
+pub(crate) fn literal(host: &str, port: u16) -> io::Result<Option<SocketAddr>> {
+    if let Ok(addr) = host.parse::<SocketAddr>() {
+        return Ok(Some(addr));
+    }
+
+    let (host, port) = match host.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((host, "")) => (host, port),
+        Some((host, rest)) => match rest.strip_prefix(':').map(str::parse) {
+            Some(Ok(port)) => (host, port),
+            _ => return Ok(None),
+        },
+        None => (host, port),
+    };
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(Some(SocketAddr::new(ip, port)));
+    }
+
+    let Some((ip, zone)) = host.split_once('%') else {
+        return Ok(None);
+    };
+    let Ok(ip) = ip.parse::<Ipv6Addr>() else {
+        return Ok(None);
+    };
+    let scope_id = match zone.parse() {
+        Ok(index) => index,
+        Err(_) => interface_index(zone)?,
+    };
+
+    Ok(Some(SocketAddrV6::new(ip, port, 0, scope_id).into()))
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> io::Result<u32> {
+    let name = std::ffi::CString::new(name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+
+    // SAFETY: `name` is a valid NUL-terminated string for the duration of the call.
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unknown interface in IPv6 zone",
+        )),
+        index => Ok(index),
+    }
+}
+
+#[cfg(not(unix))]
+fn interface_index(_: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "IPv6 zones must be interface indexes on this platform",
+    ))
+}
+
+/// Picks the address to bind for talking to `dest`: the local address and port of
+/// `options`, with the unspecified address of the same family and any port standing in for
+/// those not given.
+pub(crate) fn bind_addr(dest: SocketAddr, options: &SocketOptions) -> SocketAddr {
+    let ip = options.local_ip.unwrap_or(match dest {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    });
+
+    SocketAddr::new(ip, options.local_port)
+}
+
+/// How a session's UDP socket is bound and set up, left to the system unless given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SocketOptions {
+    pub(crate) local_ip: Option<IpAddr>,
+    /// The source port, any free one when 0.
+    pub(crate) local_port: u16,
+    /// The interface to send through regardless of routing, SO_BINDTODEVICE.
+    pub(crate) device: Option<String>,
+    /// The TOS octet, or traffic class for IPv6; DSCP in its upper six bits.
+    pub(crate) tos: Option<u8>,
+    /// The TTL, or hop limit for IPv6.
+    pub(crate) ttl: Option<u32>,
+    /// The kernel buffer for datagrams waiting to be received, SO_RCVBUF.
+    pub(crate) recv_buffer: Option<usize>,
+}
+
+impl SocketOptions {
+    pub(crate) fn apply(&self, socket: SockRef<'_>, v6: bool) -> io::Result<()> {
+        if let Some(device) = &self.device {
+            bind_device(&socket, device)?;
+        }
+        if let Some(tos) = self.tos {
+            if v6 {
+                set_tclass_v6(&socket, tos)?;
+            } else {
+                socket.set_tos_v4(tos.into())?;
+            }
+        }
+        if let Some(ttl) = self.ttl {
+            if v6 {
+                socket.set_unicast_hops_v6(ttl)?;
+            } else {
+                socket.set_ttl_v4(ttl)?;
+            }
+        }
+        if let Some(size) = self.recv_buffer {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_tclass_v6(socket: &SockRef<'_>, tos: u8) -> io::Result<()> {
+    socket.set_tclass_v6(tos.into())
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_tclass_v6(_: &SockRef<'_>, _: u8) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IPv6 traffic class not supported on this platform",
+    ))
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_device(socket: &SockRef<'_>, device: &str) -> io::Result<()> {
+    socket.bind_device(Some(device.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_device(_: &SockRef<'_>, _: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is not supported on this platform",
+    ))
+}
+
+pub(crate) fn no_addrs() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "empty list of socket addrs")
+}
+
+/// A socket set up by `options` and connected to `dest`.
+pub(crate) fn connect_udp(dest: SocketAddr, options: &SocketOptions) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(bind_addr(dest, options))?;
+    options.apply(SockRef::from(&socket), dest.is_ipv6())?;
+    socket.connect(dest)?;
+
+    Ok(socket)
+}
+
+/// The agent as given: host names, IP addresses, or socket addresses, with the port for
+/// those that have none. Host names are looked up again when
+/// [`SessionBuilder::resolve_every`](crate::SessionBuilder::resolve_every) or
+/// [`SessionBuilder::resolve_on_failure`](crate::SessionBuilder::resolve_on_failure) asks.
+#[derive(Debug, Clone)]
+pub(crate) struct Hosts {
+    hosts: Vec<String>,
+    port: u16,
+}
+
+impl Hosts {
+    pub(crate) fn new(hosts: Vec<String>, port: u16) -> Self {
+        Hosts { hosts, port }
+    }
+
+    /// Every address of every host, in order.
+    pub(crate) fn resolve(&self) -> io::Result<Vec<SocketAddr>> {
+        let mut addrs = Vec::new();
+        for host in &self.hosts {
+            match literal(host, self.port)? {
+                Some(addr) => addrs.push(addr),
+                None => addrs.extend((host.as_str(), self.port).to_socket_addrs()?),
+            }
+        }
+
+        Ok(addrs)
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn resolve_async(&self) -> io::Result<Vec<SocketAddr>> {
+        let mut addrs = Vec::new();
+        for host in &self.hosts {
+            match literal(host, self.port)? {
+                Some(addr) => addrs.push(addr),
+                None => addrs.extend(tokio::net::lookup_host((host.as_str(), self.port)).await?),
+            }
+        }
+
+        Ok(addrs)
+    }
+}
+
+/// A socket connected to the first of `addrs` one can be set up for, and its index.
+fn connect_first<S>(
+    addrs: &[SocketAddr],
+    options: &SocketOptions,
+    socket: impl Fn(UdpSocket) -> io::Result<S>,
+) -> io::Result<(usize, S)> {
+    let mut last_err = no_addrs();
+    for (index, addr) in addrs.iter().enumerate() {
+        match connect_udp(*addr, options).and_then(&socket) {
+            Ok(socket) => return Ok((index, socket)),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
+}
+
+#[derive(Debug)]
+struct Current {
+    addrs: Vec<SocketAddr>,
+    index: usize,
+}
+
+/// The agent's addresses and which of them a transport is connected to.
+#[derive(Debug)]
+pub(crate) struct Peers {
+    current: Mutex<Current>,
+    options: SocketOptions,
+    hosts: Option<Hosts>,
+}
+
+impl Peers {
+    /// Connects to the first of `addrs` a socket can be set up for, e.g. skipping IPv6
+    /// addresses on hosts without IPv6, and turns the socket into the transport's own.
+    /// `hosts` are what `addrs` were resolved from, if they are to be looked up again.
+    pub(crate) fn connect<S>(
+        addrs: Vec<SocketAddr>,
+        options: SocketOptions,
+        hosts: Option<Hosts>,
+        socket: impl Fn(UdpSocket) -> io::Result<S>,
+    ) -> io::Result<(Self, S)> {
+        let (index, socket) = connect_first(&addrs, &options, socket)?;
+        let peers = Peers {
+            current: Mutex::new(Current { addrs, index }),
+            options,
+            hosts,
+        };
+
+        Ok((peers, socket))
+    }
+
+    fn current(&self) -> MutexGuard<'_, Current> {
+        self.current.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn alternatives(&self) -> usize {
+        self.current().addrs.len().saturating_sub(1)
+    }
+
+    pub(crate) fn hosts(&self) -> Option<&Hosts> {
+        self.hosts.as_ref()
+    }
+
+    /// A socket connected to the next address after the current one that a socket can be
+    /// set up for.
+    pub(crate) fn next<S>(&self, socket: impl Fn(UdpSocket) -> io::Result<S>) -> io::Result<S> {
+        let mut current = self.current();
+        let mut last_err = no_addrs();
+        for _ in 1..current.addrs.len() {
+            current.index = (current.index + 1) % current.addrs.len();
+
+            match connect_udp(current.addrs[current.index], &self.options).and_then(&socket) {
+                Ok(socket) => return Ok(socket),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Switches to `addrs`, looked up again, unless they are the ones already known in
+    /// some order: a socket connected to the first of them one can be set up for, or
+    /// `None` when nothing changed.
+    pub(crate) fn replace<S>(
+        &self,
+        addrs: Vec<SocketAddr>,
+        socket: impl Fn(UdpSocket) -> io::Result<S>,
+    ) -> io::Result<Option<S>> {
+        let mut current = self.current();
+        let (mut old, mut new) = (current.addrs.clone(), addrs.clone());
+        old.sort();
+        new.sort();
+        if old == new {
+            return Ok(None);
+        }
+
+        let (index, socket) = connect_first(&addrs, &self.options, socket)?;
+        *current = Current { addrs, index };
+        Ok(Some(socket))
+    }
+}
+
+/// Plain SNMP over UDP (RFC 3417), one message per datagram.
+///
+/// A host name may resolve to several addresses, such as an IPv6 and an IPv4 one. The
+/// transport connects to the first it can, and the session fails over to the others in
+/// turn when a request goes unanswered.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: RwLock<UdpSocket>,
+    peers: Option<Peers>,
+}
+
+impl UdpTransport {
+    pub fn connect<A: ToSocketAddrs>(dest_addr: A) -> io::Result<Self> {
+        Self::open(dest_addr, &SocketOptions::default())
+    }
+
+    pub(crate) fn open<A: ToSocketAddrs>(
+        dest_addr: A,
+        options: &SocketOptions,
+    ) -> io::Result<Self> {
+        let addrs = dest_addr.to_socket_addrs()?.collect();
+        let (peers, socket) = Peers::connect(addrs, options.clone(), None, Ok)?;
+
+        Ok(UdpTransport {
+            socket: RwLock::new(socket),
+            peers: Some(peers),
+        })
+    }
+
+    /// Connects to `hosts`, looking their names up again when [`Transport::resolve`] is
+    /// called.
+    pub(crate) fn open_hosts(hosts: Hosts, options: &SocketOptions) -> io::Result<Self> {
+        let addrs = hosts.resolve()?;
+        let (peers, socket) = Peers::connect(addrs, options.clone(), Some(hosts), Ok)?;
+
+        Ok(UdpTransport {
+            socket: RwLock::new(socket),
+            peers: Some(peers),
+        })
+    }
+
+    fn socket(&self) -> RwLockReadGuard<'_, UdpSocket> {
+        self.socket.read().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Wraps a socket that is already connected to the agent.
+impl From<UdpSocket> for UdpTransport {
+    fn from(socket: UdpSocket) -> Self {
+        UdpTransport {
+            socket: RwLock::new(socket),
+            peers: None,
+        }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&self, data: &[u8]) -> io::Result<()> {
+        self.socket().send(data).map(drop)
+    }
+
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let socket = self.socket();
+        socket.set_read_timeout(Some(timeout))?;
+        socket.recv(buf)
+    }
+
+    fn max_msg_size(&self) -> usize {
+        UDP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().local_addr()
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.socket().peer_addr()
+    }
+
+    fn alternatives(&self) -> usize {
+        self.peers.as_ref().map_or(0, Peers::alternatives)
+    }
+
+    fn fail_over(&self) -> io::Result<()> {
+        if let Some(peers) = &self.peers {
+            let socket = peers.next(Ok)?;
+            *self.socket.write().unwrap_or_else(PoisonError::into_inner) = socket;
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self) -> io::Result<bool> {
+        let Some((peers, hosts)) = self
+            .peers
+            .as_ref()
+            .and_then(|peers| Some((peers, peers.hosts()?)))
+        else {
+            return Ok(false);
+        };
+        let Some(socket) = peers.replace(hosts.resolve()?, Ok)? else {
+            return Ok(false);
+        };
+
+        *self.socket.write().unwrap_or_else(PoisonError::into_inner) = socket;
+        Ok(true)
+    }
+}
+
+/// Reads one BER-framed message from a stream into `buf`.
+pub(crate) fn read_message(mut stream: impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    if buf.len() < 6 {
+        return Err(too_large());
+    }
+
+    // Tag and the first length octet, then any further length octets.
+    stream.read_exact(&mut buf[..2])?;
+    let extra = match buf[1] {
+        len if len & 0x80 != 0 => (len & 0x7f) as usize,
+        _ => 0,
+    };
+    if extra > 4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid BER length",
+        ));
+    }
+    stream.read_exact(&mut buf[2..2 + extra])?;
+
+    let (head, len) = header(&buf[..2 + extra])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid BER length"))?;
+    let total = head.checked_add(len).ok_or_else(too_large)?;
+    if total > buf.len() {
+        return Err(too_large());
+    }
+
+    stream.read_exact(&mut buf[head..total])?;
+
+    Ok(total)
+}
+
+fn too_large() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "message larger than the receive buffer",
+    )
+}
+
+/// SNMP over TCP (RFC 3430); messages are delimited by their BER framing. A timeout in the
+/// middle of a message leaves the stream out of step, so prefer generous timeouts.
+#[derive(Debug)]
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn connect<A: ToSocketAddrs>(dest_addr: A) -> io::Result<Self> {
+        Ok(TcpTransport {
+            stream: TcpStream::connect(dest_addr)?,
+        })
+    }
+}
+
+impl From<TcpStream> for TcpTransport {
+    fn from(stream: TcpStream) -> Self {
+        TcpTransport { stream }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&self, data: &[u8]) -> io::Result<()> {
+        (&self.stream).write_all(data)
+    }
+
+    fn recv(&self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(timeout))?;
+        read_message(&self.stream, buf)
+    }
+
+    fn max_msg_size(&self) -> usize {
+        TCP_MAX_MESSAGE_SIZE
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.local_addr()
+    }
+}
@@ -0,0 +1,725 @@
+//! Receiving notifications: SNMPv1 traps, SNMPv2 traps and informs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use rasn_snmp::{v1, v2, v2c};
+
+use crate::pdu::{decode, encode, SNMP_TRAP_OID, SYS_UP_TIME};
+use crate::trace;
+use crate::translate;
+use crate::transport::UDP_MAX_MESSAGE_SIZE;
+use crate::usm::{self, Engine, EngineClocks, Received, Rejected};
+use crate::{
+    ber, EngineId, Formatter, Mib, Oid, SnmpError, SnmpResult, TrapSink, UsmUserTable, Value,
+    Version,
+};
+
+/// The contents of a notification as sent.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Notification {
+    /// An SNMPv1 Trap-PDU (RFC 1157 4.1.6).
+    V1 {
+        enterprise: Oid,
+        agent_addr: Ipv4Addr,
+        generic_trap: u32,
+        specific_trap: u32,
+        timestamp: u32,
+        bindings: Vec<(Oid, Value)>,
+    },
+    /// An SNMPv2-Trap or InformRequest; `bindings` leaves out the leading sysUpTime.0 and
+    /// snmpTrapOID.0, which are in `uptime` and `trap_oid`.
+    V2 {
+        uptime: u32,
+        trap_oid: Oid,
+        bindings: Vec<(Oid, Value)>,
+    },
+}
+
+impl Notification {
+    /// The snmpTrapOID.0 of a v2 notification, or what RFC 3584 translates a v1 trap's to.
+    pub fn trap_oid(&self) -> Oid {
+        match self {
+            Notification::V1 {
+                enterprise,
+                generic_trap,
+                specific_trap,
+                ..
+            } => translate::v1_trap_oid(enterprise, *generic_trap, *specific_trap),
+            Notification::V2 { trap_oid, .. } => trap_oid.clone(),
+        }
+    }
+
+    pub fn bindings(&self) -> &[(Oid, Value)] {
+        match self {
+            Notification::V1 { bindings, .. } | Notification::V2 { bindings, .. } => bindings,
+        }
+    }
+}
+
+/// A binding of a notification as its listener's [`Mib`] names and formats it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NamedBinding {
+    pub oid: Oid,
+    /// E.g. `IF-MIB::ifIndex.3`, if the MIB knows the object.
+    pub name: Option<String>,
+    pub value: Value,
+    /// The value as [`Formatter`] renders it, e.g. `up(1)`.
+    pub display: String,
+}
+
+/// A notification received by a [`TrapListener`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrapEvent {
+    pub source: SocketAddr,
+    pub version: Version,
+    /// The community, or for SNMPv3 the user name.
+    pub community: Vec<u8>,
+    /// For SNMPv3, the authoritative engine: the sender's for traps, the listener's for
+    /// informs.
+    pub engine_id: Option<EngineId>,
+    /// Whether this was an InformRequest, which the listener has acknowledged.
+    pub inform: bool,
+    pub notification: Notification,
+    pub received: SystemTime,
+    /// The datagram as it was received.
+    pub raw: Vec<u8>,
+    /// The MIB name of the [trap OID](Notification::trap_oid), e.g. `IF-MIB::linkDown`, if
+    /// the listener has a MIB that knows it.
+    pub trap_name: Option<String>,
+    /// The notification's bindings, named and formatted with the listener's MIB if it has
+    /// one.
+    pub bindings: Vec<NamedBinding>,
+    /// Whatever the listener's [hook](TrapListener::hook) added.
+    pub annotations: BTreeMap<String, String>,
+    /// How many copies of this notification the listener [dropped](TrapListener::dedup)
+    /// since it last returned one.
+    pub suppressed: u64,
+}
+
+impl TrapEvent {
+    fn new(
+        source: SocketAddr,
+        version: Version,
+        community: Vec<u8>,
+        engine_id: Option<EngineId>,
+        inform: bool,
+        notification: Notification,
+    ) -> Self {
+        TrapEvent {
+            source,
+            version,
+            community,
+            engine_id,
+            inform,
+            notification,
+            received: SystemTime::now(),
+            raw: Vec::new(),
+            trap_name: None,
+            bindings: Vec::new(),
+            annotations: BTreeMap::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Fills in the names of the trap and its bindings from `mib`.
+    fn resolve(&mut self, mib: Option<&Mib>) {
+        self.trap_name = mib.and_then(|mib| mib.name_of(&self.notification.trap_oid()));
+        self.bindings = self
+            .notification
+            .bindings()
+            .iter()
+            .map(|(oid, value)| NamedBinding {
+                oid: oid.clone(),
+                name: mib.and_then(|mib| mib.name_of(oid)),
+                value: value.clone(),
+                display: match mib {
+                    Some(mib) => Formatter::new(mib).format(oid, value),
+                    None => value.to_string(),
+                },
+            })
+            .collect();
+    }
+}
+
+/// A snapshot of a listener's counters of the datagrams it received.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrapStats {
+    /// Notifications returned by the listener.
+    pub accepted: u64,
+    /// Notifications dropped for a community that is not allowed from their source, or
+    /// that failed SNMPv3 authentication: an unknown user or engine, a lower security
+    /// level than the user's, a wrong digest or a stale clock, which replays have.
+    pub unauthenticated: u64,
+    /// Datagrams dropped for being malformed or not notifications at all.
+    pub invalid: u64,
+    /// Notifications dropped as copies of one returned shortly before.
+    pub duplicates: u64,
+    /// Datagrams dropped for exceeding their source's rate limit.
+    pub rate_limited: u64,
+    /// Events a [sink](TrapListener::sink) failed to take.
+    pub sink_errors: u64,
+}
+
+#[derive(Debug)]
+struct Seen {
+    bindings: Vec<(Oid, Value)>,
+    delivered: Instant,
+    suppressed: u64,
+}
+
+/// The notifications delivered recently, by source address and trap OID, to drop the
+/// copies of.
+#[derive(Debug)]
+struct Dedup {
+    window: Duration,
+    seen: HashMap<(IpAddr, Oid), Vec<Seen>>,
+    pruned: Instant,
+}
+
+impl Dedup {
+    fn new(window: Duration) -> Self {
+        Dedup {
+            window,
+            seen: HashMap::new(),
+            pruned: Instant::now(),
+        }
+    }
+
+    /// Whether `event` is to be delivered, counting the copies dropped before it into it.
+    fn admit(&mut self, event: &mut TrapEvent, now: Instant) -> bool {
+        self.prune(now);
+
+        let bindings = event.notification.bindings();
+        let seen = self
+            .seen
+            .entry((event.source.ip(), event.notification.trap_oid()))
+            .or_default();
+        match seen.iter_mut().find(|seen| seen.bindings == bindings) {
+            Some(seen) if now.duration_since(seen.delivered) < self.window => {
+                seen.suppressed += 1;
+                false
+            }
+            Some(seen) => {
+                event.suppressed = std::mem::take(&mut seen.suppressed);
+                seen.delivered = now;
+                true
+            }
+            None => {
+                seen.push(Seen {
+                    bindings: bindings.to_vec(),
+                    delivered: now,
+                    suppressed: 0,
+                });
+                true
+            }
+        }
+    }
+
+    /// Forgets, at most once a window, the notifications whose window passed without
+    /// copies; those with copies are kept another window for the next one to report them,
+    /// then forgotten with their count.
+    fn prune(&mut self, now: Instant) {
+        if now.duration_since(self.pruned) < self.window {
+            return;
+        }
+        self.pruned = now;
+
+        let window = self.window;
+        self.seen.retain(|_, seen| {
+            seen.retain(|seen| {
+                let kept = match seen.suppressed {
+                    0 => window,
+                    _ => window * 2,
+                };
+                let keep = now.duration_since(seen.delivered) < kept;
+                if !keep && seen.suppressed > 0 {
+                    trace::event!(debug, suppressed = seen.suppressed, "forgetting duplicates");
+                }
+                keep
+            });
+            !seen.is_empty()
+        });
+    }
+}
+
+/// A token bucket per source address.
+#[derive(Debug)]
+struct SourceLimit {
+    per_second: f64,
+    burst: f64,
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+    pruned: Instant,
+}
+
+impl SourceLimit {
+    fn new(per_second: u32, burst: u32) -> Self {
+        SourceLimit {
+            per_second: f64::from(per_second.max(1)),
+            burst: f64::from(burst.max(1)),
+            buckets: HashMap::new(),
+            pruned: Instant::now(),
+        }
+    }
+
+    fn admit(&mut self, source: IpAddr, now: Instant) -> bool {
+        let refill = |tokens: f64, updated: Instant, per_second: f64| {
+            tokens + now.duration_since(updated).as_secs_f64() * per_second
+        };
+
+        // Sources whose bucket has filled up again are as good as new.
+        if now.duration_since(self.pruned) >= Duration::from_secs(1) {
+            self.pruned = now;
+            let (per_second, burst) = (self.per_second, self.burst);
+            self.buckets
+                .retain(|_, (tokens, updated)| refill(*tokens, *updated, per_second) < burst);
+        }
+
+        let (tokens, updated) = self.buckets.entry(source).or_insert((self.burst, now));
+        *tokens = refill(*tokens, *updated, self.per_second).min(self.burst);
+        *updated = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Splits off the sysUpTime.0 and snmpTrapOID.0 bindings every v2 notification starts with
+/// (RFC 3416 4.2.6).
+fn notification(pdu: v2::Pdu) -> SnmpResult<Notification> {
+    let mut bindings = pdu
+        .variable_bindings
+        .into_iter()
+        .map(|var| (Oid::from_asn(&var.name), Value::from(var.value)));
+
+    let uptime = match bindings.next() {
+        Some((name, Value::TimeTicks(uptime))) if name.as_slice() == SYS_UP_TIME => uptime,
+        _ => {
+            return Err(SnmpError::InvalidMessage(
+                "notification without sysUpTime.0",
+            ))
+        }
+    };
+    let trap_oid = match bindings.next() {
+        Some((name, Value::Oid(trap_oid))) if name.as_slice() == SNMP_TRAP_OID => trap_oid,
+        _ => {
+            return Err(SnmpError::InvalidMessage(
+                "notification without snmpTrapOID.0",
+            ))
+        }
+    };
+
+    Ok(Notification::V2 {
+        uptime,
+        trap_oid: trap_oid.into(),
+        bindings: bindings.collect(),
+    })
+}
+
+fn communities<C: AsRef<[u8]>>(communities: impl IntoIterator<Item = C>) -> Vec<Vec<u8>> {
+    communities
+        .into_iter()
+        .map(|community| community.as_ref().to_vec())
+        .collect()
+}
+
+type Hook = dyn FnMut(&mut TrapEvent) + Send;
+
+/// An ICMP error for an earlier reply, which some platforms report on the next receive.
+pub(crate) fn is_icmp_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+    )
+}
+
+/// What a listener accepts and what it adds to the events, shared by [`TrapListener`] and
+/// its tokio counterpart.
+#[derive(Default)]
+pub(crate) struct Receiver {
+    communities: Option<Vec<Vec<u8>>>,
+    source_communities: HashMap<IpAddr, Vec<Vec<u8>>>,
+    users: Option<UsmUserTable>,
+    engine: Option<Engine>,
+    clocks: EngineClocks,
+    mib: Option<Arc<Mib>>,
+    hook: Option<Box<Hook>>,
+    dedup: Option<Dedup>,
+    limit: Option<SourceLimit>,
+    sinks: Vec<Box<dyn TrapSink>>,
+    stats: TrapStats,
+}
+
+impl Receiver {
+    pub(crate) fn communities<C: AsRef<[u8]>>(&mut self, list: impl IntoIterator<Item = C>) {
+        self.communities = Some(communities(list));
+    }
+
+    pub(crate) fn source_communities<C: AsRef<[u8]>>(
+        &mut self,
+        source: IpAddr,
+        list: impl IntoIterator<Item = C>,
+    ) {
+        self.source_communities.insert(source, communities(list));
+    }
+
+    pub(crate) fn users(&mut self, users: &UsmUserTable) {
+        self.users = Some(users.clone());
+    }
+
+    pub(crate) fn local_engine(&mut self, engine_id: EngineId, boots: u32) {
+        self.engine = Some(Engine::new(engine_id, boots));
+    }
+
+    pub(crate) fn mib(&mut self, mib: Arc<Mib>) {
+        self.mib = Some(mib);
+    }
+
+    pub(crate) fn hook(&mut self, hook: Box<Hook>) {
+        self.hook = Some(hook);
+    }
+
+    pub(crate) fn dedup(&mut self, window: Duration) {
+        self.dedup = Some(Dedup::new(window));
+    }
+
+    pub(crate) fn source_rate_limit(&mut self, per_second: u32, burst: u32) {
+        self.limit = Some(SourceLimit::new(per_second, burst));
+    }
+
+    pub(crate) fn sink(&mut self, sink: Box<dyn TrapSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub(crate) fn stats(&self) -> TrapStats {
+        self.stats
+    }
+
+    /// Without any list, every community is; otherwise those listed for the source, or
+    /// for any source if none are.
+    fn allows(&self, source: IpAddr, community: &[u8]) -> bool {
+        let allowed = match self.source_communities.get(&source) {
+            Some(allowed) => allowed,
+            None if self.source_communities.is_empty() && self.communities.is_none() => {
+                return true;
+            }
+            None => match &self.communities {
+                Some(allowed) => allowed,
+                None => return false,
+            },
+        };
+
+        allowed.iter().any(|allowed| allowed == community)
+    }
+
+    /// Decodes a datagram from `source` into the notification it carries, if it is one
+    /// the listener accepts, and returns what to answer it with: the Response that
+    /// acknowledges an inform, echoing its request-id and bindings (RFC 3416 4.2.7), or
+    /// a report about an SNMPv3 message. Dropped datagrams are counted.
+    pub(crate) fn receive(
+        &mut self,
+        source: SocketAddr,
+        datagram: &[u8],
+    ) -> (Option<TrapEvent>, Option<Vec<u8>>) {
+        let now = Instant::now();
+        // Before decoding, so that a flood costs as little as possible.
+        if let Some(limit) = self.limit.as_mut() {
+            if !limit.admit(source.ip(), now) {
+                trace::event!(debug, %source, "dropping datagram over the rate limit");
+                self.stats.rate_limited += 1;
+                return (None, None);
+            }
+        }
+
+        match self.decode(source, datagram) {
+            Ok((mut event, reply)) => {
+                if let Some(dedup) = self.dedup.as_mut() {
+                    if event.as_mut().is_some_and(|event| !dedup.admit(event, now)) {
+                        self.stats.duplicates += 1;
+                        // A duplicate inform is still acknowledged, or it would be resent.
+                        return (None, reply);
+                    }
+                }
+
+                if let Some(event) = event.as_mut() {
+                    self.stats.accepted += 1;
+
+                    event.raw = datagram.to_vec();
+                    event.resolve(self.mib.as_deref());
+                    if let Some(hook) = self.hook.as_mut() {
+                        hook(event);
+                    }
+
+                    for sink in &mut self.sinks {
+                        if let Err(_err) = sink.deliver(event) {
+                            trace::event!(debug, %source, error = %_err, "sink failed");
+                            self.stats.sink_errors += 1;
+                        }
+                    }
+                }
+
+                (event, reply)
+            }
+            Err(Rejected { error, report }) => {
+                trace::event!(debug, %source, %error, "dropping datagram");
+
+                match error {
+                    SnmpError::AuthenticationError
+                    | SnmpError::UnknownUserName
+                    | SnmpError::UnknownEngineId
+                    | SnmpError::UnsupportedSecLevel
+                    | SnmpError::WrongDigest
+                    | SnmpError::DecryptionError
+                    | SnmpError::NotInTimeWindow => self.stats.unauthenticated += 1,
+                    _ => self.stats.invalid += 1,
+                }
+
+                (None, report)
+            }
+        }
+    }
+
+    fn decode(
+        &mut self,
+        source: SocketAddr,
+        datagram: &[u8],
+    ) -> Result<(Option<TrapEvent>, Option<Vec<u8>>), Rejected> {
+        match ber::message_version(datagram) {
+            Some(0) => {
+                let message: v1::Message<v1::Pdus> = decode(datagram)?;
+                let v1::Pdus::Trap(trap) = message.data else {
+                    return Err(SnmpError::UnexpectedPdu.into());
+                };
+                if !self.allows(source.ip(), &message.community) {
+                    return Err(SnmpError::AuthenticationError.into());
+                }
+
+                let event = TrapEvent::new(
+                    source,
+                    Version::V1,
+                    message.community.to_vec(),
+                    None,
+                    false,
+                    crate::v1::from_trap(trap)?,
+                );
+
+                Ok((Some(event), None))
+            }
+            Some(1) => {
+                let message: v2c::Message<v2::Pdus> = decode(datagram)?;
+                if !self.allows(source.ip(), &message.community) {
+                    return Err(SnmpError::AuthenticationError.into());
+                }
+
+                let (pdu, ack) = match message.data {
+                    v2::Pdus::InformRequest(v2::InformRequest(pdu)) => {
+                        let response = v2c::Message {
+                            version: message.version.clone(),
+                            community: message.community.clone(),
+                            data: v2::Pdus::Response(v2::Response(pdu.clone())),
+                        };
+
+                        (pdu, Some(encode(&response)?))
+                    }
+                    v2::Pdus::Trap(v2::Trap(pdu)) => (pdu, None),
+                    _ => return Err(SnmpError::UnexpectedPdu.into()),
+                };
+
+                let event = TrapEvent::new(
+                    source,
+                    Version::V2c,
+                    message.community.to_vec(),
+                    None,
+                    ack.is_some(),
+                    notification(pdu)?,
+                );
+
+                Ok((Some(event), ack))
+            }
+            Some(3) => {
+                let users = self.users.as_ref().ok_or(SnmpError::Unsupported(
+                    "SNMPv3 notifications require a user table",
+                ))?;
+
+                let (user, engine_id, data, ack) =
+                    match usm::receive(datagram, users, self.engine.as_ref(), &mut self.clocks)? {
+                        Received::Discovery(report) => return Ok((None, Some(report))),
+                        Received::Notification {
+                            user,
+                            engine_id,
+                            data,
+                            ack,
+                        } => (user, engine_id, data, ack),
+                    };
+                let pdu = match data {
+                    v2::Pdus::Trap(v2::Trap(pdu))
+                    | v2::Pdus::InformRequest(v2::InformRequest(pdu)) => pdu,
+                    _ => return Err(SnmpError::UnexpectedPdu.into()),
+                };
+
+                let event = TrapEvent::new(
+                    source,
+                    Version::V3,
+                    user,
+                    Some(engine_id),
+                    ack.is_some(),
+                    notification(pdu)?,
+                );
+
+                Ok((Some(event), ack))
+            }
+            _ => Err(SnmpError::InvalidMessage("unknown message version").into()),
+        }
+    }
+}
+
+/// A blocking receiver for notifications, usually bound to port 162. Datagrams that are not
+/// notifications are dropped; informs are acknowledged before they are returned.
+///
+/// Any community is accepted until some are allowed with [`TrapListener::communities`] or
+/// [`TrapListener::source_communities`]. SNMPv3 notifications are dropped unless their user
+/// is in the table given to [`TrapListener::users`] and they are as secure as it; to
+/// receive informs, the listener also needs an engine ID of its own.
+pub struct TrapListener {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    receiver: Receiver,
+}
+
+impl TrapListener {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        Ok(TrapListener {
+            socket: UdpSocket::bind(addr)?,
+            buffer: vec![0; UDP_MAX_MESSAGE_SIZE],
+            receiver: Receiver::default(),
+        })
+    }
+
+    /// Accepts SNMPv1 and v2c notifications from any source with one of `communities`,
+    /// unless the source has [its own](TrapListener::source_communities).
+    pub fn communities<C: AsRef<[u8]>>(mut self, communities: impl IntoIterator<Item = C>) -> Self {
+        self.receiver.communities(communities);
+        self
+    }
+
+    /// Accepts SNMPv1 and v2c notifications from `source` only with one of `communities`.
+    pub fn source_communities<C: AsRef<[u8]>>(
+        mut self,
+        source: IpAddr,
+        communities: impl IntoIterator<Item = C>,
+    ) -> Self {
+        self.receiver.source_communities(source, communities);
+        self
+    }
+
+    /// Authenticates and decrypts SNMPv3 notifications for the users in `users`, caching
+    /// their keys there.
+    pub fn users(mut self, users: &UsmUserTable) -> Self {
+        self.receiver.users(users);
+        self
+    }
+
+    /// Receives SNMPv3 informs as the engine `engine_id`, restarted `boots` times, which
+    /// senders discover and localize their keys to.
+    pub fn local_engine(mut self, engine_id: EngineId, boots: u32) -> Self {
+        self.receiver.local_engine(engine_id, boots);
+        self
+    }
+
+    /// Names trap OIDs and bindings with `mib` in the events returned.
+    pub fn mib(mut self, mib: impl Into<Arc<Mib>>) -> Self {
+        self.receiver.mib(mib.into());
+        self
+    }
+
+    /// Calls `hook` with every event before it is returned, e.g. to add
+    /// [annotations](TrapEvent::annotations) such as the site of the source.
+    pub fn hook(mut self, hook: impl FnMut(&mut TrapEvent) + Send + 'static) -> Self {
+        self.receiver.hook(Box::new(hook));
+        self
+    }
+
+    /// Returns only the first of identical notifications, with the same source address,
+    /// trap OID and bindings, within `window` of it; the first one after that tells how
+    /// many were dropped in [`TrapEvent::suppressed`].
+    pub fn dedup(mut self, window: Duration) -> Self {
+        self.receiver.dedup(window);
+        self
+    }
+
+    /// Drops datagrams from a source address beyond `per_second` a second on average,
+    /// allowing bursts of `burst`.
+    pub fn source_rate_limit(mut self, per_second: u32, burst: u32) -> Self {
+        self.receiver.source_rate_limit(per_second, burst);
+        self
+    }
+
+    /// Hands every event to `sink` too, after the [hook](TrapListener::hook).
+    pub fn sink(mut self, sink: impl TrapSink + 'static) -> Self {
+        self.receiver.sink(Box::new(sink));
+        self
+    }
+
+    pub fn stats(&self) -> TrapStats {
+        self.receiver.stats()
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// With a timeout, [`TrapListener::recv`] fails with `WouldBlock` or `TimedOut` when no
+    /// notification arrives in time.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.socket.set_read_timeout(timeout)
+    }
+
+    /// Waits for the next notification. Failing to answer one, whose source may be
+    /// anything the sender claims, does not fail receiving it.
+    pub fn recv(&mut self) -> io::Result<TrapEvent> {
+        loop {
+            let (len, source) = match self.socket.recv_from(&mut self.buffer) {
+                Ok(received) => received,
+                Err(err) if is_icmp_error(&err) => continue,
+                Err(err) => return Err(err),
+            };
+
+            let (event, reply) = self.receiver.receive(source, &self.buffer[..len]);
+            if let Some(reply) = reply {
+                if let Err(_err) = self.socket.send_to(&reply, source) {
+                    trace::event!(debug, %source, error = %_err, "failed to send reply");
+                }
+            }
+            if let Some(event) = event {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Receives notifications for the sinks until the socket fails, e.g. when its read
+    /// timeout passes.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.recv()?;
+        }
+    }
+}
+
+impl Iterator for TrapListener {
+    type Item = io::Result<TrapEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.recv())
+    }
+}
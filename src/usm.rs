@@ -0,0 +1,1121 @@
+//! User-based Security Model for SNMPv3 (RFC 3414).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use rasn::types::OctetString;
+use rasn_snmp::{v2, v3};
+
+use crate::ber::header;
+use crate::crypto;
+use crate::options::Context;
+use crate::pdu::{self, decode, encode, encode_into};
+use crate::trace;
+use crate::transport::UDP_MAX_MESSAGE_SIZE;
+use crate::{Decoding, EngineId, Oid, SnmpError, SnmpResult, Value, Version};
+
+pub(crate) const FLAG_AUTH: u8 = 0x01;
+pub(crate) const FLAG_PRIV: u8 = 0x02;
+pub(crate) const FLAG_REPORTABLE: u8 = 0x04;
+
+pub(crate) const SECURITY_MODEL_USM: u32 = 3;
+
+pub const USM_STATS_UNSUPPORTED_SEC_LEVELS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 1, 0];
+pub const USM_STATS_NOT_IN_TIME_WINDOWS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 2, 0];
+pub const USM_STATS_UNKNOWN_USER_NAMES: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 3, 0];
+pub const USM_STATS_UNKNOWN_ENGINE_IDS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 4, 0];
+pub const USM_STATS_WRONG_DIGESTS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 5, 0];
+pub const USM_STATS_DECRYPTION_ERRORS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 6, 0];
+
+/// HMAC-MD5-96 and HMAC-SHA-96 (RFC 3414), or the HMAC-SHA-2 protocols of RFC 7860.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthProtocol {
+    Md5,
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+/// CBC-DES (RFC 3414), CFB128-AES-128 (RFC 3826), or AES-192/256 with the localized key
+/// extended as in draft-blumenthal-aes-usm-04 or, for the `Cisco` variants, as in
+/// draft-reeder-snmpv3-usm-3desede. Which extension an agent expects is vendor-specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivProtocol {
+    Des,
+    Aes128,
+    Aes192,
+    Aes256,
+    Aes192Cisco,
+    Aes256Cisco,
+}
+
+/// The DES IV: the pre-IV in the second half of the key, XORed with the salt.
+fn des_iv(key: &[u8], salt: &[u8]) -> [u8; 8] {
+    let mut iv = [0; 8];
+    for ((iv, pre), salt) in iv.iter_mut().zip(&key[8..16]).zip(salt) {
+        *iv = pre ^ salt;
+    }
+    iv
+}
+
+/// The AES IV: the engine boots and time, then the salt.
+fn aes_iv(boots: u32, time: u32, salt: &[u8]) -> [u8; 16] {
+    let mut iv = [0; 16];
+    iv[..4].copy_from_slice(&boots.to_be_bytes());
+    iv[4..8].copy_from_slice(&time.to_be_bytes());
+    iv[8..].copy_from_slice(salt);
+    iv
+}
+
+impl AuthProtocol {
+    /// Turns a passphrase into a (non-localized) key, RFC 3414 A.2.
+    pub fn password_to_key(self, password: &[u8]) -> Vec<u8> {
+        if password.is_empty() {
+            return Vec::new();
+        }
+
+        crypto::provider().password_to_key(self, password)
+    }
+
+    /// Localizes a key to the given authoritative engine, RFC 3414 2.6.
+    pub fn localize_key(self, key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+        crypto::provider().digest(self, &[key, engine_id, key])
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        crypto::provider().digest(self, &[data])
+    }
+
+    /// Length of msgAuthenticationParameters: the truncated HMAC.
+    fn mac_len(self) -> usize {
+        match self {
+            AuthProtocol::Md5 | AuthProtocol::Sha1 => 12,
+            AuthProtocol::Sha224 => 16,
+            AuthProtocol::Sha256 => 24,
+            AuthProtocol::Sha384 => 32,
+            AuthProtocol::Sha512 => 48,
+        }
+    }
+
+    fn sign(self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        let mut mac = crypto::provider().hmac(self, key, message);
+        mac.truncate(self.mac_len());
+        mac
+    }
+}
+
+impl PrivProtocol {
+    /// Key bytes the cipher consumes; DES takes its pre-IV from the second half.
+    fn key_len(self) -> usize {
+        match self {
+            PrivProtocol::Des | PrivProtocol::Aes128 => 16,
+            PrivProtocol::Aes192 | PrivProtocol::Aes192Cisco => 24,
+            PrivProtocol::Aes256 | PrivProtocol::Aes256Cisco => 32,
+        }
+    }
+
+    /// Extends a localized key that is shorter than the cipher needs.
+    pub fn extend_key(self, auth: AuthProtocol, key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+        let mut extended = key.to_vec();
+        let mut last = key.to_vec();
+
+        while extended.len() < self.key_len() {
+            match self {
+                PrivProtocol::Aes192Cisco | PrivProtocol::Aes256Cisco => {
+                    last = auth.localize_key(&auth.password_to_key(&last), engine_id);
+                    extended.extend_from_slice(&last);
+                }
+                _ => {
+                    let digest = auth.digest(&extended);
+                    extended.extend_from_slice(&digest);
+                }
+            }
+        }
+
+        extended.truncate(self.key_len().max(key.len()));
+        extended
+    }
+
+    fn encrypt(
+        self,
+        key: &[u8],
+        boots: u32,
+        time: u32,
+        salt: u64,
+        data: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        match self {
+            PrivProtocol::Des => {
+                let salt = [&boots.to_be_bytes()[..], &(salt as u32).to_be_bytes()[..]].concat();
+                let iv = des_iv(key, &salt);
+
+                let mut data = data.to_vec();
+                data.resize(data.len().div_ceil(8) * 8, 0);
+                crypto::provider().des_cbc_encrypt(&key[..8], &iv, &mut data);
+
+                (data, salt)
+            }
+            aes => {
+                let salt = salt.to_be_bytes().to_vec();
+                let iv = aes_iv(boots, time, &salt);
+
+                let mut data = data.to_vec();
+                crypto::provider().aes_cfb_encrypt(&key[..aes.key_len()], &iv, &mut data);
+
+                (data, salt)
+            }
+        }
+    }
+
+    fn decrypt(
+        self,
+        key: &[u8],
+        boots: u32,
+        time: u32,
+        salt: &[u8],
+        data: &[u8],
+    ) -> SnmpResult<Vec<u8>> {
+        if salt.len() != 8 {
+            return Err(SnmpError::AuthenticationError);
+        }
+
+        match self {
+            PrivProtocol::Des => {
+                let mut data = data.to_vec();
+                crypto::provider().des_cbc_decrypt(&key[..8], &des_iv(key, salt), &mut data)?;
+
+                Ok(data)
+            }
+            aes => {
+                let iv = aes_iv(boots, time, salt);
+
+                let mut data = data.to_vec();
+                crypto::provider().aes_cfb_decrypt(&key[..aes.key_len()], &iv, &mut data);
+
+                Ok(data)
+            }
+        }
+    }
+}
+
+/// An SNMPv3 user and its credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsmUser {
+    name: Vec<u8>,
+    auth: Option<(AuthProtocol, Vec<u8>)>,
+    privacy: Option<(PrivProtocol, Vec<u8>)>,
+}
+
+impl UsmUser {
+    /// A user without authentication or privacy (noAuthNoPriv).
+    pub fn new(name: &[u8]) -> Self {
+        UsmUser {
+            name: name.to_vec(),
+            auth: None,
+            privacy: None,
+        }
+    }
+
+    pub fn auth(mut self, protocol: AuthProtocol, passphrase: &[u8]) -> Self {
+        self.auth = Some((protocol, passphrase.to_vec()));
+        self
+    }
+
+    /// Privacy is only valid together with [`UsmUser::auth`].
+    pub fn privacy(mut self, protocol: PrivProtocol, passphrase: &[u8]) -> Self {
+        self.privacy = Some((protocol, passphrase.to_vec()));
+        self
+    }
+
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    pub(crate) fn is_valid(&self) -> bool {
+        self.privacy.is_none() || self.auth.is_some()
+    }
+
+    /// The keys of the passphrases, before localization (RFC 3414 A.2), which is the
+    /// expensive part.
+    fn master_keys(&self) -> Option<Keys> {
+        let (auth, passphrase) = self.auth.as_ref()?;
+
+        Some(Keys {
+            auth: auth.password_to_key(passphrase),
+            privacy: self
+                .privacy
+                .as_ref()
+                .map(|(_, passphrase)| auth.password_to_key(passphrase)),
+        })
+    }
+
+    /// The keys of `master` localized to `engine_id`.
+    fn localize(&self, master: &Keys, engine_id: &[u8]) -> Keys {
+        let auth = self
+            .auth
+            .as_ref()
+            .map_or(AuthProtocol::Md5, |(auth, _)| *auth);
+
+        Keys {
+            auth: auth.localize_key(&master.auth, engine_id),
+            privacy: self.privacy.as_ref().zip(master.privacy.as_ref()).map(
+                |((privacy, _), key)| {
+                    privacy.extend_key(auth, &auth.localize_key(key, engine_id), engine_id)
+                },
+            ),
+        }
+    }
+}
+
+struct UserEntry {
+    user: UsmUser,
+    master: Option<Keys>,
+    localized: HashMap<Vec<u8>, Keys>,
+}
+
+/// SNMPv3 users by name, for sessions built with
+/// [`SessionBuilder::v3_user`](crate::SessionBuilder::v3_user) and for authenticating
+/// notifications. Keys are derived from the passphrases when first needed and kept, on
+/// their own and localized to every engine ID they were used with, so sessions to many
+/// agents with the same users hash each passphrase once.
+///
+/// Clones share the table; users added or replaced apply to sessions built afterwards.
+#[derive(Clone, Default)]
+pub struct UsmUserTable(Arc<Mutex<BTreeMap<Vec<u8>, UserEntry>>>);
+
+impl fmt::Debug for UsmUserTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Not the users, whose passphrases and keys would show.
+        f.debug_struct("UsmUserTable")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl UsmUserTable {
+    pub fn new() -> Self {
+        UsmUserTable::default()
+    }
+
+    fn users(&self) -> MutexGuard<'_, BTreeMap<Vec<u8>, UserEntry>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Adds `user`, replacing any user of the same name along with its keys.
+    pub fn add(&self, user: UsmUser) {
+        let entry = UserEntry {
+            user: user.clone(),
+            master: None,
+            localized: HashMap::new(),
+        };
+        self.users().insert(user.name, entry);
+    }
+
+    pub fn remove(&self, name: &[u8]) -> Option<UsmUser> {
+        self.users().remove(name).map(|entry| entry.user)
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<UsmUser> {
+        self.users().get(name).map(|entry| entry.user.clone())
+    }
+
+    /// The names of the users, in order.
+    pub fn names(&self) -> Vec<Vec<u8>> {
+        self.users().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.users().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users().is_empty()
+    }
+
+    /// The keys of `user` localized to `engine_id`, from the cache when `user` is still
+    /// the one in the table under its name; `None` without authentication.
+    pub(crate) fn keys(&self, user: &UsmUser, engine_id: &[u8]) -> Option<Keys> {
+        user.auth.as_ref()?;
+
+        let mut users = self.users();
+        let Some(entry) = users
+            .get_mut(&user.name)
+            .filter(|entry| entry.user == *user)
+        else {
+            drop(users);
+            return user
+                .master_keys()
+                .map(|master| user.localize(&master, engine_id));
+        };
+
+        if let Some(keys) = entry.localized.get(engine_id) {
+            return Some(keys.clone());
+        }
+        if entry.master.is_none() {
+            entry.master = user.master_keys();
+        }
+        let keys = user.localize(entry.master.as_ref()?, engine_id);
+        entry.localized.insert(engine_id.to_vec(), keys.clone());
+        Some(keys)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Engine {
+    id: EngineId,
+    boots: u32,
+    time: u32,
+    synced: Instant,
+}
+
+impl Engine {
+    /// An engine of this process, restarted `boots` times, whose clock starts now.
+    pub(crate) fn new(id: EngineId, boots: u32) -> Self {
+        Engine {
+            id,
+            boots,
+            time: 0,
+            synced: Instant::now(),
+        }
+    }
+
+    fn time(&self) -> u32 {
+        self.time
+            .saturating_add(self.synced.elapsed().as_secs() as u32)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Keys {
+    auth: Vec<u8>,
+    privacy: Option<Vec<u8>>,
+}
+
+/// Per-session USM state: the discovered engine and the keys localized to it.
+pub(crate) struct Usm {
+    user: UsmUser,
+    /// Where the user's keys are cached, if it came from a table.
+    users: Option<UsmUserTable>,
+    engine: Option<Engine>,
+    keys: Option<Keys>,
+    /// This session's own engine and the keys localized to it, which it is authoritative
+    /// for in the traps it sends.
+    local: Option<Box<(Engine, Option<Keys>)>>,
+    time_synced: bool,
+    /// Set when the last report invalidated the engine state and the request may be resent.
+    resync: bool,
+    msg_id: i32,
+    salt: u64,
+    max_size: usize,
+}
+
+/// Finds the offset of the msgAuthenticationParameters contents in an encoded message,
+/// which must be `mac_len` bytes long.
+fn auth_params_offset(message: &[u8], mac_len: usize) -> Option<usize> {
+    fn skip(bytes: &[u8], pos: usize) -> Option<usize> {
+        let (head, len) = header(bytes.get(pos..)?)?;
+        (pos + head).checked_add(len)
+    }
+
+    fn enter(bytes: &[u8], pos: usize) -> Option<usize> {
+        let (head, _) = header(bytes.get(pos..)?)?;
+        Some(pos + head)
+    }
+
+    // Message -> version, header data, security parameters (OCTET STRING -> SEQUENCE).
+    let mut pos = enter(message, 0)?;
+    pos = skip(message, pos)?;
+    pos = skip(message, pos)?;
+    pos = enter(message, pos)?;
+    pos = enter(message, pos)?;
+
+    // Engine ID, boots, time and user name precede the authentication parameters.
+    for _ in 0..4 {
+        pos = skip(message, pos)?;
+    }
+
+    let (head, len) = header(message.get(pos..)?)?;
+    (len == mac_len && message.len() >= pos + head + len).then_some(pos + head)
+}
+
+impl Usm {
+    /// `max_size` is advertised as msgMaxSize, the largest response this side accepts.
+    pub(crate) fn new(user: UsmUser, max_size: usize) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_nanos() as u64)
+            .unwrap_or_default();
+
+        Usm {
+            user,
+            users: None,
+            engine: None,
+            keys: None,
+            local: None,
+            time_synced: false,
+            resync: false,
+            msg_id: 0,
+            salt: seed,
+            max_size,
+        }
+    }
+
+    /// Sends traps from the engine `id`, restarted `boots` times.
+    pub(crate) fn with_local_engine(mut self, id: EngineId, boots: u32) -> Self {
+        let keys = self.localized_keys(id.as_bytes());
+        self.local = Some(Box::new((Engine::new(id, boots), keys)));
+        self
+    }
+
+    /// Sets the clock of the local engine to `time` seconds since it booted.
+    #[cfg(test)]
+    pub(crate) fn set_local_time(&mut self, time: u32) {
+        if let Some(local) = self.local.as_deref_mut() {
+            local.0.time = time;
+            local.0.synced = Instant::now();
+        }
+    }
+
+    /// The user's keys localized to `engine_id`; `None` without authentication.
+    fn localized_keys(&self, engine_id: &[u8]) -> Option<Keys> {
+        match &self.users {
+            Some(users) => users.keys(&self.user, engine_id),
+            None => self
+                .user
+                .master_keys()
+                .map(|master| self.user.localize(&master, engine_id)),
+        }
+    }
+
+    /// Takes the user's keys from `users`, caching them there.
+    pub(crate) fn with_users(mut self, users: UsmUserTable) -> Self {
+        self.users = Some(users);
+        self
+    }
+
+    pub(crate) fn engine_id(&self) -> Option<&EngineId> {
+        self.engine.as_ref().map(|engine| &engine.id)
+    }
+
+    /// True when authentication is in use but the engine clock has not been learned yet.
+    fn needs_time_sync(&self) -> bool {
+        self.user.auth.is_some()
+            && !self.time_synced
+            && self
+                .engine
+                .as_ref()
+                .is_some_and(|engine| engine.boots == 0 && engine.time == 0)
+    }
+
+    /// The next message that has to be exchanged before requests can be sent, if any.
+    pub(crate) fn handshake(&mut self) -> SnmpResult<Option<Vec<u8>>> {
+        if self.engine.is_none() {
+            self.discovery_message().map(Some)
+        } else if self.needs_time_sync() {
+            self.encode(Self::empty_get()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Processes the agent's answer to a [`Usm::handshake`] message.
+    pub(crate) fn complete_handshake(&mut self, response: &[u8]) -> SnmpResult<()> {
+        if self.engine.is_none() {
+            self.discover(response)
+        } else {
+            self.time_synced = true;
+            self.decode(response)?;
+            // The expected notInTimeWindows report is the handshake, not a failed request.
+            self.resync = false;
+            Ok(())
+        }
+    }
+
+    /// Whether the report that failed the last request refreshed the engine state, so that
+    /// resending the request can succeed. Cleared on every call.
+    pub(crate) fn resync(&mut self) -> bool {
+        std::mem::take(&mut self.resync)
+    }
+
+    /// Reacts to the USM error counters of RFC 3414 3.2: an unknown engine ID means the agent
+    /// was replaced and has to be discovered again, while an authenticated notInTimeWindows
+    /// report carries the clock of a rebooted agent, which [`Usm::sync`] has just adopted.
+    fn handle_report(&mut self, report: &Oid, authenticated: bool) {
+        trace::event!(debug, %report, authenticated, "received USM report");
+
+        self.resync = match report.as_slice() {
+            USM_STATS_UNKNOWN_ENGINE_IDS => {
+                self.engine = None;
+                self.keys = None;
+                self.time_synced = false;
+                true
+            }
+            USM_STATS_NOT_IN_TIME_WINDOWS => authenticated,
+            _ => false,
+        };
+    }
+
+    fn next_msg_id(&mut self) -> i32 {
+        self.msg_id = self.msg_id.wrapping_add(1) & i32::MAX;
+        self.msg_id
+    }
+
+    fn message(
+        &self,
+        msg_id: i32,
+        flags: u8,
+        params: &v3::USMSecurityParameters,
+        scoped_data: v3::ScopedPduData,
+    ) -> SnmpResult<v3::Message> {
+        Ok(v3::Message {
+            version: 3.into(),
+            global_data: v3::HeaderData {
+                message_id: msg_id.into(),
+                max_size: self.max_size.into(),
+                flags: vec![flags].into(),
+                security_model: SECURITY_MODEL_USM.into(),
+            },
+            security_parameters: encode(params)?.into(),
+            scoped_data,
+        })
+    }
+
+    fn empty_get() -> v2::Pdus {
+        v2::Pdus::GetRequest(v2::GetRequest(v2::Pdu {
+            request_id: 0,
+            error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+            error_index: 0,
+            variable_bindings: Vec::new(),
+        }))
+    }
+
+    /// A noAuthNoPriv probe that makes the agent report its engine ID.
+    fn discovery_message(&mut self) -> SnmpResult<Vec<u8>> {
+        let msg_id = self.next_msg_id();
+
+        let params = v3::USMSecurityParameters {
+            authoritative_engine_id: OctetString::new(),
+            authoritative_engine_boots: 0.into(),
+            authoritative_engine_time: 0.into(),
+            user_name: OctetString::new(),
+            authentication_parameters: OctetString::new(),
+            privacy_parameters: OctetString::new(),
+        };
+
+        let scoped = v3::ScopedPduData::CleartextPdu(v3::ScopedPdu {
+            engine_id: OctetString::new(),
+            name: OctetString::new(),
+            data: Self::empty_get(),
+        });
+
+        encode(&self.message(msg_id, FLAG_REPORTABLE, &params, scoped)?)
+    }
+
+    fn decode_params(message: &v3::Message) -> SnmpResult<v3::USMSecurityParameters> {
+        if message.global_data.security_model != SECURITY_MODEL_USM.into() {
+            return Err(SnmpError::InvalidMessage("unsupported security model"));
+        }
+
+        decode(&message.security_parameters)
+    }
+
+    /// Learns the engine ID (and clock, if present) from a discovery report.
+    pub(crate) fn discover(&mut self, response: &[u8]) -> SnmpResult<()> {
+        let message: v3::Message = decode(response)?;
+        let params = Self::decode_params(&message)?;
+
+        if params.authoritative_engine_id.is_empty() {
+            return Err(SnmpError::InvalidMessage(
+                "discovery report without engine ID",
+            ));
+        }
+
+        let engine_id = params.authoritative_engine_id.to_vec();
+
+        self.keys = self.localized_keys(&engine_id);
+
+        self.engine = Some(Engine {
+            id: EngineId::from_wire(engine_id),
+            boots: u32::try_from(&params.authoritative_engine_boots).unwrap_or_default(),
+            time: u32::try_from(&params.authoritative_engine_time).unwrap_or_default(),
+            synced: Instant::now(),
+        });
+
+        Ok(())
+    }
+
+    /// Updates the engine clock from an authenticated message that is ahead of it, of later
+    /// boots or of the same boots and a later time (RFC 3414 3.2.7 b); older messages, e.g.
+    /// delayed or replayed ones, leave it as it is.
+    fn sync(&mut self, params: &v3::USMSecurityParameters) {
+        let Some(engine) = self.engine.as_mut() else {
+            return;
+        };
+        let boots = u32::try_from(&params.authoritative_engine_boots).unwrap_or_default();
+        let time = u32::try_from(&params.authoritative_engine_time).unwrap_or_default();
+
+        if boots > engine.boots || (boots == engine.boots && time > engine.time) {
+            engine.boots = boots;
+            engine.time = time;
+            engine.synced = Instant::now();
+        }
+    }
+
+    pub(crate) fn encode(&mut self, data: v2::Pdus) -> SnmpResult<Vec<u8>> {
+        self.encode_into(data, &Context::default(), Vec::new())
+    }
+
+    /// Encodes a request into `buffer`, reusing its allocation for the message. Traps are
+    /// sent from the local engine, everything else to the agent's (RFC 3414 1.5.1).
+    pub(crate) fn encode_into(
+        &mut self,
+        data: v2::Pdus,
+        context: &Context,
+        buffer: Vec<u8>,
+    ) -> SnmpResult<Vec<u8>> {
+        let msg_id = self.next_msg_id();
+        self.seal(msg_id, data, context, buffer)
+    }
+
+    /// Encodes an answer to the message `msg_id`, e.g. an inform's Response.
+    fn reply(&mut self, msg_id: i32, data: v2::Pdus) -> SnmpResult<Vec<u8>> {
+        self.seal(msg_id, data, &Context::default(), Vec::new())
+    }
+
+    fn seal(
+        &mut self,
+        msg_id: i32,
+        data: v2::Pdus,
+        context: &Context,
+        buffer: Vec<u8>,
+    ) -> SnmpResult<Vec<u8>> {
+        self.salt = self.salt.wrapping_add(1);
+
+        let trap = matches!(data, v2::Pdus::Trap(_));
+        let (engine, keys) = if trap {
+            let (engine, keys) = self.local.as_deref().ok_or(SnmpError::Unsupported(
+                "SNMPv3 traps require a local engine ID",
+            ))?;
+            (engine, keys.as_ref())
+        } else {
+            let engine = self
+                .engine
+                .as_ref()
+                .ok_or(SnmpError::InvalidMessage("engine ID not discovered"))?;
+            (engine, self.keys.as_ref())
+        };
+        let (boots, time) = (engine.boots, engine.time());
+
+        let scoped = v3::ScopedPdu {
+            engine_id: context
+                .engine_id
+                .as_ref()
+                .unwrap_or(&engine.id)
+                .as_bytes()
+                .to_vec()
+                .into(),
+            name: context.name.clone().unwrap_or_default().into(),
+            data,
+        };
+
+        // Nothing answers a trap, a response or a report, not even with a report.
+        let unconfirmed = matches!(
+            scoped.data,
+            v2::Pdus::Trap(_) | v2::Pdus::Response(_) | v2::Pdus::Report(_)
+        );
+        let mut flags = if unconfirmed { 0 } else { FLAG_REPORTABLE };
+        let mut privacy_parameters = OctetString::new();
+
+        let scoped = match (&self.user.privacy, keys) {
+            (
+                Some((privacy, _)),
+                Some(Keys {
+                    privacy: Some(key), ..
+                }),
+            ) => {
+                flags |= FLAG_PRIV;
+
+                let plain = encode(&scoped)?;
+                let (encrypted, salt) = privacy.encrypt(key, boots, time, self.salt, &plain);
+                privacy_parameters = salt.into();
+
+                v3::ScopedPduData::EncryptedPdu(encrypted.into())
+            }
+            _ => v3::ScopedPduData::CleartextPdu(scoped),
+        };
+
+        if keys.is_some() {
+            flags |= FLAG_AUTH;
+        }
+
+        let params = v3::USMSecurityParameters {
+            authoritative_engine_id: engine.id.as_bytes().to_vec().into(),
+            authoritative_engine_boots: boots.into(),
+            authoritative_engine_time: time.into(),
+            user_name: self.user.name.clone().into(),
+            authentication_parameters: match &self.user.auth {
+                Some((auth, _)) if flags & FLAG_AUTH != 0 => vec![0; auth.mac_len()].into(),
+                _ => OctetString::new(),
+            },
+            privacy_parameters,
+        };
+
+        let mut encoded = encode_into(&self.message(msg_id, flags, &params, scoped)?, buffer)?;
+
+        if let (Some((auth, _)), Some(keys)) = (&self.user.auth, keys) {
+            let offset = auth_params_offset(&encoded, auth.mac_len()).ok_or(
+                SnmpError::InvalidMessage("authentication parameters not found"),
+            )?;
+            let digest = auth.sign(&keys.auth, &encoded);
+            encoded[offset..offset + digest.len()].copy_from_slice(&digest);
+        }
+
+        Ok(encoded)
+    }
+
+    pub(crate) fn decode(&mut self, response: &[u8]) -> SnmpResult<v2::Pdus> {
+        self.decode_with(response, Decoding::default())
+    }
+
+    /// [`Usm::decode`], holding the scoped PDU to the encoding rules as `decoding` says.
+    pub(crate) fn decode_with(
+        &mut self,
+        response: &[u8],
+        decoding: Decoding,
+    ) -> SnmpResult<v2::Pdus> {
+        let message: v3::Message = decode(response)?;
+        let params = Self::decode_params(&message)?;
+        let flags = message
+            .global_data
+            .flags
+            .first()
+            .copied()
+            .unwrap_or_default();
+
+        if flags & FLAG_AUTH != 0 {
+            let (Some((auth, _)), Some(keys)) = (&self.user.auth, &self.keys) else {
+                return Err(SnmpError::AuthenticationError);
+            };
+
+            let offset = auth_params_offset(response, auth.mac_len())
+                .ok_or(SnmpError::AuthenticationError)?;
+            let mut zeroed = response.to_vec();
+            zeroed[offset..offset + auth.mac_len()].fill(0);
+
+            let digest = auth.sign(&keys.auth, &zeroed);
+
+            if digest != params.authentication_parameters.as_ref() {
+                return Err(SnmpError::AuthenticationError);
+            }
+
+            self.sync(&params);
+        }
+
+        let scoped = match message.scoped_data {
+            v3::ScopedPduData::CleartextPdu(scoped) => {
+                decoding.check_encoding(response, Version::V3)?;
+                scoped
+            }
+            v3::ScopedPduData::EncryptedPdu(encrypted) => {
+                let (
+                    Some((privacy, _)),
+                    Some(Keys {
+                        privacy: Some(key), ..
+                    }),
+                ) = (&self.user.privacy, &self.keys)
+                else {
+                    return Err(SnmpError::AuthenticationError);
+                };
+
+                if flags & FLAG_PRIV == 0 {
+                    return Err(SnmpError::InvalidMessage("encrypted PDU without privFlag"));
+                }
+
+                let plain = privacy.decrypt(
+                    key,
+                    u32::try_from(&params.authoritative_engine_boots).unwrap_or_default(),
+                    u32::try_from(&params.authoritative_engine_time).unwrap_or_default(),
+                    &params.privacy_parameters,
+                    &encrypted,
+                )?;
+
+                decoding.check_encoding(&plain, Version::V3)?;
+                rasn::ber::decode(&plain).map_err(|_| SnmpError::AuthenticationError)?
+            }
+        };
+
+        if self.user.auth.is_some() && flags & FLAG_AUTH == 0 {
+            // Only reports may come back unauthenticated for an authenticated request.
+            if !matches!(scoped.data, v2::Pdus::Report(_)) {
+                return Err(SnmpError::AuthenticationError);
+            }
+        }
+
+        if let Some(report) = report_oid(&scoped.data) {
+            self.handle_report(&report, flags & FLAG_AUTH != 0);
+        }
+
+        Ok(scoped.data)
+    }
+}
+
+/// How far the clock in a message to this engine may be off, RFC 3414 3.2.7.
+const TIME_WINDOW: u32 = 150;
+
+/// An SNMPv3 message a notification listener accepted.
+pub(crate) enum Received {
+    /// A probe for the listener's engine ID, answered with this report.
+    Discovery(Vec<u8>),
+    Notification {
+        user: Vec<u8>,
+        /// The authoritative engine: the sender's for traps, the listener's for informs.
+        engine_id: EngineId,
+        data: v2::Pdus,
+        /// The Response that acknowledges an inform.
+        ack: Option<Vec<u8>>,
+    },
+}
+
+/// A message a notification listener dropped, and the report that tells the sender why,
+/// if it asked for one.
+pub(crate) struct Rejected {
+    pub(crate) error: SnmpError,
+    pub(crate) report: Option<Vec<u8>>,
+}
+
+impl From<SnmpError> for Rejected {
+    fn from(error: SnmpError) -> Self {
+        Rejected {
+            error,
+            report: None,
+        }
+    }
+}
+
+/// A report of the counter `oid` from `local` about the message `msg_id`, authenticated
+/// when `keys` are given.
+fn report(
+    local: &Engine,
+    user: UsmUser,
+    keys: Option<Keys>,
+    msg_id: i32,
+    oid: &[u32],
+) -> SnmpResult<Vec<u8>> {
+    let mut usm = Usm::new(user, UDP_MAX_MESSAGE_SIZE);
+    usm.engine = Some(local.clone());
+    usm.keys = keys;
+
+    let bindings = [(Oid::from(oid), Value::Counter32(1))];
+    usm.reply(
+        msg_id,
+        v2::Pdus::Report(v2::Report(v2::Pdu {
+            request_id: 0,
+            error_status: v2::Pdu::ERROR_STATUS_NO_ERROR,
+            error_index: 0,
+            variable_bindings: pdu::var_binds(&bindings),
+        })),
+    )
+}
+
+/// Verifies and decrypts a notification from one of `users` (RFC 3414 3.2). Traps are
+/// authenticated with keys localized to the sender's engine, and have to be within the
+/// time window of the latest of its clocks in `clocks`; informs are sent to `local`, whose
+/// ID their senders discover first, and have to be within its time window.
+pub(crate) fn receive(
+    datagram: &[u8],
+    users: &UsmUserTable,
+    local: Option<&Engine>,
+    clocks: &mut EngineClocks,
+) -> Result<Received, Rejected> {
+    let message: v3::Message = decode(datagram)?;
+    let params = Usm::decode_params(&message)?;
+    let msg_id = i32::try_from(&message.global_data.message_id).unwrap_or_default();
+    let flags = message
+        .global_data
+        .flags
+        .first()
+        .copied()
+        .unwrap_or_default();
+    let engine_id = params.authoritative_engine_id.to_vec();
+    let name = params.user_name.to_vec();
+
+    let to_local = local.filter(|local| local.id.as_bytes() == engine_id.as_slice());
+    // Only messages to the local engine ask for reports; traps never do.
+    let reportable = local.filter(|_| flags & FLAG_REPORTABLE != 0);
+    let reject = |error: SnmpError, oid: &[u32]| Rejected {
+        report: reportable
+            .and_then(|local| report(local, UsmUser::new(&name), None, msg_id, oid).ok()),
+        error,
+    };
+
+    if engine_id.is_empty() {
+        return match reportable {
+            Some(local) => Ok(Received::Discovery(report(
+                local,
+                UsmUser::new(&name),
+                None,
+                msg_id,
+                USM_STATS_UNKNOWN_ENGINE_IDS,
+            )?)),
+            None => Err(SnmpError::UnknownEngineId.into()),
+        };
+    }
+
+    let Some(user) = users.get(&name) else {
+        return Err(reject(
+            SnmpError::UnknownUserName,
+            USM_STATS_UNKNOWN_USER_NAMES,
+        ));
+    };
+
+    // The message has to be as secure as the user is, and not claim more.
+    let authenticated = flags & FLAG_AUTH != 0;
+    let private = flags & FLAG_PRIV != 0;
+    if authenticated != user.auth.is_some() || private != user.privacy.is_some() {
+        return Err(reject(
+            SnmpError::UnsupportedSecLevel,
+            USM_STATS_UNSUPPORTED_SEC_LEVELS,
+        ));
+    }
+
+    let keys = users.keys(&user, &engine_id);
+    let mut usm = Usm::new(user, UDP_MAX_MESSAGE_SIZE);
+    usm.engine = Some(Engine {
+        id: EngineId::from_wire(engine_id),
+        boots: u32::try_from(&params.authoritative_engine_boots).unwrap_or_default(),
+        time: u32::try_from(&params.authoritative_engine_time).unwrap_or_default(),
+        synced: Instant::now(),
+    });
+    usm.keys = keys;
+
+    let data = match usm.decode(datagram) {
+        Ok(data) => data,
+        Err(SnmpError::AuthenticationError) => {
+            return Err(reject(SnmpError::WrongDigest, USM_STATS_WRONG_DIGESTS));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let engine_id = usm
+        .engine
+        .take()
+        .map(|engine| engine.id)
+        .unwrap_or_else(EngineId::local);
+
+    let ack = match (&data, to_local) {
+        (v2::Pdus::Trap(_), _) => {
+            if authenticated && !clocks.admit(&engine_id, &params) {
+                return Err(SnmpError::NotInTimeWindow.into());
+            }
+
+            None
+        }
+        (v2::Pdus::InformRequest(v2::InformRequest(pdu)), Some(local)) => {
+            if authenticated && !in_time_window(local, &params) {
+                return Err(Rejected {
+                    error: SnmpError::NotInTimeWindow,
+                    report: report(
+                        local,
+                        usm.user.clone(),
+                        usm.keys.clone(),
+                        msg_id,
+                        USM_STATS_NOT_IN_TIME_WINDOWS,
+                    )
+                    .ok(),
+                });
+            }
+
+            usm.engine = Some(local.clone());
+            Some(usm.reply(msg_id, v2::Pdus::Response(v2::Response(pdu.clone())))?)
+        }
+        (v2::Pdus::InformRequest(_), None) => {
+            return Err(reject(
+                SnmpError::UnknownEngineId,
+                USM_STATS_UNKNOWN_ENGINE_IDS,
+            ));
+        }
+        _ => return Err(SnmpError::UnexpectedPdu.into()),
+    };
+
+    Ok(Received::Notification {
+        user: name,
+        engine_id,
+        data,
+        ack,
+    })
+}
+
+/// Whether a message claims the boots of `local` and a time no further off than
+/// [`TIME_WINDOW`].
+fn in_time_window(local: &Engine, params: &v3::USMSecurityParameters) -> bool {
+    let boots = u32::try_from(&params.authoritative_engine_boots).unwrap_or_default();
+    let time = u32::try_from(&params.authoritative_engine_time).unwrap_or_default();
+
+    boots == local.boots && time.abs_diff(local.time()) <= TIME_WINDOW
+}
+
+/// The latest boots and time authenticated traps came with from each authoritative
+/// engine, so that replays of older ones are told apart (RFC 3414 3.2.7b).
+#[derive(Debug, Default)]
+pub(crate) struct EngineClocks {
+    engines: HashMap<EngineId, Engine>,
+}
+
+impl EngineClocks {
+    /// Whether a verified message from `engine_id` is recent enough: of the latest boots
+    /// and no more than [`TIME_WINDOW`] behind its time. Recent ones advance the clock.
+    fn admit(&mut self, engine_id: &EngineId, params: &v3::USMSecurityParameters) -> bool {
+        let boots = u32::try_from(&params.authoritative_engine_boots).unwrap_or_default();
+        let time = u32::try_from(&params.authoritative_engine_time).unwrap_or_default();
+        let engine = Engine {
+            id: engine_id.clone(),
+            boots,
+            time,
+            synced: Instant::now(),
+        };
+
+        let Some(latest) = self.engines.get_mut(engine_id) else {
+            self.engines.insert(engine_id.clone(), engine);
+            return true;
+        };
+        let latest_time = latest.time();
+        if boots < latest.boots
+            || (boots == latest.boots && time.saturating_add(TIME_WINDOW) < latest_time)
+        {
+            return false;
+        }
+
+        if boots > latest.boots || time > latest_time {
+            *latest = engine;
+        }
+        true
+    }
+}
+
+/// The error a report for the counter `oid` stands for.
+pub(crate) fn report_error(oid: Oid) -> SnmpError {
+    match oid.as_slice() {
+        USM_STATS_UNSUPPORTED_SEC_LEVELS => SnmpError::UnsupportedSecLevel,
+        USM_STATS_NOT_IN_TIME_WINDOWS => SnmpError::NotInTimeWindow,
+        USM_STATS_UNKNOWN_USER_NAMES => SnmpError::UnknownUserName,
+        USM_STATS_UNKNOWN_ENGINE_IDS => SnmpError::UnknownEngineId,
+        USM_STATS_WRONG_DIGESTS => SnmpError::WrongDigest,
+        USM_STATS_DECRYPTION_ERRORS => SnmpError::DecryptionError,
+        _ => SnmpError::Report(oid),
+    }
+}
+
+/// The OID of the counter a report PDU refers to, if any.
+pub(crate) fn report_oid(pdus: &v2::Pdus) -> Option<Oid> {
+    match pdus {
+        v2::Pdus::Report(report) => report
+            .0
+            .variable_bindings
+            .first()
+            .map(|var| Oid::from_asn(&var.name)),
+        _ => None,
+    }
+}
@@ -0,0 +1,187 @@
+//! Translation between the v2 PDUs used throughout the crate and SNMPv1 (RFC 1157) messages.
+
+use std::net::Ipv4Addr;
+
+use rasn::types::Integer;
+use rasn_smi::{v1 as smi1, v2 as smi2};
+use rasn_snmp::{v1, v2};
+
+use crate::{pdu, Notification, Oid, SnmpError, SnmpResult, Value};
+
+fn to_syntax(value: v2::VarBindValue) -> SnmpResult<smi1::ObjectSyntax> {
+    let syntax = match value {
+        v2::VarBindValue::Value(smi2::ObjectSyntax::Simple(simple)) => match simple {
+            smi2::SimpleSyntax::Integer(int) => smi1::SimpleSyntax::Number(int).into(),
+            smi2::SimpleSyntax::String(str) => smi1::SimpleSyntax::String(str).into(),
+            smi2::SimpleSyntax::ObjectId(oid) => smi1::SimpleSyntax::Object(oid).into(),
+        },
+        v2::VarBindValue::Value(smi2::ObjectSyntax::ApplicationWide(wide)) => match wide {
+            smi2::ApplicationSyntax::Address(ip) => {
+                smi1::ApplicationSyntax::Address(smi1::NetworkAddress::Internet(ip)).into()
+            }
+            smi2::ApplicationSyntax::Counter(counter) => {
+                smi1::ApplicationSyntax::Counter(counter).into()
+            }
+            smi2::ApplicationSyntax::Ticks(ticks) => smi1::ApplicationSyntax::Ticks(ticks).into(),
+            smi2::ApplicationSyntax::Unsigned(gauge) => {
+                smi1::ApplicationSyntax::Gauge(gauge).into()
+            }
+            smi2::ApplicationSyntax::Arbitrary(opaque) => {
+                smi1::ApplicationSyntax::Arbitrary(opaque).into()
+            }
+            smi2::ApplicationSyntax::BigCounter(_) => {
+                return Err(SnmpError::Unsupported(
+                    "Counter64 requires SNMPv2c or later",
+                ))
+            }
+        },
+        v2::VarBindValue::Unspecified => smi1::SimpleSyntax::Empty.into(),
+        _ => {
+            return Err(SnmpError::Unsupported(
+                "exception values require SNMPv2c or later",
+            ))
+        }
+    };
+
+    Ok(syntax)
+}
+
+pub(crate) fn from_syntax(syntax: smi1::ObjectSyntax) -> v2::VarBindValue {
+    let syntax: smi2::ObjectSyntax = match syntax {
+        smi1::ObjectSyntax::Simple(simple) => match simple {
+            smi1::SimpleSyntax::Number(int) => smi2::SimpleSyntax::Integer(int).into(),
+            smi1::SimpleSyntax::String(str) => smi2::SimpleSyntax::String(str).into(),
+            smi1::SimpleSyntax::Object(oid) => smi2::SimpleSyntax::ObjectId(oid).into(),
+            smi1::SimpleSyntax::Empty => return v2::VarBindValue::Unspecified,
+        },
+        smi1::ObjectSyntax::ApplicationWide(wide) => match wide {
+            smi1::ApplicationSyntax::Address(smi1::NetworkAddress::Internet(ip)) => {
+                smi2::ApplicationSyntax::Address(ip).into()
+            }
+            smi1::ApplicationSyntax::Counter(counter) => {
+                smi2::ApplicationSyntax::Counter(counter).into()
+            }
+            smi1::ApplicationSyntax::Gauge(gauge) => {
+                smi2::ApplicationSyntax::Unsigned(gauge).into()
+            }
+            smi1::ApplicationSyntax::Ticks(ticks) => smi2::ApplicationSyntax::Ticks(ticks).into(),
+            smi1::ApplicationSyntax::Arbitrary(opaque) => {
+                smi2::ApplicationSyntax::Arbitrary(opaque).into()
+            }
+        },
+    };
+
+    v2::VarBindValue::Value(syntax)
+}
+
+fn to_var_binds(vars: Vec<v2::VarBind>) -> SnmpResult<Vec<v1::VarBind>> {
+    vars.into_iter()
+        .map(|var| {
+            Ok(v1::VarBind {
+                name: var.name,
+                value: to_syntax(var.value)?,
+            })
+        })
+        .collect()
+}
+
+fn to_pdu(pdu: v2::Pdu) -> SnmpResult<v1::Pdu> {
+    Ok(v1::Pdu {
+        request_id: pdu.request_id.into(),
+        error_status: pdu.error_status.into(),
+        error_index: pdu.error_index.into(),
+        variable_bindings: to_var_binds(pdu.variable_bindings)?,
+    })
+}
+
+fn int<T: TryFrom<Integer>>(int: Integer) -> SnmpResult<T> {
+    T::try_from(int).map_err(|_| SnmpError::InvalidMessage("PDU integer out of range"))
+}
+
+fn from_pdu(pdu: v1::Pdu) -> SnmpResult<v2::Pdu> {
+    Ok(v2::Pdu {
+        request_id: int(pdu.request_id)?,
+        error_status: int(pdu.error_status)?,
+        error_index: int(pdu.error_index)?,
+        variable_bindings: pdu
+            .variable_bindings
+            .into_iter()
+            .map(|var| v2::VarBind {
+                name: var.name,
+                value: from_syntax(var.value),
+            })
+            .collect(),
+    })
+}
+
+/// Maps a request onto its v1 equivalent; GETBULK, informs and v2 traps have none.
+pub(crate) fn to_pdus(data: v2::Pdus) -> SnmpResult<v1::Pdus> {
+    match data {
+        v2::Pdus::GetRequest(req) => Ok(v1::Pdus::GetRequest(v1::GetRequest(to_pdu(req.0)?))),
+        v2::Pdus::GetNextRequest(req) => {
+            Ok(v1::Pdus::GetNextRequest(v1::GetNextRequest(to_pdu(req.0)?)))
+        }
+        v2::Pdus::SetRequest(req) => Ok(v1::Pdus::SetRequest(v1::SetRequest(to_pdu(req.0)?))),
+        v2::Pdus::Response(res) => Ok(v1::Pdus::GetResponse(v1::GetResponse(to_pdu(res.0)?))),
+        v2::Pdus::GetBulkRequest(_) => {
+            Err(SnmpError::Unsupported("GETBULK requires SNMPv2c or later"))
+        }
+        v2::Pdus::InformRequest(_) => Err(SnmpError::Unsupported(
+            "InformRequest requires SNMPv2c or later",
+        )),
+        v2::Pdus::Trap(_) => Err(SnmpError::Unsupported(
+            "SNMPv2-Trap requires SNMPv2c or later",
+        )),
+        v2::Pdus::Report(_) => Err(SnmpError::Unsupported("Report requires SNMPv3")),
+    }
+}
+
+/// Maps a received v1 PDU onto the v2 PDUs the rest of the crate works with.
+pub(crate) fn from_pdus(data: v1::Pdus) -> SnmpResult<v2::Pdus> {
+    match data {
+        v1::Pdus::GetRequest(req) => Ok(v2::Pdus::GetRequest(v2::GetRequest(from_pdu(req.0)?))),
+        v1::Pdus::GetNextRequest(req) => Ok(v2::Pdus::GetNextRequest(v2::GetNextRequest(
+            from_pdu(req.0)?,
+        ))),
+        v1::Pdus::SetRequest(req) => Ok(v2::Pdus::SetRequest(v2::SetRequest(from_pdu(req.0)?))),
+        v1::Pdus::GetResponse(res) => Ok(v2::Pdus::Response(v2::Response(from_pdu(res.0)?))),
+        v1::Pdus::Trap(_) => Err(SnmpError::UnexpectedPdu),
+    }
+}
+
+/// Builds a v1 Trap-PDU; `bindings` are converted the same way as request bindings.
+pub(crate) fn trap(
+    enterprise: &Oid,
+    agent_addr: Ipv4Addr,
+    generic_trap: u32,
+    specific_trap: u32,
+    uptime: u32,
+    bindings: &[(Oid, Value)],
+) -> SnmpResult<v1::Trap> {
+    Ok(v1::Trap {
+        enterprise: enterprise.to_asn(),
+        agent_addr: smi1::NetworkAddress::Internet(smi1::IpAddress(agent_addr.octets().into())),
+        generic_trap: generic_trap.into(),
+        specific_trap: specific_trap.into(),
+        time_stamp: smi1::TimeTicks(uptime),
+        variable_bindings: to_var_binds(pdu::var_binds(bindings))?,
+    })
+}
+
+/// Maps a received Trap-PDU onto a [`Notification`], with its bindings as v2 values.
+pub(crate) fn from_trap(trap: v1::Trap) -> SnmpResult<Notification> {
+    let smi1::NetworkAddress::Internet(smi1::IpAddress(addr)) = trap.agent_addr;
+
+    Ok(Notification::V1 {
+        enterprise: Oid::from_asn(&trap.enterprise),
+        agent_addr: Ipv4Addr::from(*addr),
+        generic_trap: int(trap.generic_trap)?,
+        specific_trap: int(trap.specific_trap)?,
+        timestamp: trap.time_stamp.0,
+        bindings: trap
+            .variable_bindings
+            .into_iter()
+            .map(|var| (Oid::from_asn(&var.name), from_syntax(var.value).into()))
+            .collect(),
+    })
+}
@@ -0,0 +1,551 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, SystemTime};
+
+use rasn::types::{Integer, ObjectIdentifier, OctetString};
+use rasn_smi::v1::{Counter, Gauge, IpAddress, Opaque, TimeTicks};
+use rasn_smi::v2::{ApplicationSyntax, Counter64, ObjectSyntax, SimpleSyntax};
+use rasn_snmp::v2;
+
+use crate::{visit, Bits, DateAndTime, MacAddr, Oid, RowStatus, SnmpError, SnmpResult};
+
+/// A varbind value, independent of the underlying ASN.1 library.
+///
+/// The floats and 64-bit integers net-snmp wraps in an Opaque are decoded into the
+/// variants after [`Value::Opaque`], and wrapped the same way when sent.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Oid(Vec<u32>),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Counter64(u64),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Opaque(Vec<u8>),
+    Float(f32),
+    Double(f64),
+    Integer64(i64),
+    Unsigned64(u64),
+    Null,
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl Value {
+    /// Returns `true` for the v2 exception values, which carry no data.
+    pub fn is_exception(&self) -> bool {
+        matches!(
+            self,
+            Value::NoSuchObject | Value::NoSuchInstance | Value::EndOfMibView
+        )
+    }
+
+    /// The raw contents of an OCTET STRING or Opaque.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::OctetString(bytes) | Value::Opaque(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// An OCTET STRING as text, if it reads as text; see the [`Display`](fmt::Display)
+    /// impl, which shows anything else as [`Hex`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::OctetString(bytes) => printable(bytes),
+            _ => None,
+        }
+    }
+
+    /// The SMI name of the value's type, e.g. `Counter32`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Integer(_) => "INTEGER",
+            Value::OctetString(_) => "OCTET STRING",
+            Value::Oid(_) => "OBJECT IDENTIFIER",
+            Value::IpAddress(_) => "IpAddress",
+            Value::Counter32(_) => "Counter32",
+            Value::Counter64(_) => "Counter64",
+            Value::Gauge32(_) => "Gauge32",
+            Value::TimeTicks(_) => "TimeTicks",
+            Value::Opaque(_) => "Opaque",
+            Value::Float(_) => "Float",
+            Value::Double(_) => "Double",
+            Value::Integer64(_) => "Integer64",
+            Value::Unsigned64(_) => "Unsigned64",
+            Value::Null => "NULL",
+            Value::NoSuchObject => "noSuchObject",
+            Value::NoSuchInstance => "noSuchInstance",
+            Value::EndOfMibView => "endOfMibView",
+        }
+    }
+
+    /// Decodes the special types of an Opaque, and keeps anything else as it is.
+    pub(crate) fn from_opaque(bytes: Vec<u8>) -> Value {
+        visit::opaque(&bytes).map_or(Value::Opaque(bytes), Value::from)
+    }
+
+    /// The contents of the Opaque that an Opaque, or one of the special types wrapped in
+    /// one, is sent as.
+    pub(crate) fn opaque_contents(&self) -> Option<Vec<u8>> {
+        let (kind, contents) = match self {
+            Value::Opaque(bytes) => return Some(bytes.clone()),
+            Value::Float(float) => (OPAQUE_FLOAT, float.to_be_bytes().to_vec()),
+            Value::Double(double) => (OPAQUE_DOUBLE, double.to_be_bytes().to_vec()),
+            Value::Integer64(int) => (OPAQUE_INTEGER64, signed_octets(*int)),
+            Value::Unsigned64(int) => (OPAQUE_UNSIGNED64, unsigned_octets(*int)),
+            _ => return None,
+        };
+
+        Some([&[OPAQUE_TAG, kind, contents.len() as u8][..], &contents].concat())
+    }
+
+    fn wrong_type(self, expected: &'static str) -> SnmpError {
+        SnmpError::WrongType {
+            expected,
+            got: self,
+        }
+    }
+}
+
+/// An OCTET STRING as text, with anything that is not UTF-8 replaced and a trailing NUL
+/// dropped.
+impl TryFrom<Value> for String {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::OctetString(bytes) => {
+                let text = bytes.strip_suffix(&[0]).unwrap_or(&bytes);
+                Ok(String::from_utf8_lossy(text).into_owned())
+            }
+            other => Err(other.wrong_type("an OCTET STRING")),
+        }
+    }
+}
+
+/// The contents of an OCTET STRING or Opaque.
+impl TryFrom<Value> for Vec<u8> {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::OctetString(bytes) | Value::Opaque(bytes) => Ok(bytes),
+            other => Err(other.wrong_type("an OCTET STRING")),
+        }
+    }
+}
+
+/// An INTEGER, or an unsigned integer type in range.
+impl TryFrom<Value> for i64 {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Integer(int) | Value::Integer64(int) => Ok(int),
+            Value::Counter32(n) | Value::Gauge32(n) | Value::TimeTicks(n) => Ok(n.into()),
+            Value::Counter64(n) | Value::Unsigned64(n) if i64::try_from(n).is_ok() => Ok(n as i64),
+            other => Err(other.wrong_type("an integer")),
+        }
+    }
+}
+
+/// A Counter32, Gauge32, TimeTicks, or INTEGER in range.
+impl TryFrom<Value> for u32 {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Counter32(n) | Value::Gauge32(n) | Value::TimeTicks(n) => Ok(n),
+            Value::Integer(int) if u32::try_from(int).is_ok() => Ok(int as u32),
+            other => Err(other.wrong_type("a 32-bit unsigned integer")),
+        }
+    }
+}
+
+/// Any unsigned integer type, or a non-negative INTEGER.
+impl TryFrom<Value> for u64 {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Counter64(n) | Value::Unsigned64(n) => Ok(n),
+            Value::Counter32(n) | Value::Gauge32(n) | Value::TimeTicks(n) => Ok(n.into()),
+            Value::Integer(int) | Value::Integer64(int) if int >= 0 => Ok(int as u64),
+            other => Err(other.wrong_type("an unsigned integer")),
+        }
+    }
+}
+
+/// A Float or Double, or an integer of any type.
+impl TryFrom<Value> for f64 {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Float(float) => Ok(float.into()),
+            Value::Double(double) => Ok(double),
+            Value::Integer(int) | Value::Integer64(int) => Ok(int as f64),
+            Value::Counter64(n) | Value::Unsigned64(n) => Ok(n as f64),
+            Value::Counter32(n) | Value::Gauge32(n) | Value::TimeTicks(n) => Ok(n.into()),
+            other => Err(other.wrong_type("a number")),
+        }
+    }
+}
+
+/// An IpAddress, or an OCTET STRING of four or sixteen octets as the InetAddress of the
+/// INET-ADDRESS-MIB holds.
+impl TryFrom<Value> for IpAddr {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::IpAddress(ip) => Ok(ip.into()),
+            Value::OctetString(bytes) => {
+                if let Ok(v4) = <[u8; 4]>::try_from(&bytes[..]) {
+                    return Ok(v4.into());
+                }
+                if let Ok(v6) = <[u8; 16]>::try_from(&bytes[..]) {
+                    return Ok(v6.into());
+                }
+                Err(Value::OctetString(bytes).wrong_type("an IP address"))
+            }
+            other => Err(other.wrong_type("an IP address")),
+        }
+    }
+}
+
+/// An INTEGER from 1 to 6.
+impl TryFrom<Value> for RowStatus {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Integer(status) => {
+                RowStatus::try_from(status).map_err(|_| value.wrong_type("a RowStatus"))
+            }
+            other => Err(other.wrong_type("a RowStatus")),
+        }
+    }
+}
+
+/// An OCTET STRING, of any length.
+impl TryFrom<Value> for Bits {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::OctetString(bytes) => Ok(Bits(bytes)),
+            other => Err(other.wrong_type("BITS")),
+        }
+    }
+}
+
+impl From<Bits> for Value {
+    fn from(bits: Bits) -> Self {
+        Value::OctetString(bits.0)
+    }
+}
+
+/// An OCTET STRING of 8 or 11 octets, with every field in range.
+impl TryFrom<Value> for DateAndTime {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::OctetString(bytes) => match DateAndTime::from_octets(&bytes) {
+                Some(date) => Ok(date),
+                None => Err(Value::OctetString(bytes).wrong_type("a DateAndTime")),
+            },
+            other => Err(other.wrong_type("a DateAndTime")),
+        }
+    }
+}
+
+impl From<DateAndTime> for Value {
+    fn from(date: DateAndTime) -> Self {
+        Value::OctetString(date.to_octets())
+    }
+}
+
+/// An OCTET STRING of six octets.
+impl TryFrom<Value> for MacAddr {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::OctetString(bytes) => match <[u8; 6]>::try_from(&bytes[..]) {
+                Ok(octets) => Ok(MacAddr(octets)),
+                Err(_) => Err(Value::OctetString(bytes).wrong_type("a MAC address")),
+            },
+            other => Err(other.wrong_type("a MAC address")),
+        }
+    }
+}
+
+impl TryFrom<Value> for Ticks {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::TimeTicks(ticks) => Ok(Ticks(ticks)),
+            other => Err(other.wrong_type("TimeTicks")),
+        }
+    }
+}
+
+/// TimeTicks, in hundredths of a second.
+impl TryFrom<Value> for Duration {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        Ticks::try_from(value).map(Ticks::duration)
+    }
+}
+
+impl TryFrom<Value> for Oid {
+    type Error = SnmpError;
+
+    fn try_from(value: Value) -> SnmpResult<Self> {
+        match value {
+            Value::Oid(arcs) => Ok(arcs.into()),
+            other => Err(other.wrong_type("an OBJECT IDENTIFIER")),
+        }
+    }
+}
+
+/// Formats bytes as space-separated uppercase hex pairs, `00 1A 2B 3C 4D 5E`, like
+/// net-snmp's Hex-STRING.
+#[derive(Debug, Clone, Copy)]
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.0.iter();
+
+        if let Some(first) = bytes.next() {
+            write!(f, "{:02X}", first)?;
+        }
+        for byte in bytes {
+            write!(f, " {:02X}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// TimeTicks, in hundredths of a second, displayed as net-snmp shows them:
+/// `(12345678) 1 day, 10:17:36.78`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ticks(pub u32);
+
+impl Ticks {
+    pub fn duration(self) -> Duration {
+        Duration::from_millis(u64::from(self.0) * 10)
+    }
+
+    /// When the agent was last re-initialized, estimated from a sysUpTime of these ticks
+    /// read at `read_at`. TimeTicks wrap after about 497 days, so agents up longer than
+    /// that seem to have booted more recently than they did.
+    pub fn boot_time(self, read_at: SystemTime) -> SystemTime {
+        read_at
+            .checked_sub(self.duration())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+impl From<Ticks> for Duration {
+    fn from(ticks: Ticks) -> Self {
+        ticks.duration()
+    }
+}
+
+impl fmt::Display for Ticks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let centis = self.0 % 100;
+        let secs = self.0 / 100;
+        let (days, hours, minutes, secs) =
+            (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+        match days {
+            0 => write!(f, "({}) ", self.0)?,
+            1 => write!(f, "({}) 1 day, ", self.0)?,
+            days => write!(f, "({}) {} days, ", self.0, days)?,
+        }
+        write!(f, "{}:{:02}:{:02}.{:02}", hours, minutes, secs, centis)
+    }
+}
+
+/// Whether an OCTET STRING reads as text: valid UTF-8 without control characters other
+/// than whitespace. A trailing NUL, which some agents append, is tolerated.
+fn printable(bytes: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(bytes.strip_suffix(&[0]).unwrap_or(bytes)).ok()?;
+
+    (!text.chars().any(|c| c.is_control() && !c.is_whitespace())).then_some(text)
+}
+
+/// The first identifier octet of the types net-snmp wraps in an Opaque, which the octet
+/// after it tells apart.
+pub(crate) const OPAQUE_TAG: u8 = 0x9f;
+pub(crate) const OPAQUE_FLOAT: u8 = 0x78;
+pub(crate) const OPAQUE_DOUBLE: u8 = 0x79;
+pub(crate) const OPAQUE_INTEGER64: u8 = 0x7a;
+pub(crate) const OPAQUE_UNSIGNED64: u8 = 0x7b;
+
+/// The shortest two's complement octets of `int`.
+fn signed_octets(int: i64) -> Vec<u8> {
+    let octets = int.to_be_bytes();
+    let skip = octets
+        .windows(2)
+        .take_while(|pair| {
+            matches!(pair, [0x00, next] if next & 0x80 == 0)
+                || matches!(pair, [0xff, next] if next & 0x80 != 0)
+        })
+        .count();
+
+    octets[skip..].to_vec()
+}
+
+/// The shortest octets of `int` as a non-negative INTEGER, with a leading zero when the
+/// high bit is set.
+fn unsigned_octets(int: u64) -> Vec<u8> {
+    let octets = int.to_be_bytes();
+    let skip = octets
+        .iter()
+        .take(7)
+        .take_while(|octet| **octet == 0)
+        .count();
+
+    let mut contents = octets[skip..].to_vec();
+    if contents[0] & 0x80 != 0 {
+        contents.insert(0, 0);
+    }
+    contents
+}
+
+fn integer_to_i64(int: &Integer) -> i64 {
+    i64::try_from(int).unwrap_or_else(|_| {
+        if int.to_string().starts_with('-') {
+            i64::MIN
+        } else {
+            i64::MAX
+        }
+    })
+}
+
+impl From<ObjectSyntax> for Value {
+    fn from(syntax: ObjectSyntax) -> Self {
+        match syntax {
+            ObjectSyntax::Simple(simple) => match simple {
+                SimpleSyntax::Integer(int) => Value::Integer(integer_to_i64(&int)),
+                SimpleSyntax::String(str) => Value::OctetString(str.to_vec()),
+                SimpleSyntax::ObjectId(oid) => Value::Oid(oid.to_vec()),
+            },
+            ObjectSyntax::ApplicationWide(wide) => match wide {
+                ApplicationSyntax::Address(ip) => {
+                    let octets: [u8; 4] = *ip.0;
+                    Value::IpAddress(octets.into())
+                }
+                ApplicationSyntax::Counter(counter) => Value::Counter32(counter.0),
+                ApplicationSyntax::Ticks(tick) => Value::TimeTicks(tick.0),
+                ApplicationSyntax::BigCounter(counter) => Value::Counter64(counter.0),
+                ApplicationSyntax::Unsigned(gauge) => Value::Gauge32(gauge.0),
+                ApplicationSyntax::Arbitrary(opaque) => {
+                    Value::from_opaque(opaque.as_ref().to_vec())
+                }
+            },
+        }
+    }
+}
+
+impl From<v2::VarBindValue> for Value {
+    fn from(value: v2::VarBindValue) -> Self {
+        match value {
+            v2::VarBindValue::Value(syntax) => syntax.into(),
+            v2::VarBindValue::Unspecified => Value::Null,
+            v2::VarBindValue::NoSuchObject => Value::NoSuchObject,
+            v2::VarBindValue::NoSuchInstance => Value::NoSuchInstance,
+            v2::VarBindValue::EndOfMibView => Value::EndOfMibView,
+        }
+    }
+}
+
+fn to_opaque(bytes: &[u8]) -> Opaque {
+    // `Opaque` keeps its contents private, so re-tag an OCTET STRING and decode it back.
+    let mut encoded = rasn::ber::encode(&OctetString::copy_from_slice(bytes))
+        .expect("an OCTET STRING always encodes");
+    encoded[0] = 0x44;
+    rasn::ber::decode(&encoded).expect("a re-tagged OCTET STRING is a valid Opaque")
+}
+
+impl From<Value> for v2::VarBindValue {
+    fn from(value: Value) -> Self {
+        let syntax: ObjectSyntax = match value {
+            Value::Integer(int) => SimpleSyntax::Integer(int.into()).into(),
+            Value::OctetString(str) => SimpleSyntax::String(str.into()).into(),
+            Value::Oid(oid) => {
+                SimpleSyntax::ObjectId(ObjectIdentifier::new_unchecked(oid.into())).into()
+            }
+            Value::IpAddress(ip) => {
+                ApplicationSyntax::Address(IpAddress(ip.octets().into())).into()
+            }
+            Value::Counter32(counter) => ApplicationSyntax::Counter(Counter(counter)).into(),
+            Value::Counter64(counter) => ApplicationSyntax::BigCounter(Counter64(counter)).into(),
+            Value::Gauge32(gauge) => ApplicationSyntax::Unsigned(Gauge(gauge)).into(),
+            Value::TimeTicks(tick) => ApplicationSyntax::Ticks(TimeTicks(tick)).into(),
+            Value::Opaque(_)
+            | Value::Float(_)
+            | Value::Double(_)
+            | Value::Integer64(_)
+            | Value::Unsigned64(_) => {
+                let contents = value.opaque_contents().unwrap_or_default();
+                ApplicationSyntax::Arbitrary(to_opaque(&contents)).into()
+            }
+            Value::Null => return v2::VarBindValue::Unspecified,
+            Value::NoSuchObject => return v2::VarBindValue::NoSuchObject,
+            Value::NoSuchInstance => return v2::VarBindValue::NoSuchInstance,
+            Value::EndOfMibView => return v2::VarBindValue::EndOfMibView,
+        };
+
+        v2::VarBindValue::Value(syntax)
+    }
+}
+
+fn join<T: ToString>(parts: &[T]) -> String {
+    parts
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(int) => write!(f, "{}", int),
+            Value::OctetString(bytes) => match self.as_str() {
+                Some(text) => f.write_str(text),
+                None => write!(f, "{}", Hex(bytes)),
+            },
+            Value::Oid(oid) => write!(f, "{}", join(oid)),
+            Value::IpAddress(ip) => write!(f, "{}", ip),
+            Value::Counter32(counter) => write!(f, "{}", counter),
+            Value::Counter64(counter) => write!(f, "{}", counter),
+            Value::Gauge32(gauge) => write!(f, "{}", gauge),
+            Value::TimeTicks(tick) => write!(f, "{}", tick),
+            Value::Opaque(opaque) => write!(f, "{}", Hex(opaque)),
+            Value::Float(float) => write!(f, "{}", float),
+            Value::Double(double) => write!(f, "{}", double),
+            Value::Integer64(int) => write!(f, "{}", int),
+            Value::Unsigned64(int) => write!(f, "{}", int),
+            Value::Null => f.write_str(""),
+            Value::NoSuchObject => f.write_str("No Such Object"),
+            Value::NoSuchInstance => f.write_str("No Such Instance"),
+            Value::EndOfMibView => f.write_str("End of MIB View"),
+        }
+    }
+}
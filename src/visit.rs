@@ -0,0 +1,356 @@
+//! Walking a subtree without collecting it: SNMPv2c responses are decoded in the receive
+//! buffer and their varbinds handed to a visitor as borrowed views.
+
+use std::mem;
+use std::net::Ipv4Addr;
+use std::ops::ControlFlow;
+
+use rasn_snmp::v2;
+
+use crate::value::{OPAQUE_DOUBLE, OPAQUE_FLOAT, OPAQUE_INTEGER64, OPAQUE_TAG, OPAQUE_UNSIGNED64};
+use crate::{ber, pdu, Oid, SnmpError, SnmpResult, Value};
+
+/// A [`Value`] borrowing its contents from the message it was decoded from, as handed to
+/// the visitor of [`SyncSession::bulk_walk_visit`](crate::SyncSession::bulk_walk_visit).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRef<'a> {
+    Integer(i64),
+    OctetString(&'a [u8]),
+    Oid(&'a [u32]),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Counter64(u64),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Opaque(&'a [u8]),
+    Float(f32),
+    Double(f64),
+    Integer64(i64),
+    Unsigned64(u64),
+    Null,
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl<'a> From<&'a Value> for ValueRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Integer(int) => ValueRef::Integer(*int),
+            Value::OctetString(bytes) => ValueRef::OctetString(bytes),
+            Value::Oid(arcs) => ValueRef::Oid(arcs),
+            Value::IpAddress(ip) => ValueRef::IpAddress(*ip),
+            Value::Counter32(counter) => ValueRef::Counter32(*counter),
+            Value::Counter64(counter) => ValueRef::Counter64(*counter),
+            Value::Gauge32(gauge) => ValueRef::Gauge32(*gauge),
+            Value::TimeTicks(ticks) => ValueRef::TimeTicks(*ticks),
+            Value::Opaque(bytes) => ValueRef::Opaque(bytes),
+            Value::Float(float) => ValueRef::Float(*float),
+            Value::Double(double) => ValueRef::Double(*double),
+            Value::Integer64(int) => ValueRef::Integer64(*int),
+            Value::Unsigned64(int) => ValueRef::Unsigned64(*int),
+            Value::Null => ValueRef::Null,
+            Value::NoSuchObject => ValueRef::NoSuchObject,
+            Value::NoSuchInstance => ValueRef::NoSuchInstance,
+            Value::EndOfMibView => ValueRef::EndOfMibView,
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Integer(int) => Value::Integer(int),
+            ValueRef::OctetString(bytes) => Value::OctetString(bytes.to_vec()),
+            ValueRef::Oid(arcs) => Value::Oid(arcs.to_vec()),
+            ValueRef::IpAddress(ip) => Value::IpAddress(ip),
+            ValueRef::Counter32(counter) => Value::Counter32(counter),
+            ValueRef::Counter64(counter) => Value::Counter64(counter),
+            ValueRef::Gauge32(gauge) => Value::Gauge32(gauge),
+            ValueRef::TimeTicks(ticks) => Value::TimeTicks(ticks),
+            ValueRef::Opaque(bytes) => Value::Opaque(bytes.to_vec()),
+            ValueRef::Float(float) => Value::Float(float),
+            ValueRef::Double(double) => Value::Double(double),
+            ValueRef::Integer64(int) => Value::Integer64(int),
+            ValueRef::Unsigned64(int) => Value::Unsigned64(int),
+            ValueRef::Null => Value::Null,
+            ValueRef::NoSuchObject => Value::NoSuchObject,
+            ValueRef::NoSuchInstance => Value::NoSuchInstance,
+            ValueRef::EndOfMibView => Value::EndOfMibView,
+        }
+    }
+}
+
+/// A two's complement INTEGER of at most eight octets.
+fn integer(contents: &[u8]) -> Option<i64> {
+    if contents.is_empty() || contents.len() > 8 {
+        return None;
+    }
+    let sign = if contents[0] & 0x80 != 0 { -1 } else { 0 };
+
+    Some(
+        contents
+            .iter()
+            .fold(sign, |value, byte| (value << 8) | *byte as i64),
+    )
+}
+
+/// A non-negative INTEGER of at most 64 bits, such as a Counter64.
+fn unsigned(contents: &[u8]) -> Option<u64> {
+    let digits = match contents {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        [first, ..] if first & 0x80 == 0 => contents,
+        _ => return None,
+    };
+    if digits.len() > 8 {
+        return None;
+    }
+
+    Some(
+        digits
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64),
+    )
+}
+
+fn unsigned32(contents: &[u8]) -> Option<u32> {
+    unsigned(contents)?.try_into().ok()
+}
+
+/// The float or 64-bit integer net-snmp wrapped in an Opaque of `contents`, if it is one
+/// of those.
+pub(crate) fn opaque(contents: &[u8]) -> Option<ValueRef<'static>> {
+    let [OPAQUE_TAG, kind, len, contents @ ..] = contents else {
+        return None;
+    };
+    if usize::from(*len) != contents.len() {
+        return None;
+    }
+
+    Some(match *kind {
+        OPAQUE_FLOAT => ValueRef::Float(f32::from_be_bytes(contents.try_into().ok()?)),
+        OPAQUE_DOUBLE => ValueRef::Double(f64::from_be_bytes(contents.try_into().ok()?)),
+        OPAQUE_INTEGER64 => ValueRef::Integer64(integer(contents)?),
+        OPAQUE_UNSIGNED64 => ValueRef::Unsigned64(unsigned(contents)?),
+        _ => return None,
+    })
+}
+
+/// Decodes the subidentifiers of an OBJECT IDENTIFIER into `arcs`, replacing its contents.
+fn arcs(contents: &[u8], arcs: &mut Vec<u32>) -> Option<()> {
+    arcs.clear();
+    let mut arc: u32 = 0;
+
+    for byte in contents {
+        if arc > u32::MAX >> 7 {
+            return None;
+        }
+        arc = (arc << 7) | (byte & 0x7f) as u32;
+
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                // The first subidentifier packs the first two arcs (X.690 8.19.4).
+                let first = (arc / 40).min(2);
+                arcs.extend([first, arc - first * 40]);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+
+    contents
+        .last()
+        .is_some_and(|byte| byte & 0x80 == 0)
+        .then_some(())
+}
+
+/// A varbind value; OBJECT IDENTIFIERs are decoded into `scratch`.
+fn value<'a>(tag: u8, contents: &'a [u8], scratch: &'a mut Vec<u32>) -> Option<ValueRef<'a>> {
+    Some(match tag {
+        0x02 => ValueRef::Integer(integer(contents)?),
+        0x04 => ValueRef::OctetString(contents),
+        0x05 if contents.is_empty() => ValueRef::Null,
+        0x06 => {
+            arcs(contents, scratch)?;
+            ValueRef::Oid(scratch)
+        }
+        0x40 => ValueRef::IpAddress(<[u8; 4]>::try_from(contents).ok()?.into()),
+        0x41 => ValueRef::Counter32(unsigned32(contents)?),
+        0x42 => ValueRef::Gauge32(unsigned32(contents)?),
+        0x43 => ValueRef::TimeTicks(unsigned32(contents)?),
+        0x44 => opaque(contents).unwrap_or(ValueRef::Opaque(contents)),
+        0x46 => ValueRef::Counter64(unsigned(contents)?),
+        0x80 if contents.is_empty() => ValueRef::NoSuchObject,
+        0x81 if contents.is_empty() => ValueRef::NoSuchInstance,
+        0x82 if contents.is_empty() => ValueRef::EndOfMibView,
+        _ => return None,
+    })
+}
+
+/// The varbind list of `message` if it is a well-framed, error-free SNMPv2c Response to
+/// `request_id` in `community`; anything else is left to the regular decoding.
+fn response<'a>(message: &'a [u8], community: &[u8], request_id: i32) -> Option<&'a [u8]> {
+    let (0x30, message, []) = ber::element(message)? else {
+        return None;
+    };
+    let (0x02, [1], rest) = ber::element(message)? else {
+        return None;
+    };
+    let (0x04, name, rest) = ber::element(rest)? else {
+        return None;
+    };
+    let (0xa2, pdu, []) = ber::element(rest)? else {
+        return None;
+    };
+    let (0x02, id, rest) = ber::element(pdu)? else {
+        return None;
+    };
+    let (0x02, [0], rest) = ber::element(rest)? else {
+        return None;
+    };
+    let (0x02, _, rest) = ber::element(rest)? else {
+        return None;
+    };
+    let (0x30, bindings, []) = ber::element(rest)? else {
+        return None;
+    };
+
+    (name == community && integer(id)? == i64::from(request_id)).then_some(bindings)
+}
+
+/// Splits the first varbind off `bindings`: its name decoded into `name`, its value, and
+/// the varbinds that follow.
+fn binding<'m: 'v, 's: 'v, 'v>(
+    bindings: &'m [u8],
+    name: &mut Vec<u32>,
+    scratch: &'s mut Vec<u32>,
+) -> Option<(ValueRef<'v>, &'m [u8])> {
+    let (0x30, binding, rest) = ber::element(bindings)? else {
+        return None;
+    };
+    let (0x06, encoded, contents) = ber::element(binding)? else {
+        return None;
+    };
+    arcs(encoded, name)?;
+    let (tag, contents, []) = ber::element(contents)? else {
+        return None;
+    };
+
+    Some((value(tag, contents, scratch)?, rest))
+}
+
+/// The state of [`SyncSession::bulk_walk_visit`](crate::SyncSession::bulk_walk_visit),
+/// with the buffers OIDs are decoded into kept from one response to the next.
+pub(crate) struct VisitWalk {
+    start: Oid,
+    current: Option<Oid>,
+    pub(crate) max_repetitions: u32,
+    name: Vec<u32>,
+    previous: Vec<u32>,
+    scratch: Vec<u32>,
+}
+
+impl VisitWalk {
+    pub(crate) fn new(start: Oid, max_repetitions: u32) -> Self {
+        VisitWalk {
+            current: Some(start.clone()),
+            start,
+            max_repetitions,
+            name: Vec::new(),
+            previous: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// The OID to continue from, or `None` once the walk has ended.
+    pub(crate) fn current(&self) -> Option<Oid> {
+        self.current.clone()
+    }
+
+    /// The GETBULK continuing the walk.
+    pub(crate) fn request(&self, current: &Oid) -> v2::Pdus {
+        pdu::getbulk(std::slice::from_ref(current), 0, self.max_repetitions)
+    }
+
+    /// Visits the varbinds of `message` in place, continuing from `current`. Returns
+    /// `None` when the message is not the error-free v2c Response expected, or holds
+    /// something this decoder does not cover, before anything was visited.
+    pub(crate) fn accept<F>(
+        &mut self,
+        message: &[u8],
+        community: &[u8],
+        request_id: i32,
+        visit: &mut F,
+    ) -> Option<SnmpResult<()>>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        let bindings = response(message, community, request_id)?;
+
+        // Check the whole list first, so a visitor never sees half a response twice.
+        let mut rest = bindings;
+        while !rest.is_empty() {
+            (_, rest) = binding(rest, &mut self.name, &mut self.scratch)?;
+        }
+
+        let current = self.current.take()?;
+        self.previous.clear();
+        self.previous.extend_from_slice(&current);
+
+        let mut rest = bindings;
+        let mut visited = 0;
+        while !rest.is_empty() && visited < self.max_repetitions {
+            let (value, next) = binding(rest, &mut self.name, &mut self.scratch)?;
+            rest = next;
+
+            if value == ValueRef::EndOfMibView {
+                return Some(Ok(()));
+            }
+            if self.name <= self.previous {
+                return Some(Err(SnmpError::OidNotIncreasing {
+                    previous: Oid::from(self.previous.clone()),
+                    next: Oid::from(self.name.clone()),
+                }));
+            }
+            if !self.name.starts_with(&self.start) {
+                return Some(Ok(()));
+            }
+            if visit(&self.name, value).is_break() {
+                return Some(Ok(()));
+            }
+
+            mem::swap(&mut self.name, &mut self.previous);
+            visited += 1;
+        }
+
+        if visited > 0 {
+            self.current = Some(Oid::from(self.previous.as_slice()));
+        }
+        Some(Ok(()))
+    }
+
+    /// Visits varbinds that were decoded the regular way, continuing from `current`.
+    pub(crate) fn accept_owned<F>(
+        &mut self,
+        current: &Oid,
+        vars: Vec<(Oid, Value)>,
+        visit: &mut F,
+    ) -> SnmpResult<()>
+    where
+        F: FnMut(&[u32], ValueRef<'_>) -> ControlFlow<()>,
+    {
+        let mut step = Vec::new();
+        let next = pdu::walk_step(&self.start, current, vars, &mut step)?;
+        self.current = next;
+
+        for (name, value) in &step {
+            if visit(name, value.into()).is_break() {
+                self.current = None;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+use crate::{
+    pdu, IntoOid, Oid, SnmpError, SnmpResult, SyncSession, Transport, UdpTransport, Value,
+};
+
+/// A lazy walk of a subtree, returned by [`SyncSession::walk_iter`], or of everything after
+/// an OID, returned by [`SyncSession::next_iter`]. Each GETNEXT is only sent once the
+/// previous results have been consumed; the walk ends after the first error.
+pub struct Walk<'a, T = UdpTransport> {
+    session: &'a SyncSession<T>,
+    start: Oid,
+    current: Option<Oid>,
+    buffer: VecDeque<(Oid, Value)>,
+    error: Option<SnmpError>,
+}
+
+impl<'a, T: Transport> Walk<'a, T> {
+    pub(crate) fn new(session: &'a SyncSession<T>, start: SnmpResult<Oid>) -> Self {
+        let (start, error) = match start {
+            Ok(start) => (start, None),
+            Err(err) => (Oid::default(), Some(err)),
+        };
+
+        Walk {
+            session,
+            current: error.is_none().then(|| start.clone()),
+            start,
+            buffer: VecDeque::new(),
+            error,
+        }
+    }
+
+    /// A walk from `after` to the end of the agent's MIB view.
+    pub(crate) fn after(session: &'a SyncSession<T>, after: SnmpResult<Oid>) -> Self {
+        let (current, error) = match after {
+            Ok(after) => (Some(after), None),
+            Err(err) => (None, Some(err)),
+        };
+
+        Walk {
+            session,
+            start: Oid::default(),
+            current,
+            buffer: VecDeque::new(),
+            error,
+        }
+    }
+
+    fn fetch(&mut self, current: Oid) -> SnmpResult<()> {
+        let vars = self.session.getnext(&current)?;
+
+        self.current = pdu::walk_step(&self.start, &current, vars, &mut self.buffer)?;
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> Iterator for Walk<'_, T> {
+    type Item = SnmpResult<(Oid, Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+
+        while self.buffer.is_empty() {
+            let current = self.current.take()?;
+
+            if let Err(err) = self.fetch(current) {
+                return Some(Err(err));
+            }
+        }
+
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+/// Where a walk stopped, for continuing it later with [`SyncSession::walk_resume`], e.g.
+/// a little of a huge table each polling cycle. With the `serde` feature it can be saved
+/// between runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WalkCursor {
+    start: Oid,
+    after: Option<Oid>,
+    done: bool,
+}
+
+impl WalkCursor {
+    /// A walk of the subtree at `oid` that has not started yet.
+    pub fn new(oid: impl IntoOid) -> SnmpResult<Self> {
+        Ok(WalkCursor {
+            start: oid.into_oid()?,
+            after: None,
+            done: false,
+        })
+    }
+
+    /// A walk of the subtree at `oid` continuing after `after`, such as the
+    /// [`PartialWalk::last`](crate::PartialWalk::last) of a walk that failed.
+    pub fn resume(oid: impl IntoOid, after: Option<Oid>) -> SnmpResult<Self> {
+        Ok(WalkCursor {
+            after,
+            ..WalkCursor::new(oid)?
+        })
+    }
+
+    pub fn start(&self) -> &Oid {
+        &self.start
+    }
+
+    /// The last OID walked so far.
+    pub fn after(&self) -> Option<&Oid> {
+        self.after.as_ref()
+    }
+
+    /// Whether the walk reached the end of the subtree.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// The OID to continue from, or `None` once done.
+    pub(crate) fn current(&self) -> Option<Oid> {
+        match (&self.after, self.done) {
+            (_, true) => None,
+            (Some(after), false) => Some(after.clone()),
+            (None, false) => Some(self.start.clone()),
+        }
+    }
+
+    /// Moves on to `next`, as returned by the walk step; `None` ends the walk.
+    pub(crate) fn advance(&mut self, next: Option<Oid>) {
+        match next {
+            Some(next) => self.after = Some(next),
+            None => self.done = true,
+        }
+    }
+}